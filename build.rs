@@ -0,0 +1,22 @@
+// Injects build metadata (git commit, dirty flag, build date) for the --version / VERSION output.
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn main() {
+    let hash = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"])
+    .map(|s| !s.is_empty())
+    .unwrap_or(false);
+
+    println!("cargo:rustc-env=TWL_GIT_HASH={}{}", hash, if dirty { "-dirty" } else { "" });
+    println!("cargo:rustc-env=TWL_BUILD_DATE={}", chrono::Local::now().format("%Y-%m-%d"));
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/index");
+}
@@ -0,0 +1,119 @@
+//! SOUND and NOTIFY: which channels alert with a sound plus notification, and which with
+//! a notification only. A channel has at most one of the two.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::state::LoggerState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertMode {
+    Sound,
+    Notify,
+}
+
+impl AlertMode {
+    /// "Sound" / "Notifications", as the command output says it.
+    pub fn label(self) -> &'static str {
+        match self {
+            AlertMode::Sound => "Sound",
+            AlertMode::Notify => "Notifications",
+        }
+    }
+
+    fn channels(self, state: &LoggerState) -> &Arc<Mutex<HashSet<String>>> {
+        match self {
+            AlertMode::Sound => &state.sound_channels,
+            AlertMode::Notify => &state.notification_channels,
+        }
+    }
+
+    fn other(self) -> AlertMode {
+        match self {
+            AlertMode::Sound => AlertMode::Notify,
+            AlertMode::Notify => AlertMode::Sound,
+        }
+    }
+}
+
+pub fn is_on(state: &LoggerState, channel: &str, mode: AlertMode) -> bool {
+    mode.channels(state).lock().unwrap().contains(channel)
+}
+
+/// How a chat message in `channel` alerts, if at all.
+pub fn alert_for(state: &LoggerState, channel: &str) -> Option<AlertMode> {
+    if !state.alerts {
+        return None;
+    }
+    [AlertMode::Sound, AlertMode::Notify].into_iter().find(|mode| is_on(state, channel, *mode))
+}
+
+/// Turn `mode` on or off for `channel`; turning one on turns the other off. Returns
+/// `false` if it already was that way.
+pub fn set_alert_mode(state: &LoggerState, channel: &str, mode: AlertMode, on: bool) -> bool {
+    if !on {
+        return mode.channels(state).lock().unwrap().remove(channel);
+    }
+    mode.other().channels(state).lock().unwrap().remove(channel);
+    mode.channels(state).lock().unwrap().insert(channel.to_string())
+}
+
+/// Whether `channel` may beep at `now`: its `sound_cooldown` from channels.txt has passed
+/// since the last beep. If so, `now` becomes the last beep.
+pub fn sound_cooldown_passed(state: &LoggerState, channel: &str, now: Instant) -> bool {
+    let cooldown = state.vip_info(channel).map_or(0, |info| info.sound_cooldown_ms);
+    if cooldown == 0 {
+        return true;
+    }
+    let mut last_sounds = state.last_sounds.lock().unwrap();
+    if last_sounds.get(channel).is_some_and(|last| now.saturating_duration_since(*last) < Duration::from_millis(cooldown)) {
+        return false;
+    }
+    last_sounds.insert(channel.to_string(), now);
+    true
+}
+
+/// `SOUND ALL ON|OFF`: every joined channel, with whether it changed.
+pub fn set_alert_mode_all(state: &LoggerState, mode: AlertMode, on: bool) -> Vec<(String, bool)> {
+    let channels = state.channels.lock().unwrap().clone();
+    channels
+    .into_iter()
+    .map(|channel| {
+        let changed = set_alert_mode(state, &channel, mode, on);
+        (channel, changed)
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_sets_every_joined_channel_explicitly() {
+        let state = LoggerState::default();
+        *state.channels.lock().unwrap() = vec!["forsen".to_string(), "xqcow".to_string()];
+        set_alert_mode(&state, "forsen", AlertMode::Notify, true);
+        set_alert_mode(&state, "xqcow", AlertMode::Sound, true);
+
+        let changed = set_alert_mode_all(&state, AlertMode::Sound, true);
+        assert_eq!(changed, vec![("forsen".to_string(), true), ("xqcow".to_string(), false)]);
+        assert!(is_on(&state, "forsen", AlertMode::Sound));
+        assert!(!is_on(&state, "forsen", AlertMode::Notify));
+
+        set_alert_mode_all(&state, AlertMode::Sound, false);
+        assert!(state.sound_channels.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sounds_wait_for_the_cooldown() {
+        let (config, _) = crate::channel_config::parse_channel_config("0\nforsen sound_cooldown=1000\nxqcow\n").unwrap();
+        let state = LoggerState { config: Arc::new(config), ..Default::default() };
+        let start = Instant::now();
+        assert!(sound_cooldown_passed(&state, "forsen", start));
+        assert!(!sound_cooldown_passed(&state, "forsen", start + Duration::from_millis(999)));
+        assert!(sound_cooldown_passed(&state, "forsen", start + Duration::from_millis(1000)));
+        assert!(sound_cooldown_passed(&state, "xqcow", start) && sound_cooldown_passed(&state, "xqcow", start));
+    }
+}
@@ -0,0 +1,323 @@
+//! All-time numbers per channel (`STATS <channel> ALLTIME`): messages, hours covered and
+//! top chatters over every session. What a session added since the last merge is merged
+//! into a JSON file every few minutes and on exit. The file is locked while merging, so a
+//! TUI and a headless archiver running at the same time both get counted.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::heatmap::SLOTS;
+use crate::output::{is_dry_run, rewrite_file};
+use crate::state::{data_file, LoggerState};
+
+pub fn alltime_stats_file() -> PathBuf {
+    data_file("alltime_stats.json")
+}
+
+/// Bumped when the meaning of a field changes; new fields only need `#[serde(default)]`.
+pub const ALLTIME_VERSION: u32 = 1;
+/// Chatters kept per channel; the ones with the fewest messages make room.
+const MAX_CHATTERS: usize = 5000;
+const MERGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How long a merge waits for another logger to finish its own.
+const LOCK_WAIT: Duration = Duration::from_secs(3);
+const TOP_CHATTERS: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AllTimeStats {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub channels: BTreeMap<String, ChannelTotals>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelTotals {
+    pub messages: u64,
+    /// Time the channel was joined, over all sessions.
+    pub seconds_covered: u64,
+    pub sessions: u32,
+    /// "YYYY-MM-DD" of the first and the latest session.
+    pub first_session: Option<String>,
+    pub last_session: Option<String>,
+    pub chatters: HashMap<String, u64>,
+    /// Messages per hour of the week (see `heatmap::slot`), empty until the first one.
+    pub heatmap: Vec<u64>,
+}
+
+/// A channel's numbers of this session, or what was added to them since the last merge.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionTotals {
+    pub messages: u64,
+    pub seconds: u64,
+    pub chatters: HashMap<String, u64>,
+    /// Messages per `heatmap::slot`.
+    pub hours: BTreeMap<usize, u64>,
+}
+
+/// The numbers of this session so far, per joined or logged channel.
+pub fn session_totals(state: &LoggerState) -> HashMap<String, SessionTotals> {
+    let mut totals: HashMap<String, SessionTotals> = HashMap::new();
+    for (channel, counts) in state.user_message_counts.lock().unwrap().iter() {
+        let entry = totals.entry(channel.clone()).or_default();
+        entry.chatters = counts.iter().map(|(user, n)| (user.clone(), u64::from(*n))).collect();
+        entry.messages = entry.chatters.values().sum();
+    }
+    for (channel, hours) in state.hourly_messages.lock().unwrap().iter() {
+        totals.entry(channel.clone()).or_default().hours = hours.clone();
+    }
+    for (channel, joined_at) in state.channel_joined_at.lock().unwrap().iter() {
+        totals.entry(channel.clone()).or_default().seconds = joined_at.elapsed().as_secs();
+    }
+    totals
+}
+
+/// What `current` added to `merged`, leaving out channels without anything new.
+pub fn deltas(current: &HashMap<String, SessionTotals>, merged: &HashMap<String, SessionTotals>) -> BTreeMap<String, SessionTotals> {
+    let mut deltas = BTreeMap::new();
+    for (channel, now) in current {
+        let before = merged.get(channel).cloned().unwrap_or_default();
+        // Joined again since: the time counts from the new JOIN
+        let seconds = if now.seconds >= before.seconds { now.seconds - before.seconds } else { now.seconds };
+        let chatters: HashMap<String, u64> = now.chatters
+        .iter()
+        .map(|(user, n)| (user.clone(), n.saturating_sub(before.chatters.get(user).copied().unwrap_or(0))))
+        .filter(|(_, n)| *n > 0)
+        .collect();
+        let hours: BTreeMap<usize, u64> = now.hours
+        .iter()
+        .map(|(slot, n)| (*slot, n.saturating_sub(before.hours.get(slot).copied().unwrap_or(0))))
+        .filter(|(_, n)| *n > 0)
+        .collect();
+        let delta = SessionTotals { messages: now.messages.saturating_sub(before.messages), seconds, chatters, hours };
+        if delta != SessionTotals::default() {
+            deltas.insert(channel.clone(), delta);
+        }
+    }
+    deltas
+}
+
+impl AllTimeStats {
+    /// Parse the file content, refusing files of a newer version (their numbers would be
+    /// misread and then overwritten).
+    pub fn parse(content: &str) -> Result<Self, String> {
+        if content.trim().is_empty() {
+            return Ok(AllTimeStats { version: ALLTIME_VERSION, ..Default::default() });
+        }
+        let stats: AllTimeStats = serde_json::from_str(content).map_err(|e| format!("unreadable all-time stats: {}", e))?;
+        if stats.version > ALLTIME_VERSION {
+            return Err(format!("all-time stats are version {}, this logger only knows up to {}", stats.version, ALLTIME_VERSION));
+        }
+        Ok(stats)
+    }
+
+    /// Add `deltas`; `new_sessions` are the channels merged for the first time this session.
+    pub fn apply(&mut self, deltas: &BTreeMap<String, SessionTotals>, new_sessions: &[String], date: &str) {
+        self.version = ALLTIME_VERSION;
+        for (channel, delta) in deltas {
+            let totals = self.channels.entry(channel.clone()).or_default();
+            totals.messages += delta.messages;
+            totals.seconds_covered += delta.seconds;
+            for (user, n) in &delta.chatters {
+                *totals.chatters.entry(user.clone()).or_default() += n;
+            }
+            if !delta.hours.is_empty() {
+                totals.heatmap.resize(SLOTS, 0);
+            }
+            for (slot, n) in delta.hours.iter().filter(|(slot, _)| **slot < SLOTS) {
+                totals.heatmap[*slot] += n;
+            }
+            if totals.chatters.len() > MAX_CHATTERS {
+                let mut counts: Vec<u64> = totals.chatters.values().copied().collect();
+                counts.sort_unstable_by(|a, b| b.cmp(a));
+                let min = counts[MAX_CHATTERS - 1];
+                totals.chatters.retain(|_, n| *n > min);
+            }
+            if new_sessions.contains(channel) {
+                totals.sessions += 1;
+            }
+            totals.first_session.get_or_insert_with(|| date.to_string());
+            totals.last_session = Some(date.to_string());
+        }
+    }
+}
+
+/// Merge what this session added since the last merge into `path`.
+pub fn merge_alltime(state: &LoggerState, path: &Path) -> Result<(), String> {
+    let current = session_totals(state);
+    let merged = state.alltime_merged.lock().unwrap().clone();
+    let deltas = deltas(&current, &merged);
+    if deltas.is_empty() {
+        return Ok(());
+    }
+    let new_sessions: Vec<String> = deltas.keys().filter(|c| !merged.contains_key(*c)).cloned().collect();
+    // Opening the file would already create it
+    if is_dry_run() {
+        return Ok(());
+    }
+
+    let mut file = lock_file(path)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut stats = AllTimeStats::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    stats.apply(&deltas, &new_sessions, &Local::now().format("%Y-%m-%d").to_string());
+    let json = serde_json::to_string_pretty(&stats).map_err(|e| e.to_string())?;
+    if rewrite_file(&mut file, path, json).map_err(|e| format!("{}: {}", path.display(), e))? {
+        *state.alltime_merged.lock().unwrap() = current;
+    }
+    Ok(())
+}
+
+/// Open and lock `path`, waiting a bit for another logger that is merging.
+fn lock_file(path: &Path) -> Result<File, String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let file = OpenOptions::new()
+    .read(true)
+    .write(true)
+    .create(true)
+    .truncate(false)
+    .open(path)
+    .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let waited = std::time::Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(file),
+            Err(TryLockError::WouldBlock) if waited.elapsed() < LOCK_WAIT => std::thread::sleep(Duration::from_millis(100)),
+            Err(TryLockError::WouldBlock) => {
+                return Err(format!("{} is locked by another logger, all-time stats not merged this time", path.display()));
+            }
+            Err(TryLockError::Error(e)) => return Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Merge now, reporting failures instead of returning them.
+pub fn save_alltime(state: &LoggerState) {
+    if let Err(e) = merge_alltime(state, &alltime_stats_file()) {
+        eprintln!("⚠️ {}", e);
+    }
+}
+
+/// Merge every `MERGE_INTERVAL`, so a crash loses at most that much.
+pub fn spawn_alltime_merger(state: LoggerState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MERGE_INTERVAL);
+        // The first tick is immediate, nothing to merge yet
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let state = state.clone();
+            let _ = tokio::task::spawn_blocking(move || save_alltime(&state)).await;
+        }
+    });
+}
+
+/// The stored totals of `channel` plus what this session has not merged yet.
+pub fn alltime_totals(state: &LoggerState, path: &Path, channel: &str) -> Result<Option<ChannelTotals>, String> {
+    let content = std::fs::read_to_string(path).unwrap_or_default();
+    let mut stats = AllTimeStats::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let merged = state.alltime_merged.lock().unwrap().clone();
+    let pending: BTreeMap<String, SessionTotals> = deltas(&session_totals(state), &merged).into_iter().filter(|(c, _)| c == channel).collect();
+    let new_session: Vec<String> = pending.keys().filter(|c| !merged.contains_key(*c)).cloned().collect();
+    stats.apply(&pending, &new_session, &Local::now().format("%Y-%m-%d").to_string());
+    Ok(stats.channels.remove(channel))
+}
+
+/// STATS <channel> ALLTIME
+pub fn format_alltime(channel: &str, totals: &ChannelTotals) -> String {
+    let mut top: Vec<(&String, &u64)> = totals.chatters.iter().collect();
+    top.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let mut lines = vec![
+        format!(
+            "--- #{} all time ({} sessions since {}) ---",
+            channel,
+            totals.sessions,
+            totals.first_session.as_deref().unwrap_or("today")
+        ),
+        format!("Messages:      {}", totals.messages),
+        format!("Hours covered: {:.1}", totals.seconds_covered as f64 / 3600.0),
+    ];
+    if !top.is_empty() {
+        lines.push("Top chatters:".to_string());
+        lines.extend(top.into_iter().take(TOP_CHATTERS).map(|(user, n)| format!("{:>7}  {}", n, user)));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(messages: u64, seconds: u64, chatters: &[(&str, u64)]) -> SessionTotals {
+        SessionTotals { messages, seconds, chatters: chatters.iter().map(|(u, n)| (u.to_string(), *n)).collect(), ..Default::default() }
+    }
+
+    #[test]
+    fn merges_only_what_is_new() {
+        let merged = HashMap::from([("forsen".to_string(), totals(3, 600, &[("alice", 2), ("bob", 1)]))]);
+        let current = HashMap::from([
+            ("forsen".to_string(), totals(5, 900, &[("alice", 4), ("bob", 1)])),
+            ("xqcow".to_string(), totals(1, 60, &[("carol", 1)])),
+        ]);
+        let deltas = deltas(&current, &merged);
+        assert_eq!(deltas["forsen"], totals(2, 300, &[("alice", 2)]));
+
+        let mut stats = AllTimeStats::default();
+        stats.apply(&deltas, &["xqcow".to_string()], "2026-10-16");
+        stats.apply(&deltas, &[], "2026-10-17");
+        let forsen = &stats.channels["forsen"];
+        assert_eq!((forsen.messages, forsen.seconds_covered, forsen.sessions), (4, 600, 0));
+        assert_eq!(forsen.chatters["alice"], 4);
+        assert_eq!(forsen.first_session.as_deref(), Some("2026-10-16"));
+        assert_eq!(stats.channels["xqcow"].sessions, 1);
+        assert!(forsen.heatmap.is_empty());
+    }
+
+    #[test]
+    fn hours_add_up_in_the_heatmap() {
+        let mut merged = totals(2, 0, &[]);
+        merged.hours = BTreeMap::from([(20, 2)]);
+        let mut current = totals(5, 0, &[]);
+        current.hours = BTreeMap::from([(20, 4), (21, 1)]);
+        let deltas = deltas(&HashMap::from([("forsen".to_string(), current)]), &HashMap::from([("forsen".to_string(), merged)]));
+        assert_eq!(deltas["forsen"].hours, BTreeMap::from([(20, 2), (21, 1)]));
+
+        let mut stats = AllTimeStats::default();
+        stats.apply(&deltas, &[], "2026-10-16");
+        stats.apply(&deltas, &[], "2026-10-17");
+        let heatmap = &stats.channels["forsen"].heatmap;
+        assert_eq!((heatmap.len(), heatmap[20], heatmap[21]), (SLOTS, 4, 2));
+    }
+
+    #[test]
+    fn older_files_load_and_newer_ones_are_refused() {
+        // Written before `sessions` existed
+        let old = r#"{"version":1,"channels":{"forsen":{"messages":10,"seconds_covered":60,"chatters":{"a":10}}}}"#;
+        assert_eq!(AllTimeStats::parse(old).unwrap().channels["forsen"].sessions, 0);
+        assert!(AllTimeStats::parse(r#"{"version":99,"channels":{}}"#).is_err());
+        assert!(AllTimeStats::parse("").unwrap().channels.is_empty());
+    }
+
+    #[test]
+    fn merge_writes_the_file_and_a_second_merge_adds_nothing() {
+        let path = std::env::temp_dir().join(format!("alltime_test_{}.json", std::process::id()));
+        let state = LoggerState::default();
+        state.user_message_counts.lock().unwrap().insert("forsen".to_string(), HashMap::from([("alice".to_string(), 3)]));
+
+        merge_alltime(&state, &path).unwrap();
+        merge_alltime(&state, &path).unwrap();
+        let stats = AllTimeStats::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(stats.version, ALLTIME_VERSION);
+        assert_eq!((stats.channels["forsen"].messages, stats.channels["forsen"].sessions), (3, 1));
+    }
+}
@@ -0,0 +1,148 @@
+//! Spikes of new chatters: many people chatting for the first time this session within
+//! one minute usually means a raid, a hate raid or a big host.
+
+use std::collections::VecDeque;
+
+/// Minutes the trailing average covers. No alerts until that much history exists, since
+/// at the start of a session every chatter is new.
+pub const TRAILING_MINUTES: usize = 10;
+
+/// Minutes without a spike before the next alert, so one event alerts once.
+pub const COOLDOWN_MINUTES: usize = 5;
+
+/// What an alert reports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChatterSpike {
+    /// New chatters in the current minute.
+    pub new_chatters: usize,
+    /// New chatters per minute over the last `TRAILING_MINUTES`.
+    pub average: f64,
+}
+
+/// New chatters per minute of one channel, fed by the message handler.
+#[derive(Debug, Default)]
+pub struct ChatterSpikeDetector {
+    /// Completed minutes, oldest first.
+    history: VecDeque<usize>,
+    /// Minute number of `current`, `None` before the first chatter.
+    minute: Option<u64>,
+    current: usize,
+    /// Quiet minutes still needed before alerting again, 0 when ready.
+    cooldown: usize,
+}
+
+impl ChatterSpikeDetector {
+    /// Move on to `minute` (minutes since any fixed point), closing the minutes in between.
+    pub fn advance_to(&mut self, minute: u64, factor: f64, min_new: usize) {
+        let Some(current) = self.minute else {
+            // The minutes before the first chatter are unknown, not empty
+            self.minute = Some(minute);
+            return;
+        };
+        // After this many empty minutes nothing of the past is left anyway
+        let gap = minute.saturating_sub(current).min((TRAILING_MINUTES + COOLDOWN_MINUTES) as u64);
+        for _ in 0..gap {
+            self.close_minute(factor, min_new);
+        }
+        self.minute = Some(current.max(minute));
+    }
+
+    /// Count a new chatter in the current minute. Returns the spike the first time the
+    /// minute crosses the threshold, unless an alert was given recently.
+    pub fn record(&mut self, factor: f64, min_new: usize) -> Option<ChatterSpike> {
+        self.current += 1;
+        if self.cooldown > 0 || !self.is_spike(self.current, factor, min_new) {
+            return None;
+        }
+        self.cooldown = COOLDOWN_MINUTES;
+        Some(ChatterSpike { new_chatters: self.current, average: self.average() })
+    }
+
+    fn close_minute(&mut self, factor: f64, min_new: usize) {
+        // A minute still above the threshold keeps the cooldown going
+        if self.cooldown > 0 {
+            self.cooldown = if self.is_spike(self.current, factor, min_new) { COOLDOWN_MINUTES } else { self.cooldown - 1 };
+        }
+        self.history.push_back(self.current);
+        if self.history.len() > TRAILING_MINUTES {
+            self.history.pop_front();
+        }
+        self.current = 0;
+    }
+
+    fn average(&self) -> f64 {
+        self.history.iter().sum::<usize>() as f64 / TRAILING_MINUTES as f64
+    }
+
+    fn is_spike(&self, count: usize, factor: f64, min_new: usize) -> bool {
+        self.history.len() == TRAILING_MINUTES && count >= min_new && count as f64 > self.average() * factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FACTOR: f64 = 5.0;
+    const MIN_NEW: usize = 10;
+
+    /// A detector with `TRAILING_MINUTES` of `per_minute` new chatters behind it.
+    fn warmed_up(per_minute: usize) -> ChatterSpikeDetector {
+        let mut detector = ChatterSpikeDetector::default();
+        for minute in 0..TRAILING_MINUTES as u64 {
+            detector.advance_to(minute, FACTOR, MIN_NEW);
+            for _ in 0..per_minute {
+                assert_eq!(detector.record(FACTOR, MIN_NEW), None);
+            }
+        }
+        detector.advance_to(TRAILING_MINUTES as u64, FACTOR, MIN_NEW);
+        detector
+    }
+
+    fn record_many(detector: &mut ChatterSpikeDetector, count: usize) -> Vec<ChatterSpike> {
+        (0..count).filter_map(|_| detector.record(FACTOR, MIN_NEW)).collect()
+    }
+
+    #[test]
+    fn spike_above_the_average_alerts_once() {
+        let mut detector = warmed_up(3);
+        let spikes = record_many(&mut detector, 40);
+        assert_eq!(spikes, vec![ChatterSpike { new_chatters: 16, average: 3.0 }]);
+    }
+
+    #[test]
+    fn small_channels_need_the_minimum() {
+        let mut detector = warmed_up(0);
+        assert!(record_many(&mut detector, MIN_NEW - 1).is_empty());
+        assert_eq!(record_many(&mut detector, 1).len(), 1);
+    }
+
+    #[test]
+    fn no_alerts_before_there_is_an_average() {
+        let mut detector = ChatterSpikeDetector::default();
+        assert!(record_many(&mut detector, 100).is_empty());
+    }
+
+    #[test]
+    fn minutes_before_the_first_chatter_do_not_count() {
+        let mut detector = ChatterSpikeDetector::default();
+        detector.advance_to(1000, FACTOR, MIN_NEW);
+        assert!(record_many(&mut detector, 100).is_empty());
+    }
+
+    #[test]
+    fn ongoing_event_does_not_realert() {
+        let mut detector = warmed_up(1);
+        let mut minute = TRAILING_MINUTES as u64;
+        assert_eq!(record_many(&mut detector, 30).len(), 1);
+        // Still well above the (raised) average of 3.9 a minute later
+        minute += 1;
+        detector.advance_to(minute, FACTOR, MIN_NEW);
+        assert!(record_many(&mut detector, 25).is_empty());
+
+        // Quiet minutes end the cooldown, the next raid alerts again
+        minute += 1 + COOLDOWN_MINUTES as u64;
+        detector.advance_to(minute, FACTOR, MIN_NEW);
+        assert_eq!(record_many(&mut detector, 40).len(), 1);
+    }
+}
@@ -0,0 +1,221 @@
+//! Autosave: a channel is saved once its oldest unsaved line is `autosave_minutes` old or
+//! it has `autosave_lines` unsaved lines, whichever comes first. Busy channels are saved
+//! often, sleepy ones rarely and never without new lines. Due channels are saved one after
+//! another, so many of them coming due together don't hit the disk all at once.
+//!
+//! `autosave_files` says how the files are named: a new one each time like SAVE, or one per
+//! channel and day that is replaced (`AUTOSAVE <minutes>`, `--autosave`).
+//!
+//! Separately, `auto_save_interval` snapshots every channel on a fixed timer into
+//! `.autosave` files that are overwritten each time, so a crash loses at most one interval.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+use owo_colors::OwoColorize;
+
+use crate::console::print_status;
+use crate::save::{autosave_channel, logged_channels, snapshot_channel};
+use crate::state::LoggerState;
+
+/// How often the budgets are checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Pause between two channels saved in the same round.
+const SAVE_SPACING: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy)]
+struct Unsaved {
+    lines: u64,
+    oldest: Instant,
+}
+
+/// Lines logged per channel since its last save.
+#[derive(Debug, Clone, Default)]
+pub struct UnsavedLines {
+    channels: HashMap<String, Unsaved>,
+}
+
+impl UnsavedLines {
+    /// Called by `append_line` for every line.
+    pub fn record(&mut self, channel: &str, now: Instant) {
+        match self.channels.get_mut(channel) {
+            Some(unsaved) => unsaved.lines += 1,
+            None => {
+                self.channels.insert(channel.to_string(), Unsaved { lines: 1, oldest: now });
+            }
+        }
+    }
+
+    /// Called when `channel` is saved, by SAVE or the autosave.
+    pub fn saved(&mut self, channel: &str) {
+        self.channels.remove(channel);
+    }
+
+    pub fn lines(&self, channel: &str) -> u64 {
+        self.channels.get(channel).map_or(0, |unsaved| unsaved.lines)
+    }
+
+    /// Unsaved lines of each channel, by name.
+    pub fn by_channel(&self) -> BTreeMap<String, u64> {
+        self.channels.iter().map(|(channel, unsaved)| (channel.clone(), unsaved.lines)).collect()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.channels.values().map(|unsaved| unsaved.lines).sum()
+    }
+
+    /// Channels over one of the budgets, most unsaved lines first. `None` or 0 turns a budget off.
+    pub fn due(&self, now: Instant, max_age: Option<Duration>, max_lines: u64) -> Vec<String> {
+        let mut due: Vec<(&String, u64)> = self
+        .channels
+        .iter()
+        .filter(|(_, unsaved)| {
+            let too_old = max_age.is_some_and(|age| now.saturating_duration_since(unsaved.oldest) >= age);
+            let too_many = max_lines > 0 && unsaved.lines >= max_lines;
+            too_old || too_many
+        })
+        .map(|(channel, unsaved)| (channel, unsaved.lines))
+        .collect();
+        due.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        due.into_iter().map(|(channel, _)| channel.clone()).collect()
+    }
+}
+
+/// Check the budgets every few seconds and save the due channels, one at a time, off the
+/// runtime threads.
+pub fn spawn_autosave(state: LoggerState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let (max_age, max_lines, files) = {
+                let settings = state.settings.lock().unwrap();
+                let minutes = settings.autosave_minutes.value;
+                ((minutes > 0).then(|| Duration::from_secs(minutes * 60)), settings.autosave_lines.value, settings.autosave_files.value)
+            };
+            let due = state.unsaved.lock().unwrap().due(Instant::now(), max_age, max_lines);
+
+            let (mut channels, mut lines) = (0, 0);
+            for (i, channel) in due.iter().enumerate() {
+                if i > 0 {
+                    tokio::time::sleep(SAVE_SPACING).await;
+                }
+                let state = state.clone();
+                let channel = channel.clone();
+                let saved = tokio::task::spawn_blocking(move || autosave_channel(&channel, &state, files)).await.unwrap_or(0);
+                if saved > 0 {
+                    channels += 1;
+                    lines += saved;
+                }
+            }
+            if channels > 0 {
+                print_status(&format_autosave(channels, lines).dimmed().to_string());
+            }
+        }
+    });
+}
+
+/// Snapshot all channels with logs every `auto_save_interval` until `exit` fires or its
+/// sender is dropped. The interval is read again each round, `CONFIG SET` applies.
+pub fn spawn_snapshots(state: LoggerState, mut exit: oneshot::Receiver<()>) {
+    tokio::spawn(async move {
+        loop {
+            let interval = state.settings.lock().unwrap().auto_save_interval.value;
+            tokio::select! {
+                _ = &mut exit => break,
+                _ = tokio::time::sleep(interval.unwrap_or(CHECK_INTERVAL)) => {}
+            }
+            // Streamed logs are on disk already
+            if interval.is_none() || state.streaming.load(Ordering::Relaxed) {
+                continue;
+            }
+            let state = state.clone();
+            let _ = tokio::task::spawn_blocking(move || snapshot_all(&state)).await;
+        }
+    });
+}
+
+/// "autosaved 3 channels (14213 lines)"
+pub fn format_autosave(channels: usize, lines: u64) -> String {
+    format!("autosaved {} channel{} ({} lines)", channels, if channels == 1 { "" } else { "s" }, lines)
+}
+
+/// One snapshot of every channel that has lines in any log. Returns the files written.
+pub fn snapshot_all(state: &LoggerState) -> usize {
+    logged_channels(state).iter().map(|channel| snapshot_channel(channel, state)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::AutosaveFiles;
+
+    #[test]
+    fn channels_come_due_by_age_or_by_lines() {
+        let start = Instant::now();
+        let mut unsaved = UnsavedLines::default();
+        for _ in 0..5 {
+            unsaved.record("busy", start);
+        }
+        unsaved.record("sleepy", start);
+        let minutes = Some(Duration::from_secs(600));
+
+        assert!(unsaved.due(start, minutes, 10).is_empty());
+        assert_eq!(unsaved.due(start, minutes, 5), vec!["busy"]);
+        assert_eq!(unsaved.due(start + Duration::from_secs(600), minutes, 0), vec!["busy", "sleepy"]);
+        assert!(unsaved.due(start + Duration::from_secs(600), None, 0).is_empty());
+
+        unsaved.saved("busy");
+        assert_eq!(unsaved.lines("busy"), 0);
+        // The age counts from the oldest unsaved line, not from the save
+        unsaved.record("busy", start + Duration::from_secs(300));
+        assert_eq!(unsaved.due(start + Duration::from_secs(600), minutes, 0), vec!["sleepy"]);
+    }
+
+    #[test]
+    fn snapshots_overwrite_and_leave_lines_unsaved() {
+        let dir = std::env::temp_dir().join(format!("snapshots_{}", std::process::id()));
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().log_dir.value = dir.display().to_string();
+        crate::buckets::append_line(&state, "msgs", "forsen", "12:00:00 <alice>\nhi\n".to_string());
+        assert_eq!(snapshot_all(&state), 1);
+        crate::buckets::append_line(&state, "msgs", "forsen", "12:00:01 <bob>\nhey\n".to_string());
+        assert_eq!(snapshot_all(&state), 1);
+
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().flatten().map(|e| e.file_name()).collect();
+        assert_eq!(files, ["forsen_msgs.txt.autosave"]);
+        assert!(std::fs::read_to_string(dir.join("forsen_msgs.txt.autosave")).unwrap().contains("hey"));
+        assert_eq!(state.unsaved.lock().unwrap().lines("forsen"), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn daily_autosave_replaces_one_file_and_skips_busy_channels() {
+        let dir = std::env::temp_dir().join(format!("daily_autosave_{}", std::process::id()));
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().log_dir.value = dir.display().to_string();
+        crate::buckets::append_line(&state, "msgs", "forsen", "12:00:00 <alice>\nhi\n".to_string());
+        crate::buckets::append_line(&state, "msgs", "xqcow", "12:00:00 <bob>\nyo\n".to_string());
+        assert_eq!(crate::save::daily_autosave(&state), (2, 2));
+        crate::buckets::append_line(&state, "msgs", "forsen", "12:00:01 <bob>\nhey\n".to_string());
+        {
+            let _saving = crate::save::SaveGuard::try_begin(&state, "xqcow").unwrap();
+            assert_eq!(crate::save::daily_autosave(&state), (1, 2));
+            assert_eq!(autosave_channel("xqcow", &state, AutosaveFiles::Daily), 0);
+        }
+        crate::buckets::append_line(&state, "msgs", "xqcow", "12:00:01 <alice>\nyo\n".to_string());
+        assert_eq!(autosave_channel("xqcow", &state, AutosaveFiles::Daily), 2);
+        assert_eq!(format_autosave(1, 2), "autosaved 1 channel (2 lines)");
+
+        let mut files: Vec<String> = std::fs::read_dir(&dir).unwrap().flatten().map(|e| e.file_name().to_string_lossy().to_string()).collect();
+        files.sort();
+        let today = crate::state::date_stamp();
+        assert_eq!(files, [format!("forsen_msgs_{}_autosave.txt", today), format!("xqcow_msgs_{}_autosave.txt", today)]);
+        assert!(std::fs::read_to_string(dir.join(&files[0])).unwrap().contains("hey"));
+        assert!(std::fs::read_to_string(dir.join(&files[1])).unwrap().contains("alice"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
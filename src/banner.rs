@@ -0,0 +1,69 @@
+//! Summary of the active configuration, printed at startup and by `CONFIG SHOW`.
+
+use chrono::Local;
+use owo_colors::OwoColorize;
+
+use crate::console_println;
+use crate::output::is_dry_run;
+use crate::save::HeaderFormat;
+use crate::state::{config_file_name, LoggerState};
+use crate::tags::format_tags;
+use crate::sound::audio_available;
+
+/// Label/value pairs of the banner, in display order.
+pub fn config_summary(state: &LoggerState) -> Vec<(&'static str, String)> {
+    let display = std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let audio = state.alerts && audio_available();
+
+    let mut alerts = Vec::new();
+    if audio {
+        alerts.push("sound");
+    }
+    if state.alerts && display {
+        alerts.push("desktop notifications");
+    }
+
+    let mut detected = Vec::new();
+    if !state.alerts {
+        detected.push("headless, alerts off".to_string());
+    } else {
+        if !display {
+            detected.push("no DISPLAY, desktop notifications off".to_string());
+        }
+        if !audio {
+            detected.push("no audio output device, sound off".to_string());
+        }
+    }
+
+    let (log_dir, log_header) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.log_dir.value.clone(), settings.log_header.value)
+    };
+    let header = match log_header {
+        HeaderFormat::Full => String::new(),
+        other => format!(", {} log header", other),
+    };
+    let dry_run = if is_dry_run() { " (dry-run, nothing is written)" } else { "" };
+
+    let tags = state.session_tags.lock().unwrap().clone();
+    let mut rows = vec![
+        ("Config", config_file_name()),
+        ("Output", format!("{}{}{}", log_dir, header, dry_run)),
+        ("Timezone", format!("local {} (file dates Europe/Berlin)", Local::now().offset())),
+        ("Channels", format!("{} default, {} VIPs", state.config.default_channels.len(), state.config.vips.len())),
+        ("Alerts", if alerts.is_empty() { "none".to_string() } else { alerts.join(", ") }),
+        ("Login", "anonymous (read-only)".to_string()),
+        ("Detected", if detected.is_empty() { "-".to_string() } else { detected.join("; ") }),
+    ];
+    // Kept from the last run, easy to forget about
+    if !tags.is_empty() {
+        rows.push(("Tag", format_tags(&tags)));
+    }
+    rows
+}
+
+pub fn print_banner(state: &LoggerState) {
+    for (label, value) in config_summary(state) {
+        console_println!("{} {}", format!("{:>9}:", label).dimmed(), value);
+    }
+}
@@ -0,0 +1,278 @@
+//! Headless log archiver: joins channels and logs without any interactive input.
+//!
+//! Controlled by signals:
+//! - SIGUSR1 saves all channels and keeps running
+//! - SIGINT / SIGTERM save all channels, part them and exit
+//!
+//! With `--health-listen`, `GET /health` on that address reports the state as JSON, with
+//! status 503 while disconnected or shutting down.
+//!
+//! If the client gives up connecting (see `--max-connection-failures`), all channels are
+//! saved and the process exits with status 1.
+
+use anyhow::Result;
+use chrono::Local;
+use clap::Parser;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+use twitch_irc::login::StaticLoginCredentials;
+use twitch_irc::{ClientConfig, SecureTCPTransport, TwitchIRCClient};
+
+use twitch_logger_core::banner::print_banner;
+use twitch_logger_core::build_info;
+use twitch_logger_core::console::{self, ColorChoice};
+use twitch_logger_core::channel_file::{read_channel_file, watch_channel_file};
+use twitch_logger_core::diag;
+use twitch_logger_core::handlers::{handle_connection_event, handle_received};
+use twitch_logger_core::health::start_health_server;
+use twitch_logger_core::journal::{offer_recovery, remove_session_journal, spawn_journal};
+use twitch_logger_core::alltime::{save_alltime, spawn_alltime_merger};
+use twitch_logger_core::autosave::{spawn_autosave, spawn_snapshots};
+use twitch_logger_core::encryption::setup_encryption;
+use twitch_logger_core::irc_relay::start_irc_relay;
+use twitch_logger_core::membership::spawn_join_log_writer;
+use twitch_logger_core::save::{save_logs, AutosaveFiles, HeaderFormat, LogFormat};
+use twitch_logger_core::settings::Source;
+use twitch_logger_core::output;
+use twitch_logger_core::retention;
+use twitch_logger_core::session_report::save_session_report;
+use twitch_logger_core::startup::{initial_channels, join_initial_channels, startup_delay};
+use twitch_logger_core::jsonl_log::spawn_jsonl;
+use twitch_logger_core::sqlite_log::spawn_sqlite;
+use twitch_logger_core::stream::spawn_stream;
+use twitch_logger_core::state::{self, LoggerState};
+use twitch_logger_core::vip_incidents::save_vip_incidents;
+use twitch_logger_core::vip_parts::{release_parts, spawn_part_release};
+use twitch_logger_core::vip_visits::save_vip_join_counts;
+
+#[derive(Parser, Debug)]
+#[command(author, version = build_info::VERSION, about = "Headless Twitch chat archiver", long_about = None)]
+struct Cli {
+    /// List of Twitch channels to join (defaults from channels.txt)
+    #[arg(name = "CHANNELS")]
+    channels: Vec<String>,
+
+    /// channels.txt to use instead of $XDG_CONFIG_HOME/rustTwitchLogger/ or ~/.rustTwitchLogger/
+    #[arg(long = "config", value_name = "FILE")]
+    config: Option<std::path::PathBuf>,
+
+    /// Directory the logs are saved to (overrides log_dir in channels.txt)
+    #[arg(long = "log-dir", visible_alias = "output-dir", value_name = "DIR")]
+    log_dir: Option<std::path::PathBuf>,
+
+    /// Header of saved message logs: full, minimal or none (overrides log_header in channels.txt)
+    #[arg(long = "log-header", value_name = "FORMAT")]
+    log_header: Option<HeaderFormat>,
+
+    /// Format of SAVE: text, json, csv or jsonl; jsonl also writes JSON Lines live (overrides log_format)
+    #[arg(long = "format", value_name = "FORMAT")]
+    log_format: Option<LogFormat>,
+
+    /// Also write every message and event to this SQLite database (overrides sqlite_db)
+    #[arg(long = "sqlite", value_name = "PATH")]
+    sqlite: Option<std::path::PathBuf>,
+
+    /// Autosave every N minutes into one file per channel and day (overrides autosave_minutes and autosave_files)
+    #[arg(long = "autosave", value_name = "MINUTES")]
+    autosave: Option<u64>,
+
+    /// Give up and exit after this many connections failed in a row
+    #[arg(long = "max-connection-failures", value_name = "N", default_value_t = 10)]
+    max_connection_failures: usize,
+
+    /// Load the journal of a crashed session (otherwise it is only archived)
+    #[arg(long = "resume")]
+    resume: bool,
+
+    /// Don't print the configuration banner at startup
+    #[arg(long = "quiet", short = 'q')]
+    quiet: bool,
+
+    /// Wait this many seconds before joining the channels (spreads out several loggers started together)
+    #[arg(long = "startup-delay", value_name = "SECONDS")]
+    startup_delay: Option<u64>,
+
+    /// Name saved files after the channel's display name (e.g. JanisTanTV) once the broadcaster has chatted
+    #[arg(long = "use-display-names")]
+    use_display_names: bool,
+
+    /// Also join the channels in this file (one per line) and follow changes to it
+    #[arg(long = "channel-file", value_name = "FILE")]
+    channel_file: Option<std::path::PathBuf>,
+
+    /// Connect, log and alert as usual, but don't write, move or delete any file
+    #[arg(long = "dry-run")]
+    dry_run: bool,
+
+    /// Only report the saved files max_files_per_channel / max_total_log_bytes would delete
+    #[arg(long = "retention-dry-run")]
+    retention_dry_run: bool,
+
+    /// Colored console output: auto (on a terminal, unless NO_COLOR is set), always or never
+    #[arg(long = "color", value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Serve the chat of the joined channels to IRC clients (irssi, weechat) on this address, e.g. 127.0.0.1:6667
+    #[arg(long = "irc-listen", value_name = "ADDR:PORT")]
+    irc_listen: Option<String>,
+
+    /// Allow --irc-listen on a non-loopback address; the relay has no authentication
+    #[arg(long = "irc-listen-insecure", requires = "irc_listen")]
+    irc_listen_insecure: bool,
+
+    /// Answer health checks (GET /health, JSON, 503 when disconnected) on this address, e.g. 127.0.0.1:8080
+    #[arg(long = "health-listen", value_name = "ADDR:PORT")]
+    health_listen: Option<String>,
+
+    /// Passphrase for the channels with `encrypt=true` (first line of the file) instead of asking at startup
+    #[arg(long = "key-file", value_name = "FILE")]
+    key_file: Option<std::path::PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if let Some(path) = &cli.config {
+        state::set_config_file(path.clone());
+    }
+    output::set_dry_run(cli.dry_run);
+    diag::capture_panics();
+    retention::set_retention_dry_run(cli.retention_dry_run);
+    console::set_color_choice(cli.color);
+    println!("{}", build_info::build_info());
+
+    let file_channels = match &cli.channel_file {
+        Some(path) => read_channel_file(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read channel file {}: {}", path.display(), e))?,
+        None => Vec::new(),
+    };
+    let initial_channels = initial_channels(&cli.channels, &file_channels);
+
+    let (events_tx, mut connection_events) = mpsc::unbounded_channel();
+    let client_config = ClientConfig {
+        emit_malformed_messages: true,
+        connection_events: Some(events_tx),
+        max_consecutive_connection_failures: Some(cli.max_connection_failures),
+        ..ClientConfig::default()
+    };
+    let (mut incoming_messages, client) =
+    TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new_timestamped(client_config);
+
+    // No sound or desktop notifications on a server
+    let state = LoggerState::new(&initial_channels, false);
+    {
+        let mut settings = state.settings.lock().unwrap();
+        if let Some(dir) = &cli.log_dir {
+            settings.log_dir.set(dir.display().to_string(), Source::Flag);
+        }
+        if let Some(header) = cli.log_header {
+            settings.log_header.set(header, Source::Flag);
+        }
+        if let Some(format) = cli.log_format {
+            settings.log_format.set(format, Source::Flag);
+        }
+        if let Some(path) = &cli.sqlite {
+            settings.sqlite_db.set(Some(path.display().to_string()), Source::Flag);
+        }
+        if let Some(minutes) = cli.autosave {
+            settings.autosave_minutes.set(minutes, Source::Flag);
+            settings.autosave_files.set(AutosaveFiles::Daily, Source::Flag);
+        }
+        if let Some(delay) = cli.startup_delay {
+            settings.startup_delay.set(delay, Source::Flag);
+        }
+        if cli.use_display_names {
+            settings.use_display_names.set(true, Source::Flag);
+        }
+    }
+
+    if !cli.quiet {
+        print_banner(&state);
+    }
+
+    setup_encryption(&state, cli.key_file.as_deref()).map_err(anyhow::Error::msg)?;
+    // Before the recovery, so lines loaded back are streamed too
+    spawn_stream(&state);
+    spawn_jsonl(&state);
+    spawn_sqlite(&state);
+    spawn_journal(&state);
+    offer_recovery(&state, cli.resume, false);
+    spawn_join_log_writer(state.clone());
+    spawn_part_release(state.clone());
+    spawn_alltime_merger(state.clone());
+    spawn_autosave(state.clone());
+    let (snapshot_exit_tx, snapshot_exit_rx) = tokio::sync::oneshot::channel::<()>();
+    spawn_snapshots(state.clone(), snapshot_exit_rx);
+    if let Some(addr) = &cli.irc_listen {
+        start_irc_relay(addr, cli.irc_listen_insecure, &state).await?;
+    }
+    if let Some(addr) = &cli.health_listen {
+        start_health_server(addr, client.clone(), &state).await?;
+    }
+
+    startup_delay(&state).await;
+    join_initial_channels(&client, &state).await;
+    let _channel_watcher = match cli.channel_file.clone() {
+        Some(path) => Some(watch_channel_file(path, file_channels, client.clone(), state.clone())?),
+        None => None,
+    };
+
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigusr1 = signal(SignalKind::user_defined1())?;
+
+    let client_closed = client.wait_closed();
+    tokio::pin!(client_closed);
+    let mut exit_code = 0;
+
+    loop {
+        tokio::select! {
+            message = incoming_messages.recv() => {
+                match message {
+                    Some(received) => handle_received(received, &state),
+                    None => {
+                        eprintln!("Connection closed, saving and exiting.");
+                        break;
+                    }
+                }
+            }
+            Some(event) = connection_events.recv() => {
+                handle_connection_event(event, &state);
+            }
+            _ = sigusr1.recv() => {
+                save_logs("ALL", &state, None, false, LogFormat::Text);
+            }
+            cause = &mut client_closed => {
+                match cause {
+                    Some(e) => eprintln!("Client gave up connecting: {}", e),
+                    None => eprintln!("Client closed unexpectedly."),
+                }
+                exit_code = 1;
+                break;
+            }
+            _ = sigint.recv() => break,
+            _ = sigterm.recv() => break,
+        }
+    }
+
+    println!("Shutting down...");
+    let _ = snapshot_exit_tx.send(());
+    *state.session_end.lock().unwrap() = Some(Local::now());
+    release_parts(&state, None);
+    save_session_report(&state);
+    save_vip_incidents(&state);
+    save_alltime(&state);
+    save_logs("ALL", &state, None, false, LogFormat::Text);
+    save_vip_join_counts(&state.vip_join_counts.lock().unwrap());
+    state.sinks.flush();
+    remove_session_journal();
+    let joined_channels = state.channels.lock().unwrap().clone();
+    for channel in joined_channels {
+        client.part(channel);
+    }
+
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+    Ok(())
+}
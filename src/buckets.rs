@@ -0,0 +1,286 @@
+//! The per-channel logs written by SAVE. A new log only needs an entry in
+//! `LOG_BUCKETS` to be included in every save, including the one at shutdown.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::build_info::build_info;
+use crate::membership::event_count;
+use crate::save::{render_file_name, HeaderFormat};
+use crate::jsonl_log::LogRecord;
+use crate::sequence::current_stamp;
+use crate::sink::LogEntry;
+use crate::state::{LogStore, LoggerState, SESSION_START};
+use crate::stats::compute_channel_stats;
+use crate::stream::{memory_limit, trim_in_memory};
+use crate::timestamps::record_first_line;
+
+/// One line of a per-channel log. The text is what the `.txt` files hold; the JSON Lines
+/// format is written from the record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogLine {
+    /// Chat, USERNOTICEs, moderation and markers, with what the logger knew of them.
+    Event { text: String, record: Arc<LogRecord> },
+    /// Only text: JOIN/PART and stray lines, lines read back with LOAD or recovered.
+    Text(String),
+}
+
+impl LogLine {
+    pub fn text(&self) -> &str {
+        match self {
+            LogLine::Event { text, .. } | LogLine::Text(text) => text,
+        }
+    }
+
+    pub fn record(&self) -> Option<&LogRecord> {
+        match self {
+            LogLine::Event { record, .. } => Some(record),
+            LogLine::Text(_) => None,
+        }
+    }
+}
+
+impl AsRef<str> for LogLine {
+    fn as_ref(&self) -> &str {
+        self.text()
+    }
+}
+
+impl PartialEq<&str> for LogLine {
+    fn eq(&self, other: &&str) -> bool {
+        self.text() == *other
+    }
+}
+
+/// Text form of the lines of `channel` in `store`, `None` if the channel has no log there.
+pub fn log_texts(store: &LogStore, channel: &str) -> Option<Vec<String>> {
+    store.lock().unwrap().get(channel).map(|lines| line_texts(lines))
+}
+
+pub fn line_texts(lines: &[LogLine]) -> Vec<String> {
+    lines.iter().map(|line| line.text().to_string()).collect()
+}
+
+pub struct LogBucket {
+    /// Part of the file name (`<channel>_<name>_<timestamp>.txt`) and of the SAVE summary.
+    pub name: &'static str,
+    /// What the entries are, for "Saved N <label> to <file>".
+    pub label: &'static str,
+    pub store: fn(&LoggerState) -> &LogStore,
+    /// File content for one channel.
+    pub format: fn(&str, &[String], &LoggerState) -> String,
+    /// Number of entries the lines stand for, shown after saving.
+    pub count: fn(&[String]) -> u64,
+    /// Whether the file starts with a UTF-8 BOM.
+    pub bom: bool,
+}
+
+pub static LOG_BUCKETS: &[LogBucket] = &[
+    LogBucket {
+        name: "msgs",
+        label: "messages",
+        store: |state| &state.logs,
+        format: format_message_log,
+        count: |lines| lines.len() as u64,
+        bom: true,
+    },
+    LogBucket {
+        name: "joins",
+        label: "JOIN/PART events",
+        store: |state| &state.join_logs,
+        format: |_, lines, _| lines.join("\n"),
+        count: event_count,
+        bom: false,
+    },
+    LogBucket {
+        name: "moderation",
+        label: "moderation events",
+        store: |state| &state.moderation_logs,
+        format: |_, lines, _| lines.join("\n"),
+        count: |lines| lines.len() as u64,
+        bom: false,
+    },
+    LogBucket {
+        name: "stray",
+        label: "stray messages",
+        store: |state| &state.stray_messages,
+        format: |_, lines, _| lines.join("\n"),
+        count: |lines| lines.len() as u64,
+        bom: false,
+    },
+];
+
+/// Add a line to the log of `bucket` (a `LogBucket::name`) and hand it to the sinks.
+/// Every log line goes through here.
+pub fn append_line(state: &LoggerState, bucket: &'static str, channel: &str, line: String) {
+    append_line_with(state, bucket, channel, line, false, None);
+}
+
+/// A `[<kind>] <text>` line the logger writes itself into the message log of `channel`.
+pub fn append_marker(state: &LoggerState, channel: &str, time_str: &str, kind: &str, text: &str) {
+    let record = LogRecord::marker(current_stamp().received, channel, kind, text);
+    append_line_with(state, "msgs", channel, format!("{} [{}] {}", time_str, kind, text), false, Some(record));
+}
+
+/// `append_line`, telling the sinks whether the chat text was normalized and handing
+/// them and the log the structured `record` of the entry.
+pub fn append_line_with(
+    state: &LoggerState,
+    bucket: &'static str,
+    channel: &str,
+    line: String,
+    normalized: bool,
+    record: Option<LogRecord>,
+) {
+    let Some(log_bucket) = LOG_BUCKETS.iter().find(|b| b.name == bucket) else {
+        return;
+    };
+    let stamp = current_stamp();
+    let record = record.map(Arc::new);
+    let entry = state.sinks.is_active().then(|| LogEntry {
+        bucket,
+        channel: channel.to_string(),
+        line: line.clone(),
+        normalized,
+        seq: stamp.seq,
+        received: stamp.received,
+        record: record.clone(),
+    });
+    if bucket == "msgs" {
+        record_first_line(state, channel, &line);
+    }
+    let keep = memory_limit(state, channel);
+    let dropped = {
+        let mut store = (log_bucket.store)(state).lock().unwrap();
+        let lines = store.entry(channel.to_string()).or_default();
+        let at = state.log_order.lock().unwrap().insert(bucket, channel, lines.len(), stamp.seq);
+        lines.insert(at, match record {
+            Some(record) => LogLine::Event { text: line, record },
+            None => LogLine::Text(line),
+        });
+        // Under the lock, so every sink gets the lines of a log in the order they were added
+        if let Some(entry) = entry {
+            state.sinks.dispatch(entry);
+        }
+        keep.map_or(0, |keep| trim_in_memory(lines, keep))
+    };
+    // Lines read back with LOAD are the oldest ones, they go first
+    if dropped > 0 && bucket == "msgs" {
+        *state.trimmed_lines.lock().unwrap().entry(channel.to_string()).or_default() += dropped;
+        if let Some(loaded) = state.loaded_lines.lock().unwrap().get_mut(channel) {
+            *loaded = loaded.saturating_sub(dropped);
+        }
+    }
+    state.unsaved.lock().unwrap().record(channel, Instant::now());
+}
+
+impl LogBucket {
+    /// The message log is the main file: a custom name replaces "msgs" instead of being added.
+    pub fn file_name(&self, dir: &Path, template: &str, channel: &str, custom_name: Option<&str>, timestamp: &str, extension: &str) -> String {
+        let suffix = match custom_name {
+            Some(name) if self.name == "msgs" => name.to_string(),
+            Some(name) => format!("{}_{}", name, self.name),
+            None => self.name.to_string(),
+        };
+        dir.join(render_file_name(template, channel, &suffix, timestamp, extension)).display().to_string()
+    }
+}
+
+/// RFC 3339 "Session started: ..." line, plus "Session ended: ..." in the save at shutdown.
+fn session_times(state: &LoggerState) -> String {
+    let mut lines = format!("Session started: {}\n", SESSION_START.to_rfc3339());
+    if let Some(end) = *state.session_end.lock().unwrap() {
+        lines.push_str(&format!("Session ended: {}\n", end.to_rfc3339()));
+    }
+    lines
+}
+
+/// "event: <tag>" line if the channel has a `TAG`.
+fn header_event(channel: &str, state: &LoggerState) -> String {
+    match state.session_tags.lock().unwrap().for_channel(channel) {
+        Some(tag) => format!("event: {}\n", tag),
+        None => String::new(),
+    }
+}
+
+/// "(longest: ...)" and "(most repeated: ...)" lines with `header_records`.
+fn header_records(channel: &str, state: &LoggerState) -> String {
+    if !state.settings.lock().unwrap().header_records.value {
+        return String::new();
+    }
+    let records = state.message_records.lock().unwrap().get(channel).map(|r| r.snapshot()).unwrap_or_default();
+    records.lines()
+    .into_iter()
+    .map(|line| format!("({})\n", line))
+    .collect()
+}
+
+/// Header (see `HeaderFormat`) and numbered lines.
+fn format_message_log(channel: &str, messages: &[String], state: &LoggerState) -> String {
+    let numbered_messages = messages
+    .iter()
+    .enumerate()
+    .map(|(i, line)| format!("{}. {}", i + 1, line))
+    .collect::<Vec<_>>()
+    .join("\n");
+
+    format!("{}{}", format_message_header(channel, messages, state), numbered_messages)
+}
+
+/// Header of the message log, with the counts of `messages`.
+pub fn format_message_header(channel: &str, messages: &[String], state: &LoggerState) -> String {
+    let stats = compute_channel_stats(channel, messages);
+
+    let log_header = state.settings.lock().unwrap().log_header.value;
+    match log_header {
+        HeaderFormat::Full => format!(
+            "--- Message/Event Log --- ({})\n# {}\n{}{}({} messages from {} chatters)\n({} Banns, Deletions, and Timeouts)\n({} Subs/Giftsubs)\n({} Raids)\n{}",
+                             build_info(),
+                             channel,
+                             header_event(channel, state),
+                             session_times(state),
+                             stats.message_count,
+                             stats.unique_chatters,
+                             stats.moderation_events,
+                             stats.sub_events,
+                             stats.raid_events,
+                             header_records(channel, state),
+        ),
+        HeaderFormat::Minimal => format!(
+            "# {}: {} messages ({} chatters)\n",
+            channel,
+            stats.message_count,
+            stats.unique_chatters
+        ),
+        HeaderFormat::None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Local};
+
+    #[test]
+    fn full_header_has_session_times() {
+        let state = LoggerState::default();
+        let log = format_message_log("forsen", &[], &state);
+        let started = log.lines().find_map(|l| l.strip_prefix("Session started: ")).unwrap();
+        assert!(DateTime::parse_from_rfc3339(started).is_ok());
+        assert!(!log.contains("Session ended"));
+
+        *state.session_end.lock().unwrap() = Some(Local::now());
+        let log = format_message_log("forsen", &[], &state);
+        let ended = log.lines().find_map(|l| l.strip_prefix("Session ended: ")).unwrap();
+        assert!(DateTime::parse_from_rfc3339(ended).is_ok());
+    }
+
+    #[test]
+    fn full_header_has_the_tag() {
+        let state = LoggerState::default();
+        state.session_tags.lock().unwrap().session = Some("dreamhack_day2".to_string());
+        let log = format_message_log("forsen", &[], &state);
+        assert_eq!(log.lines().nth(2), Some("event: dreamhack_day2"));
+    }
+}
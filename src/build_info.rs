@@ -0,0 +1,21 @@
+/// "0.1.0 (abc1234, built 2025-08-14)"
+pub const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("TWL_GIT_HASH"),
+    ", built ",
+    env!("TWL_BUILD_DATE"),
+    ")"
+);
+
+/// Compiled-in alert backends.
+pub const FEATURES: &str = if cfg!(unix) {
+    "sound: rodio, notifications: notify-rust"
+} else {
+    "sound: none, notifications: notify-rust"
+};
+
+/// One line for the startup output and the VERSION command.
+pub fn build_info() -> String {
+    format!("{} {} [{}]", env!("CARGO_PKG_NAME"), VERSION, FEATURES)
+}
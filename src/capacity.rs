@@ -0,0 +1,59 @@
+//! How many channels are joined, against the `channel_warnings` thresholds and the
+//! `channel_cap`. The client opens another connection every 90 channels on its own, but
+//! a few hundred channels by mistake get the connections throttled by Twitch.
+
+use crate::state::LoggerState;
+
+/// Whether a JOIN going from `before` to `after` channels may go ahead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JoinCheck {
+    Allowed {
+        /// Thresholds reached by this JOIN, to warn about.
+        warnings: Vec<usize>,
+    },
+    /// Beyond the cap, needs CONFIRM.
+    OverCap { cap: usize },
+}
+
+pub fn check_join(before: usize, after: usize, warnings: &[usize], cap: usize, confirmed: bool) -> JoinCheck {
+    if cap > 0 && after > cap && after > before && !confirmed {
+        return JoinCheck::OverCap { cap };
+    }
+    let warnings = warnings.iter().copied().filter(|&w| before < w && w <= after).collect();
+    JoinCheck::Allowed { warnings }
+}
+
+/// `check_join` with the joined channels and settings of `state`, for joining `new`
+/// channels that are not joined yet.
+pub fn check_join_of(state: &LoggerState, new: usize, confirmed: bool) -> JoinCheck {
+    let before = state.channels.lock().unwrap().len();
+    let settings = state.settings.lock().unwrap();
+    check_join(before, before + new, &settings.channel_warnings.value, settings.channel_cap.value, confirmed)
+}
+
+/// The warning printed when `threshold` channels are reached.
+pub fn threshold_warning(threshold: usize) -> String {
+    format!("⚠️ {} channels joined now, Twitch may throttle the connections with too many", threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_once_per_threshold_crossed() {
+        assert_eq!(check_join(10, 49, &[50, 100], 0, false), JoinCheck::Allowed { warnings: vec![] });
+        assert_eq!(check_join(49, 50, &[50, 100], 0, false), JoinCheck::Allowed { warnings: vec![50] });
+        assert_eq!(check_join(50, 51, &[50, 100], 0, false), JoinCheck::Allowed { warnings: vec![] });
+        assert_eq!(check_join(10, 120, &[50, 100], 0, false), JoinCheck::Allowed { warnings: vec![50, 100] });
+    }
+
+    #[test]
+    fn going_over_the_cap_needs_confirmation() {
+        assert_eq!(check_join(100, 300, &[], 150, false), JoinCheck::OverCap { cap: 150 });
+        assert_eq!(check_join(100, 300, &[], 150, true), JoinCheck::Allowed { warnings: vec![] });
+        assert_eq!(check_join(100, 150, &[], 150, false), JoinCheck::Allowed { warnings: vec![] });
+        // No cap
+        assert_eq!(check_join(100, 300, &[], 0, false), JoinCheck::Allowed { warnings: vec![] });
+    }
+}
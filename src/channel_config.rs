@@ -1,65 +1,263 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Result, anyhow};
 use owo_colors::OwoColorize;
+use regex::Regex;
 
-#[derive(Debug)]
+use crate::console::{print_status, strip_ansi};
+use crate::membership::MembershipMode;
+use crate::redaction::parse_pattern;
+use crate::settings::parse_bool;
+use crate::sound::SoundProfile;
+use crate::state::LoggerState;
+
+#[derive(Debug, Clone, Default)]
 pub struct ChannelInfo {
     pub color: Option<String>, // Optional named color
+    pub members: Option<MembershipMode>, // JOIN/PART logging mode (`members=...`)
+    pub encrypt: bool, // Seal saved logs and journal lines (`encrypt=true`)
+    pub sound_cooldown_ms: u64, // Least time between two SOUND beeps (`sound_cooldown=2000`), 0 for none
+    pub sound: Option<SoundProfile>, // Own beep (`name:color:440:200`, Hz and ms), none for the shared one
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ChannelConfig {
     pub default_channels: Vec<String>,
     pub vips: HashMap<String, ChannelInfo>,
+    pub settings: HashMap<String, String>, // Global `key = value` lines
+    pub redaction: Vec<(String, Regex)>, // `name = pattern` lines of the `[redaction]` section
+    pub sound_path: Option<PathBuf>, // MP3/WAV played instead of the beep (`sound_path = alert.mp3`)
+}
+
+impl ChannelConfig {
+    /// Raw value of a global `key = value` setting.
+    pub fn setting(&self, key: &str) -> Option<&str> {
+        self.settings.get(key).map(String::as_str)
+    }
+}
+
+/// What `parse_channel_config` found besides the config itself.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConfigSummary {
+    pub defaults: usize,
+    pub vips: usize,
+    pub duplicates: usize,
+    /// Problems that did not stop the file from loading, with line numbers.
+    pub warnings: Vec<String>,
+}
+
+impl ConfigSummary {
+    /// "7 defaults, 23 VIPs, 2 duplicates ignored"
+    pub fn totals(&self) -> String {
+        let mut totals = format!("{} defaults, {} VIPs", self.defaults, self.vips);
+        if self.duplicates > 0 {
+            totals.push_str(&format!(", {} duplicates ignored", self.duplicates));
+        }
+        totals
+    }
+}
+
+/// Load channel configuration from file, see `parse_channel_config`. Warnings and the
+/// totals are printed.
+pub fn load_channel_config(path: impl AsRef<Path>) -> Result<ChannelConfig> {
+    let content = fs::read_to_string(&path)?;
+    let (mut config, summary) = parse_channel_config(&content)?;
+    // A relative sound file sits next to channels.txt
+    if let (Some(sound), Some(dir)) = (&config.sound_path, path.as_ref().parent()) {
+        config.sound_path = Some(dir.join(sound));
+    }
+    for warning in &summary.warnings {
+        eprintln!("⚠️ channels.txt: {}", warning);
+    }
+    print_status(&format!("channels.txt: {}", summary.totals()));
+    Ok(config)
 }
 
-/// Load channel configuration from file.
 /// First line = number of default channels (N).
-/// Next N lines = default channels (also VIPs).
-/// Remaining lines = additional VIPs.
-pub fn load_channel_config(path: &str) -> Result<ChannelConfig> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file).lines().filter_map(Result::ok);
-
-    let default_count: usize = reader
-    .next()
-    .ok_or_else(|| anyhow!("Missing first line for default count"))?
-    .trim()
+/// Next N channel lines = default channels (also VIPs).
+/// Remaining channel lines = additional VIPs.
+///
+/// Channel lines look like `name[:color[:hz[:ms]]] [key=value ...]`, e.g. `somechannel:red:440:200 members=counts-only encrypt=true sound_cooldown=2000`;
+/// the frequency and duration give the channel its own beep (duration 150 ms if left out).
+/// Lines of the form `key = value` are global settings, `sound_path = <file>` the alert
+/// sound. Empty lines and `#` comments are skipped, a channel listed twice only counts
+/// the first time.
+///
+/// A `[redaction]` line starts the section of redaction patterns, `name = regex` per line
+/// up to the end of the file; invalid patterns are left out with a warning.
+pub fn parse_channel_config(content: &str) -> Result<(ChannelConfig, ConfigSummary)> {
+    let mut lines = content.trim_start_matches('\u{feff}').lines().enumerate().map(|(i, line)| (i + 1, line));
+
+    let first = lines.next().map(|(_, line)| line.trim()).unwrap_or_default();
+    let default_count: usize = first
     .parse()
-    .map_err(|e| anyhow!("Invalid number on first line: {e}"))?;
+    .map_err(|_| anyhow!("line 1: expected the number of default channels, got '{}'", first))?;
 
     let mut default_channels = Vec::new();
     let mut vips = HashMap::new();
+    let mut settings = HashMap::new();
+    let mut redaction = Vec::new();
+    let mut sound_path = None;
+    let mut summary = ConfigSummary::default();
+    // Line each channel was first listed on
+    let mut seen: HashMap<String, usize> = HashMap::new();
 
-    for (i, line) in reader.enumerate() {
+    while let Some((line_number, line)) = lines.next() {
         let line = line.trim();
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
 
-        let mut parts = line.splitn(2, ':');
-        let name = parts.next().unwrap().trim().to_string();
-        let color = parts.next().map(|c| c.trim().to_string());
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let in_redaction = section.trim().eq_ignore_ascii_case("redaction");
+            if !in_redaction {
+                summary.warnings.push(format!("line {}: unknown section [{}], ignoring the lines after it", line_number, section.trim()));
+            }
+            // The lines up to the end belong to the section
+            for (line_number, line) in lines.by_ref() {
+                let line = line.trim();
+                if !in_redaction || line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let Some((name, pattern)) = line.split_once('=') else {
+                    summary.warnings.push(format!("line {}: expected name = pattern in [redaction]", line_number));
+                    continue;
+                };
+                match parse_pattern(name.trim(), pattern.trim()) {
+                    Ok(regex) => redaction.push((name.trim().to_string(), regex)),
+                    Err(e) => summary.warnings.push(format!("line {}: {}", line_number, e)),
+                }
+            }
+            break;
+        }
 
-        if i < default_count {
+        if let Some((key, value)) = line.split_once('=') {
+            let key = key.trim();
+            if key.eq_ignore_ascii_case("sound_path") {
+                let value = value.trim().trim_matches('"');
+                sound_path = (!value.is_empty()).then(|| PathBuf::from(value));
+                continue;
+            }
+            if !key.contains(char::is_whitespace) && !key.contains(':') {
+                settings.insert(key.to_lowercase(), value.trim().trim_matches('"').to_string());
+                continue;
+            }
+        }
+
+        let mut tokens = line.split_whitespace();
+        let mut parts = tokens.next().unwrap_or_default().split(':');
+        let name = parts.next().unwrap_or_default().trim().to_lowercase();
+        let color = parts.next().map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+        let sound = parse_sound_profile(parts.next(), parts.next());
+        if name.is_empty() {
+            summary.warnings.push(format!("line {}: no channel name, ignored", line_number));
+            continue;
+        }
+        if let Some(first_line) = seen.get(&name) {
+            summary.warnings.push(format!("line {}: {} is already listed on line {}, ignored", line_number, name, first_line));
+            summary.duplicates += 1;
+            continue;
+        }
+        seen.insert(name.clone(), line_number);
+
+        let sound = match sound {
+            Ok(sound) => sound,
+            Err(e) => {
+                summary.warnings.push(format!("line {}: {}: {}, using the shared beep", line_number, name, e));
+                None
+            }
+        };
+        let mut info = ChannelInfo { color, members: None, encrypt: false, sound_cooldown_ms: 0, sound };
+        for attr in tokens {
+            match attr.split_once('=') {
+                Some(("members", mode)) => match mode.parse() {
+                    Ok(mode) => info.members = Some(mode),
+                    Err(e) => summary.warnings.push(format!("line {}: {}: {}", line_number, name, e)),
+                },
+                Some(("encrypt", value)) => match parse_bool("encrypt", value) {
+                    Ok(encrypt) => info.encrypt = encrypt,
+                    Err(e) => summary.warnings.push(format!("line {}: {}: {}", line_number, name, e)),
+                },
+                Some(("sound_cooldown", ms)) => match ms.parse() {
+                    Ok(ms) => info.sound_cooldown_ms = ms,
+                    Err(_) => summary.warnings.push(format!("line {}: {}: sound_cooldown needs milliseconds, got '{}'", line_number, name, ms)),
+                },
+                _ => summary.warnings.push(format!("line {}: {}: ignoring unknown option '{}'", line_number, name, attr)),
+            }
+        }
+
+        if default_channels.len() < default_count {
             default_channels.push(name.clone());
         }
 
-        vips.insert(name, ChannelInfo { color });
+        vips.insert(name, info);
+    }
+
+    if default_channels.len() < default_count {
+        summary.warnings.push(format!(
+            "line 1 declares {} default channels, but only {} channels follow",
+            default_count,
+            default_channels.len()
+        ));
+    } else if default_count == 0 && !vips.is_empty() {
+        summary.warnings.push("line 1 declares 0 default channels, all listed channels are VIPs only".to_string());
     }
+    summary.defaults = default_channels.len();
+    summary.vips = vips.len();
 
-    Ok(ChannelConfig {
+    Ok((ChannelConfig {
         default_channels,
        vips,
-    })
+       settings,
+       redaction,
+       sound_path,
+    }, summary))
+}
+
+/// Sound profile of the `:hz:ms` fields of a channel line, `None` without them.
+fn parse_sound_profile(freq: Option<&str>, duration: Option<&str>) -> Result<Option<SoundProfile>, String> {
+    let Some(freq) = freq.map(str::trim).filter(|f| !f.is_empty()) else {
+        return Ok(None);
+    };
+    let freq_hz: f32 = freq.parse().ok().filter(|f: &f32| f.is_finite() && *f > 0.0)
+    .ok_or_else(|| format!("sound frequency needs Hz, got '{}'", freq))?;
+    let duration_ms = match duration.map(str::trim) {
+        Some(ms) => ms.parse().ok().filter(|ms| *ms > 0).ok_or_else(|| format!("sound duration needs milliseconds, got '{}'", ms))?,
+        None => SoundProfile::default().duration_ms,
+    };
+    Ok(Some(SoundProfile { freq_hz, duration_ms }))
+}
+
+/// Colors for channels without one in channels.txt: readable on dark and light
+/// backgrounds, apart from each other, and none close to the red of moderation events.
+const AUTO_PALETTE: &[&str] = &[
+    "#5fafff", "#5fd7af", "#87d75f", "#d7af5f", "#af87ff", "#5fd7ff",
+    "#d7d75f", "#87afd7", "#afd787", "#d787d7", "#87d7d7", "#afafff",
+];
+
+/// Palette color of `channel`, the same in every session (FNV-1a, unlike `DefaultHasher`
+/// stable across Rust versions).
+pub fn auto_color(channel: &str) -> &'static str {
+    let hash = channel.to_lowercase().bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    AUTO_PALETTE[(hash % AUTO_PALETTE.len() as u64) as usize]
+}
+
+/// Color of `channel` for `apply_named_color`: the one from channels.txt, otherwise its
+/// `auto_color` unless `auto_colors` is off.
+pub fn channel_color(state: &LoggerState, channel: &str) -> Option<String> {
+    match state.vip_info(channel).and_then(|info| info.color) {
+        Some(color) => Some(color),
+        None if state.settings.lock().unwrap().auto_colors.value => Some(auto_color(channel).to_string()),
+        None => None,
+    }
 }
 
 /// Apply a named color to a string using owo-colors.
 /// Falls back to cyan if unknown or not provided.
-
 pub fn apply_named_color(text: &str, color_name: Option<&str>) -> String {
     match color_name.map(str::to_lowercase).as_deref() {
         Some("red") => format!("{}", text.red().bold()),
@@ -84,3 +282,131 @@ pub fn apply_named_color(text: &str, color_name: Option<&str>) -> String {
     }
 }
 
+
+/// Pad or truncate a channel name to `width` characters for aligned console columns.
+pub fn fit_to_width(name: &str, width: usize) -> String {
+    let len = name.chars().count();
+    if width == 0 || len == width {
+        name.to_string()
+    } else if len < width {
+        format!("{}{}", name, " ".repeat(width - len))
+    } else {
+        let mut short: String = name.chars().take(width.saturating_sub(1)).collect();
+        short.push('…');
+        short
+    }
+}
+
+/// Number of characters shown on the console, ignoring color escape codes.
+pub fn visible_width(line: &str) -> usize {
+    strip_ansi(line).chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_count_channel_lines_only() {
+        let (config, summary) = parse_channel_config(
+            "\u{feff}2\n# defaults\nforsen:red\n\nlog_header = minimal\nsound_path = \"sounds/ding.mp3\"\nXqcow members=off sound_cooldown=1500\npajlada encrypt=true\nforsen:blue\n",
+        ).unwrap();
+        assert_eq!(config.default_channels, vec!["forsen", "xqcow"]);
+        assert_eq!(config.vips["forsen"].color.as_deref(), Some("red"));
+        assert_eq!(config.setting("log_header"), Some("minimal"));
+        assert_eq!(config.sound_path, Some(PathBuf::from("sounds/ding.mp3")));
+        assert!(config.vips["pajlada"].encrypt && !config.vips["forsen"].encrypt);
+        assert_eq!((config.vips["xqcow"].sound_cooldown_ms, config.vips["forsen"].sound_cooldown_ms), (1500, 0));
+        assert_eq!(config.vips["forsen"].sound, None);
+        assert_eq!(summary.totals(), "2 defaults, 3 VIPs, 1 duplicates ignored");
+        assert_eq!(summary.warnings, vec!["line 9: forsen is already listed on line 3, ignored"]);
+    }
+
+    #[test]
+    fn sound_path_is_relative_to_channels_txt() {
+        let dir = std::env::temp_dir().join(format!("channel_config_sound_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let channels = dir.join("channels.txt");
+
+        // The sound file need not exist yet, the sound thread falls back to the beep
+        fs::write(&channels, "1\nforsen\nsound_path = sounds/ding.mp3\n").unwrap();
+        let config = load_channel_config(&channels).unwrap();
+        assert_eq!(config.sound_path, Some(dir.join("sounds/ding.mp3")));
+
+        let absolute = std::env::temp_dir().join("ding.wav");
+        fs::write(&channels, format!("1\nforsen\nsound_path = {}\n", absolute.display())).unwrap();
+        assert_eq!(load_channel_config(&channels).unwrap().sound_path, Some(absolute));
+
+        fs::write(&channels, "1\nforsen\n").unwrap();
+        assert_eq!(load_channel_config(&channels).unwrap().sound_path, None);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(load_channel_config(&channels).is_err());
+    }
+
+    #[test]
+    fn channels_without_a_color_get_a_stable_one() {
+        assert_eq!(auto_color("forsen"), auto_color("Forsen"));
+        let colors: std::collections::HashSet<&str> = ["forsen", "xqcow", "pajlada", "zneix", "nymn"].iter().map(|c| auto_color(c)).collect();
+        assert!(colors.len() > 1);
+
+        let state = LoggerState::default();
+        assert_eq!(channel_color(&state, "forsen").as_deref(), Some(auto_color("forsen")));
+        state.settings.lock().unwrap().auto_colors.value = false;
+        assert_eq!(channel_color(&state, "forsen"), None);
+    }
+
+    #[test]
+    fn count_larger_than_the_list_warns() {
+        let (config, summary) = parse_channel_config("5\nforsen\nxqcow\n").unwrap();
+        assert_eq!(config.default_channels.len(), 2);
+        assert_eq!(summary.warnings, vec!["line 1 declares 5 default channels, but only 2 channels follow"]);
+    }
+
+    #[test]
+    fn zero_defaults_with_channels_warns() {
+        let (config, summary) = parse_channel_config("0\nforsen\n").unwrap();
+        assert!(config.default_channels.is_empty());
+        assert_eq!(summary.warnings.len(), 1);
+        assert!(parse_channel_config("0\n").unwrap().1.warnings.is_empty());
+    }
+
+    #[test]
+    fn bad_first_line_is_an_error() {
+        for content in ["", "forsen\nxqcow\n", "-1\n", "two\n"] {
+            let error = parse_channel_config(content).unwrap_err().to_string();
+            assert!(error.starts_with("line 1:"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn malformed_channel_lines_are_skipped() {
+        let (config, summary) = parse_channel_config("1\n:red\nforsen members=sometimes colour=red\n").unwrap();
+        assert_eq!(config.default_channels, vec!["forsen"]);
+        assert_eq!(summary.warnings.len(), 3);
+        assert!(summary.warnings[0].starts_with("line 2:"));
+    }
+
+    #[test]
+    fn channels_can_have_their_own_beep() {
+        let (config, summary) = parse_channel_config("0\njanistantv:red:440:200\nforsen::880\nxqcow:blue:loud\n").unwrap();
+        assert_eq!(config.vips["janistantv"].sound, Some(SoundProfile { freq_hz: 440.0, duration_ms: 200 }));
+        assert_eq!(config.vips["janistantv"].color.as_deref(), Some("red"));
+        assert_eq!(config.vips["forsen"].sound, Some(SoundProfile { freq_hz: 880.0, duration_ms: 150 }));
+        assert_eq!(config.vips["forsen"].color, None);
+        assert_eq!((config.vips["xqcow"].sound, config.vips["xqcow"].color.as_deref()), (None, Some("blue")));
+        assert_eq!(summary.warnings[0], "line 4: xqcow: sound frequency needs Hz, got 'loud', using the shared beep");
+    }
+
+    #[test]
+    fn redaction_section_ends_the_channel_list() {
+        let (config, summary) = parse_channel_config("1\nforsen\n[redaction]\nphone = \\+\\d{6,}\nbroken = (\nxqcow\n").unwrap();
+        assert_eq!(config.vips.keys().collect::<Vec<_>>(), ["forsen"]);
+        assert_eq!(config.redaction.len(), 1);
+        assert_eq!(config.redaction[0].0, "phone");
+        assert_eq!(summary.warnings.len(), 2);
+        assert!(summary.warnings[0].starts_with("line 5: broken: invalid pattern"), "{}", summary.warnings[0]);
+        assert_eq!(summary.warnings[1], "line 6: expected name = pattern in [redaction]");
+    }
+}
@@ -0,0 +1,106 @@
+//! `--channel-file`: channels listed in a plain text file are joined at startup, and
+//! the file is watched afterwards. A channel added to it is JOINed, a removed one PARTed,
+//! so an external program can manage the channels by writing the file.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use twitch_irc::login::LoginCredentials;
+use twitch_irc::transport::Transport;
+use twitch_irc::TwitchIRCClient;
+
+use crate::console::print_status;
+use crate::startup::{join_channel, part_channel};
+use crate::state::LoggerState;
+
+/// One channel per line, `#` starts a comment. Names are lowercased.
+pub fn parse_channel_file(content: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    content
+    .lines()
+    .map(|line| line.split('#').next().unwrap_or("").trim())
+    .filter(|line| !line.is_empty())
+    .map(|line| line.to_lowercase())
+    .filter(|channel| seen.insert(channel.clone()))
+    .collect()
+}
+
+pub fn read_channel_file(path: &Path) -> io::Result<Vec<String>> {
+    Ok(parse_channel_file(&fs::read_to_string(path)?))
+}
+
+/// Channels added to and removed from the file between two reads.
+pub fn diff_channels(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let added = new.iter().filter(|c| !old.contains(c)).cloned().collect();
+    let removed = old.iter().filter(|c| !new.contains(c)).cloned().collect();
+    (added, removed)
+}
+
+/// Watch `path` until the returned watcher is dropped. `initial` is the content the
+/// startup joins were based on. Channels joined or parted by hand are left alone unless
+/// their line in the file changes.
+pub fn watch_channel_file<T: Transport, L: LoginCredentials>(
+    path: PathBuf,
+    initial: Vec<String>,
+    client: TwitchIRCClient<T, L>,
+    state: LoggerState,
+) -> notify::Result<RecommendedWatcher> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let file_name = path.file_name().map(|n| n.to_os_string());
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let ours = event.paths.iter().any(|p| p.file_name().map(|n| n.to_os_string()) == file_name);
+            if ours && matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                let _ = tx.send(());
+            }
+        }
+    })?;
+    // Editors often replace the file instead of writing it, so the directory is watched
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    tokio::spawn(async move {
+        let mut current = initial;
+        while rx.recv().await.is_some() {
+            let channels = match read_channel_file(&path) {
+                Ok(channels) => channels,
+                // Half way through being replaced, the next event brings the new file
+                Err(_) => continue,
+            };
+            let (added, removed) = diff_channels(&current, &channels);
+            for channel in &added {
+                join_channel(&client, &state, channel);
+                print_status(&format!("{}: joined {}", path.display(), channel));
+            }
+            for channel in &removed {
+                part_channel(&client, &state, channel);
+                print_status(&format!("{}: parted from {}", path.display(), channel));
+            }
+            current = channels;
+        }
+    });
+
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_blank_lines_and_duplicates_are_skipped() {
+        let content = "# managed by the bot\nforsen\n\n  #xqcow  \nPajlada # streams at night\nforsen\n";
+        assert_eq!(parse_channel_file(content), vec!["forsen", "pajlada"]);
+    }
+
+    #[test]
+    fn changes_become_joins_and_parts() {
+        let old = vec!["forsen".to_string(), "pajlada".to_string()];
+        let new = vec!["pajlada".to_string(), "xqcow".to_string()];
+        assert_eq!(diff_channels(&old, &new), (vec!["xqcow".to_string()], vec!["forsen".to_string()]));
+    }
+}
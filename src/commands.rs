@@ -0,0 +1,1020 @@
+//! The interactive commands (JOIN, SAVE, REPORT, ...). `CommandSession::execute` runs
+//! one input line against the logger state; the binary only reads the lines and wires
+//! up the client, so the commands can be driven without a terminal.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::time::Duration;
+
+use owo_colors::OwoColorize;
+use tokio::runtime::Handle;
+use twitch_irc::login::{LoginCredentials, StaticLoginCredentials};
+use twitch_irc::transport::Transport;
+use twitch_irc::{ClientConfig, TwitchIRCClient};
+
+use crate::alltime::{alltime_stats_file, alltime_totals, format_alltime, save_alltime};
+use crate::alert_mode::{is_on, set_alert_mode, set_alert_mode_all, AlertMode};
+use crate::autosave::format_autosave;
+use crate::banner::print_banner;
+use crate::buckets::{line_texts, log_texts};
+use crate::build_info;
+use crate::capacity::{check_join_of, threshold_warning, JoinCheck};
+use crate::channel_config::{apply_named_color, channel_color};
+use crate::console;
+use crate::console_println;
+use crate::diag::format_unknown_messages;
+use crate::encryption::SEALED_EXTENSION;
+use crate::heatmap::{format_heatmap, heatmap_json};
+use crate::helix::{followed_channels, HelixCredentials};
+use crate::incident::format_duration;
+use crate::journal::remove_session_journal;
+use crate::lists::{apply_lists, load_lists, save_lists};
+use crate::load::load_log;
+use crate::membership::{configured_mode, set_membership_mode, MembershipMode};
+use crate::prune::{format_join_failures, prune_config_file, NOT_CONFIRMED};
+use crate::pauses::{find_pauses, format_pauses, DEFAULT_PAUSE_MINUTES};
+use crate::query::{between, parse_time_arg, search, since, tail, SearchPattern};
+use crate::raids::raids_of;
+use crate::redaction::format_redaction_hits;
+use crate::rate_limiter::{TokenBucket, JOIN_CAPACITY, JOIN_RATE};
+use crate::report::{build_report, format_report, save_report};
+use crate::save::{daily_autosave, log_dir, open_file, save_logs, save_stats_json, LogFormat};
+use crate::session_report::{save_session_report, session_report};
+use crate::settings::{format_setting, Source, SETTING_KEYS};
+use crate::sqlite_log::session_lines;
+use crate::startup::{join_channel, part_channel};
+use crate::state::{config_file_name, LoggerState, CONFIG_FILE};
+use crate::status_bar;
+use crate::tags::{format_tags, sanitize_label, save_session_tags};
+use crate::stats::{compute_channel_stats, format_channel_stats, format_latency, format_notification_stats, format_user_counts};
+use crate::subs::format_subs;
+use crate::timestamps::{display_log_line, styled_log_line};
+use crate::vip_import::{import_vips, load_vip_import, save_imported_vips, validate_names, VipImport};
+use crate::vip_incidents::{format_vip_incidents, save_vip_incidents};
+use crate::vip_parts::release_parts;
+use crate::vip_visits::save_vip_join_counts;
+
+/// Names offered by tab completion.
+pub const COMMANDS: &[&str] = &[
+    "JOIN", "PART", "SOUND", "SAVE", "NOTIFY", "EXIT", "RECONNECT", "PAUSES", "STATS", "MEMBERS", "VERSION",
+    "SINCE", "BETWEEN", "TAIL", "USERS", "REPORT", "OPEN", "SLEEP", "CONFIG", "RAIDS", "COUNTUP", "LISTS",
+    "SINKS", "LOAD", "TIMEFMT", "DIAG", "TAG", "LIST", "STATUSBAR", "PRUNE", "MUTE", "SAY", "SAYQUEUE", "HEATMAP", "SEARCH",
+    "SUMMARY", "VIP", "SUBS", "AUTOSAVE",
+];
+
+const DEFAULT_PROMPT: &str = ">> ";
+/// Prompt while SEARCH output is paged.
+const MORE_PROMPT: &str = "--more-- ";
+/// SEARCH lines shown at once.
+const SEARCH_PAGE: usize = 20;
+/// Lines TAIL shows before following the channel, and at most.
+const TAIL_LINES: usize = 20;
+const MAX_TAIL_LINES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    /// EXIT: the session is saved, the input loop should end.
+    Exit,
+}
+
+/// What the commands keep between input lines besides the logger state.
+pub struct CommandSession<T: Transport, L: LoginCredentials> {
+    client: TwitchIRCClient<T, L>,
+    state: LoggerState,
+    join_limiter: TokenBucket,
+    /// For the client's async calls; `None` outside a runtime.
+    runtime: Option<Handle>,
+    /// Changed by TAIL.
+    prompt: String,
+    /// SEARCH output not shown yet, and the prompt to go back to after it.
+    more: VecDeque<String>,
+    prompt_before_more: String,
+}
+
+impl<T: Transport, L: LoginCredentials> CommandSession<T, L> {
+    pub fn new(client: TwitchIRCClient<T, L>, state: LoggerState) -> Self {
+        CommandSession {
+            client,
+            state,
+            join_limiter: TokenBucket::new(JOIN_CAPACITY, JOIN_RATE),
+            runtime: Handle::try_current().ok(),
+            prompt: DEFAULT_PROMPT.to_string(),
+            more: VecDeque::new(),
+            prompt_before_more: DEFAULT_PROMPT.to_string(),
+        }
+    }
+
+    /// Drop what is left of the SEARCH output and go back to the previous prompt.
+    fn end_more(&mut self) {
+        self.more.clear();
+        if self.prompt == MORE_PROMPT {
+            self.prompt = std::mem::take(&mut self.prompt_before_more);
+        }
+    }
+
+    /// Print up to `SEARCH_PAGE` lines of the pending output, with a `--more--` prompt if
+    /// some are left.
+    fn print_page(&mut self) {
+        for line in self.more.drain(..SEARCH_PAGE.min(self.more.len())) {
+            console_println!("{}", line);
+        }
+        if self.more.is_empty() {
+            self.end_more();
+        } else {
+            if self.prompt != MORE_PROMPT {
+                self.prompt_before_more = std::mem::replace(&mut self.prompt, MORE_PROMPT.to_string());
+            }
+            console_println!("{}", format!("--more-- ({} left, Enter for the next {}, q to stop)", self.more.len(), SEARCH_PAGE).dimmed());
+        }
+    }
+
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    /// Run one input line.
+    pub fn execute(&mut self, input: &str) -> Flow {
+        // At a --more-- prompt Enter shows the next page, anything else ends the paging
+        if !self.more.is_empty() {
+            if input.trim().is_empty() {
+                self.print_page();
+                return Flow::Continue;
+            }
+            self.end_more();
+            if input.trim().eq_ignore_ascii_case("q") {
+                return Flow::Continue;
+            }
+        }
+        let parts: Vec<&str> = input.split_whitespace().collect();
+        if parts.is_empty() {
+            return Flow::Continue;
+        }
+
+        let cmd = parts[0].to_uppercase();
+        let arg = parts.get(1).map(|s| s.to_string());
+
+        match cmd.as_str() {
+            "JOIN" => {
+                // JOIN <channel> [channel...] [CONFIRM]
+                let confirmed = parts.len() > 2 && parts[parts.len() - 1].eq_ignore_ascii_case("CONFIRM");
+                let requested = &parts[1..parts.len() - usize::from(confirmed)];
+                if requested.is_empty() {
+                    console_println!("Usage: JOIN <channel> [channel...] [CONFIRM]");
+                    return Flow::Continue;
+                }
+                let mut new: Vec<String> = Vec::new();
+                {
+                    let joined = self.state.channels.lock().unwrap();
+                    for channel in requested {
+                        if !joined.iter().any(|c| c == channel) && !new.iter().any(|c| c == channel) {
+                            new.push(channel.to_string());
+                        }
+                    }
+                }
+                let before = self.state.channels.lock().unwrap().len();
+                let warnings = match check_join_of(&self.state, new.len(), confirmed) {
+                    JoinCheck::OverCap { cap } => {
+                        console_println!(
+                            "{}",
+                            format!("Joining {} more would make {} channels, over channel_cap {}. Add CONFIRM to join anyway", new.len(), before + new.len(), cap).yellow()
+                        );
+                        return Flow::Continue;
+                    }
+                    JoinCheck::Allowed { warnings } => warnings,
+                };
+                for channel in requested.iter().filter(|c| !new.iter().any(|n| n == *c)) {
+                    console_println!("{}", format!("Already joined {}", channel).dimmed());
+                }
+                for channel in &new {
+                    if !self.join_limiter.try_consume() {
+                        console_println!("{}", format!("Rate limited — try again in {:.1}s", self.join_limiter.retry_after().as_secs_f64()).yellow());
+                        break;
+                    }
+                    join_channel(&self.client, &self.state, channel);
+                    console_println!("Joined {}", channel.green());
+                }
+                // Only the thresholds actually reached, the rate limit may have stopped early
+                let now = self.state.channels.lock().unwrap().len();
+                for threshold in warnings.into_iter().filter(|&w| w <= now) {
+                    console_println!("{}", threshold_warning(threshold).yellow());
+                }
+            },
+            "LIST" => {
+                let mut channels = self.state.channels.lock().unwrap().clone();
+                channels.sort();
+                let (warnings, cap) = {
+                    let settings = self.state.settings.lock().unwrap();
+                    (settings.get("channel_warnings").map(|(v, _)| v).unwrap_or_default(), settings.channel_cap.value)
+                };
+                let width = channels.iter().map(|c| c.chars().count()).max().unwrap_or(0);
+                {
+                    let unsaved = self.state.unsaved.lock().unwrap();
+                    for channel in &channels {
+                        console_println!("{:<w$}  {} unsaved", channel, unsaved.lines(channel), w = width);
+                    }
+                }
+                console_println!("{} channels joined (warnings at {}, cap {})", channels.len().green(), warnings, cap);
+                // The command thread is not a runtime thread, so it can wait on the client
+                if let Some(runtime) = &self.runtime {
+                    let pool = runtime.block_on(self.client.pool_status());
+                    console_println!(
+                        "{} connections ({} channels each), {} channels confirmed, {} joins pending",
+                        pool.connections, ClientConfig::<StaticLoginCredentials>::default().max_channels_per_connection, pool.server_channels, pool.pending_joins
+                    );
+                }
+            },
+            "PART" => {
+                // PART <channel> [channel...] or PART ALL
+                let targets: Vec<String> = if arg.as_deref().is_some_and(|a| a.eq_ignore_ascii_case("ALL")) {
+                    self.state.channels.lock().unwrap().clone()
+                } else {
+                    parts[1..].iter().map(|c| c.to_string()).collect()
+                };
+                for channel in targets {
+                    part_channel(&self.client, &self.state, &channel);
+                    console_println!("Parted from {}", channel.red());
+                }
+            },
+            "SOUND" | "NOTIFY" => {
+                // SOUND <channel> toggles; ALL needs ON or OFF, toggling a mixed set is ambiguous
+                let mode = if cmd == "SOUND" { AlertMode::Sound } else { AlertMode::Notify };
+                let switch = match parts.get(2).map(|s| s.to_uppercase()).as_deref() {
+                    None => Some(None),
+                    Some("ON") => Some(Some(true)),
+                    Some("OFF") => Some(Some(false)),
+                    Some(_) => None,
+                };
+                match (arg, switch) {
+                    (Some(target), Some(Some(on))) if target.eq_ignore_ascii_case("ALL") => {
+                        let results = set_alert_mode_all(&self.state, mode, on);
+                        for (channel, changed) in &results {
+                            console_println!("{}", alert_mode_line(mode, channel, on, *changed));
+                        }
+                        let changed = results.iter().filter(|(_, changed)| *changed).count();
+                        console_println!("{} {} for {} of {} channels", mode.label(), if on { "ON" } else { "OFF" }, changed, results.len());
+                    }
+                    (Some(channel), Some(on)) if !channel.eq_ignore_ascii_case("ALL") => {
+                        let on = on.unwrap_or_else(|| !is_on(&self.state, &channel, mode));
+                        let changed = set_alert_mode(&self.state, &channel, mode, on);
+                        console_println!("{}", alert_mode_line(mode, &channel, on, changed));
+                    }
+                    _ => console_println!("Usage: {} <channel> [ON|OFF] | {} ALL ON|OFF", cmd, cmd),
+                }
+            },
+            "SAVE" => {
+                let pauses = parts.iter().any(|p| p.eq_ignore_ascii_case("--pauses"));
+                let json = parts.iter().any(|p| p.eq_ignore_ascii_case("--json"));
+                let mut parts: Vec<&str> = parts.iter().copied().filter(|p| !p.eq_ignore_ascii_case("--pauses") && !p.eq_ignore_ascii_case("--json")).collect();
+                let mut format = if json { LogFormat::Json } else { self.state.settings.lock().unwrap().log_format.value };
+                if let Some(i) = parts.iter().position(|p| p.eq_ignore_ascii_case("--format")) {
+                    match parts.get(i + 1).map(|f| f.parse::<LogFormat>()) {
+                        Some(Ok(parsed)) => format = parsed,
+                        Some(Err(e)) => {
+                            console_println!("{}", e.red());
+                            return Flow::Continue;
+                        }
+                        None => {
+                            console_println!("{}", "--format needs text, json, csv or jsonl".red());
+                            return Flow::Continue;
+                        }
+                    }
+                    parts.drain(i..i + 2);
+                }
+                if parts.len() >= 2 {
+                    let target = parts[1];
+                    let custom_name = if parts.len() > 2 {
+                        Some(parts[2..].join("_"))
+                    } else {
+                        None
+                    };
+                    save_logs(
+                        target,
+                        &self.state,
+                        custom_name.as_deref(),
+                        pauses,
+                        format
+                    );
+                } else {
+                    console_println!("Usage: SAVE <channel|ALL> [optional_custom_name] [--pauses] [--format text|json|csv|jsonl] [--json]");
+                }
+            },
+            "AUTOSAVE" => match arg.as_deref().map(str::to_uppercase).as_deref() {
+                None => {
+                    let settings = self.state.settings.lock().unwrap();
+                    match (settings.autosave_minutes.value, settings.autosave_lines.value) {
+                        (0, 0) => console_println!("Autosave: off (AUTOSAVE <minutes>|OFF|NOW)"),
+                        (minutes, lines) => console_println!(
+                            "Autosave: after {} minutes or {} lines, {} files (AUTOSAVE <minutes>|OFF|NOW)",
+                            minutes,
+                            lines,
+                            settings.autosave_files.value
+                        ),
+                    }
+                }
+                Some("NOW") => {
+                    let (channels, lines) = daily_autosave(&self.state);
+                    console_println!("{}", format_autosave(channels, lines).dimmed());
+                }
+                // Both budgets off turns the autosave off
+                Some("OFF") => {
+                    let mut settings = self.state.settings.lock().unwrap();
+                    settings.set_runtime("autosave_minutes", "0").and_then(|()| settings.set_runtime("autosave_lines", "0")).unwrap();
+                    console_println!("Autosave: {}", "off".yellow());
+                }
+                Some(minutes) => {
+                    let mut settings = self.state.settings.lock().unwrap();
+                    match settings.set_runtime("autosave_minutes", minutes) {
+                        Ok(()) => {
+                            settings.set_runtime("autosave_files", "daily").unwrap();
+                            console_println!("Autosave: every {} minutes into daily files", minutes.green());
+                        }
+                        Err(e) => console_println!("{}", e.red()),
+                    }
+                }
+            },
+            "PAUSES" => {
+                let minutes = match parts.get(2).map(|m| m.parse::<u64>()) {
+                    None => Some(DEFAULT_PAUSE_MINUTES),
+                    // Longer than u64 seconds is no pause length, it gets the usage
+                    Some(Ok(minutes)) if minutes > 0 && minutes.checked_mul(60).is_some() => Some(minutes),
+                    Some(_) => None,
+                };
+                match (arg, minutes) {
+                    (Some(channel), Some(minutes)) => {
+                        match log_texts(&self.state.logs, &channel) {
+                            Some(lines) => {
+                                let pauses = find_pauses(&lines, Duration::from_secs(minutes * 60));
+                                console_println!("{}", format_pauses(&channel, &pauses, minutes));
+                            }
+                            None => console_println!("No logs for {}", channel.yellow()),
+                        }
+                    }
+                    _ => console_println!("Usage: PAUSES <channel> [minutes]  (default {})", DEFAULT_PAUSE_MINUTES),
+                }
+            },
+            "MEMBERS" => {
+                match (arg, parts.get(2)) {
+                    (Some(channel), Some(mode)) => match mode.parse::<MembershipMode>() {
+                        Ok(mode) => {
+                            set_membership_mode(&self.state, &channel, mode);
+                            console_println!("Membership logging for {}: {}", channel.green(), mode);
+                        }
+                        Err(e) => console_println!("{}", e.red()),
+                    },
+                    (Some(channel), None) => {
+                        let mode = self.state.membership.lock().unwrap()
+                        .get(&channel)
+                        .map(|t| t.mode)
+                        .unwrap_or_else(|| configured_mode(&channel, &self.state));
+                        console_println!("Membership logging for {}: {}", channel.green(), mode);
+                    }
+                    _ => console_println!("Usage: MEMBERS <channel> [all|vips-only|counts-only|off]"),
+                }
+            },
+            "STATS" if parts.get(2).is_some_and(|p| p.eq_ignore_ascii_case("ALLTIME")) => {
+                let channel = parts[1].to_string();
+                match alltime_totals(&self.state, &alltime_stats_file(), &channel) {
+                    Ok(Some(totals)) => console_println!("{}", format_alltime(&channel, &totals)),
+                    Ok(None) => console_println!("No all-time stats for {}", channel.yellow()),
+                    Err(e) => console_println!("{}", e.red()),
+                }
+            },
+            "HEATMAP" => match arg {
+                Some(channel) => match alltime_totals(&self.state, &alltime_stats_file(), &channel) {
+                    Ok(totals) => {
+                        let grid = totals.map(|t| t.heatmap).unwrap_or_default();
+                        if parts.get(2).is_some_and(|p| p.eq_ignore_ascii_case("--json")) {
+                            console_println!("{}", heatmap_json(&channel, &grid));
+                        } else {
+                            console_println!("{}", format_heatmap(&channel, &grid));
+                        }
+                    }
+                    Err(e) => console_println!("{}", e.red()),
+                },
+                None => console_println!("Usage: HEATMAP <channel> [--json]"),
+            },
+            "STATS" => {
+                if let Some(channel) = arg {
+                    let messages = self.state.logs.lock().unwrap().get(&channel).cloned();
+                    match messages.map(|messages| session_lines(&self.state, &channel, &messages).unwrap_or_else(|| line_texts(&messages))) {
+                        Some(messages) => {
+                            let stats = compute_channel_stats(&channel, &messages);
+                            console_println!("{}", format_channel_stats(&stats));
+                            if let Some(line) = self.state.latency.lock().unwrap().get(&channel).and_then(format_latency) {
+                                console_println!("{}", line);
+                            }
+                            if let Some(line) = format_notification_stats(&self.state.notifications.lock().unwrap()) {
+                                console_println!("{}", line);
+                            }
+                            if let Some(line) = self.state.redaction_hits.lock().unwrap().get(&channel).and_then(format_redaction_hits) {
+                                console_println!("{}", line);
+                            }
+                            if parts.get(2).is_some_and(|p| p.eq_ignore_ascii_case("--save")) {
+                                let tag = self.state.session_tags.lock().unwrap().for_channel(&channel).map(str::to_string);
+                                let template = self.state.settings.lock().unwrap().file_name_template.value.clone();
+                                if let Some(dir) = log_dir(&self.state) {
+                                    save_stats_json(&dir, &template, &stats, &messages, tag.as_deref());
+                                }
+                            }
+                        }
+                        None => console_println!("No logs for {}", channel.yellow()),
+                    }
+                } else {
+                    console_println!("Usage: STATS <channel> [--save|ALLTIME]");
+                }
+            },
+            "USERS" => {
+                if let Some(channel) = arg {
+                    let limit = parts.get(2).and_then(|n| n.parse().ok()).unwrap_or(20);
+                    let counts = self.state.user_message_counts.lock().unwrap().get(&channel).cloned();
+                    match counts {
+                        Some(counts) => {
+                            let total: u32 = counts.values().sum();
+                            console_println!("{}", format!("--- #{}: {} messages from {} chatters ---", channel, total, counts.len()).cyan());
+                            console_println!("{}", format_user_counts(&counts, limit));
+                        }
+                        None => console_println!("No messages for {}", channel.yellow()),
+                    }
+                } else {
+                    console_println!("Usage: USERS <channel> [max_rows]");
+                }
+            },
+            "REPORT" => {
+                if let Some(channel) = arg {
+                    // Snapshot first, counting happens without holding the locks
+                    let messages = log_texts(&self.state.logs, &channel);
+                    let user_counts = self.state.user_message_counts.lock().unwrap().get(&channel).cloned();
+                    let latency = self.state.latency.lock().unwrap().get(&channel).copied();
+                    let raids = raids_of(&self.state.raids.lock().unwrap(), &channel);
+                    let records = self.state.message_records.lock().unwrap().get(&channel).map(|r| r.snapshot()).unwrap_or_default();
+                    let gift_recipients = self.state.gift_recipients.lock().unwrap().get(&channel).map_or(0, |r| r.len());
+                    match messages {
+                        Some(messages) => {
+                            let mut report = build_report(&channel, &messages, user_counts.as_ref(), latency.as_ref(), raids, records, gift_recipients);
+                            report.event = self.state.session_tags.lock().unwrap().for_channel(&channel).map(str::to_string);
+                            let json = parts.get(2).is_some_and(|p| p.eq_ignore_ascii_case("--json"));
+                            if json {
+                                match serde_json::to_string_pretty(&report) {
+                                    Ok(text) => console_println!("{}", text),
+                                    Err(e) => console_println!("{}", e.red()),
+                                }
+                            } else {
+                                console_println!("{}", format_report(&report));
+                            }
+                            let template = self.state.settings.lock().unwrap().file_name_template.value.clone();
+                            if let Some(dir) = log_dir(&self.state) {
+                                save_report(&dir, &template, &report, &messages, json);
+                            }
+                        }
+                        None => console_println!("No logs for {}", channel.yellow()),
+                    }
+                } else {
+                    console_println!("Usage: REPORT <channel> [--json]");
+                }
+            },
+            "COUNTUP" => {
+                if let Some(channel) = arg {
+                    let joined_at = self.state.channel_joined_at.lock().unwrap().get(&channel).copied();
+                    match joined_at {
+                        Some(at) => console_println!("Monitoring #{} for {}", channel.green(), format_duration(at.elapsed())),
+                        None => console_println!("Not monitoring {}", channel.yellow()),
+                    }
+                } else {
+                    console_println!("Usage: COUNTUP <channel>");
+                }
+            },
+            "LISTS" => {
+                let subcommand = arg.as_deref().map(str::to_uppercase);
+                let replace = parts.get(3).is_some_and(|p| p.eq_ignore_ascii_case("--replace"));
+                match (subcommand.as_deref(), parts.get(2)) {
+                    (Some("EXPORT"), Some(file)) => match save_lists(&self.state, Path::new(file)) {
+                        Ok(Some(count)) => console_println!("Exported {} entries to {}", count, file),
+                        Ok(None) => {}
+                        Err(e) => console_println!("{}", e.red()),
+                    },
+                    (Some("IMPORT"), Some(file)) => match load_lists(Path::new(file)) {
+                        Ok(lists) => {
+                            let summary = apply_lists(&self.state, lists, replace);
+                            console_println!(
+                                "Imported {}: {} added, {} already set{}",
+                                file,
+                                summary.added.green(),
+                                summary.skipped,
+                                if replace { format!(", {} removed", summary.removed) } else { String::new() }
+                            );
+                        }
+                        Err(e) => console_println!("{} {}", "Nothing imported:".red(), e),
+                    },
+                    _ => console_println!("Usage: LISTS EXPORT <file> | LISTS IMPORT <file> [--replace]"),
+                }
+            },
+            "VIP" => {
+                let subcommand = arg.as_deref().map(str::to_uppercase);
+                let save = parts.get(3).is_some_and(|p| p.eq_ignore_ascii_case("--save"));
+                match (subcommand.as_deref(), parts.get(2)) {
+                    (Some("IMPORT"), Some(source)) if source.eq_ignore_ascii_case("HELIX") => {
+                        let credentials = {
+                            let settings = self.state.settings.lock().unwrap();
+                            HelixCredentials::from_settings(settings.helix_client_id.value.as_deref(), settings.helix_token.value.as_deref())
+                        };
+                        let (Some(credentials), Some(runtime)) = (credentials, &self.runtime) else {
+                            console_println!("{}", "VIP IMPORT HELIX needs helix_client_id and helix_token (or TWITCH_OAUTH_TOKEN) in channels.txt".red());
+                            return Flow::Continue;
+                        };
+                        console_println!("Fetching the followed channels from Helix...");
+                        match runtime.block_on(followed_channels(&credentials)) {
+                            Ok(logins) => self.import_vip_names("Helix", validate_names(logins), save),
+                            Err(e) => console_println!("{} {}", "Nothing imported:".red(), e),
+                        }
+                    }
+                    (Some("IMPORT"), Some(file)) => match load_vip_import(Path::new(file)) {
+                        Ok(import) => self.import_vip_names(file, import, save),
+                        Err(e) => console_println!("{} {}", "Nothing imported:".red(), e),
+                    },
+                    _ => console_println!("Usage: VIP IMPORT <file|HELIX> [--save]"),
+                }
+            },
+            "SINKS" => {
+                let subcommand = arg.as_deref().map(str::to_uppercase);
+                match (subcommand.as_deref(), parts.get(2)) {
+                    (None, _) => {
+                        let sinks = self.state.sinks.status();
+                        if sinks.is_empty() {
+                            console_println!("No sinks");
+                        }
+                        for sink in sinks {
+                            console_println!(
+                                "{:<12} {} {} dropped, {} errors",
+                                sink.name,
+                                if sink.enabled { format!("{:<8}", "enabled").green().to_string() } else { "disabled".red().to_string() },
+                                sink.dropped,
+                                sink.errors
+                            );
+                        }
+                    }
+                    (Some(action @ ("ENABLE" | "DISABLE")), Some(name)) => {
+                        if self.state.sinks.set_enabled(name, action == "ENABLE") {
+                            console_println!("{} {}d", name, action.to_lowercase());
+                        } else {
+                            console_println!("{}: '{}'", "Unknown sink".red(), name);
+                        }
+                    }
+                    _ => console_println!("Usage: SINKS | SINKS ENABLE <name> | SINKS DISABLE <name>"),
+                }
+            },
+            "LOAD" => match (arg, parts.get(2)) {
+                (Some(channel), Some(file)) => match load_log(&self.state, &channel, Path::new(file)) {
+                    Ok(summary) => {
+                        console_println!("Loaded {} earlier entries of {}", summary.loaded.green(), channel);
+                        if summary.duplicates > 0 {
+                            console_println!("{}", format!("Skipped {} entries already in the log", summary.duplicates).dimmed());
+                        }
+                    }
+                    Err(e) => console_println!("{}", e.red()),
+                },
+                _ => console_println!("Usage: LOAD <channel> <file>"),
+            },
+            "TAG" => {
+                // TAG <label> | TAG OFF | TAG <channel> <label|OFF>
+                let (channel, label) = match (arg, parts.get(2)) {
+                    (Some(channel), Some(label)) => (Some(channel.to_lowercase()), Some(label.to_string())),
+                    (label, _) => (None, label),
+                };
+                match label {
+                    Some(label) => {
+                        let label = if label.eq_ignore_ascii_case("OFF") { Ok(None) } else { sanitize_label(&label).map(Some) };
+                        match label {
+                            Ok(label) => {
+                                let tags = {
+                                    let mut tags = self.state.session_tags.lock().unwrap();
+                                    match (&channel, label) {
+                                        (Some(channel), Some(label)) => { tags.channels.insert(channel.clone(), label); }
+                                        (Some(channel), None) => { tags.channels.remove(channel); }
+                                        (None, label) => tags.session = label,
+                                    }
+                                    tags.clone()
+                                };
+                                save_session_tags(&tags);
+                                console_println!("Tags: {}", format_tags(&tags).green());
+                            }
+                            Err(e) => console_println!("{}", e.red()),
+                        }
+                    }
+                    None => {
+                        let tags = self.state.session_tags.lock().unwrap().clone();
+                        console_println!("Tags: {} (TAG <label> | TAG OFF | TAG <channel> <label|OFF>)", format_tags(&tags));
+                    }
+                }
+            },
+            "DIAG" => {
+                let unknown = self.state.unknown_messages.lock().unwrap().clone();
+                console_println!("{}", format_unknown_messages(&unknown));
+            },
+            "SUBS" => {
+                if let Some(channel) = arg {
+                    let counts = self.state.sub_counts.lock().unwrap().get(&channel).cloned();
+                    match counts {
+                        Some(counts) => console_println!("{}", format_subs(&channel, &counts)),
+                        None => console_println!("No subs in {} so far", channel.yellow()),
+                    }
+                } else {
+                    console_println!("Usage: SUBS <channel>");
+                }
+            },
+            "RAIDS" => {
+                let raids = self.state.raids.lock().unwrap().clone();
+                if raids.is_empty() {
+                    console_println!("No raids so far");
+                }
+                for raid in &raids {
+                    console_println!("{}", raid);
+                }
+            },
+            "SUMMARY" => {
+                let report = session_report(&self.state);
+                console_println!(
+                    "Session: {} running, {} messages, {} bans, {} timeouts, {} subs, {} raids",
+                    format_duration(Duration::from_secs(report.duration_secs as u64)),
+                    report.messages,
+                    report.bans,
+                    report.timeouts,
+                    report.subs,
+                    report.raids
+                );
+                let incidents = self.state.vip_incidents.lock().unwrap().clone();
+                let text = format_vip_incidents(&incidents);
+                if incidents.is_empty() {
+                    console_println!("{}", text.dimmed());
+                } else {
+                    console_println!("{}", text.red());
+                }
+            },
+            "SAY" => {
+                // SAY <channel> <message>, the message as typed
+                let mut split = input.trim().splitn(3, char::is_whitespace);
+                let (Some(channel), Some(text)) = (split.nth(1), split.next().map(str::trim).filter(|t| !t.is_empty())) else {
+                    console_println!("Usage: SAY <channel> <message>");
+                    return Flow::Continue;
+                };
+                let channel = channel.trim_start_matches('#').to_lowercase();
+                let queue = &self.state.say_queue;
+                if queue.login().is_none() {
+                    console_println!("{}", "SAY needs a login: set TWITCH_LOGIN and TWITCH_OAUTH_TOKEN and restart".red());
+                } else if !self.state.channels.lock().unwrap().contains(&channel) {
+                    console_println!("Not joined to {}", channel);
+                } else {
+                    let id = queue.push(&channel, text);
+                    console_println!("SAY #{} queued for #{} ({} pending)", id, channel, queue.len());
+                }
+            },
+            "SAYQUEUE" => {
+                let queue = &self.state.say_queue;
+                if arg.as_deref().is_some_and(|a| a.eq_ignore_ascii_case("CLEAR")) {
+                    console_println!("Dropped {} pending SAY messages", queue.clear());
+                } else {
+                    let pending = queue.pending();
+                    if pending.is_empty() {
+                        console_println!("No SAY messages pending");
+                    }
+                    for message in pending {
+                        console_println!("#{} to #{}: {}", message.id, message.channel, message.text);
+                    }
+                }
+            },
+            "MUTE" => match arg {
+                Some(user) => {
+                    let user = user.trim_start_matches('@').to_lowercase();
+                    let mut muted = self.state.muted_users.lock().unwrap();
+                    if muted.remove(&user) {
+                        console_println!("Unmuted {}", user.green());
+                    } else {
+                        console_println!("Muted {}, their messages are still logged", user.yellow());
+                        muted.insert(user);
+                    }
+                }
+                None => {
+                    let mut muted: Vec<String> = self.state.muted_users.lock().unwrap().iter().cloned().collect();
+                    muted.sort();
+                    if muted.is_empty() {
+                        console_println!("Nobody is muted (MUTE <user>)");
+                    } else {
+                        console_println!("Muted: {}", muted.join(", "));
+                    }
+                }
+            },
+            "PRUNE" => {
+                let config = arg.as_deref().is_some_and(|a| a.eq_ignore_ascii_case("CONFIG"));
+                let confirmed = parts.len() > 2 && parts[parts.len() - 1].eq_ignore_ascii_case("CONFIRM");
+                let failed = self.failed_channels();
+                if failed.is_empty() {
+                    console_println!("No channels failed to join");
+                    return Flow::Continue;
+                }
+                let names: Vec<String> = failed.keys().cloned().collect();
+                console_println!("Failed to join: {}", format_join_failures(&failed));
+                if config && !confirmed {
+                    console_println!("PRUNE CONFIG CONFIRM parts them and comments them out in {}", config_file_name());
+                    return Flow::Continue;
+                }
+
+                for channel in &names {
+                    if self.state.channels.lock().unwrap().contains(channel) {
+                        part_channel(&self.client, &self.state, channel);
+                    }
+                }
+                console_println!("Parted {}", names.join(", ").yellow());
+                if config {
+                    let result = match CONFIG_FILE.as_deref() {
+                        Some(path) => prune_config_file(path, &names),
+                        None => Err("no channels.txt in use".to_string()),
+                    };
+                    let file = config_file_name();
+                    match result {
+                        Ok(pruned) if pruned.is_empty() => console_println!("None of them is in {}", file),
+                        Ok(pruned) => console_println!("Commented out {} in {} (backup: {}.bak)", pruned.join(", "), file, file),
+                        Err(e) => console_println!("{} {}", "Failed to update channels.txt:".red(), e),
+                    }
+                } else {
+                    console_println!("PRUNE CONFIG CONFIRM also comments them out in channels.txt");
+                }
+            },
+            "OPEN" => {
+                if let Some(channel) = arg {
+                    let file = self.state.last_saved.lock().unwrap().get(&channel).cloned();
+                    match file {
+                        Some(file) if file.ends_with(SEALED_EXTENSION) => {
+                            console_println!("{} is encrypted, use `twitch_chat_logger decrypt {}`", file, file)
+                        }
+                        Some(file) => open_file(&file),
+                        None => console_println!("Nothing saved for {} yet, use SAVE first", channel.yellow()),
+                    }
+                } else {
+                    console_println!("Usage: OPEN <channel>");
+                }
+            },
+            "SINCE" => {
+                match (arg, parts.get(2).and_then(|t| parse_time_arg(t))) {
+                    (Some(target), Some(after)) => {
+                        let channels: Vec<String> = if target.eq_ignore_ascii_case("ALL") {
+                            let mut keys: Vec<String> = self.state.logs.lock().unwrap().keys().cloned().collect();
+                            keys.sort();
+                            keys
+                        } else {
+                            vec![target]
+                        };
+                        let mut total = 0;
+                        for channel in channels {
+                            let lines = since(&channel, after, &self.state.logs);
+                            if lines.is_empty() {
+                                continue;
+                            }
+                            console_println!("{}", format!("--- #{} since {} ({} entries) ---", channel, after, lines.len()).cyan());
+                            for line in &lines {
+                                console_println!("{}", display_log_line(&self.state, line.trim_end()));
+                            }
+                            total += lines.len();
+                        }
+                        if total == 0 {
+                            console_println!("Nothing logged since {}", after);
+                        }
+                    }
+                    _ => console_println!("Usage: SINCE <channel|ALL> <HH:MM:SS>"),
+                }
+            },
+            "SEARCH" => {
+                // SEARCH <channel|ALL> [--regex] <pattern>
+                let regex = parts.get(2).is_some_and(|p| *p == "--regex");
+                let pattern = parts.get(2 + usize::from(regex)..).map(|p| p.join(" ")).unwrap_or_default();
+                let Some(target) = arg.filter(|_| !pattern.is_empty()) else {
+                    console_println!("Usage: SEARCH <channel|ALL> [--regex] <pattern>  (/pattern/ is a regex too)");
+                    return Flow::Continue;
+                };
+                let pattern = match SearchPattern::parse(&pattern, regex) {
+                    Ok(pattern) => pattern,
+                    Err(e) => {
+                        console_println!("{}", e.red());
+                        return Flow::Continue;
+                    }
+                };
+                let all = target.eq_ignore_ascii_case("ALL");
+                let channels: Vec<String> = if all {
+                    let mut keys: Vec<String> = self.state.logs.lock().unwrap().keys().cloned().collect();
+                    keys.sort();
+                    keys
+                } else {
+                    vec![target.clone()]
+                };
+                let mut output = Vec::new();
+                let mut total = 0;
+                for channel in channels {
+                    let Some(found) = search(&channel, &pattern, &self.state.logs) else {
+                        console_println!("{}", format!("No logs for {}, nothing from it has been received", channel).yellow());
+                        return Flow::Continue;
+                    };
+                    if found.is_empty() {
+                        continue;
+                    }
+                    if all {
+                        output.push(format!("--- #{} ({} matches) ---", channel, found.len()).cyan().to_string());
+                    }
+                    total += found.len();
+                    output.extend(found.iter().map(|(n, line)| format!("{}. {}", n, display_log_line(&self.state, line.trim_end()))));
+                }
+                if total == 0 {
+                    console_println!("No matches in {}", if all { "any channel" } else { target.as_str() });
+                    return Flow::Continue;
+                }
+                console_println!("{}", format!("{} matches", total).dimmed());
+                self.more = output.into();
+                self.print_page();
+            },
+            "BETWEEN" => {
+                let start = parts.get(2).and_then(|t| parse_time_arg(t));
+                let end = parts.get(3).and_then(|t| parse_time_arg(t));
+                match (arg, start, end) {
+                    (Some(channel), Some(start), Some(end)) => {
+                        if !self.state.logs.lock().unwrap().contains_key(&channel) {
+                            console_println!("No logs for {}", channel.yellow());
+                        } else {
+                            let lines = between(&channel, start, end, &self.state.logs);
+                            console_println!("{}", format!("--- #{} {} - {} ({} entries) ---", channel, start, end, lines.len()).cyan());
+                            for line in &lines {
+                                console_println!("{}", display_log_line(&self.state, line.trim_end()));
+                            }
+                        }
+                    }
+                    _ => console_println!("Usage: BETWEEN <channel> <HH:MM:SS> <HH:MM:SS>"),
+                }
+            },
+            "TAIL" => {
+                match arg.filter(|c| !c.eq_ignore_ascii_case("OFF")) {
+                    Some(channel) => {
+                        let count = match parts.get(2).map(|n| n.parse::<usize>()) {
+                            None => TAIL_LINES,
+                            Some(Ok(n)) if n > 0 => n.min(MAX_TAIL_LINES),
+                            Some(_) => {
+                                console_println!("Usage: TAIL <channel> [lines, 1-{}] | TAIL OFF", MAX_TAIL_LINES);
+                                return Flow::Continue;
+                            }
+                        };
+                        let lines = tail(&channel, count, &self.state.logs);
+                        if !lines.is_empty() {
+                            console_println!("{}", format!("--- last {} entries of #{} ---", lines.len(), channel).cyan());
+                        }
+                        for line in &lines {
+                            console_println!("{}", styled_log_line(&self.state, &channel, line.trim_end()));
+                        }
+                        let color = channel_color(&self.state, &channel);
+                        self.prompt = console::styled(&format!("[TAIL:{}] >> ", apply_named_color(&format!("#{}", channel), color.as_deref())));
+                        console_println!("Tailing {}, other channels are still logged", channel.green());
+                        *self.state.tail.lock().unwrap() = Some(channel);
+                    }
+                    None => {
+                        if self.state.tail.lock().unwrap().take().is_some() {
+                            console_println!("Left TAIL mode");
+                        }
+                        self.prompt = DEFAULT_PROMPT.to_string();
+                    }
+                }
+            },
+            "STATUSBAR" => {
+                let on = match arg.as_deref().map(str::to_uppercase).as_deref() {
+                    Some("ON") => true,
+                    Some("OFF") => false,
+                    _ => {
+                        console_println!("Usage: STATUSBAR ON|OFF");
+                        return Flow::Continue;
+                    }
+                };
+                self.state.settings.lock().unwrap().status_bar.set(on, Source::Runtime);
+                if !on {
+                    status_bar::clear();
+                } else if !status_bar::is_supported() {
+                    console_println!("The status bar needs a terminal");
+                }
+            },
+            "SLEEP" => {
+                // Pauses scripted input, e.g. `twitch_chat_logger < commands.txt`
+                match arg.and_then(|ms| ms.parse::<u64>().ok()) {
+                    Some(ms) => std::thread::sleep(Duration::from_millis(ms)),
+                    None => console_println!("Usage: SLEEP <milliseconds>"),
+                }
+            },
+            "CONFIG" => {
+                let subcommand = arg.as_deref().map(str::to_uppercase);
+                let key = parts.get(2).map(|k| k.to_lowercase());
+                match (subcommand.as_deref(), key) {
+                    (Some("SHOW"), None) => {
+                        print_banner(&self.state);
+                        let settings = self.state.settings.lock().unwrap();
+                        for key in SETTING_KEYS {
+                            if let Some((value, source)) = settings.get(key) {
+                                console_println!("{}", format_setting(key, &value, source));
+                            }
+                        }
+                    }
+                    (Some("SHOW"), Some(key)) => match self.state.settings.lock().unwrap().get(&key) {
+                        Some((value, source)) => console_println!("{}", format_setting(&key, &value, source)),
+                        None => console_println!("Unknown setting '{}'", key.yellow()),
+                    },
+                    (Some("SET"), Some(key)) if parts.len() > 3 => {
+                        let value = parts[3..].join(" ");
+                        let result = self.state.settings.lock().unwrap().set_runtime(&key, &value);
+                        match result {
+                            Ok(()) => {
+                                self.state.refresh_channel_width();
+                                console_println!("{} = {}", key, value.green());
+                            }
+                            Err(e) => console_println!("{}", e.red()),
+                        }
+                    }
+                    _ => console_println!("Usage: CONFIG SHOW [key] | CONFIG SET <key> <value>"),
+                }
+            },
+            "TIMEFMT" => match arg {
+                Some(format) => match self.state.settings.lock().unwrap().set_runtime("time_format", &format) {
+                    Ok(()) => console_println!("Timestamps: {}", format.to_lowercase().green()),
+                    Err(e) => console_println!("{}", e.red()),
+                },
+                None => {
+                    let current = self.state.settings.lock().unwrap().time_format.value;
+                    console_println!("Timestamps: {} (TIMEFMT absolute|relative|both)", current);
+                }
+            },
+            "VERSION" => console_println!("{}", build_info::build_info()),
+            "EXIT" => {
+                console_println!("Shutting down...");
+                let joined_channels = self.state.channels.lock().unwrap().clone();
+                for channel in joined_channels {
+                    self.client.part(channel.clone());
+                    console_println!("Left channel: {}", channel);
+                }
+                finish_session(&self.state);
+                return Flow::Exit;
+            }
+            _ => console_println!("{}: '{}'", "Unknown command".red(), input.trim()),
+        }
+        Flow::Continue
+    }
+
+    /// VIP IMPORT from `source`: add the names, report them and with `save` append the new
+    /// ones to channels.txt.
+    fn import_vip_names(&self, source: &str, import: VipImport, save: bool) {
+        for name in &import.invalid {
+            console_println!("{} '{}' is not a channel name, skipped", "⚠️".yellow(), name);
+        }
+        let summary = import_vips(&self.state, import.names);
+        console_println!(
+            "Imported {}: {} VIPs added, {} already VIPs, {} invalid",
+            source,
+            summary.added.len().green(),
+            summary.duplicates,
+            import.invalid.len()
+        );
+        if save && !summary.added.is_empty() {
+            let result = match CONFIG_FILE.as_deref() {
+                Some(path) => save_imported_vips(path, &summary.added),
+                None => Err("no channels.txt location".to_string()),
+            };
+            match result {
+                Ok(()) => console_println!("Added them to {} (backup in {}.bak)", config_file_name(), config_file_name()),
+                Err(e) => console_println!("{} {}", "Not saved:".red(), e),
+            }
+        }
+    }
+
+    /// The recorded join failures, forgetting unconfirmed channels that have been confirmed since.
+    fn failed_channels(&self) -> BTreeMap<String, String> {
+        let mut failures = self.state.join_failures.lock().unwrap().clone();
+        if let Some(runtime) = &self.runtime {
+            failures.retain(|channel, reason| {
+                reason != NOT_CONFIRMED || !runtime.block_on(self.client.get_channel_status(channel.clone())).1
+            });
+        }
+        self.state.join_failures.lock().unwrap().retain(|channel, _| failures.contains_key(channel));
+        failures
+    }
+}
+
+/// Everything saved when the session ends, by EXIT or Ctrl-C/Ctrl-D.
+pub fn finish_session(state: &LoggerState) {
+    release_parts(state, None);
+    save_session_report(state);
+    save_vip_incidents(state);
+    save_alltime(state);
+    save_vip_join_counts(&state.vip_join_counts.lock().unwrap());
+    state.sinks.flush();
+    remove_session_journal();
+}
+
+/// Result line of SOUND/NOTIFY for one channel.
+fn alert_mode_line(mode: AlertMode, channel: &str, on: bool, changed: bool) -> String {
+    match (on, changed) {
+        (true, true) if mode == AlertMode::Notify => format!("Notifications ON for {} (Sound is now OFF)", channel.cyan()),
+        (true, true) => format!("{} ON for {}", mode.label(), channel.green()),
+        (false, true) => format!("{} OFF for {}", mode.label(), channel.yellow()),
+        (true, false) => format!("{} already ON for {}", mode.label(), channel).dimmed().to_string(),
+        (false, false) => format!("{} already OFF for {}", mode.label(), channel).dimmed().to_string(),
+    }
+}
@@ -1,17 +1,46 @@
 use rustyline::completion::{Completer, Pair};
 use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
 use rustyline::validate::{Validator, ValidationContext, ValidationResult};
-use rustyline::{Context, Helper};
-use std::collections::HashMap;
+use rustyline::{Context, Editor, Helper};
+use std::collections::BTreeSet;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
+use twitch_logger_core::output::is_dry_run;
+use twitch_logger_core::settings::SETTING_KEYS;
+use twitch_logger_core::state::LogStore;
+
+/// Prompt history of earlier sessions, loaded before the first prompt and saved on exit.
+/// A missing or unreadable file only means starting without history.
+pub fn load_history(rl: &mut Editor<CommandCompleter, DefaultHistory>, path: &Path) {
+    if path.exists() {
+        if let Err(e) = rl.load_history(path) {
+            eprintln!("⚠️ Ignoring history {}: {}", path.display(), e);
+        }
+    }
+}
+
+pub fn save_history(rl: &mut Editor<CommandCompleter, DefaultHistory>, path: &Path) {
+    if is_dry_run() {
+        return;
+    }
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(e) = rl.save_history(path) {
+        eprintln!("⚠️ Failed to save history {}: {}", path.display(), e);
+    }
+}
+
 /// The completer now holds shared references to the application's dynamic state.
 pub struct CommandCompleter {
     pub commands: Vec<String>,
     pub joined_channels: Arc<Mutex<Vec<String>>>,
     pub vips: Vec<String>,
-    pub log_channels: Arc<Mutex<HashMap<String, Vec<String>>>>,
+    pub imported_vips: Arc<Mutex<BTreeSet<String>>>,
+    pub log_channels: LogStore,
 }
 
 impl Completer for CommandCompleter {
@@ -36,15 +65,27 @@ impl Completer for CommandCompleter {
 }
 
 impl CommandCompleter {
+    /// The VIPs of channels.txt followed by those imported with `VIP IMPORT` since startup.
+    fn all_vips(&self) -> Vec<String> {
+        let mut vips = self.vips.clone();
+        let imported = self.imported_vips.lock().unwrap();
+        vips.extend(imported.iter().filter(|name| !self.vips.contains(name)).cloned());
+        vips
+    }
+
     /// Generates completion suggestions dynamically based on the current application state.
     pub fn dynamic_complete(&self, line: &str) -> (usize, Vec<String>) {
         let start_of_content = line.len() - line.trim_start().len();
         let trimmed = line.trim_start();
         let words: Vec<&str> = trimmed.split_whitespace().collect();
 
-        // Block completions if three or more words are already typed
+        // Block completions if three or more words are already typed (PART and JOIN take a list of channels,
+        // CONFIG SHOW/SET a setting name, SOUND/NOTIFY ON or OFF, PRUNE CONFIG CONFIRM)
         let word_count = words.len() + if line.ends_with(' ') { 1 } else { 0 };
-        if word_count >= 3 {
+        let is_part = words.first().is_some_and(|w| w.eq_ignore_ascii_case("PART") || w.eq_ignore_ascii_case("JOIN"));
+        let is_config_key = word_count == 3 && words.first().is_some_and(|w| w.eq_ignore_ascii_case("CONFIG"));
+        let is_switch = word_count == 3 && words.first().is_some_and(|w| ["SOUND", "NOTIFY", "PRUNE"].iter().any(|c| w.eq_ignore_ascii_case(c)));
+        if word_count >= 3 && !is_part && !is_config_key && !is_switch {
             return (line.len(), vec![]);
         }
 
@@ -66,15 +107,52 @@ impl CommandCompleter {
         let command = words[0].to_uppercase();
 
         let potential_args = match command.as_str() {
-            "PART" => self.joined_channels.lock().unwrap().clone(),
-            "JOIN" => self.vips.clone(),
+            "PART" => {
+                // Skip channels already in the list, offer ALL only as the first argument
+                let typed_end = if line.ends_with(' ') { words.len() } else { words.len() - 1 };
+                let typed = &words[1..typed_end];
+                let mut channels: Vec<String> = self.joined_channels.lock().unwrap()
+                .iter()
+                .filter(|c| !typed.iter().any(|t| t.eq_ignore_ascii_case(c)))
+                .cloned()
+                .collect();
+                if typed.is_empty() {
+                    channels.push("ALL".to_string());
+                }
+                channels
+            }
+            "MEMBERS" | "COUNTUP" | "LOAD" | "SAY" | "HEATMAP" => self.joined_channels.lock().unwrap().clone(),
+            "JOIN" if word_count >= 3 => {
+                let mut vips = self.all_vips();
+                vips.push("CONFIRM".to_string());
+                vips
+            }
+            "JOIN" => self.all_vips(),
+            "CONFIG" if is_config_key => SETTING_KEYS.iter().map(|k| k.to_string()).collect(),
+            "CONFIG" => vec!["SHOW".to_string(), "SET".to_string()],
+            "LISTS" => vec!["EXPORT".to_string(), "IMPORT".to_string()],
+            "VIP" => vec!["IMPORT".to_string()],
+            "AUTOSAVE" => vec!["NOW".to_string(), "OFF".to_string()],
+            "SINKS" => vec!["ENABLE".to_string(), "DISABLE".to_string()],
+            "TIMEFMT" => vec!["absolute".to_string(), "relative".to_string(), "both".to_string()],
+            "TAG" => {
+                let mut args = self.joined_channels.lock().unwrap().clone();
+                args.push("OFF".to_string());
+                args
+            }
+            "PRUNE" if word_count >= 3 => vec!["CONFIRM".to_string()],
+            "PRUNE" => vec!["CONFIG".to_string()],
+            "STATUSBAR" => vec!["ON".to_string(), "OFF".to_string()],
+            "SAYQUEUE" => vec!["CLEAR".to_string()],
+            "SOUND" | "NOTIFY" if is_switch => vec!["ON".to_string(), "OFF".to_string()],
             "SOUND" | "NOTIFY" => {
                 let log_keys: Vec<String> = self.log_channels.lock().unwrap().keys().cloned().collect();
                 let mut combined = self.joined_channels.lock().unwrap().clone();
                 combined.extend(log_keys);
-                combined.extend(self.vips.clone());
+                combined.extend(self.all_vips());
                 combined.sort_unstable();
                 combined.dedup();
+                combined.push("ALL".to_string());
                 combined
                 /* //before gemini change
                 let log_keys: Vec<String> = self.log_channels.lock().unwrap().keys().cloned().collect();
@@ -86,7 +164,7 @@ impl CommandCompleter {
                 combined
                 */
             }
-            "SAVE" => self.log_channels.lock().unwrap().keys().cloned().collect(),
+            "SAVE" | "TAIL" | "STATS" | "SINCE" | "SEARCH" | "BETWEEN" | "PAUSES" | "USERS" | "REPORT" | "OPEN" | "SUBS" => self.log_channels.lock().unwrap().keys().cloned().collect(),
             _ => Vec::new(),
         };
 
@@ -0,0 +1,172 @@
+//! Terminal output of the message handlers. In pipe mode (`--pipe`) the same lines
+//! are written without colors, so they can go straight into `grep`, `tee` or `awk`.
+//!
+//! Colors are added with owo_colors at the call sites and removed here when they are
+//! off (`--color never`, `NO_COLOR`, or output that is not a terminal), so console
+//! output has to go through `console_println!`, `console_eprintln!` or `styled`.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+static COLORS: AtomicBool = AtomicBool::new(true);
+
+/// `--color`: when console output is colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Colors on a terminal, unless `NO_COLOR` is set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(format!("unknown color mode '{}' (auto, always, never)", other)),
+        }
+    }
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Decide once at startup whether console output is colored.
+pub fn set_color_choice(choice: ColorChoice) {
+    let colors = use_colors(choice, std::env::var_os("NO_COLOR"), io::stdout().is_terminal());
+    COLORS.store(colors, Ordering::Relaxed);
+}
+
+/// An explicit `--color` wins over `NO_COLOR`, see https://no-color.org.
+fn use_colors(choice: ColorChoice, no_color: Option<OsString>, terminal: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => no_color.is_none_or(|v| v.is_empty()) && terminal,
+    }
+}
+
+pub fn colors_enabled() -> bool {
+    COLORS.load(Ordering::Relaxed) && !is_plain()
+}
+
+/// `text` as it should appear on the console, for output that is not printed by the
+/// macros below (e.g. the prompt).
+pub fn styled(text: &str) -> String {
+    if colors_enabled() {
+        text.to_string()
+    } else {
+        strip_ansi(text)
+    }
+}
+
+/// Switch to plain output: no ANSI escapes and no PING/PONG status line.
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// `line` without "ESC [ ... m" color sequences.
+pub fn strip_ansi(line: &str) -> String {
+    let mut plain = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Print one (possibly colored) line. A closed pipe, e.g. `| head`, ends the process
+/// quietly instead of panicking like `println!`.
+pub fn print_line(line: &str) {
+    let result = writeln!(io::stdout().lock(), "{}", styled(line));
+    if result.is_err() {
+        std::process::exit(0);
+    }
+}
+
+/// Warnings and errors on stderr.
+pub fn print_error(line: &str) {
+    eprintln!("{}", styled(line));
+}
+
+/// Progress and status output, which goes to stderr in pipe mode to keep stdout clean.
+pub fn print_status(line: &str) {
+    if is_plain() {
+        print_error(line);
+    } else {
+        print_line(line);
+    }
+}
+
+/// `println!` for message output, see `print_line`.
+#[macro_export]
+macro_rules! console_println {
+    ($($arg:tt)*) => {
+        $crate::console::print_line(&format!($($arg)*))
+    };
+}
+
+/// `eprintln!` for (colored) warnings, see `print_error`.
+#[macro_export]
+macro_rules! console_eprintln {
+    ($($arg:tt)*) => {
+        $crate::console::print_error(&format!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use owo_colors::OwoColorize;
+
+    #[test]
+    fn colors_are_stripped() {
+        let line = format!("{} [{}] {}", "12:00:00".dimmed(), "forsen".green(), "hi".red().bold());
+        assert_eq!(strip_ansi(&line), "12:00:00 [forsen] hi");
+    }
+
+    #[test]
+    fn color_choice() {
+        let no_color = || Some(OsString::from("1"));
+        assert!(use_colors(ColorChoice::Auto, None, true));
+        assert!(!use_colors(ColorChoice::Auto, None, false));
+        assert!(!use_colors(ColorChoice::Auto, no_color(), true));
+        assert!(use_colors(ColorChoice::Auto, Some(OsString::new()), true));
+        assert!(use_colors(ColorChoice::Always, no_color(), false));
+        assert!(!use_colors(ColorChoice::Never, None, true));
+    }
+
+    #[test]
+    fn text_without_colors_is_unchanged() {
+        assert_eq!(strip_ansi("12:00:00 <x> [🔒] → ok"), "12:00:00 <x> [🔒] → ok");
+    }
+}
@@ -0,0 +1,127 @@
+//! `SAVE <channel> --format csv`: the message log and the join log as CSV (RFC 4180,
+//! CRLF line ends) for spreadsheets and pandas. Message log columns are `line_number,
+//! timestamp, channel, sender, badges, event_type, message_text`, the join log has
+//! `timestamp, event, channel, username`. Badges are one field, "mod/1;sub/12".
+
+use std::borrow::Cow;
+
+use crate::json_log::{parse_entry, JsonMessage};
+
+const MESSAGE_COLUMNS: &[&str] = &["line_number", "timestamp", "channel", "sender", "badges", "event_type", "message_text"];
+const JOIN_COLUMNS: &[&str] = &["timestamp", "event", "channel", "username"];
+
+/// Quote `value` if it contains a comma, a quote or a line break, doubling the quotes.
+pub fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields: Vec<Cow<str>> = fields.iter().map(|f| csv_field(f.as_ref())).collect();
+    format!("{}\r\n", fields.join(","))
+}
+
+/// Event of a message log entry: PRIVMSG for chat, USER_BANNED / TIMEOUT / CLEARMSG for
+/// moderation, the USERNOTICE kind (SUB, RAID, ...) and the marker (GAP, SPAM, ...) otherwise.
+/// Fills in the sender of user notices.
+pub fn classify(message: &mut JsonMessage) -> String {
+    if message.sender.is_some() {
+        return "PRIVMSG".to_string();
+    }
+    let text = message.message_text.as_str();
+    // "[channel][sender] <EVENT> body"
+    if let Some((channel_part, rest)) = text.strip_prefix('[').and_then(|t| t.split_once("][")) {
+        if let Some((sender, event)) = rest.split_once("] <").and_then(|(s, r)| Some((s, r.split_once('>')?.0))) {
+            if !channel_part.contains(' ') {
+                message.sender = Some(sender.to_string());
+                return event.to_string();
+            }
+        }
+    }
+    // "[GAP] connection lost ..."
+    if let Some(marker) = text.strip_prefix('[').and_then(|t| t.split_once(']')).map(|(m, _)| m) {
+        return marker.to_string();
+    }
+    // "TIMEOUT: [#channel] troll (600s timeout)"
+    match text.split_once(": ") {
+        Some((event, _)) if !event.is_empty() && event.chars().all(|c| c.is_ascii_uppercase() || c == '_') => event.to_string(),
+        _ => "OTHER".to_string(),
+    }
+}
+
+/// Content of the CSV message log of `channel`.
+pub fn format_csv_log(channel: &str, messages: &[String]) -> String {
+    let mut csv = csv_row(MESSAGE_COLUMNS);
+    for (i, entry) in messages.iter().enumerate() {
+        let mut message = parse_entry(i + 1, entry);
+        let event_type = classify(&mut message);
+        csv.push_str(&csv_row(&[
+            message.line_number.to_string(),
+            message.timestamp.unwrap_or_default(),
+            channel.to_string(),
+            message.sender.unwrap_or_default(),
+            message.badges.join(";"),
+            event_type,
+            message.message_text,
+        ]));
+    }
+    csv
+}
+
+/// Content of the CSV join log of `channel`. Lines are "HH:MM:SS [J] user" and "[P]";
+/// the counts-only aggregates ("HH:MM [COUNTS] +214 / -189, ~12.3k present") become
+/// COUNTS rows with the counts in the `username` column.
+pub fn format_csv_join_log(channel: &str, lines: &[String]) -> String {
+    let mut csv = csv_row(JOIN_COLUMNS);
+    for line in lines {
+        let Some((timestamp, rest)) = line.split_once(' ') else {
+            continue;
+        };
+        let (event, username) = match rest.split_once(' ') {
+            Some(("[J]" | "[JOIN]", user)) => ("JOIN", user),
+            Some(("[P]" | "[PART]", user)) => ("PART", user),
+            Some(("[COUNTS]", counts)) => ("COUNTS", counts),
+            _ => ("OTHER", rest),
+        };
+        csv.push_str(&csv_row(&[timestamp, event, channel, username]));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_quoted_per_rfc_4180() {
+        assert_eq!(csv_field("hello"), "hello");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+
+        let messages = [
+            "20:15:03 <Alice> [mod/1,sub/12]\nhi, \"chat\"\n".to_string(),
+            "20:15:04 [forsen][bob] <RESUB> 12 months → bob subscribed".to_string(),
+            "20:15:05 TIMEOUT: [#forsen] troll (600s timeout)".to_string(),
+            "20:16:00 [GAP] connection lost for 12s".to_string(),
+        ];
+        let csv = format_csv_log("forsen", &messages);
+        let rows: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(rows[0], "line_number,timestamp,channel,sender,badges,event_type,message_text");
+        assert_eq!(rows[1], "1,20:15:03,forsen,Alice,mod/1;sub/12,PRIVMSG,\"hi, \"\"chat\"\"\"");
+        assert_eq!(rows[2], "2,20:15:04,forsen,bob,,RESUB,[forsen][bob] <RESUB> 12 months → bob subscribed");
+        assert_eq!(rows[3], "3,20:15:05,forsen,,,TIMEOUT,TIMEOUT: [#forsen] troll (600s timeout)");
+        assert_eq!(rows[4], "4,20:16:00,forsen,,,GAP,[GAP] connection lost for 12s");
+    }
+
+    #[test]
+    fn join_log_has_its_own_columns() {
+        let lines = ["12:00:00 [J] alice".to_string(), "12:00:01 [P] bob".to_string(), "12:01 [COUNTS] +2 / -1, ~1 present".to_string()];
+        assert_eq!(
+            format_csv_join_log("forsen", &lines),
+            "timestamp,event,channel,username\r\n12:00:00,JOIN,forsen,alice\r\n12:00:01,PART,forsen,bob\r\n12:01,COUNTS,forsen,\"+2 / -1, ~1 present\"\r\n"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Messages the logger does not know (commands without a handler, USERNOTICEs with a new
+//! msg-id). The first few of each type are printed and captured raw in
+//! `unknown_messages.jsonl`, after that they are only counted, so a new message type
+//! Twitch sends constantly does not flood the console or the disk. `DIAG` shows the
+//! counts, the session report includes them. Panics are captured in `panics.log`; both
+//! files are rotated by size.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::Serialize;
+
+use crate::output::{append_file, move_file, remove_file};
+use crate::state::data_file;
+
+/// Occurrences of each type printed per session.
+pub const SAMPLES_PER_TYPE: u64 = 3;
+/// Types counted separately; any further ones only add to `other_types`.
+pub const MAX_TYPES: usize = 100;
+/// A capture file is rotated before it grows past this...
+pub const MAX_CAPTURE_BYTES: u64 = 1024 * 1024;
+/// ...to `<file>.1`, keeping `<file>.1` to `<file>.<CAPTURE_ROTATIONS>`.
+pub const CAPTURE_ROTATIONS: usize = 3;
+
+/// The raw samples of unknown messages, next to channels.txt.
+pub fn unknown_messages_file() -> PathBuf {
+    data_file("unknown_messages.jsonl")
+}
+
+/// Panic messages with their location, next to channels.txt.
+pub fn panics_file() -> PathBuf {
+    data_file("panics.log")
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UnknownMessages {
+    /// Type (command or "USERNOTICE/<msg-id>") -> count.
+    pub counts: BTreeMap<String, u64>,
+    /// Messages of types beyond `MAX_TYPES`.
+    pub other_types: u64,
+}
+
+impl UnknownMessages {
+    /// Count one message of `kind`. True while it should still be printed.
+    pub fn record(&mut self, kind: &str) -> bool {
+        if !self.counts.contains_key(kind) && self.counts.len() >= MAX_TYPES {
+            self.other_types += 1;
+            return false;
+        }
+        let count = self.counts.entry(kind.to_string()).or_default();
+        *count += 1;
+        *count <= SAMPLES_PER_TYPE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty() && self.other_types == 0
+    }
+}
+
+#[derive(Serialize)]
+struct UnknownSample<'a> {
+    time: String,
+    kind: &'a str,
+    raw: &'a str,
+}
+
+/// Append a sampled unknown message of type `kind` to `unknown_messages.jsonl`.
+pub fn capture_unknown_message(kind: &str, raw: &str) {
+    let sample = UnknownSample { time: Local::now().to_rfc3339(), kind, raw };
+    let line = serde_json::to_string(&sample).expect("samples serialize");
+    capture(&unknown_messages_file(), &line);
+}
+
+/// Also write panics to `panics.log`, then report them as before.
+pub fn capture_panics() {
+    let report = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        capture(&panics_file(), &format!("{} {}", Local::now().to_rfc3339(), info));
+        report(info);
+    }));
+}
+
+fn capture(path: &Path, line: &str) {
+    if let Err(e) = append_rotated(path, line, MAX_CAPTURE_BYTES, CAPTURE_ROTATIONS) {
+        eprintln!("⚠️ Failed to write {}: {}", path.display(), e);
+    }
+}
+
+/// Append `line` to `path`, first rotating it if it would grow past `max_bytes`:
+/// `<path>.<n>` becomes `<path>.<n+1>`, the oldest beyond `keep` is deleted.
+fn append_rotated(path: &Path, line: &str, max_bytes: u64, keep: usize) -> io::Result<bool> {
+    let size = path.metadata().map_or(0, |meta| meta.len());
+    if size > 0 && size + line.len() as u64 + 1 > max_bytes {
+        let rotation = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+        if rotation(keep).exists() {
+            remove_file(&rotation(keep))?;
+        }
+        for n in (1..keep).rev() {
+            if rotation(n).exists() {
+                move_file(&rotation(n), &rotation(n + 1))?;
+            }
+        }
+        if keep > 0 {
+            move_file(path, &rotation(1))?;
+        } else {
+            remove_file(path)?;
+        }
+    }
+    append_file(path, format!("{}\n", line))
+}
+
+/// DIAG table: most frequent types first.
+pub fn format_unknown_messages(unknown: &UnknownMessages) -> String {
+    if unknown.is_empty() {
+        return "No unknown messages this session".to_string();
+    }
+    let mut rows: Vec<(&String, &u64)> = unknown.counts.iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let mut lines: Vec<String> = rows.into_iter().map(|(kind, n)| format!("{:>7}  {}", n, kind)).collect();
+    if unknown.other_types > 0 {
+        lines.push(format!("{:>7}  (other types)", unknown.other_types));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_then_counts_and_stays_bounded() {
+        let mut unknown = UnknownMessages::default();
+        let printed = (0..10).filter(|_| unknown.record("USERNOTICE/newthing")).count();
+        assert_eq!(printed as u64, SAMPLES_PER_TYPE);
+        assert_eq!(unknown.counts["USERNOTICE/newthing"], 10);
+
+        for i in 0..MAX_TYPES + 5 {
+            unknown.record(&format!("TYPE{}", i));
+        }
+        assert_eq!(unknown.counts.len(), MAX_TYPES);
+        assert_eq!(unknown.other_types, 6);
+        assert!(format_unknown_messages(&unknown).starts_with("     10  USERNOTICE/newthing"));
+    }
+
+    #[test]
+    fn capture_files_rotate_and_keep_the_last_ones() {
+        let dir = std::env::temp_dir().join(format!("diag_rotation_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("panics.log");
+
+        // 10 bytes per line, 3 lines per file
+        for i in 0..10 {
+            append_rotated(&file, &format!("line {:04}", i), 30, 2).unwrap();
+        }
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("panics.log"), "line 0009\n");
+        assert_eq!(read("panics.log.1"), "line 0006\nline 0007\nline 0008\n");
+        assert_eq!(read("panics.log.2"), "line 0003\nline 0004\nline 0005\n");
+        assert!(!dir.join("panics.log.3").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+//! Encryption at rest for channels marked `encrypt=true` in channels.txt. Their saved
+//! logs and journal lines are sealed with ChaCha20-Poly1305; the key comes from a
+//! passphrase (prompted at startup or read from `--key-file`) through Argon2id.
+//! `twitch_chat_logger decrypt <file>` turns a saved file back into plaintext.
+//!
+//! A sealed blob is `MAGIC | salt (16) | nonce (12) | ciphertext + tag`, so each one can
+//! be opened with the passphrase alone. The key is derived once per session with a
+//! random salt; blobs of other sessions are opened with the salt they carry.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, AeadCore, OsRng};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+
+use crate::state::LoggerState;
+
+/// Start of every sealed blob, also tells a sealed file from a plain one.
+pub const MAGIC: &[u8; 8] = b"TCLENC1\n";
+/// Appended to the names of sealed files.
+pub const SEALED_EXTENSION: &str = ".enc";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecryptError {
+    /// No `MAGIC` at the start.
+    NotEncrypted,
+    /// Shorter than a header and a tag.
+    Truncated,
+    /// The tag didn't verify: wrong passphrase, or the file was changed.
+    WrongPassphrase,
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecryptError::NotEncrypted => write!(f, "not an encrypted log file"),
+            DecryptError::Truncated => write!(f, "file is truncated"),
+            DecryptError::WrongPassphrase => write!(f, "wrong passphrase or damaged file"),
+        }
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+/// Argon2id with the OWASP minimum (19 MiB, 2 passes), fast enough to run per file on open.
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key {
+    let params = Params::new(19 * 1024, 2, 1, Some(32)).expect("valid Argon2 parameters");
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+    .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+    .expect("valid Argon2 output length");
+    key
+}
+
+/// The session key, derived from the passphrase at startup.
+pub struct LogKey {
+    passphrase: String,
+    salt: [u8; SALT_LEN],
+    cipher: ChaCha20Poly1305,
+}
+
+impl LogKey {
+    pub fn new(passphrase: &str) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        LogKey { passphrase: passphrase.to_string(), salt, cipher: ChaCha20Poly1305::new(&derive_key(passphrase, &salt)) }
+    }
+
+    /// For blobs of earlier sessions, e.g. a crashed session's journal.
+    pub fn opener(&self) -> Opener {
+        Opener::new(&self.passphrase)
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        // Only fails for plaintexts beyond 256 GiB
+        let ciphertext = self.cipher.encrypt(&nonce, plaintext).expect("plaintext too long to encrypt");
+        let mut blob = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        blob.extend_from_slice(MAGIC);
+        blob.extend_from_slice(&self.salt);
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&ciphertext);
+        blob
+    }
+}
+
+/// Opens blobs with a passphrase, deriving the key once per salt.
+pub struct Opener {
+    passphrase: String,
+    ciphers: HashMap<[u8; SALT_LEN], ChaCha20Poly1305>,
+}
+
+impl Opener {
+    pub fn new(passphrase: &str) -> Self {
+        Opener { passphrase: passphrase.to_string(), ciphers: HashMap::new() }
+    }
+
+    pub fn open(&mut self, blob: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        if !blob.starts_with(MAGIC) {
+            return Err(DecryptError::NotEncrypted);
+        }
+        if blob.len() < HEADER_LEN + 16 {
+            return Err(DecryptError::Truncated);
+        }
+        let salt: [u8; SALT_LEN] = blob[MAGIC.len()..MAGIC.len() + SALT_LEN].try_into().unwrap();
+        let nonce = Nonce::from_slice(&blob[MAGIC.len() + SALT_LEN..HEADER_LEN]);
+        let passphrase = &self.passphrase;
+        let cipher = self.ciphers.entry(salt).or_insert_with(|| ChaCha20Poly1305::new(&derive_key(passphrase, &salt)));
+        cipher.decrypt(nonce, &blob[HEADER_LEN..]).map_err(|_| DecryptError::WrongPassphrase)
+    }
+}
+
+/// Whether `channel` has `encrypt=true` in channels.txt.
+pub fn encrypts(state: &LoggerState, channel: &str) -> bool {
+    state.vip_info(channel).is_some_and(|info| info.encrypt)
+}
+
+/// Channels of channels.txt with `encrypt=true`, sorted.
+pub fn encrypted_channels(state: &LoggerState) -> Vec<String> {
+    let mut channels: Vec<String> = state.config.vips.iter().filter(|(_, info)| info.encrypt).map(|(c, _)| c.clone()).collect();
+    channels.sort();
+    channels
+}
+
+/// First line of `path`, for `--key-file`.
+pub fn read_key_file(path: &Path) -> Result<String, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let passphrase = content.lines().next().unwrap_or_default().to_string();
+    if passphrase.is_empty() {
+        return Err(format!("{}: the first line is empty", path.display()));
+    }
+    Ok(passphrase)
+}
+
+/// The passphrase for encrypted channels: from `key_file`, otherwise asked on the terminal
+/// (twice, a typo would make the files unreadable).
+pub fn read_passphrase(key_file: Option<&Path>, confirm: bool) -> Result<String, String> {
+    if let Some(path) = key_file {
+        return read_key_file(path);
+    }
+    let passphrase = rpassword::prompt_password("Passphrase for encrypted channels: ")
+    .map_err(|e| format!("can't read the passphrase ({}), use --key-file", e))?;
+    if passphrase.is_empty() {
+        return Err("empty passphrase".to_string());
+    }
+    if confirm && rpassword::prompt_password("Repeat the passphrase: ").map_err(|e| e.to_string())? != passphrase {
+        return Err("the passphrases don't match".to_string());
+    }
+    Ok(passphrase)
+}
+
+/// Set up the session key if any channel of channels.txt is encrypted. Without a key
+/// these channels are not saved at all, so this is an error for the caller to stop on.
+pub fn setup_encryption(state: &LoggerState, key_file: Option<&Path>) -> Result<(), String> {
+    let channels = encrypted_channels(state);
+    if channels.is_empty() {
+        return Ok(());
+    }
+    println!("Encrypted channels: {}", channels.join(", "));
+    let passphrase = read_passphrase(key_file, true)?;
+    *state.log_key.lock().unwrap() = Some(std::sync::Arc::new(LogKey::new(&passphrase)));
+    Ok(())
+}
+
+/// Default output of `decrypt`: the file name without `.enc`.
+pub fn plaintext_path(sealed: &Path) -> Option<PathBuf> {
+    sealed.to_str()?.strip_suffix(SEALED_EXTENSION).filter(|p| !p.is_empty()).map(PathBuf::from)
+}
+
+/// Read and open a saved file; fails on a wrong passphrase instead of returning garbage.
+pub fn decrypt_file(path: &Path, passphrase: &str) -> Result<Vec<u8>, String> {
+    let blob = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Opener::new(passphrase).open(&blob).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Lowercase hex, for sealed lines in the JSON journal.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_blobs_open_only_with_the_passphrase() {
+        let key = LogKey::new("correct horse");
+        let blob = key.seal(b"12:00:00 <alice> []\nhello chat\n");
+        assert!(blob.starts_with(MAGIC));
+        assert!(!blob.windows(10).any(|w| w == b"hello chat"));
+
+        assert_eq!(Opener::new("correct horse").open(&blob).unwrap(), b"12:00:00 <alice> []\nhello chat\n");
+        assert_eq!(Opener::new("wrong horse").open(&blob), Err(DecryptError::WrongPassphrase));
+
+        let mut tampered = blob.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(Opener::new("correct horse").open(&tampered), Err(DecryptError::WrongPassphrase));
+        assert_eq!(Opener::new("correct horse").open(&blob[..HEADER_LEN]), Err(DecryptError::Truncated));
+        assert_eq!(Opener::new("correct horse").open(b"plain text"), Err(DecryptError::NotEncrypted));
+    }
+
+    #[test]
+    fn plaintext_path_drops_the_extension() {
+        assert_eq!(plaintext_path(Path::new("/tmp/a_msgs.txt.enc")), Some(PathBuf::from("/tmp/a_msgs.txt")));
+        assert_eq!(plaintext_path(Path::new("/tmp/a_msgs.txt")), None);
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(to_hex(&[0, 15, 255]), "000fff");
+        assert_eq!(from_hex("000fff"), Some(vec![0, 15, 255]));
+        assert_eq!(from_hex("0f0"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
+}
@@ -0,0 +1,1023 @@
+use std::io::{self, Write};
+use std::time::Instant;
+
+use chrono::{DateTime, Local};
+use owo_colors::OwoColorize;
+use twitch_irc::ConnectionEvent;
+use twitch_irc::message::{
+    AsRawIRC, ClearChatAction, FollowersOnlyMode, IRCMessage, PrivmsgMessage, ReceivedMessage, RoomStateMessage,
+    ServerMessage, UserNoticeEvent, UserNoticeMessage,
+};
+
+use crate::alert_mode::{alert_for, AlertMode};
+use crate::buckets::{append_line, append_line_with, append_marker};
+use crate::console::is_plain;
+use crate::console_println;
+use crate::channel_config::{apply_named_color, channel_color, fit_to_width, visible_width};
+use crate::diag::{capture_unknown_message, SAMPLES_PER_TYPE};
+use crate::membership::{configured_mode, ChannelMembership, MembershipMode};
+use crate::heatmap::slot;
+use crate::incident::{format_duration, render_box, IncidentTransition, RoomRestrictions};
+use crate::jsonl_log::LogRecord;
+use crate::normalize::normalize_message;
+use crate::notification::{send_channel_notification, send_desktop_notification};
+use crate::prune::check_join_notice;
+use crate::raids::record_raid;
+use crate::redaction::redact_message;
+use crate::subs::{record_sub, sub_description, sub_tier};
+use crate::save::record_channel_display_name;
+use crate::say_queue::check_say_notice;
+use crate::sequence::{current_stamp, next_stamp, with_stamp};
+use crate::sound::{play_alarm, play_channel_sound};
+use crate::state::LoggerState;
+use crate::stray::divert_stray;
+use crate::timestamps::display_time;
+use crate::vip_incidents::VipIncident;
+use crate::vip_parts::{check_flap, hold_part};
+use crate::vip_visits::record_vip_join;
+
+/// Stamps a message with the time it arrived on the socket, so a burst handled late
+/// still gets the right times, and with its sequence number (see `sequence`), records its
+/// latency and passes it to `handle_message`.
+pub fn handle_received(received: ReceivedMessage, state: &LoggerState) {
+    let received_at: DateTime<Local> = received.received_at.into();
+    *state.last_received.lock().unwrap() = Some(Instant::now());
+
+    if let ServerMessage::Privmsg(msg) = &received.message {
+        let ms = received_at.signed_duration_since(msg.server_timestamp).num_milliseconds();
+        state.latency.lock().unwrap().entry(msg.channel_login.clone()).or_default().record(ms);
+        state.irc_relay.publish(&msg.channel_login, &msg.source);
+        state.message_rate.lock().unwrap().record(Instant::now());
+        *state.hourly_messages.lock().unwrap()
+        .entry(msg.channel_login.clone())
+        .or_default()
+        .entry(slot(received_at))
+        .or_default() += 1;
+    }
+
+    let time_str = received_at.format("%H:%M:%S").to_string();
+    with_stamp(next_stamp(received_at), || handle_message(&time_str, received.message, state));
+}
+
+/// Pool connection events: a re-joined channel gets a gap marker in its log.
+pub fn handle_connection_event(event: ConnectionEvent, state: &LoggerState) {
+    if let ConnectionEvent::ChannelRejoined { channel, downtime } = event {
+        let time_str = Local::now().format("%H:%M:%S").to_string();
+        let gap = format!(
+            "connection lost for {}, messages in between are missing",
+            format_duration(downtime)
+        );
+        if state.is_visible(&channel) {
+            console_println!("{} [{}] {}", display_time(state, &channel, &time_str).dimmed(), channel, format!("[GAP] {}", gap).yellow());
+        }
+        append_marker(state, &channel, &time_str, "GAP", &gap);
+    }
+}
+
+/// Routes one incoming message to its handler. `time_str` is the HH:MM:SS receive time.
+pub fn handle_message(time_str: &str, message: ServerMessage, state: &LoggerState) {
+    if divert_stray(time_str, &message, state) {
+        return;
+    }
+
+    match message {
+        ServerMessage::Privmsg(msg) => {
+            handle_privmsg(time_str, msg, state);
+        }
+
+        ServerMessage::Join(msg) =>{
+           handle_join_or_part("JOIN", time_str, &msg.channel_login, &msg.user_login, state);
+        }
+
+        ServerMessage::Part(msg) => {
+            handle_join_or_part("PART", time_str, &msg.channel_login, &msg.user_login, state);
+        }
+
+        // Status line only, it would end up in the middle of piped output
+        ServerMessage::Ping(_msg) => {
+            if !is_plain() {
+                print!("{} PING      \r", time_str); // Padding to overwrite leftover text
+                io::stdout().flush().unwrap();
+            }
+        }
+        ServerMessage::Pong(_msg) => {
+            if !is_plain() {
+                print!("{} PONG      \r", time_str); // Same here
+                io::stdout().flush().unwrap();
+            }
+        }
+        ServerMessage::RoomState(msg) => {
+            handle_room_state(time_str, &msg, state);
+        }
+
+        ServerMessage::Notice(msg) => {
+            check_join_notice(state, msg.channel_login.as_deref(), msg.message_id.as_deref(), &msg.message_text);
+            check_say_notice(state, msg.channel_login.as_deref(), msg.message_id.as_deref());
+            if msg.channel_login.as_deref().is_some_and(|c| !state.is_visible(c)) {
+                return;
+            }
+            let time = match &msg.channel_login {
+                Some(channel) => display_time(state, channel, time_str),
+                None => time_str.to_string(),
+            };
+            console_println!("{}[{}][NOTICE] {}", time.dimmed(), msg.channel_login.unwrap_or("unknown".to_string()),msg.message_text);
+        }
+
+        ServerMessage::ClearChat(msg) => {
+            match &msg.action {
+                ClearChatAction::UserBanned { user_login, .. } => {
+                    handle_moderation_event(
+                        time_str,
+                        "USER_BANNED",
+                        &msg.channel_login,
+                        user_login,
+                        owo_colors::Style::new().red().blink(),
+                state,
+                    );
+                    alert_own_moderation(time_str, &msg.channel_login, user_login, "you were banned", state);
+                    alert_vip_moderation(time_str, &msg.channel_login, user_login, "banned", state);
+                }
+                ClearChatAction::UserTimedOut { user_login, timeout_length, .. } => {
+                    let content = format!(
+                        "{} ({}s timeout)",
+                                          user_login,
+                                          timeout_length.as_secs()
+                    );
+                    handle_moderation_event(
+                        time_str,
+                        "TIMEOUT",
+                        &msg.channel_login,
+                        &content,
+                        owo_colors::Style::new().red().blink(),
+                state,
+                    );
+                    let what = format!("you were timed out for {}s", timeout_length.as_secs());
+                    alert_own_moderation(time_str, &msg.channel_login, user_login, &what, state);
+                    let action = format!("timed out for {}s", timeout_length.as_secs());
+                    alert_vip_moderation(time_str, &msg.channel_login, user_login, &action, state);
+                }
+                ClearChatAction::ChatCleared => {
+                    handle_moderation_event(
+                        time_str,
+                        "CHAT_CLEARED",
+                        &msg.channel_login,
+                        "The chat was cleared by a moderator.",
+                        owo_colors::Style::new().dimmed(),
+                state,
+                    );
+                }
+            }
+        }
+        ServerMessage::ClearMsg(msg) => {
+            // The deleted text is stored again, redacted like the message was
+            let text = redact_message(state, &msg.channel_login, &msg.message_text).unwrap_or_else(|| msg.message_text.clone());
+            handle_moderation_event(
+                time_str,
+                "CLEARMSG",
+                &msg.channel_login,
+                &text,
+                owo_colors::Style::new().bright_black().blink(),
+                state,
+            );
+            let what = format!("your message \"{}\" was deleted", text);
+            alert_own_moderation(time_str, &msg.channel_login, &msg.sender_login, &what, state);
+        }
+        ServerMessage::UserNotice(msg) => {
+            handle_user_notice(time_str, &msg, state);
+        }
+        ServerMessage::Malformed { error, .. } => {
+            console_println!("{} [SYSTEM: MALFORMED] {}", time_str.dimmed(), error.red());
+        }
+
+        _ => handle_default(time_str, &message, state),
+    }
+}
+
+// --- Message Handlers ---
+pub fn handle_default(
+    time: &str,
+    message: &ServerMessage,
+    state: &LoggerState,
+) {
+
+    let kind = match message {
+        ServerMessage::Ping(_) => "PING",
+        ServerMessage::Pong(_) => "PONG",
+        ServerMessage::Reconnect(_) => "RECONNECT",
+        ServerMessage::GlobalUserState(_) => "GLOBAL_USER_STATE",
+        ServerMessage::UserState(_) => "USER_STATE",
+        ServerMessage::RoomState(_) => "ROOM_STATE",
+        ServerMessage::Whisper(_) => "WHISPER",
+        ServerMessage::Generic(_)=> "HIDDEN",
+        _ => "OTHER",
+    };
+
+    // Messages without a handler of their own are counted for DIAG, and only the first
+    // few of each type are printed
+    if matches!(kind, "OTHER" | "HIDDEN") && !record_unknown_message(state, time, &message.source().command, message.source()) {
+        return;
+    }
+    if kind == "OTHER" {
+        console_println!("{} [SYSTEM: OTHER] {:?}", time.dimmed(), message
+        .source()
+        .tags
+        .0
+        .get("msg-id")
+        .and_then(|v| v.as_deref())
+        .unwrap_or("unknown"));
+    } else {
+        console_println!("{} ...", time.dimmed())
+    }
+}
+
+/// Count an unknown message of type `kind`; false once its samples have been printed.
+/// The printed ones are captured raw, the message that reaches the limit says so.
+fn record_unknown_message(state: &LoggerState, time: &str, kind: &str, source: &IRCMessage) -> bool {
+    let (print, count) = {
+        let mut unknown = state.unknown_messages.lock().unwrap();
+        let print = unknown.record(kind);
+        (print, unknown.counts.get(kind).copied().unwrap_or(0))
+    };
+    if print {
+        capture_unknown_message(kind, &source.as_raw_irc());
+    }
+    if print && count == SAMPLES_PER_TYPE {
+        console_println!("{} [SYSTEM] further {} messages are only counted, see DIAG", time.dimmed(), kind);
+    }
+    print
+}
+
+/// Short form of the common badges in the log and on the console.
+fn short_badge_name(name: &str) -> &str {
+    match name {
+        "moderator" => "mod",
+        "subscriber" => "sub",
+        "premium" => "prime",
+        other => other,
+    }
+}
+
+pub fn handle_privmsg(
+    time_str: &str,
+    msg: PrivmsgMessage,
+    state: &LoggerState,
+) {
+
+    record_channel_display_name(state, &msg.channel_login, &msg.sender.login, &msg.sender.name);
+
+    // Sub-only / emote-only marks from the last ROOMSTATE, kept inside the column width
+    let mode = state.incidents.lock().unwrap()
+    .get(&msg.channel_login)
+    .map(|tracker| tracker.restrictions.mode_indicator())
+    .unwrap_or_default();
+    let name_width = match state.channel_width() {
+        0 => 0,
+        width => width.saturating_sub(mode.chars().count()).max(1),
+    };
+    let channel_display = apply_named_color(
+        &format!("{}{}", fit_to_width(&msg.channel_login, name_width), mode),
+        channel_color(state, &msg.channel_login).as_deref(),
+    );
+
+    let mut custom_badges = msg.badge_set.iter()
+    .map(|b| format!("{}/{}", short_badge_name(&b.name), b.version))
+    .collect::<Vec<_>>();
+
+    let tags = &msg.source.tags;
+
+    // Add virtual badges based on tag fields
+    let is_first_msg = tags.0.get("first-msg").and_then(|v| v.as_deref()) == Some("1");
+    if is_first_msg {
+        custom_badges.push("(FIRSTMSG)".to_string());
+    }
+
+    if let Some(returning) = tags.0.get("returning-chatter").and_then(|v| v.as_deref()) {
+        if returning == "1" {
+            custom_badges.push("(RETURNING)".to_string());
+        }
+    }
+
+    let badges_for_log = custom_badges.join(",");
+    let badge_info_for_console = if !custom_badges.is_empty() {
+        format!("[{}]", custom_badges.join(", ").yellow())
+    } else {
+        String::new()
+    };
+
+    // The console shows the text as sent, the log and the detectors the normalized one
+    let normalized = {
+        let settings = state.settings.lock().unwrap();
+        settings.normalize_messages.value.then(|| normalize_message(&msg.message_text, settings.max_space_run.value)).flatten()
+    };
+    // Before anything is logged or handed to the sinks
+    let redacted = redact_message(state, &msg.channel_login, normalized.as_deref().unwrap_or(&msg.message_text));
+    let text = redacted.as_deref().or(normalized.as_deref()).unwrap_or(&msg.message_text);
+    let console_text = match &redacted {
+        Some(redacted) if state.settings.lock().unwrap().redact_console.value => redacted,
+        _ => &msg.message_text,
+    };
+
+    let log_line = format!(
+        "{} <{}>{}\n{}\n",
+        time_str,
+        msg.sender.name,
+        if badges_for_log.is_empty() {
+            "".to_string()
+        } else {
+            format!(" [{}]", badges_for_log)
+        },//badges at the end in the logfile
+        text
+    );
+
+    let record = LogRecord::from_privmsg(current_stamp().received, &msg, text);
+    append_line_with(state, "msgs", &msg.channel_login, log_line, normalized.is_some(), Some(record));
+    let sent = {
+        let mut counts = state.user_message_counts.lock().unwrap();
+        let count = counts
+        .entry(msg.channel_login.clone())
+        .or_default()
+        .entry(msg.sender.name.clone())
+        .or_default();
+        *count += 1;
+        *count
+    };
+    if sent == 1 {
+        check_chatter_spike(time_str, &msg.channel_login, state);
+    }
+    let min_repeat_chars = state.settings.lock().unwrap().copypasta_min_length.value;
+    state.message_records.lock().unwrap()
+    .entry(msg.channel_login.clone())
+    .or_default()
+    .record(&msg.sender.name, text, min_repeat_chars);
+    check_cross_channel_spam(time_str, &msg, text, min_repeat_chars, state);
+
+    // --- END OF BADGE LOGIC ---
+
+    let user_styled = if let Some(color) = msg.name_color {
+        msg.sender.name.truecolor(color.r, color.g, color.b).to_string()
+    } else {
+        msg.sender.name.clone()
+    };
+
+    // IRC style role prefix: ~ broadcaster, @ moderator, + VIP
+    let role = if msg.badge_set.is_broadcaster() {
+        "~"
+    } else if msg.badge_set.is_moderator() {
+        "@"
+    } else if msg.badge_set.is_vip() {
+        "+"
+    } else {
+        ""
+    };
+
+    let muted = state.muted_users.lock().unwrap().contains(&msg.sender.login);
+    if state.is_visible(&msg.channel_login) && !muted {
+        let line = format!(
+            "{} [{}] {}{}{}: {}",
+            display_time(state, &msg.channel_login, time_str).dimmed(),
+                 channel_display,
+                 role.bright_white(),
+                 user_styled.bold(),
+                 badge_info_for_console,
+                 console_text
+        );
+        if is_first_msg && state.settings.lock().unwrap().highlight_first_msg.value {
+            console_println!("{}", frame_gold(&line));
+        } else {
+            console_println!("{}", line);
+        }
+    }
+
+    if muted {
+        return;
+    }
+    let summary = format!("#{}", msg.channel_login);
+    let body = format!("{}: {}", msg.sender.name, console_text);
+
+    match alert_for(state, &msg.channel_login) {
+        Some(AlertMode::Sound) => {
+            send_channel_notification(state, &msg.channel_login, &summary, &body);
+            play_channel_sound(state, &msg.channel_login);
+        }
+        // Notify mode: only sends a notification
+        Some(AlertMode::Notify) => send_channel_notification(state, &msg.channel_login, &summary, &body),
+        None => {}
+    }
+}
+
+/// Count a chatter new to this session. A spike of them gets an orange line, a notification
+/// whatever the channel's SOUND/NOTIFY mode, and an `[ANOMALY]` marker in the log.
+fn check_chatter_spike(time_str: &str, channel: &str, state: &LoggerState) {
+    let (factor, min_new) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.chatter_spike_factor.value, settings.chatter_spike_min.value)
+    };
+    if min_new == 0 {
+        return;
+    }
+    let minute = (Local::now().timestamp() / 60) as u64;
+    let spike = {
+        let mut detectors = state.chatter_spikes.lock().unwrap();
+        let detector = detectors.entry(channel.to_string()).or_default();
+        detector.advance_to(minute, factor, min_new);
+        detector.record(factor, min_new)
+    };
+    let Some(spike) = spike else {
+        return;
+    };
+
+    let what = format!("{} new chatters this minute, usually {:.1}", spike.new_chatters, spike.average);
+    let line = format!("{} in #{}", what, channel);
+    console_println!("{}", format!("*** {} [ANOMALY] {} ***", display_time(state, channel, time_str), line).truecolor(255, 165, 0).bold());
+    if state.alerts {
+        send_desktop_notification(state, &format!("Chatter spike in #{}", channel), &what);
+    }
+    append_marker(state, channel, time_str, "ANOMALY", &line);
+}
+
+/// The same text in several channels within a minute gets a red line, a notification, a
+/// `[SPAM]` marker in each channel's log and, with `spam_auto_mute`, the senders muted.
+fn check_cross_channel_spam(time_str: &str, msg: &PrivmsgMessage, text: &str, min_length: usize, state: &LoggerState) {
+    let wave = state.cross_channel_spam.lock().unwrap()
+    .record(text, &msg.channel_login, &msg.sender.login, Instant::now(), min_length);
+    let Some(wave) = wave else {
+        return;
+    };
+
+    let channels = wave.channels.iter().map(|c| format!("#{}", c)).collect::<Vec<_>>().join(", ");
+    let what = format!("same message in {} by {}: \"{}\"", channels, wave.users.join(", "), text);
+    console_println!("{}", format!("*** {} [SPAM] {} ***", display_time(state, &msg.channel_login, time_str), what).red().bold());
+    if state.alerts {
+        send_desktop_notification(state, "Cross-channel spam", &what);
+    }
+    for channel in &wave.channels {
+        append_marker(state, channel, time_str, "SPAM", &what);
+    }
+    if state.settings.lock().unwrap().spam_auto_mute.value {
+        state.muted_users.lock().unwrap().extend(wave.users.iter().cloned());
+        console_println!("Muted {} (MUTE <user> to undo)", wave.users.join(", "));
+    }
+}
+
+/// Double line gold frame around a (colored) console line, used for first-time chatters.
+fn frame_gold(line: &str) -> String {
+    let bar = "═".repeat(visible_width(line) + 2);
+    format!(
+        "{}\n{} {} {}\n{}",
+        format!("╔{}╗", bar).truecolor(255, 215, 0),
+        "║".truecolor(255, 215, 0),
+        line,
+        "║".truecolor(255, 215, 0),
+        format!("╚{}╝", bar).truecolor(255, 215, 0),
+    )
+}
+
+/*https://docs.rs/twitch-irc/latest/twitch_irc/message/enum.UserNoticeEvent.html*/
+
+/// Text the user attached to the event, `None` for events without one (e.g. anonymous gift subs).
+fn user_notice_text(msg: &UserNoticeMessage) -> Option<&str> {
+    msg.message_text.as_deref().map(str::trim).filter(|text| !text.is_empty())
+}
+
+/// "SUBGIFT" for `SubGift { .. }`: the variant name without its fields.
+fn user_notice_event_name(event: &UserNoticeEvent) -> String {
+    let debug = format!("{:?}", event);
+    debug.split([' ', '{', '(']).next().unwrap_or_default().to_uppercase()
+}
+
+/// Whether the sender of the notice stands in for an anonymous gifter.
+fn is_anonymous_gifter(msg: &UserNoticeMessage) -> bool {
+    match &msg.event {
+        UserNoticeEvent::SubGift { is_sender_anonymous, .. } => *is_sender_anonymous,
+        UserNoticeEvent::AnonSubMysteryGift { .. } | UserNoticeEvent::AnonGiftPaidUpgrade { .. } => true,
+        _ => msg.sender.login == ANONYMOUS_GIFTER_LOGIN,
+    }
+}
+
+/// Service account Twitch sends anonymous gifts from.
+const ANONYMOUS_GIFTER_LOGIN: &str = "ananonymousgifter";
+
+/// Sender shown in the `[channel][sender]` part, "anonymous" for anonymous gifts.
+fn user_notice_sender(msg: &UserNoticeMessage) -> &str {
+    if is_anonymous_gifter(msg) {
+        "anonymous"
+    } else {
+        &msg.sender.name
+    }
+}
+
+/// "gifter → recipient (tier 1, 3 months gifted)" of a gift sub.
+fn gift_description(msg: &UserNoticeMessage) -> Option<String> {
+    let UserNoticeEvent::SubGift { recipient, sub_plan, num_gifted_months, .. } = &msg.event else {
+        return None;
+    };
+    let months = if *num_gifted_months > 1 { format!(", {} months gifted", num_gifted_months) } else { String::new() };
+    Some(format!("{} → {} ({}{})", user_notice_sender(msg), recipient.name, sub_tier(sub_plan), months))
+}
+
+/// What the notice is about: who got a gift sub, the months and streak of a (re)sub,
+/// the system message for everything else.
+fn user_notice_summary(msg: &UserNoticeMessage) -> String {
+    gift_description(msg)
+    .or_else(|| sub_description(&msg.event))
+    .unwrap_or_else(|| msg.system_message.trim().to_string())
+}
+
+/// `<EVENT> text → summary`, or just `<EVENT> summary` without user text.
+fn format_user_notice_log(time: &str, msg: &UserNoticeMessage, event_type: &str) -> String {
+    let summary = user_notice_summary(msg);
+    let body = match user_notice_text(msg) {
+        Some(user_msg) => format!("{} → {}", user_msg, summary),
+        None => summary,
+    };
+    format!("{} [{}][{}] <{}> {}", time, msg.channel_login, user_notice_sender(msg), event_type, body)
+}
+
+pub fn handle_user_notice(
+    time: &str,
+    msg: &UserNoticeMessage,
+    state: &LoggerState,
+) {
+
+    // Fallback to raw msg-id tag if the event is unknown
+    let raw_msg_id = msg
+    .source
+    .tags
+    .0
+    .get("msg-id")
+    .and_then(|v| v.as_deref())
+    .unwrap_or("unknown");
+
+    let event_type = match &msg.event {
+        UserNoticeEvent::Unknown => raw_msg_id.to_uppercase(),
+        other => user_notice_event_name(other),
+    };
+    // Unknown ones are still logged, but only the first few printed
+    let sampled = !matches!(msg.event, UserNoticeEvent::Unknown)
+        || record_unknown_message(state, time, &format!("USERNOTICE/{}", raw_msg_id), &msg.source);
+
+    // Before anything is logged or handed to the sinks
+    let redacted = msg.message_text.as_deref()
+    .and_then(|text| redact_message(state, &msg.channel_login, text))
+    .map(|text| UserNoticeMessage { message_text: Some(text), ..msg.clone() });
+    let stored = redacted.as_ref().unwrap_or(msg);
+    let shown = if state.settings.lock().unwrap().redact_console.value { stored } else { msg };
+
+    let channel = &msg.channel_login;
+    let line = format_user_notice_log(time, stored, &event_type);
+    record_channel_display_name(state, channel, &msg.sender.login, &msg.sender.name);
+    if let UserNoticeEvent::SubGift { recipient, .. } = &msg.event {
+        state.gift_recipients.lock().unwrap()
+        .entry(channel.clone())
+        .or_default()
+        .insert(recipient.login.clone());
+    }
+    record_sub(state, msg);
+
+    if sampled && state.is_visible(channel) {
+        let prefix = format!(
+            "{} [{}][{}] {}:",
+            display_time(state, channel, time).dimmed(),
+            fit_to_width(channel, state.channel_width()),
+            user_notice_sender(msg),
+            event_type.blue()
+        );
+        let summary = user_notice_summary(msg);
+        match user_notice_text(shown) {
+            Some(user_msg) => console_println!("{} {}\n→ {}", prefix, user_msg, summary.yellow()),
+            None => console_println!("{} {}", prefix, summary.yellow()),
+        }
+    }
+
+    let record = LogRecord::from_user_notice(current_stamp().received, stored, &event_type);
+    append_line_with(state, "msgs", channel, line, false, Some(record));
+
+    record_raid(time, msg, state);
+}
+
+
+pub fn handle_room_state(
+    time_str: &str,
+    msg: &RoomStateMessage,
+    state: &LoggerState,
+) {
+    let channel = &msg.channel_login;
+    let transition = {
+        let mut trackers = state.incidents.lock().unwrap();
+        let tracker = trackers.entry(channel.clone()).or_default();
+
+        // ROOMSTATE updates only carry the settings that changed
+        let old = tracker.restrictions.clone();
+        let mut new = old.clone();
+        if let Some(mode) = &msg.follwers_only {
+            new.followers_only = match mode {
+                FollowersOnlyMode::Enabled(d) => Some(d.as_secs() / 60),
+                FollowersOnlyMode::Disabled => None,
+            };
+        }
+        if let Some(v) = msg.subscribers_only { new.subs_only = v; }
+        if let Some(v) = msg.emote_only { new.emote_only = v; }
+        if let Some(v) = msg.slow_mode { new.slow = v.as_secs(); }
+        if let Some(v) = msg.r9k { new.r9k = v; }
+
+        // The full state sent right after joining is just the baseline
+        if tracker.set_baseline(new.clone()) {
+            return;
+        }
+
+        let changes = if state.is_visible(channel) { old.diff(&new) } else { Vec::new() };
+        for (on, setting) in changes {
+            if on {
+                console_println!("{} [{}][ROOMSTATE] {}", display_time(state, channel, time_str).dimmed(), channel, format!("+ {}", setting).green());
+            } else {
+                console_println!("{} [{}][ROOMSTATE] {}", display_time(state, channel, time_str).dimmed(), channel, format!("- {}", setting).red());
+            }
+        }
+
+        tracker.update(new, Instant::now())
+    };
+
+    if let Some(transition) = transition {
+        report_incident(time_str, channel, transition, state);
+    }
+}
+
+/// Prints the boxed restriction summary and pins a copy into the channel log.
+fn report_incident(
+    time_str: &str,
+    channel: &str,
+    transition: IncidentTransition,
+    state: &LoggerState,
+) {
+    let restrictions: RoomRestrictions = state.incidents.lock().unwrap()
+    .get(channel)
+    .map(|t| t.restrictions.clone())
+    .unwrap_or_default();
+
+    let (title, marker) = match transition {
+        IncidentTransition::Opened => (
+            format!("INCIDENT #{} {}", channel, time_str),
+            "restrictions raised during moderation spike".to_string(),
+        ),
+        IncidentTransition::Closed { duration } => (
+            format!("INCIDENT OVER #{} {}", channel, time_str),
+            format!("restrictions lifted after {}", format_duration(duration)),
+        ),
+    };
+
+    let mut lines = vec![marker.clone()];
+    lines.extend(restrictions.summary_lines());
+    let boxed = render_box(&title, &lines);
+
+    if state.is_visible(channel) {
+        match transition {
+            IncidentTransition::Opened => console_println!("{}", boxed.red().bold()),
+            IncidentTransition::Closed { .. } => console_println!("{}", boxed.yellow()),
+        }
+    }
+
+    append_marker(state, channel, time_str, "INCIDENT", &format!("{}\n{}", marker, restrictions.summary_lines().join("\n")));
+}
+
+
+pub fn handle_moderation_event(
+    time_str: &str,
+    event_type: &str,
+    channel: &str,
+    content: &str,
+    style: owo_colors::Style,
+    state: &LoggerState,
+) {
+    let log_line = format!("{time_str} {event_type}: [#{channel}] {content}");
+    if state.is_visible(channel) {
+        let time = display_time(state, channel, time_str);
+        console_println!("{}", format!("{time} {event_type}: [#{channel}] {content}").style(style));
+    }
+
+    // Clearing the whole chat is not a ban-wave signal
+    if event_type != "CHAT_CLEARED" {
+        let transition = state.incidents.lock().unwrap()
+        .entry(channel.to_string())
+        .or_default()
+        .on_moderation(Instant::now());
+        if let Some(transition) = transition {
+            report_incident(time_str, channel, transition, state);
+        }
+    }
+
+    if state.alerts {
+        let summary = format!("Moderation in #{}", channel);
+        let body = format!("[{}] {}", event_type, content);
+        send_desktop_notification(state, &summary, &body);
+        play_channel_sound(state, channel);
+    }
+
+    if state.settings.lock().unwrap().moderation_in_msgs.value {
+        // Bans and timeouts start with the login of the user
+        let target = matches!(event_type, "USER_BANNED" | "TIMEOUT").then(|| content.split(' ').next()).flatten();
+        let record = LogRecord::moderation(current_stamp().received, channel, event_type, target, content);
+        append_line_with(state, "msgs", channel, log_line.clone(), false, Some(record));
+    }
+    append_line(state, "moderation", channel, log_line);
+}
+
+
+/// Ban, timeout or deletion aimed at the `own_login` account: a red line, a notification
+/// whatever the channel's SOUND/NOTIFY mode, and an `[OWN]` marker in the log.
+fn alert_own_moderation(time_str: &str, channel: &str, target_login: &str, what: &str, state: &LoggerState) {
+    let own = state.settings.lock().unwrap().own_login.value.clone();
+    if own.as_deref() != Some(target_login) {
+        return;
+    }
+
+    let line = format!("{} in #{}", what, channel);
+    console_println!("{}", format!("*** {} [OWN] {} ***", display_time(state, channel, time_str), line).red().bold());
+    if state.alerts {
+        send_desktop_notification(state, &format!("Moderated in #{}", channel), what);
+        play_channel_sound(state, channel);
+    }
+    append_marker(state, channel, time_str, "OWN", &line);
+}
+
+/// Ban or timeout of a VIP, from channels.txt or imported at runtime, in any channel: a red box, the alarm and a
+/// notification whatever the channel's SOUND/NOTIFY mode, a `[VIP-MOD]` line in the log
+/// and an entry in `vip_incidents`.
+fn alert_vip_moderation(time_str: &str, channel: &str, target_login: &str, action: &str, state: &LoggerState) {
+    if !state.is_vip(target_login) {
+        return;
+    }
+
+    let incident = VipIncident {
+        time: time_str.to_string(),
+        channel: channel.to_string(),
+        login: target_login.to_string(),
+        action: action.to_string(),
+    };
+    let title = format!("VIP MODERATED #{} {}", channel, display_time(state, channel, time_str));
+    console_println!("{}", render_box(&title, &[format!("{} was {}", target_login, action)]).red().bold());
+    if state.alerts {
+        send_desktop_notification(state, &format!("VIP {} {}", target_login, action), &format!("in #{}", channel));
+        play_alarm();
+    }
+    append_marker(state, channel, time_str, "VIP-MOD", &format!("{} was {}", target_login, action));
+    state.vip_incidents.lock().unwrap().push(incident);
+}
+
+pub fn handle_join_or_part(
+     event_type: &str,
+     time_str: &str,
+     channel: &str,
+     username: &str,
+     state: &LoggerState,
+  ){
+
+     let is_vip = state.is_vip(username);
+     let msg = format!("{time_str} [{event_type}] {username}");
+
+     let aggregate = {
+         let mut membership = state.membership.lock().unwrap();
+         let tracker = membership
+         .entry(channel.to_string())
+         .or_insert_with(|| ChannelMembership::new(configured_mode(channel, state)));
+
+         match tracker.mode {
+             MembershipMode::Off => return,
+             MembershipMode::CountsOnly => tracker.record(time_str, event_type == "JOIN"),
+             MembershipMode::VipsOnly if !is_vip => None,
+             _ => Some(msg.replace("[JOIN] ","[J] ").replace("[PART] ","[P] ")),
+         }
+     };
+
+     if let Some(line) = aggregate {
+         state.join_queue.push(channel, line);
+     }
+
+     if !is_vip {
+         return;
+     }
+     // A PART waits out `vip_part_grace`, a JOIN right after it is only a flap
+     let now = Instant::now();
+     if event_type == "PART" {
+         if !hold_part(state, channel, username, time_str, now) {
+             report_vip_event(state, event_type, time_str, channel, username, "");
+         }
+         return;
+     }
+     if check_flap(state, channel, username, time_str, now) {
+         return;
+     }
+
+     let count = record_vip_join(&mut state.vip_join_counts.lock().unwrap(), username, channel);
+     report_vip_event(state, event_type, time_str, channel, username, &format!(" (visit #{count})"));
+
+     if state.alerts && username != channel {
+         play_channel_sound(state, channel);
+         send_channel_notification(state, channel, channel, &format!("{} joined",username));
+     }
+}
+
+/// Console line and message log line of a VIP JOIN or PART.
+pub fn report_vip_event(state: &LoggerState, event_type: &str, time_str: &str, channel: &str, username: &str, visit: &str) {
+     if state.is_visible(channel) {
+         console_println!("{}", format!("*** VIP {username} has {event_type}ed {channel}{visit} ***").yellow());
+     }
+
+     // Save in general log when it's a VIP, but on same channel
+     if username != channel {
+         append_marker(state, channel, time_str, event_type, username);
+     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buckets::{log_texts, LogLine};
+    use twitch_irc::message::IRCMessage;
+
+    #[test]
+    fn moderation_alerts_wait_for_the_sound_cooldown() {
+        let (config, _) = crate::channel_config::parse_channel_config("0\nforsen sound_cooldown=60000\n").unwrap();
+        let state = LoggerState { config: std::sync::Arc::new(config), alerts: true, ..Default::default() };
+        let ban = |login: &str| {
+            let raw = format!("@room-id=22484632;target-user-id=11148817;tmi-sent-ts=1700000000000 :tmi.twitch.tv CLEARCHAT #forsen :{}", login);
+            handle_message("12:00:00", ServerMessage::try_from(IRCMessage::parse(&raw).unwrap()).unwrap(), &state);
+        };
+        ban("alice");
+        ban("bob");
+        ban("carol");
+        let sent = crate::sound::SENT.with(|sent| sent.borrow().clone());
+        assert_eq!(sent, [crate::sound::SoundKind::Alert(None)]);
+        assert_eq!(state.logs.lock().unwrap()["forsen"].len(), 3);
+    }
+
+    fn user_notice(raw: &str) -> UserNoticeMessage {
+        UserNoticeMessage::try_from(IRCMessage::parse(raw).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn user_notice_with_text_keeps_both_parts() {
+        let msg = user_notice("@badge-info=subscriber/2;badges=subscriber/0;color=#0000FF;display-name=Gutrin;emotes=;flags=;id=e0975c76-054c-4954-8cb0-91b8867ec1ca;login=gutrin;mod=0;msg-id=resub;msg-param-cumulative-months=2;msg-param-months=0;msg-param-should-share-streak=1;msg-param-streak-months=2;msg-param-sub-plan-name=Channel\\sSubscription\\s(xqcow);msg-param-sub-plan=1000;room-id=71092938;subscriber=1;system-msg=Gutrin\\ssubscribed\\sat\\sTier\\s1.;tmi-sent-ts=1581713640019;user-id=21156217;user-type= :tmi.twitch.tv USERNOTICE #xqcow :xqcL");
+        assert_eq!(
+            format_user_notice_log("12:00:00", &msg, "RESUB"),
+            "12:00:00 [xqcow][Gutrin] <RESUB> xqcL → tier 1 resub (2 months, 2-month streak)"
+        );
+    }
+
+    #[test]
+    fn user_notice_without_text_is_one_clean_line() {
+        let msg = user_notice("@badge-info=;badges=;color=;display-name=AnAnonymousGifter;emotes=;flags=;id=8db97752-3dee-460b-9001-e925d0e2ba5b;login=ananonymousgifter;mod=0;msg-id=anonsubmysterygift;msg-param-mass-gift-count=15;msg-param-origin-id=13\\s33;msg-param-sub-plan=2000;room-id=71092938;subscriber=0;system-msg=An\\sanonymous\\suser\\sis\\sgifting\\s15\\sTier\\s2\\sSubs\\sto\\sxQcOW's\\scommunity!;tmi-sent-ts=1585447099603;user-id=274598607;user-type= :tmi.twitch.tv USERNOTICE #xqcow");
+        let line = format_user_notice_log("12:00:00", &msg, &user_notice_event_name(&msg.event));
+        assert_eq!(
+            line,
+            "12:00:00 [xqcow][anonymous] <ANONSUBMYSTERYGIFT> An anonymous user is gifting 15 Tier 2 Subs to xQcOW's community!"
+        );
+        assert!(!line.contains('→'));
+    }
+
+    #[test]
+    fn gift_subs_name_the_recipient() {
+        let msg = user_notice("@badge-info=;badges=;color=;display-name=AnAnonymousGifter;emotes=;flags=;id=1;login=ananonymousgifter;mod=0;msg-id=subgift;msg-param-gift-months=3;msg-param-months=5;msg-param-recipient-display-name=Dot0422;msg-param-recipient-id=151784015;msg-param-recipient-user-name=dot0422;msg-param-sub-plan-name=Channel\\sSubscription\\s(xqcow);msg-param-sub-plan=1000;room-id=71092938;subscriber=0;system-msg=An\\sanonymous\\suser\\sgifted\\sa\\sTier\\s1\\ssub\\sto\\sDot0422!;tmi-sent-ts=1594583782376;user-id=274598607;user-type= :tmi.twitch.tv USERNOTICE #xqcow");
+        assert_eq!(user_notice_event_name(&msg.event), "SUBGIFT");
+        assert_eq!(
+            format_user_notice_log("12:00:00", &msg, "SUBGIFT"),
+            "12:00:00 [xqcow][anonymous] <SUBGIFT> anonymous → Dot0422 (tier 1, 3 months gifted)"
+        );
+
+        let state = LoggerState::default();
+        handle_user_notice("12:00:00", &msg, &state);
+        handle_user_notice("12:00:01", &msg, &state);
+        assert_eq!(state.gift_recipients.lock().unwrap()["xqcow"].len(), 1);
+    }
+
+    #[test]
+    fn blank_user_text_counts_as_none() {
+        let msg = user_notice("@badge-info=;badges=;color=;display-name=Someone;emotes=;flags=;id=1;login=someone;mod=0;msg-id=communitypayforward;room-id=1;subscriber=0;system-msg=Someone\\sis\\spaying\\sforward\\sthe\\sGift.;tmi-sent-ts=1585447099603;user-id=2;user-type= :tmi.twitch.tv USERNOTICE #pajlada :  ");
+        assert_eq!(
+            format_user_notice_log("12:00:00", &msg, "COMMUNITYPAYFORWARD"),
+            "12:00:00 [pajlada][Someone] <COMMUNITYPAYFORWARD> Someone is paying forward the Gift."
+        );
+    }
+
+    #[test]
+    fn deletion_of_own_message_is_marked() {
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().own_login.value = Some("me".to_string());
+        for login in ["someone", "me"] {
+            let raw = format!("@login={};room-id=;target-msg-id=15e5164d-f8e6-4aec-baf4-2d6a330760c4;tmi-sent-ts=1594562632383 :tmi.twitch.tv CLEARMSG #pajlada :hello", login);
+            handle_message("12:00:00", ServerMessage::try_from(IRCMessage::parse(&raw).unwrap()).unwrap(), &state);
+        }
+        let logs = state.logs.lock().unwrap();
+        let own: Vec<&str> = logs["pajlada"].iter().map(LogLine::text).filter(|l| l.contains("[OWN]")).collect();
+        assert_eq!(own, vec!["12:00:00 [OWN] your message \"hello\" was deleted in #pajlada"]);
+    }
+
+    #[test]
+    fn normalized_text_is_logged() {
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().normalize_messages.value = true;
+        let raw = "@badge-info=;badges=;color=;display-name=Alice;emotes=;id=1;room-id=1;tmi-sent-ts=1594562632383;user-id=2 :alice!alice@alice.tmi.twitch.tv PRIVMSG #pajlada :gg   wp \u{e0000}";
+        handle_message("12:00:00", ServerMessage::try_from(IRCMessage::parse(raw).unwrap()).unwrap(), &state);
+        assert_eq!(state.logs.lock().unwrap()["pajlada"], ["12:00:00 <Alice>\ngg wp\n"]);
+    }
+
+    fn vip_state() -> LoggerState {
+        let (config, _) = crate::channel_config::parse_channel_config("0\nalice\n").unwrap();
+        LoggerState { config: std::sync::Arc::new(config), ..Default::default() }
+    }
+
+    fn vip_lines(state: &LoggerState) -> Vec<String> {
+        log_texts(&state.logs, "forsen").unwrap_or_default()
+    }
+
+    #[test]
+    fn quick_rejoin_of_a_vip_is_one_flap() {
+        let state = vip_state();
+        handle_join_or_part("PART", "12:00:00", "forsen", "alice", &state);
+        assert!(vip_lines(&state).is_empty());
+        handle_join_or_part("JOIN", "12:00:05", "forsen", "alice", &state);
+        let lines = vip_lines(&state);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("12:00:05 [FLAP] alice (parted at 12:00:00, back after "), "{}", lines[0]);
+        // Not a new visit
+        assert!(state.vip_join_counts.lock().unwrap().is_empty());
+        assert!(state.pending_parts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn vip_part_is_reported_after_the_grace_period() {
+        let state = vip_state();
+        handle_join_or_part("PART", "12:00:00", "forsen", "alice", &state);
+        crate::vip_parts::release_expired_parts(&state, Instant::now());
+        assert!(vip_lines(&state).is_empty());
+        crate::vip_parts::release_expired_parts(&state, Instant::now() + std::time::Duration::from_secs(60));
+        assert_eq!(vip_lines(&state), ["12:00:00 [PART] alice"]);
+
+        handle_join_or_part("JOIN", "12:05:00", "forsen", "alice", &state);
+        assert_eq!(vip_lines(&state)[1], "12:05:00 [JOIN] alice");
+    }
+
+    #[test]
+    fn held_parts_are_reported_on_exit_or_without_grace() {
+        let state = vip_state();
+        handle_join_or_part("PART", "12:00:00", "forsen", "alice", &state);
+        crate::vip_parts::release_parts(&state, None);
+        assert_eq!(vip_lines(&state), ["12:00:00 [PART] alice"]);
+
+        state.settings.lock().unwrap().vip_part_grace.value = 0;
+        handle_join_or_part("PART", "12:01:00", "forsen", "alice", &state);
+        assert_eq!(vip_lines(&state)[1], "12:01:00 [PART] alice");
+        assert!(state.pending_parts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn late_lines_keep_their_receive_order() {
+        use crate::sequence::{next_stamp, with_stamp};
+        let state = vip_state();
+        // As `handle_received` does, one stamp per event
+        let receive = |time: &str, event: &str, user: &str| {
+            with_stamp(next_stamp(Local::now()), || handle_join_or_part(event, time, "forsen", user, &state));
+        };
+        receive("12:00:00", "PART", "alice");
+        receive("12:00:01", "JOIN", "bob");
+        receive("12:00:02", "JOIN", "carol");
+        with_stamp(next_stamp(Local::now()), || handle_moderation_event("12:00:03", "TIMEOUT", "forsen", "troll (600s timeout)", owo_colors::Style::new(), &state));
+        // Held until now, and the join queue is drained after all of them
+        crate::vip_parts::release_expired_parts(&state, Instant::now() + std::time::Duration::from_secs(60));
+        crate::membership::drain_join_queue(&state);
+        receive("12:00:04", "JOIN", "dave");
+        crate::membership::drain_join_queue(&state);
+
+        assert_eq!(vip_lines(&state), ["12:00:00 [PART] alice", "12:00:03 TIMEOUT: [#forsen] troll (600s timeout)"]);
+        assert_eq!(
+            state.join_logs.lock().unwrap()["forsen"],
+            ["12:00:00 [P] alice", "12:00:01 [J] bob", "12:00:02 [J] carol", "12:00:04 [J] dave"]
+        );
+    }
+
+    #[test]
+    fn moderation_has_its_own_log() {
+        let state = LoggerState::default();
+        handle_moderation_event("12:00:00", "TIMEOUT", "pajlada", "troll (600s timeout)", owo_colors::Style::new(), &state);
+        state.settings.lock().unwrap().moderation_in_msgs.value = false;
+        handle_moderation_event("12:00:01", "USER_BANNED", "pajlada", "troll", owo_colors::Style::new(), &state);
+
+        assert_eq!(
+            state.moderation_logs.lock().unwrap()["pajlada"],
+            ["12:00:00 TIMEOUT: [#pajlada] troll (600s timeout)", "12:00:01 USER_BANNED: [#pajlada] troll"]
+        );
+        assert_eq!(state.logs.lock().unwrap()["pajlada"], ["12:00:00 TIMEOUT: [#pajlada] troll (600s timeout)"]);
+    }
+
+    #[test]
+    fn timeout_of_a_vip_is_an_incident() {
+        let state = vip_state();
+        for login in ["troll", "alice"] {
+            let raw = format!("@ban-duration=600;room-id=1;target-user-id=2;tmi-sent-ts=1594562632383 :tmi.twitch.tv CLEARCHAT #forsen :{}", login);
+            handle_message("12:00:00", ServerMessage::try_from(IRCMessage::parse(&raw).unwrap()).unwrap(), &state);
+        }
+        let incidents = state.vip_incidents.lock().unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].to_string(), "12:00:00 VIP alice timed out for 600s in #forsen");
+        assert!(vip_lines(&state).contains(&"12:00:00 [VIP-MOD] alice was timed out for 600s".to_string()));
+    }
+}
@@ -0,0 +1,191 @@
+//! `--health-listen`: a minimal HTTP endpoint for liveness and readiness checks of the
+//! headless archiver (systemd, container probes). Any GET of `/` or `/health` is answered
+//! with the JSON of `Health`, status 200 while the client is connected and the message
+//! loop runs, 503 otherwise. One request per connection, no keep-alive.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use twitch_irc::login::LoginCredentials;
+use twitch_irc::transport::Transport;
+use twitch_irc::{PoolStatus, TwitchIRCClient};
+
+use crate::console::print_status;
+use crate::state::{LoggerState, SESSION_START};
+
+/// Without anything from the server for this long the connection counts as dead. The
+/// client pings every 30 seconds, so even quiet channels get a PONG in between.
+pub const STALE_AFTER: Duration = Duration::from_secs(90);
+/// Slow or silent clients are dropped after this.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Request headers read at most, the request line is all that matters.
+const MAX_HEADER_LINES: usize = 64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Health {
+    /// "ok" or "unavailable", as the status code says.
+    pub status: &'static str,
+    pub uptime_secs: i64,
+    /// The pool has a connection and the server sent something recently.
+    pub connected: bool,
+    /// False once the archiver is shutting down.
+    pub message_loop: bool,
+    pub channels: usize,
+    pub messages_last_minute: usize,
+    /// Seconds since anything (chat or PONG) arrived from the server.
+    pub last_received_secs: Option<u64>,
+    pub unsaved_lines: u64,
+    /// Unsaved lines per channel, channels without any are left out.
+    pub unsaved: BTreeMap<String, u64>,
+    /// RFC 3339 time of the last save that wrote a file.
+    pub last_save: Option<String>,
+}
+
+impl Health {
+    pub fn is_healthy(&self) -> bool {
+        self.connected && self.message_loop
+    }
+}
+
+pub fn health(state: &LoggerState, pool: &PoolStatus, now: Instant) -> Health {
+    let last_received = state.last_received.lock().unwrap().map(|at| now.saturating_duration_since(at));
+    let connected = pool.connections > 0 && last_received.is_some_and(|quiet| quiet < STALE_AFTER);
+    let message_loop = state.session_end.lock().unwrap().is_none();
+    let unsaved = state.unsaved.lock().unwrap();
+    Health {
+        status: if connected && message_loop { "ok" } else { "unavailable" },
+        uptime_secs: (Local::now() - *SESSION_START).num_seconds(),
+        connected,
+        message_loop,
+        channels: state.channels.lock().unwrap().len(),
+        messages_last_minute: state.message_rate.lock().unwrap().per_minute(now),
+        last_received_secs: last_received.map(|quiet| quiet.as_secs()),
+        unsaved_lines: unsaved.total(),
+        unsaved: unsaved.by_channel(),
+        last_save: state.last_save.lock().unwrap().map(|at| at.to_rfc3339()),
+    }
+}
+
+/// The whole HTTP response to `request_line` ("GET /health HTTP/1.1").
+pub fn http_response(request_line: &str, health: &Health) -> String {
+    let mut words = request_line.split_whitespace();
+    let (method, path) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+    let path = path.split('?').next().unwrap_or("");
+    let (status, body) = match (method, path) {
+        ("GET" | "HEAD", "/" | "/health") => {
+            let status = if health.is_healthy() { "200 OK" } else { "503 Service Unavailable" };
+            (status, serde_json::to_string(health).unwrap_or_default())
+        }
+        ("GET" | "HEAD", _) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+        _ => ("405 Method Not Allowed", r#"{"error":"method not allowed"}"#.to_string()),
+    };
+    // HEAD gets the headers of the GET, Content-Length included
+    let length = body.len();
+    let body = if method == "HEAD" { "" } else { body.as_str() };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status, length, body
+    )
+}
+
+/// Listen on `addr` and answer health checks in the background.
+pub async fn start_health_server<T: Transport, L: LoginCredentials>(
+    addr: &str,
+    client: TwitchIRCClient<T, L>,
+    state: &LoggerState,
+) -> Result<SocketAddr> {
+    let addr: SocketAddr = addr.parse().map_err(|e| anyhow!("--health-listen {}: {}", addr, e))?;
+    let listener = TcpListener::bind(addr).await.map_err(|e| anyhow!("--health-listen {}: {}", addr, e))?;
+    let local = listener.local_addr()?;
+    print_status(&format!("Health check listening on http://{}/health", local));
+    let state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let (client, state) = (client.clone(), state.clone());
+                    tokio::spawn(async move {
+                        let _ = tokio::time::timeout(REQUEST_TIMEOUT, serve_request(stream, client, state)).await;
+                    });
+                }
+                Err(e) => eprintln!("⚠️ Health check: {}", e),
+            }
+        }
+    });
+    Ok(local)
+}
+
+async fn serve_request<T: Transport, L: LoginCredentials>(stream: TcpStream, client: TwitchIRCClient<T, L>, state: LoggerState) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let Ok(Some(request_line)) = lines.next_line().await else {
+        return;
+    };
+    // The headers are read so the client isn't reset before it sees the response
+    for _ in 0..MAX_HEADER_LINES {
+        match lines.next_line().await {
+            Ok(Some(line)) if !line.is_empty() => continue,
+            _ => break,
+        }
+    }
+    let pool = client.pool_status().await;
+    let response = http_response(&request_line, &health(&state, &pool, Instant::now()));
+    let _ = writer.write_all(response.as_bytes()).await;
+    let _ = writer.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connected_pool() -> PoolStatus {
+        let mut pool = PoolStatus::default();
+        pool.connections = 1;
+        pool
+    }
+
+    #[test]
+    fn healthy_while_the_server_is_heard_from() {
+        let state = LoggerState::default();
+        let now = Instant::now();
+        assert!(!health(&state, &connected_pool(), now).connected);
+
+        *state.last_received.lock().unwrap() = Some(now);
+        state.unsaved.lock().unwrap().record("forsen", now);
+        let report = health(&state, &connected_pool(), now + Duration::from_secs(30));
+        assert!(report.is_healthy());
+        assert_eq!(report.last_received_secs, Some(30));
+        assert_eq!(report.unsaved["forsen"], 1);
+        assert!(!health(&state, &PoolStatus::default(), now).connected);
+        assert!(!health(&state, &connected_pool(), now + STALE_AFTER).connected);
+
+        *state.session_end.lock().unwrap() = Some(Local::now());
+        let report = health(&state, &connected_pool(), now);
+        assert!(report.connected && !report.message_loop);
+        assert_eq!(report.status, "unavailable");
+    }
+
+    #[test]
+    fn status_code_follows_the_health() {
+        let state = LoggerState::default();
+        let now = Instant::now();
+        let response = http_response("GET /health HTTP/1.1", &health(&state, &connected_pool(), now));
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+
+        *state.last_received.lock().unwrap() = Some(now);
+        let report = health(&state, &connected_pool(), now);
+        let response = http_response("GET /?probe=1 HTTP/1.1", &report);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let body: serde_json::Value = serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["status"], "ok");
+        assert!(http_response("HEAD /health HTTP/1.1", &report).ends_with("\r\n\r\n"));
+        assert!(http_response("GET /metrics HTTP/1.1", &report).starts_with("HTTP/1.1 404"));
+        assert!(http_response("POST /health HTTP/1.1", &report).starts_with("HTTP/1.1 405"));
+    }
+}
@@ -0,0 +1,101 @@
+//! `HEATMAP <channel>`: messages by weekday and hour over all sessions, from the all-time
+//! stats, to see when a channel is usually active. `--json` prints the raw grid instead.
+
+use chrono::{DateTime, Datelike, Local, Timelike};
+use serde_json::json;
+
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+/// Cells of the grid, one per hour of the week.
+pub const SLOTS: usize = 7 * 24;
+/// Empty, then up to a quarter, half, three quarters and all of the busiest hour.
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+/// Cell of `time`: weekday (Monday = 0) × 24 + hour, in local time.
+pub fn slot(time: DateTime<Local>) -> usize {
+    time.weekday().num_days_from_monday() as usize * 24 + time.hour() as usize
+}
+
+fn shade(count: u64, max: u64) -> char {
+    if count == 0 || max == 0 {
+        return SHADES[0];
+    }
+    let level = (count * 4).div_ceil(max).clamp(1, 4);
+    SHADES[level as usize]
+}
+
+/// The 7×24 grid, each hour two characters wide, with an hour scale and a legend.
+/// `grid` has `SLOTS` counts; missing ones count as 0.
+pub fn format_heatmap(channel: &str, grid: &[u64]) -> String {
+    let count = |slot: usize| grid.get(slot).copied().unwrap_or(0);
+    let max = (0..SLOTS).map(count).max().unwrap_or(0);
+    let mut lines = vec![format!("--- #{} messages by hour (local time) ---", channel)];
+    if max == 0 {
+        lines.push("No messages recorded by hour yet".to_string());
+        return lines.join("\n");
+    }
+
+    let scale: String = (0..24).step_by(3).map(|hour| format!("{:<6}", hour)).collect();
+    lines.push(format!("    {}", scale.trim_end()));
+    for (day, name) in WEEKDAYS.iter().enumerate() {
+        let row: String = (0..24).flat_map(|hour| [shade(count(day * 24 + hour), max); 2]).collect();
+        lines.push(format!("{} {}", name, row));
+    }
+    let busiest = (0..SLOTS).max_by_key(|&slot| (count(slot), std::cmp::Reverse(slot))).unwrap_or(0);
+    lines.push(format!(
+        "{} ≤25%  {} ≤50%  {} ≤75%  {} busiest: {} {:02}:00 ({} messages)",
+        SHADES[1],
+        SHADES[2],
+        SHADES[3],
+        SHADES[4],
+        WEEKDAYS[busiest / 24],
+        busiest % 24,
+        max
+    ));
+    lines.join("\n")
+}
+
+/// `HEATMAP <channel> --json`: `{"channel", "weekdays", "grid"}`, `grid` as 7 rows of 24.
+pub fn heatmap_json(channel: &str, grid: &[u64]) -> String {
+    let rows: Vec<Vec<u64>> = (0..7).map(|day| (0..24).map(|hour| grid.get(day * 24 + hour).copied().unwrap_or(0)).collect()).collect();
+    json!({ "channel": channel, "weekdays": WEEKDAYS, "grid": rows }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn cells_are_shaded_relative_to_the_busiest_hour() {
+        let mut grid = vec![0; SLOTS];
+        grid[20] = 2; // Monday 20:00
+        grid[5 * 24 + 20] = 8; // Saturday 20:00
+        grid[6 * 24] = 5; // Sunday 00:00
+        let map = format_heatmap("forsen", &grid);
+        let lines: Vec<&str> = map.lines().collect();
+
+        assert_eq!(lines[1], "    0     3     6     9     12    15    18    21");
+        assert_eq!(lines[2].chars().nth(4 + 40), Some('░'));
+        assert_eq!(lines[7].chars().nth(4 + 40), Some('█'));
+        assert_eq!(lines[8].chars().nth(4), Some('▓'));
+        assert_eq!(lines[8].chars().nth(6), Some(' '));
+        assert!(lines[9].ends_with("busiest: Sat 20:00 (8 messages)"));
+        assert_eq!(lines[2].chars().count(), 4 + 48);
+    }
+
+    #[test]
+    fn empty_grid_and_json() {
+        assert!(format_heatmap("forsen", &[]).ends_with("No messages recorded by hour yet"));
+        let json: serde_json::Value = serde_json::from_str(&heatmap_json("forsen", &[1, 2])).unwrap();
+        assert_eq!(json["grid"][0][1], 2);
+        assert_eq!(json["grid"][6].as_array().unwrap().len(), 24);
+        assert_eq!(json["weekdays"][6], "Sun");
+    }
+
+    #[test]
+    fn slot_is_weekday_and_hour() {
+        // 2026-10-17 is a Saturday
+        let time = Local.with_ymd_and_hms(2026, 10, 17, 20, 30, 0).unwrap();
+        assert_eq!(slot(time), 5 * 24 + 20);
+    }
+}
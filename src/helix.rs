@@ -0,0 +1,147 @@
+//! The little of the Helix API the logger uses: the channels the account of
+//! `helix_token` follows, for `VIP IMPORT HELIX`. Needs `helix_client_id` and a user
+//! token of that application (`helix_token`, or `TWITCH_OAUTH_TOKEN`) with the
+//! `user:read:follows` scope.
+
+use std::future::Future;
+
+use serde_json::Value;
+
+const API: &str = "https://api.twitch.tv/helix";
+/// Largest page `Get Followed Channels` returns.
+const PAGE_SIZE: usize = 100;
+/// Stops a cursor that never ends.
+const MAX_PAGES: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelixCredentials {
+    pub client_id: String,
+    pub token: String,
+}
+
+impl HelixCredentials {
+    /// From `helix_client_id` and `helix_token`, the token falling back to `TWITCH_OAUTH_TOKEN`.
+    pub fn from_settings(client_id: Option<&str>, token: Option<&str>) -> Option<Self> {
+        let token = token
+        .map(str::to_string)
+        .or_else(|| std::env::var("TWITCH_OAUTH_TOKEN").ok())
+        .map(|token| token.trim().trim_start_matches("oauth:").to_string())
+        .filter(|token| !token.is_empty())?;
+        let client_id = client_id.map(str::trim).filter(|id| !id.is_empty())?;
+        Some(HelixCredentials { client_id: client_id.to_string(), token })
+    }
+}
+
+/// One page of a Helix list: the `data` entries and the cursor of the next page.
+fn page(value: &Value) -> (Vec<Value>, Option<String>) {
+    let data = value.get("data").and_then(Value::as_array).cloned().unwrap_or_default();
+    let cursor = value
+    .pointer("/pagination/cursor")
+    .and_then(Value::as_str)
+    .filter(|cursor| !cursor.is_empty())
+    .map(str::to_string);
+    (data, cursor)
+}
+
+/// The entries of all pages, `get_page` fetching the one after a cursor (`None`: the first).
+async fn all_pages<F, Fut>(mut get_page: F) -> Result<Vec<Value>, String>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    let mut entries = Vec::new();
+    let mut cursor = None;
+    for _ in 0..MAX_PAGES {
+        let (data, next) = page(&get_page(cursor).await?);
+        // An empty page ends the list even if it has a cursor
+        if data.is_empty() {
+            return Ok(entries);
+        }
+        entries.extend(data);
+        match next {
+            Some(next) => cursor = Some(next),
+            None => return Ok(entries),
+        }
+    }
+    Err(format!("more than {} pages, stopped", MAX_PAGES))
+}
+
+async fn get(client: &reqwest::Client, credentials: &HelixCredentials, url: &str, query: &[(&str, String)]) -> Result<Value, String> {
+    let response = client
+    .get(url)
+    .query(query)
+    .header("Client-Id", &credentials.client_id)
+    .bearer_auth(&credentials.token)
+    .send()
+    .await
+    .map_err(|e| format!("Helix request failed: {}", e))?;
+    let status = response.status();
+    let body: Value = response.json().await.map_err(|e| format!("Helix sent no JSON ({}): {}", status, e))?;
+    if !status.is_success() {
+        let message = body.get("message").and_then(Value::as_str).unwrap_or_default();
+        return Err(format!("Helix answered {}: {}", status, message));
+    }
+    Ok(body)
+}
+
+/// Logins of the channels the token's account follows, in the order Helix lists them.
+pub async fn followed_channels(credentials: &HelixCredentials) -> Result<Vec<String>, String> {
+    let client = reqwest::Client::new();
+    let users = get(&client, credentials, &format!("{}/users", API), &[]).await?;
+    let user_id = users
+    .pointer("/data/0/id")
+    .and_then(Value::as_str)
+    .ok_or("Helix did not say whose token this is")?
+    .to_string();
+
+    let url = format!("{}/channels/followed", API);
+    let followed = all_pages(|cursor| {
+        let mut query = vec![("user_id", user_id.clone()), ("first", PAGE_SIZE.to_string())];
+        query.extend(cursor.map(|cursor| ("after", cursor)));
+        let (client, url) = (&client, &url);
+        async move { get(client, credentials, url, &query).await }
+    })
+    .await?;
+    Ok(followed
+    .iter()
+    .filter_map(|channel| channel.get("broadcaster_login").and_then(Value::as_str))
+    .map(str::to_string)
+    .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn pages_are_followed_by_cursor() {
+        let pages = [
+            json!({"total": 3, "data": [{"broadcaster_login": "forsen"}, {"broadcaster_login": "xqcow"}], "pagination": {"cursor": "abc"}}),
+            json!({"total": 3, "data": [{"broadcaster_login": "pajlada"}], "pagination": {}}),
+        ];
+        let mut cursors = Vec::new();
+        let entries = all_pages(|cursor| {
+            cursors.push(cursor.clone());
+            let page = pages[cursors.len() - 1].clone();
+            async move { Ok(page) }
+        })
+        .await
+        .unwrap();
+        let logins: Vec<&str> = entries.iter().filter_map(|e| e["broadcaster_login"].as_str()).collect();
+        assert_eq!(logins, ["forsen", "xqcow", "pajlada"]);
+        assert_eq!(cursors, [None, Some("abc".to_string())]);
+
+        let failed = all_pages(|_| async { Err::<Value, _>("Helix answered 401 Unauthorized: Invalid OAuth token".to_string()) }).await;
+        assert!(failed.unwrap_err().contains("401"));
+    }
+
+    #[test]
+    fn credentials_need_a_client_id() {
+        assert_eq!(HelixCredentials::from_settings(None, Some("abc")), None);
+        assert_eq!(
+            HelixCredentials::from_settings(Some("cid"), Some("oauth:abc")),
+            Some(HelixCredentials { client_id: "cid".to_string(), token: "abc".to_string() })
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Quick-save hotkey (`quick_save_key`, F5 by default): saves all channels like
+//! `SAVE ALL hotkey_<HH-MM-SS>` without typing a command. The readline thread only
+//! signals the runtime and returns, the input line stays as it is.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::Local;
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, KeyCode, KeyEvent, Modifiers, RepeatCount};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::console::print_status;
+use crate::save::quick_save;
+use crate::state::LoggerState;
+
+/// Ctrl keys the line editor needs: interrupt, EOF, Tab and Enter.
+const RESERVED_CTRL: &[char] = &['c', 'd', 'i', 'j', 'm'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotKey {
+    /// F1 to F12.
+    Function(u8),
+    /// Ctrl and a letter.
+    Ctrl(char),
+}
+
+impl HotKey {
+    pub fn key_event(&self) -> KeyEvent {
+        match *self {
+            HotKey::Function(n) => KeyEvent(KeyCode::F(n), Modifiers::NONE),
+            HotKey::Ctrl(c) => KeyEvent::ctrl(c),
+        }
+    }
+}
+
+impl FromStr for HotKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = s.trim().to_lowercase();
+        if let Some(n) = key.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            if (1..=12).contains(&n) {
+                return Ok(HotKey::Function(n));
+            }
+        }
+        let letter = key.strip_prefix("ctrl-").or_else(|| key.strip_prefix("ctrl+"));
+        if let Some(c) = letter.filter(|l| l.len() == 1).and_then(|l| l.chars().next()).filter(char::is_ascii_lowercase) {
+            if RESERVED_CTRL.contains(&c) {
+                return Err(format!("Ctrl-{} is needed by the line editor", c.to_ascii_uppercase()));
+            }
+            return Ok(HotKey::Ctrl(c));
+        }
+        Err(format!("unknown key '{}' (F1-F12, ctrl-<letter> or off)", s))
+    }
+}
+
+impl fmt::Display for HotKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HotKey::Function(n) => write!(f, "F{}", n),
+            HotKey::Ctrl(c) => write!(f, "Ctrl-{}", c.to_ascii_uppercase()),
+        }
+    }
+}
+
+/// Bound to the hotkey: signal the quick save and leave the input line alone.
+pub struct QuickSaveHandler(pub UnboundedSender<()>);
+
+impl ConditionalEventHandler for QuickSaveHandler {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        let _ = self.0.send(());
+        Some(Cmd::Noop)
+    }
+}
+
+/// Run the quick saves off the readline thread, one at a time. Returns the sender for
+/// `QuickSaveHandler`.
+pub fn spawn_quick_save(state: LoggerState) -> UnboundedSender<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+    tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            let label = format!("hotkey_{}", Local::now().format("%H-%M-%S"));
+            let (state, quick_label) = (state.clone(), label.clone());
+            match tokio::task::spawn_blocking(move || quick_save(&state, &quick_label)).await {
+                Ok((0, _)) => print_status("Quick save: nothing to save"),
+                Ok((files, channels)) => print_status(&format!("Quick save: {} files of {} channels as {}", files, channels, label)),
+                Err(e) => eprintln!("⚠️ Quick save failed: {}", e),
+            }
+        }
+    });
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_parse_and_show() {
+        assert_eq!("F5".parse(), Ok(HotKey::Function(5)));
+        assert_eq!("ctrl-s".parse(), Ok(HotKey::Ctrl('s')));
+        assert_eq!("Ctrl+S".parse::<HotKey>().unwrap().to_string(), "Ctrl-S");
+        assert!("f13".parse::<HotKey>().is_err());
+        assert!("ctrl-c".parse::<HotKey>().is_err());
+        assert!("ctrl-1".parse::<HotKey>().is_err());
+        assert!("space".parse::<HotKey>().is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How close (in time) a restriction flip and a moderation spike must be to count as one incident.
+pub const CORRELATION_WINDOW: Duration = Duration::from_secs(60);
+
+/// Restrictions that can open an incident when they are switched ON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restriction {
+    Followers,
+    Subscribers,
+    Emotes,
+}
+
+/// Current restriction state of a channel, built up from ROOMSTATE messages.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoomRestrictions {
+    /// `Some(minutes)` when followers-only is active.
+    pub followers_only: Option<u64>,
+    pub subs_only: bool,
+    pub emote_only: bool,
+    /// Slow mode in seconds, 0 = off.
+    pub slow: u64,
+    pub r9k: bool,
+}
+
+impl RoomRestrictions {
+    pub fn is_on(&self, restriction: Restriction) -> bool {
+        match restriction {
+            Restriction::Followers => self.followers_only.is_some(),
+            Restriction::Subscribers => self.subs_only,
+            Restriction::Emotes => self.emote_only,
+        }
+    }
+
+    /// Marks shown after the channel name of chat messages: 🔒 sub-only, 😀 emote-only.
+    pub fn mode_indicator(&self) -> String {
+        let mut marks = String::new();
+        if self.subs_only {
+            marks.push_str(" 🔒");
+        }
+        if self.emote_only {
+            marks.push_str(" 😀");
+        }
+        marks
+    }
+
+    /// True if any of the incident-relevant restrictions is active.
+    pub fn any_incident_restriction(&self) -> bool {
+        self.followers_only.is_some() || self.subs_only || self.emote_only
+    }
+
+    /// "+ setting" / "- setting" lines describing what changed from `self` to `new`.
+    pub fn diff(&self, new: &RoomRestrictions) -> Vec<(bool, String)> {
+        let mut changes = Vec::new();
+        if self.followers_only != new.followers_only {
+            match new.followers_only {
+                Some(0) => changes.push((true, "followers-only".to_string())),
+                Some(m) => changes.push((true, format!("followers-only ({}m)", m))),
+                None => changes.push((false, "followers-only".to_string())),
+            }
+        }
+        if self.subs_only != new.subs_only {
+            changes.push((new.subs_only, "sub-only".to_string()));
+        }
+        if self.emote_only != new.emote_only {
+            changes.push((new.emote_only, "emote-only".to_string()));
+        }
+        if self.slow != new.slow {
+            if new.slow > 0 {
+                changes.push((true, format!("slow ({}s)", new.slow)));
+            } else {
+                changes.push((false, "slow".to_string()));
+            }
+        }
+        if self.r9k != new.r9k {
+            changes.push((new.r9k, "r9k".to_string()));
+        }
+        changes
+    }
+
+    /// One line per setting, used for the boxed summaries.
+    pub fn summary_lines(&self) -> Vec<String> {
+        let on_off = |b: bool| if b { "ON" } else { "off" };
+        vec![
+            format!(
+                "followers-only: {}",
+                match self.followers_only {
+                    Some(0) => "ON".to_string(),
+                    Some(m) => format!("ON ({}m)", m),
+                    None => "off".to_string(),
+                }
+            ),
+            format!("sub-only:       {}", on_off(self.subs_only)),
+            format!("emote-only:     {}", on_off(self.emote_only)),
+            format!(
+                "slow:           {}",
+                if self.slow > 0 { format!("{}s", self.slow) } else { "off".to_string() }
+            ),
+            format!("r9k:            {}", on_off(self.r9k)),
+        ]
+    }
+}
+
+/// Sliding one-minute window over moderation actions (bans, timeouts, deletions).
+#[derive(Debug)]
+pub struct BanWaveDetector {
+    threshold: usize,
+    window: Duration,
+    events: VecDeque<Instant>,
+}
+
+impl BanWaveDetector {
+    pub fn new(threshold: usize, window: Duration) -> Self {
+        Self { threshold, window, events: VecDeque::new() }
+    }
+
+    /// Record a moderation action; returns true while the window holds a spike.
+    pub fn record(&mut self, at: Instant) -> bool {
+        self.events.push_back(at);
+        self.prune(at);
+        self.events.len() >= self.threshold
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(front) = self.events.front() {
+            if now.duration_since(*front) > self.window {
+                self.events.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+/// What the tracker decided after an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IncidentTransition {
+    Opened,
+    Closed { duration: Duration },
+}
+
+/// Per-channel state machine correlating restriction flips with ban waves.
+///
+/// Idle -> Active when a restriction goes ON within `CORRELATION_WINDOW` of a moderation spike
+/// (in either order). Active -> Idle once followers/sub/emote-only are all lifted again.
+#[derive(Debug)]
+pub struct IncidentTracker {
+    pub restrictions: RoomRestrictions,
+    detector: BanWaveDetector,
+    initialized: bool,
+    last_spike: Option<Instant>,
+    last_restriction_on: Option<Instant>,
+    active_since: Option<Instant>,
+}
+
+impl Default for IncidentTracker {
+    fn default() -> Self {
+        Self::new(5)
+    }
+}
+
+impl IncidentTracker {
+    /// `spike_threshold` = moderation actions within one minute that count as a ban wave.
+    pub fn new(spike_threshold: usize) -> Self {
+        Self {
+            restrictions: RoomRestrictions::default(),
+            detector: BanWaveDetector::new(spike_threshold, CORRELATION_WINDOW),
+            initialized: false,
+            last_spike: None,
+            last_restriction_on: None,
+            active_since: None,
+        }
+    }
+
+    /// The first (full) ROOMSTATE after joining only sets the baseline.
+    pub fn set_baseline(&mut self, restrictions: RoomRestrictions) -> bool {
+        let first = !self.initialized;
+        if first {
+            self.restrictions = restrictions;
+            self.initialized = true;
+        }
+        first
+    }
+
+    pub fn on_moderation(&mut self, at: Instant) -> Option<IncidentTransition> {
+        if self.detector.record(at) {
+            self.last_spike = Some(at);
+            if self.active_since.is_none() && within_window(self.last_restriction_on, at) {
+                self.active_since = Some(at);
+                return Some(IncidentTransition::Opened);
+            }
+        }
+        None
+    }
+
+    /// Apply a new restriction state; opens the incident when a restriction flipped ON near a
+    /// spike, closes it once followers/sub/emote-only are all lifted.
+    pub fn update(&mut self, new_state: RoomRestrictions, at: Instant) -> Option<IncidentTransition> {
+        let turned_on = [Restriction::Followers, Restriction::Subscribers, Restriction::Emotes]
+        .iter()
+        .any(|r| new_state.is_on(*r) && !self.restrictions.is_on(*r));
+        self.restrictions = new_state;
+        self.initialized = true;
+
+        if turned_on {
+            self.last_restriction_on = Some(at);
+        }
+
+        match self.active_since {
+            None if turned_on && within_window(self.last_spike, at) => {
+                self.active_since = Some(at);
+                Some(IncidentTransition::Opened)
+            }
+            Some(start) if !self.restrictions.any_incident_restriction() => {
+                self.active_since = None;
+                Some(IncidentTransition::Closed { duration: at.duration_since(start) })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn within_window(earlier: Option<Instant>, now: Instant) -> bool {
+    earlier.is_some_and(|t| now.saturating_duration_since(t) <= CORRELATION_WINDOW)
+}
+
+/// Draws a simple box around the given lines.
+pub fn render_box(title: &str, lines: &[String]) -> String {
+    let width = lines
+    .iter()
+    .map(|l| l.chars().count())
+    .chain(std::iter::once(title.chars().count()))
+    .max()
+    .unwrap_or(0);
+
+    let mut out = format!("┌ {} {}┐\n", title, "─".repeat(width - title.chars().count()));
+    for line in lines {
+        out.push_str(&format!("│ {}{} │\n", line, " ".repeat(width - line.chars().count())));
+    }
+    out.push_str(&format!("└{}┘", "─".repeat(width + 2)));
+    out
+}
+
+/// "1h 02m 03s" style duration for summaries.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if h > 0 {
+        format!("{}h {:02}m {:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(t0: Instant, s: u64) -> Instant {
+        t0 + Duration::from_secs(s)
+    }
+
+    fn with(f: impl FnOnce(&mut RoomRestrictions), base: &RoomRestrictions) -> RoomRestrictions {
+        let mut r = base.clone();
+        f(&mut r);
+        r
+    }
+
+    #[test]
+    fn spike_then_restriction_opens_incident() {
+        let t0 = Instant::now();
+        let mut tracker = IncidentTracker::new(3);
+        assert_eq!(tracker.on_moderation(secs(t0, 0)), None);
+        assert_eq!(tracker.on_moderation(secs(t0, 5)), None);
+        assert_eq!(tracker.on_moderation(secs(t0, 10)), None);
+        let state = with(|r| r.followers_only = Some(10), &tracker.restrictions);
+        assert_eq!(tracker.update(state, secs(t0, 30)), Some(IncidentTransition::Opened));
+        assert!(tracker.active_since.is_some());
+    }
+
+    #[test]
+    fn restriction_then_spike_opens_incident() {
+        let t0 = Instant::now();
+        let mut tracker = IncidentTracker::new(2);
+        let state = with(|r| r.emote_only = true, &tracker.restrictions);
+        assert_eq!(tracker.update(state, secs(t0, 0)), None);
+        assert_eq!(tracker.on_moderation(secs(t0, 20)), None);
+        assert_eq!(tracker.on_moderation(secs(t0, 25)), Some(IncidentTransition::Opened));
+    }
+
+    #[test]
+    fn restriction_without_spike_is_not_an_incident() {
+        let t0 = Instant::now();
+        let mut tracker = IncidentTracker::new(3);
+        tracker.on_moderation(secs(t0, 0));
+        let state = with(|r| r.subs_only = true, &tracker.restrictions);
+        assert_eq!(tracker.update(state, secs(t0, 10)), None);
+        assert!(tracker.active_since.is_none());
+    }
+
+    #[test]
+    fn spike_outside_window_is_ignored() {
+        let t0 = Instant::now();
+        let mut tracker = IncidentTracker::new(2);
+        tracker.on_moderation(secs(t0, 0));
+        tracker.on_moderation(secs(t0, 1));
+        let state = with(|r| r.subs_only = true, &tracker.restrictions);
+        assert_eq!(tracker.update(state, secs(t0, 120)), None);
+    }
+
+    #[test]
+    fn slow_mode_alone_does_not_open() {
+        let t0 = Instant::now();
+        let mut tracker = IncidentTracker::new(2);
+        tracker.on_moderation(secs(t0, 0));
+        tracker.on_moderation(secs(t0, 1));
+        let state = with(|r| r.slow = 30, &tracker.restrictions);
+        assert_eq!(tracker.update(state, secs(t0, 5)), None);
+    }
+
+    #[test]
+    fn lifting_all_restrictions_closes_with_duration() {
+        let t0 = Instant::now();
+        let mut tracker = IncidentTracker::new(2);
+        tracker.on_moderation(secs(t0, 0));
+        tracker.on_moderation(secs(t0, 1));
+        let state = with(|r| r.subs_only = true, &tracker.restrictions);
+        assert_eq!(tracker.update(state, secs(t0, 10)), Some(IncidentTransition::Opened));
+        let state = with(|r| r.emote_only = true, &tracker.restrictions);
+        assert_eq!(tracker.update(state, secs(t0, 11)), None);
+        let state = with(|r| r.subs_only = false, &tracker.restrictions);
+        assert_eq!(tracker.update(state, secs(t0, 300)), None);
+        let state = with(|r| r.emote_only = false, &tracker.restrictions);
+        assert_eq!(
+            tracker.update(state, secs(t0, 610)),
+            Some(IncidentTransition::Closed { duration: Duration::from_secs(600) })
+        );
+        assert!(tracker.active_since.is_none());
+    }
+
+    #[test]
+    fn baseline_only_applies_once() {
+        let mut tracker = IncidentTracker::default();
+        let state = RoomRestrictions { subs_only: true, ..Default::default() };
+        assert!(tracker.set_baseline(state.clone()));
+        assert!(!tracker.set_baseline(RoomRestrictions::default()));
+        assert_eq!(tracker.restrictions, state);
+    }
+
+    #[test]
+    fn diff_lists_changes() {
+        let old = RoomRestrictions { slow: 10, ..Default::default() };
+        let new = RoomRestrictions { subs_only: true, ..Default::default() };
+        assert_eq!(
+            old.diff(&new),
+            vec![(true, "sub-only".to_string()), (false, "slow".to_string())]
+        );
+    }
+
+    #[test]
+    fn box_is_aligned() {
+        let boxed = render_box("INCIDENT", &["a".to_string(), "abc".to_string()]);
+        let widths: Vec<usize> = boxed.lines().map(|l| l.chars().count()).collect();
+        assert!(widths.iter().all(|w| *w == widths[0]));
+    }
+}
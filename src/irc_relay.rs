@@ -0,0 +1,269 @@
+//! `--irc-listen`: a local, read-only IRC server mirroring chat to plain IRC clients
+//! (irssi, weechat) without a second Twitch connection. Clients pick channels with JOIN
+//! and get their PRIVMSGs as Twitch sent them, minus the tags. There is no
+//! authentication, so only loopback addresses are allowed without `--irc-listen-insecure`.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use twitch_irc::message::{AsRawIRC, IRCMessage, IRCTags};
+
+use crate::console::print_status;
+use crate::state::LoggerState;
+
+/// Lines waiting for a slow client; more are dropped for that client only.
+const RELAY_QUEUE: usize = 512;
+/// Server name in the replies.
+const SERVER: &str = "twitch-logger";
+
+struct Subscriber {
+    id: u64,
+    channels: Arc<Mutex<HashSet<String>>>,
+    tx: mpsc::Sender<String>,
+    /// Messages dropped since the last one that got through.
+    dropped: u64,
+}
+
+/// The connected relay clients, fed by the message handler.
+#[derive(Clone, Default)]
+pub struct RelayHub {
+    clients: Arc<Mutex<Vec<Subscriber>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl RelayHub {
+    /// Send a chat message of `channel` to the clients that joined it.
+    pub fn publish(&self, channel: &str, source: &IRCMessage) {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            return;
+        }
+        let mut line = None;
+        for client in clients.iter_mut() {
+            if !client.channels.lock().unwrap().contains(channel) {
+                continue;
+            }
+            let line = line.get_or_insert_with(|| IRCMessage { tags: IRCTags::new(), ..source.clone() }.as_raw_irc());
+            if client.dropped > 0 {
+                let notice = format!(":{} NOTICE #{} :{} messages dropped, the client was too slow", SERVER, channel, client.dropped);
+                if client.tx.try_send(notice).is_ok() {
+                    client.dropped = 0;
+                }
+            }
+            if client.tx.try_send(line.clone()).is_err() {
+                client.dropped += 1;
+            }
+        }
+    }
+
+    fn subscribe(&self, channels: Arc<Mutex<HashSet<String>>>, tx: mpsc::Sender<String>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.clients.lock().unwrap().push(Subscriber { id, channels, tx, dropped: 0 });
+        id
+    }
+
+    fn unsubscribe(&self, id: u64) {
+        self.clients.lock().unwrap().retain(|c| c.id != id);
+    }
+}
+
+/// IRC state of one connection; `handle_line` answers its commands.
+pub struct RelayClient {
+    nick: Option<String>,
+    user: bool,
+    registered: bool,
+    channels: Arc<Mutex<HashSet<String>>>,
+    /// Channels the logger has joined, the only ones with anything to relay.
+    logged: Arc<Mutex<Vec<String>>>,
+}
+
+impl RelayClient {
+    pub fn new(logged: Arc<Mutex<Vec<String>>>) -> Self {
+        RelayClient { nick: None, user: false, registered: false, channels: Arc::default(), logged }
+    }
+
+    /// Replies to one line from the client, and whether it quit.
+    pub fn handle_line(&mut self, line: &str) -> (Vec<String>, bool) {
+        let Ok(message) = IRCMessage::parse(line.trim_end()) else {
+            return (Vec::new(), false);
+        };
+        let nick = self.nick.clone().unwrap_or_else(|| "*".to_string());
+        let param = |i: usize| message.params.get(i).map(String::as_str).unwrap_or("");
+        let mut replies = Vec::new();
+
+        match message.command.to_uppercase().as_str() {
+            "CAP" if param(0).eq_ignore_ascii_case("LS") => replies.push(format!(":{} CAP * LS :", SERVER)),
+            "CAP" | "PASS" | "PONG" => {}
+            "NICK" => self.nick = Some(param(0).to_string()).filter(|n| !n.is_empty()),
+            "USER" => self.user = true,
+            "PING" => replies.push(format!(":{} PONG {} :{}", SERVER, SERVER, param(0))),
+            "QUIT" => return (replies, true),
+            _ if !self.registered => replies.push(format!(":{} 451 {} :You have not registered", SERVER, nick)),
+            "JOIN" => {
+                for channel in param(0).split(',').filter(|c| !c.is_empty()) {
+                    let login = channel.trim_start_matches('#').to_lowercase();
+                    if !self.logged.lock().unwrap().contains(&login) {
+                        replies.push(format!(":{} NOTICE {} :#{} is not joined by the logger, JOIN it there first", SERVER, nick, login));
+                    }
+                    self.channels.lock().unwrap().insert(login.clone());
+                    replies.push(format!(":{}!{}@{} JOIN #{}", nick, nick, SERVER, login));
+                    replies.push(format!(":{} 366 {} #{} :End of /NAMES list", SERVER, nick, login));
+                }
+            }
+            "PART" => {
+                for channel in param(0).split(',').filter(|c| !c.is_empty()) {
+                    let login = channel.trim_start_matches('#').to_lowercase();
+                    if self.channels.lock().unwrap().remove(&login) {
+                        replies.push(format!(":{}!{}@{} PART #{}", nick, nick, SERVER, login));
+                    }
+                }
+            }
+            "PRIVMSG" | "NOTICE" => {
+                replies.push(format!(":{} NOTICE {} :This relay is read-only, your message was not sent", SERVER, nick));
+            }
+            command => replies.push(format!(":{} 421 {} {} :Unknown command", SERVER, nick, command)),
+        }
+
+        if !self.registered && self.user {
+            if let Some(nick) = &self.nick {
+                self.registered = true;
+                replies.push(format!(":{} 001 {} :Welcome to the twitch_chat_logger relay (read-only)", SERVER, nick));
+                replies.push(format!(":{} 422 {} :MOTD File is missing", SERVER, nick));
+            }
+        }
+        (replies, false)
+    }
+}
+
+/// Where `--irc-listen` may listen: loopback only, unless `insecure`.
+pub fn parse_listen_addr(addr: &str, insecure: bool) -> Result<SocketAddr> {
+    let addr: SocketAddr = addr.parse().map_err(|e| anyhow!("--irc-listen {}: {}", addr, e))?;
+    if !addr.ip().is_loopback() && !insecure {
+        return Err(anyhow!(
+            "--irc-listen {}: the relay has no authentication, use a loopback address or add --irc-listen-insecure",
+            addr
+        ));
+    }
+    Ok(addr)
+}
+
+/// Listen on `addr` (checked by `parse_listen_addr`) and serve clients in the background.
+pub async fn start_irc_relay(addr: &str, insecure: bool, state: &LoggerState) -> Result<SocketAddr> {
+    let addr = parse_listen_addr(addr, insecure)?;
+    let (hub, logged) = (state.irc_relay.clone(), state.channels.clone());
+    let listener = TcpListener::bind(addr).await.map_err(|e| anyhow!("--irc-listen {}: {}", addr, e))?;
+    let local = listener.local_addr()?;
+    print_status(&format!("IRC relay listening on {}", local));
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve_client(stream, hub.clone(), logged.clone()));
+                }
+                Err(e) => eprintln!("⚠️ IRC relay: {}", e),
+            }
+        }
+    });
+    Ok(local)
+}
+
+async fn serve_client(stream: TcpStream, hub: RelayHub, logged: Arc<Mutex<Vec<String>>>) {
+    let (reader, mut writer) = stream.into_split();
+    let (tx, mut rx) = mpsc::channel::<String>(RELAY_QUEUE);
+    let mut client = RelayClient::new(logged);
+    let id = hub.subscribe(client.channels.clone(), tx.clone());
+
+    let writer_task = tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            if writer.write_all(format!("{}\r\n", line).as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(reader).lines();
+    'read: while let Ok(Some(line)) = lines.next_line().await {
+        let (replies, quit) = client.handle_line(&line);
+        // Replies wait for room in the queue, unlike chat lines
+        for reply in replies {
+            if tx.send(reply).await.is_err() {
+                break 'read;
+            }
+        }
+        if quit {
+            break;
+        }
+    }
+
+    hub.unsubscribe(id);
+    drop(tx);
+    let _ = writer_task.await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registered_client(logged: &[&str]) -> RelayClient {
+        let logged = Arc::new(Mutex::new(logged.iter().map(|c| c.to_string()).collect()));
+        let mut client = RelayClient::new(logged);
+        assert!(client.handle_line("NICK steve").0.is_empty());
+        let (replies, _) = client.handle_line("USER steve 0 * :Steve");
+        assert!(replies[0].contains(" 001 steve "));
+        client
+    }
+
+    #[test]
+    fn join_subscribes_and_privmsg_is_refused() {
+        let mut client = registered_client(&["forsen"]);
+        let (replies, _) = client.handle_line("JOIN #forsen,#xqcow");
+        assert_eq!(replies[0], ":steve!steve@twitch-logger JOIN #forsen");
+        assert!(replies.iter().any(|r| r.contains("#xqcow is not joined by the logger")));
+        assert!(client.channels.lock().unwrap().contains("forsen"));
+
+        let (replies, _) = client.handle_line("PRIVMSG #forsen :hi");
+        assert!(replies[0].contains("read-only"));
+        assert!(client.handle_line("QUIT :bye").1);
+    }
+
+    #[test]
+    fn commands_need_registration() {
+        let mut client = RelayClient::new(Arc::default());
+        let (replies, _) = client.handle_line("JOIN #forsen");
+        assert!(replies[0].contains(" 451 "));
+        assert!(client.channels.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn publish_goes_to_subscribers_without_tags() {
+        let hub = RelayHub::default();
+        let channels = Arc::new(Mutex::new(HashSet::from(["forsen".to_string()])));
+        let (tx, mut rx) = mpsc::channel(2);
+        hub.subscribe(channels, tx);
+
+        let source = IRCMessage::parse("@id=1;color=#FF0000 :alice!alice@alice.tmi.twitch.tv PRIVMSG #forsen :hello chat").unwrap();
+        hub.publish("xqcow", &source);
+        for _ in 0..3 {
+            hub.publish("forsen", &source);
+        }
+        assert_eq!(rx.try_recv().unwrap(), ":alice!alice@alice.tmi.twitch.tv PRIVMSG #forsen :hello chat");
+        rx.try_recv().unwrap();
+        // The third did not fit into the queue
+        assert!(rx.try_recv().is_err());
+        assert_eq!(hub.clients.lock().unwrap()[0].dropped, 1);
+    }
+
+    #[test]
+    fn only_loopback_without_insecure() {
+        assert!(parse_listen_addr("127.0.0.1:6667", false).is_ok());
+        assert!(parse_listen_addr("0.0.0.0:6667", false).is_err());
+        assert!(parse_listen_addr("0.0.0.0:6667", true).is_ok());
+        assert!(parse_listen_addr("localhost", false).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Write-ahead journal of the in-memory logs, so a crash doesn't lose everything since
+//! the last SAVE. It is a sink (see `sink`): new lines of every log bucket are appended
+//! as JSON lines as they come in; a clean exit deletes the journal. A journal left behind
+//! by a crashed session can be loaded back on the next start.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::buckets::{append_line, append_marker, LOG_BUCKETS};
+use crate::console::print_status;
+use crate::encryption::{encrypted_channels, from_hex, to_hex, LogKey, Opener};
+use crate::output::{is_dry_run, move_file, remove_file};
+use crate::sink::{Backpressure, LogEntry, Sink, SinkPolicy};
+use crate::state::{data_file, LoggerState, SESSION_START};
+
+/// `journal/` in the data directory.
+pub fn journal_dir() -> PathBuf {
+    data_file("journal")
+}
+
+/// Lines that can wait for the journal before the handlers wait for it.
+const JOURNAL_CAPACITY: usize = 4096;
+
+/// One log line as stored in the journal.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct JournalEntry {
+    pub bucket: String,
+    pub channel: String,
+    #[serde(default)]
+    pub line: String,
+    /// The line sealed and hex encoded instead, for channels with `encrypt=true`.
+    #[serde(default)]
+    pub sealed: Option<String>,
+    /// Receive order, see `sequence`; 0 in journals of older versions.
+    #[serde(default)]
+    pub seq: u64,
+}
+
+#[derive(Serialize)]
+struct JournalRecord<'a> {
+    bucket: &'a str,
+    channel: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sealed: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    normalized: bool,
+    seq: u64,
+}
+
+/// `<start time>_<pid>.wal`; the pid tells apart a crashed session from one still running.
+pub fn session_journal_path(dir: &Path) -> PathBuf {
+    dir.join(format!("{}_{}.wal", SESSION_START.format("%Y-%m-%d_%H-%M-%S"), std::process::id()))
+}
+
+pub struct Journal {
+    file: BufWriter<File>,
+    /// Channels whose lines are sealed with `key`; without a key they are left out.
+    sealed_channels: HashSet<String>,
+    key: Option<Arc<LogKey>>,
+}
+
+impl Journal {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: BufWriter::new(file), sealed_channels: HashSet::new(), key: None })
+    }
+
+    pub fn sealing(self, channels: impl IntoIterator<Item = String>, key: Option<Arc<LogKey>>) -> Self {
+        Self { sealed_channels: channels.into_iter().collect(), key, ..self }
+    }
+}
+
+impl Sink for Journal {
+    fn name(&self) -> &str {
+        "journal"
+    }
+
+    fn write(&mut self, entry: &LogEntry) -> io::Result<()> {
+        let mut record = JournalRecord {
+            bucket: entry.bucket,
+            channel: &entry.channel,
+            line: Some(&entry.line),
+            sealed: None,
+            normalized: entry.normalized,
+            seq: entry.seq,
+        };
+        if self.sealed_channels.contains(&entry.channel) {
+            let Some(key) = &self.key else {
+                return Ok(());
+            };
+            record.line = None;
+            record.sealed = Some(to_hex(&key.seal(entry.line.as_bytes())));
+        }
+        // Serializing plain strings cannot fail
+        self.file.write_all(serde_json::to_string(&record).unwrap_or_default().as_bytes())?;
+        self.file.write_all(b"\n")
+    }
+
+    /// Lines reach the disk once the journal has caught up, not only when the buffer is full.
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Journal this session until the process exits. Register it before `offer_recovery`,
+/// so recovered lines are journaled again.
+pub fn spawn_journal(state: &LoggerState) {
+    let path = session_journal_path(&journal_dir());
+    // Appending every few seconds can't go through `write_file`, so there's no journal at all
+    if is_dry_run() {
+        print_status(&format!("dry-run: skipped journal {}", path.display()));
+        return;
+    }
+    let journal = match Journal::create(&path) {
+        Ok(journal) => journal.sealing(encrypted_channels(state), state.log_key.lock().unwrap().clone()),
+        Err(e) => {
+            eprintln!("⚠️ Failed to create journal {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    // A journal with gaps would restore a wrong picture, so it waits instead of dropping lines
+    let policy = SinkPolicy { capacity: JOURNAL_CAPACITY, backpressure: Backpressure::Block, disable_on_error: true };
+    state.sinks.register(Box::new(journal), policy);
+}
+
+/// Called on a clean exit, nothing needs to be recovered then.
+pub fn remove_session_journal() {
+    let path = session_journal_path(&journal_dir());
+    if let Err(e) = remove_file(&path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            eprintln!("⚠️ Failed to remove journal {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Journals of sessions that ended without a clean exit, oldest first.
+pub fn unclean_journals(dir: &Path) -> Vec<PathBuf> {
+    let own = session_journal_path(dir);
+    let mut journals: Vec<PathBuf> = fs::read_dir(dir)
+    .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+    .unwrap_or_default();
+    journals.retain(|path| {
+        path.extension().is_some_and(|ext| ext == "wal") && *path != own && !session_running(path)
+    });
+    journals.sort();
+    journals
+}
+
+/// Whether the process that wrote the journal is still alive (e.g. the headless archiver next to the TUI).
+fn session_running(path: &Path) -> bool {
+    path.file_stem()
+    .and_then(|stem| stem.to_str())
+    .and_then(|stem| stem.rsplit('_').next())
+    .is_some_and(|pid| Path::new("/proc").join(pid).exists())
+}
+
+/// Entries of a journal and the number of lines that could not be read.
+/// A crash in the middle of a write leaves a truncated last line, which is skipped.
+pub fn parse_journal(content: &str) -> (Vec<JournalEntry>, usize) {
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => skipped += 1,
+        }
+    }
+    (entries, skipped)
+}
+
+/// Unseal the sealed entries in place. Those that can't be opened (no passphrase, a
+/// wrong one, damaged) are removed; returns their number.
+pub fn open_sealed_entries(entries: &mut Vec<JournalEntry>, mut opener: Option<&mut Opener>) -> usize {
+    let before = entries.len();
+    entries.retain_mut(|entry| {
+        let Some(sealed) = entry.sealed.take() else {
+            return true;
+        };
+        let line = opener
+        .as_deref_mut()
+        .zip(from_hex(&sealed))
+        .and_then(|(opener, blob)| opener.open(&blob).ok())
+        .and_then(|line| String::from_utf8(line).ok());
+        match line {
+            Some(line) => {
+                entry.line = line;
+                true
+            }
+            None => false,
+        }
+    });
+    before - entries.len()
+}
+
+/// Put the entries back into the log buckets, followed by a `[RECOVERED]` line per channel.
+/// Returns the number of recovered lines.
+pub fn restore_entries(mut entries: Vec<JournalEntry>, source: &str, state: &LoggerState) -> usize {
+    // A held VIP PART was journaled after the lines that came in during its grace period
+    entries.sort_by_key(|entry| entry.seq);
+    let mut per_channel: HashMap<String, usize> = HashMap::new();
+    for entry in entries {
+        let Some(bucket) = LOG_BUCKETS.iter().find(|b| b.name == entry.bucket) else {
+            continue;
+        };
+        append_line(state, bucket.name, &entry.channel, entry.line);
+        *per_channel.entry(entry.channel).or_default() += 1;
+    }
+
+    let time = Local::now().format("%H:%M:%S").to_string();
+    for (channel, count) in &per_channel {
+        let line = format!("{} lines above were recovered from an unclean shutdown ({})", count, source);
+        append_marker(state, channel, &time, "RECOVERED", &line);
+    }
+    per_channel.values().sum()
+}
+
+/// Move a handled journal to `archive/`, so it is not offered again.
+/// `None` if skipped because of `--dry-run`.
+pub fn archive_journal(path: &Path) -> io::Result<Option<PathBuf>> {
+    let target = path.parent().unwrap_or(Path::new(".")).join("archive").join(path.file_name().unwrap_or_default());
+    Ok(move_file(path, &target)?.then_some(target))
+}
+
+/// Offer the journals of crashed sessions for recovery. With `resume` they are loaded
+/// without asking, otherwise only if `interactive` and the user agrees. Either way they
+/// are archived afterwards.
+pub fn offer_recovery(state: &LoggerState, resume: bool, interactive: bool) {
+    let mut opener = state.log_key.lock().unwrap().as_ref().map(|key| key.opener());
+    for path in unclean_journals(&journal_dir()) {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("⚠️ Failed to read journal {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let (mut entries, skipped) = parse_journal(&content);
+        let unopened = open_sealed_entries(&mut entries, opener.as_mut());
+        if unopened > 0 {
+            eprintln!("⚠️ Can't decrypt {} journal lines of encrypted channels (no or wrong passphrase)", unopened);
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+        if !entries.is_empty() {
+            println!("Found journal of an unclean shutdown: {} ({} lines)", name, entries.len());
+            if resume || (interactive && ask("Load it back? [y/N] ")) {
+                let restored = restore_entries(entries, &name, state);
+                println!("Recovered {} lines", restored);
+                if skipped > 0 {
+                    println!("Skipped {} unreadable journal lines", skipped);
+                }
+            }
+        }
+
+        match archive_journal(&path) {
+            Ok(Some(target)) => println!("Archived journal to {}", target.display()),
+            Ok(None) => {}
+            Err(e) => eprintln!("⚠️ Failed to archive journal {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn ask(question: &str) -> bool {
+    print!("{}", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok() && answer.trim().eq_ignore_ascii_case("y")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(channel: &str, line: &str) -> JournalEntry {
+        JournalEntry { bucket: "msgs".to_string(), channel: channel.to_string(), line: line.to_string(), sealed: None, seq: 0 }
+    }
+
+    /// Parsed entries without their sequence numbers, which depend on the other tests.
+    fn without_seq(entries: Vec<JournalEntry>) -> Vec<JournalEntry> {
+        entries.into_iter().map(|entry| JournalEntry { seq: 0, ..entry }).collect()
+    }
+
+    #[test]
+    fn encrypted_channels_are_journaled_sealed() {
+        let path = std::env::temp_dir().join(format!("journal_sealed_test_{}.wal", std::process::id()));
+        let state = LoggerState::default();
+        let key = Arc::new(LogKey::new("secret"));
+        let journal = Journal::create(&path).unwrap().sealing(["a".to_string()], Some(key.clone()));
+        let policy = SinkPolicy { capacity: 4, backpressure: Backpressure::Block, disable_on_error: false };
+        state.sinks.register(Box::new(journal), policy);
+
+        append_line(&state, "msgs", "a", "12:00:00 <x>\nsensitive\n".to_string());
+        append_line(&state, "msgs", "b", "12:00:01 <y>\npublic\n".to_string());
+        state.sinks.flush();
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!content.contains("sensitive") && content.contains("public"));
+
+        let (entries, _) = parse_journal(&content);
+        let entries = without_seq(entries);
+        let mut without_key = entries.clone();
+        assert_eq!(open_sealed_entries(&mut without_key, None), 1);
+        assert_eq!(without_key, vec![entry("b", "12:00:01 <y>\npublic\n")]);
+        let mut wrong = entries.clone();
+        assert_eq!(open_sealed_entries(&mut wrong, Some(&mut Opener::new("guess"))), 1);
+        let mut opened = entries;
+        assert_eq!(open_sealed_entries(&mut opened, Some(&mut key.opener())), 0);
+        assert_eq!(opened, vec![entry("a", "12:00:00 <x>\nsensitive\n"), entry("b", "12:00:01 <y>\npublic\n")]);
+    }
+
+    #[test]
+    fn flushed_lines_parse_back() {
+        let path = std::env::temp_dir().join(format!("journal_test_{}.wal", std::process::id()));
+        let state = LoggerState::default();
+        let policy = SinkPolicy { capacity: 4, backpressure: Backpressure::Block, disable_on_error: false };
+        state.sinks.register(Box::new(Journal::create(&path).unwrap()), policy);
+
+        append_line(&state, "msgs", "a", "12:00:00 <x>\nhi\n".to_string());
+        append_line(&state, "msgs", "a", "12:00:01 <y>\n\"quoted\"\n".to_string());
+        state.sinks.flush();
+
+        let (entries, skipped) = parse_journal(&fs::read_to_string(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(skipped, 0);
+        assert!(entries[0].seq < entries[1].seq);
+        assert_eq!(without_seq(entries), vec![entry("a", "12:00:00 <x>\nhi\n"), entry("a", "12:00:01 <y>\n\"quoted\"\n")]);
+    }
+
+    #[test]
+    fn truncated_trailing_record_is_skipped() {
+        let content = "{\"bucket\":\"msgs\",\"channel\":\"a\",\"line\":\"one\"}\n{\"bucket\":\"msgs\",\"chan";
+        let (entries, skipped) = parse_journal(content);
+        assert_eq!(entries, vec![entry("a", "one")]);
+        assert_eq!(skipped, 1);
+    }
+
+    #[test]
+    fn corrupt_record_does_not_stop_recovery() {
+        let content = "{\"bucket\":\"msgs\",\"channel\":\"a\",\"line\":\"one\"}\n\u{0}\u{0}garbage\n{\"bucket\":\"msgs\",\"channel\":\"a\",\"line\":\"two\"}\n";
+        let (entries, skipped) = parse_journal(content);
+        assert_eq!(entries, vec![entry("a", "one"), entry("a", "two")]);
+        assert_eq!(skipped, 1);
+    }
+
+    #[test]
+    fn restored_entries_are_marked() {
+        let state = LoggerState::default();
+        let restored = restore_entries(vec![entry("a", "one"), entry("a", "two")], "old.wal", &state);
+        assert_eq!(restored, 2);
+        let logs = state.logs.lock().unwrap();
+        let lines = &logs["a"];
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].text().contains("[RECOVERED] 2 lines above"));
+    }
+}
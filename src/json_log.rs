@@ -0,0 +1,101 @@
+//! `SAVE <channel> --format json`: the message log as one JSON document, the header
+//! statistics under `summary` and every entry split into its fields. Lines that are not
+//! chat messages (events, markers) have no sender and their text in `message_text`.
+
+use serde::Serialize;
+
+use crate::query::line_time;
+use crate::state::LoggerState;
+use crate::stats::{compute_channel_stats, ChannelStats};
+use crate::tags::tagged;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JsonMessage {
+    /// As in the text format, from 1.
+    pub line_number: usize,
+    /// "HH:MM:SS"
+    pub timestamp: Option<String>,
+    pub sender: Option<String>,
+    /// "mod/1", "sub/12", ... as logged.
+    pub badges: Vec<String>,
+    pub message_text: String,
+}
+
+#[derive(Serialize)]
+struct JsonLog<'a> {
+    channel: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'a str>,
+    summary: ChannelStats,
+    messages: Vec<JsonMessage>,
+}
+
+/// Split a stored entry: "HH:MM:SS <Name> [badges]\ntext\n" for chat, "HH:MM:SS text" otherwise.
+pub fn parse_entry(line_number: usize, entry: &str) -> JsonMessage {
+    let entry = entry.trim_end();
+    let timestamp = line_time(entry).map(|t| t.format("%H:%M:%S").to_string());
+    let rest = if timestamp.is_some() { entry[8..].trim_start_matches(' ') } else { entry };
+    let (head, body) = rest.split_once('\n').unwrap_or((rest, ""));
+
+    let chat = head.strip_prefix('<').and_then(|head| head.split_once('>'));
+    let Some((sender, after)) = chat.filter(|_| !body.is_empty()) else {
+        return JsonMessage { line_number, timestamp, sender: None, badges: Vec::new(), message_text: rest.to_string() };
+    };
+    let badges = after
+    .trim()
+    .strip_prefix('[')
+    .and_then(|b| b.strip_suffix(']'))
+    .map(|b| b.split(',').filter(|b| !b.is_empty()).map(str::to_string).collect())
+    .unwrap_or_default();
+    JsonMessage { line_number, timestamp, sender: Some(sender.to_string()), badges, message_text: body.to_string() }
+}
+
+/// Content of the JSON message log of `channel`.
+pub fn format_json_log(channel: &str, messages: &[String], state: &LoggerState) -> String {
+    let tag = state.session_tags.lock().unwrap().for_channel(channel).map(str::to_string);
+    let log = JsonLog {
+        channel,
+        event: tag.as_deref(),
+        summary: compute_channel_stats(channel, messages),
+        messages: messages.iter().enumerate().map(|(i, entry)| parse_entry(i + 1, entry)).collect(),
+    };
+    match serde_json::to_string_pretty(&log) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("⚠️ Failed to serialize the log of {}: {}", tagged(channel, tag.as_deref()), e);
+            String::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chat_lines_are_split_into_fields() {
+        let message = parse_entry(3, "20:15:03 <Alice> [mod/1,sub/12]\nhello <3 chat\n");
+        assert_eq!(message.line_number, 3);
+        assert_eq!(message.timestamp.as_deref(), Some("20:15:03"));
+        assert_eq!(message.sender.as_deref(), Some("Alice"));
+        assert_eq!(message.badges, ["mod/1", "sub/12"]);
+        assert_eq!(message.message_text, "hello <3 chat");
+
+        let plain = parse_entry(1, "20:15:04 <bob>\nhi\n");
+        assert!(plain.badges.is_empty());
+        assert_eq!(plain.message_text, "hi");
+    }
+
+    #[test]
+    fn other_lines_have_no_sender() {
+        let marker = parse_entry(1, "20:16:00 [GAP] connection lost for 12s, messages in between are missing");
+        assert_eq!(marker.sender, None);
+        assert_eq!(marker.timestamp.as_deref(), Some("20:16:00"));
+        assert_eq!(marker.message_text, "[GAP] connection lost for 12s, messages in between are missing");
+
+        let state = LoggerState::default();
+        let json: serde_json::Value = serde_json::from_str(&format_json_log("forsen", &["20:15:03 <Alice>\nhi\n".to_string()], &state)).unwrap();
+        assert_eq!(json["summary"]["message_count"], 1);
+        assert_eq!(json["messages"][0]["sender"], "Alice");
+    }
+}
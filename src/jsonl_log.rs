@@ -0,0 +1,362 @@
+//! JSON Lines: one `LogRecord` per message log entry. The handlers hand every entry's
+//! record to `append_line_with`, which stores it with its text line as a `LogLine`; the
+//! text line is one rendering of it, this is another. With `log_format = jsonl`
+//! (`--format jsonl`) the `jsonl` sink writes the records to
+//! `<channel>_msgs_<YYYY-MM-DD>.jsonl` as they arrive. `SAVE --format jsonl` writes the
+//! records kept in memory; lines known only as text (LOADed or recovered ones) carry just
+//! their time and text.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDate, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use twitch_irc::message::{PrivmsgMessage, RGBColor, UserNoticeMessage};
+
+use crate::buckets::LogLine;
+use crate::console::print_status;
+use crate::encryption::encrypted_channels;
+use crate::output::is_dry_run;
+use crate::query::line_time;
+use crate::save::{log_dir, LogFormat};
+use crate::sink::{Backpressure, LogEntry, OrderBuffer, Sink, SinkPolicy};
+use crate::state::{LoggerState, SESSION_START};
+use crate::vip_parts::late_part_window;
+
+/// Name of the sink in `SINKS`.
+pub const JSONL_SINK: &str = "jsonl";
+/// How often held records are looked at while no new ones come.
+const HOLD_TICK: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// RFC 3339, local time.
+    pub timestamp: String,
+    pub channel: String,
+    pub sender_login: Option<String>,
+    pub sender_name: Option<String>,
+    pub user_id: Option<String>,
+    pub message_id: Option<String>,
+    /// "moderator/1", "subscriber/12", ... as sent.
+    #[serde(default)]
+    pub badges: Vec<String>,
+    /// "#RRGGBB"
+    pub color: Option<String>,
+    pub bits: Option<u64>,
+    pub message_text: String,
+    /// None for chat and lines known only as text; the USERNOTICE kind (SUB, RAID, ...),
+    /// USER_BANNED, TIMEOUT, CLEARMSG or the marker (GAP, SPAM, ...) otherwise.
+    pub event_type: Option<String>,
+}
+
+fn hex_color(color: &RGBColor) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+}
+
+fn rfc3339(time: DateTime<Local>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+}
+
+impl LogRecord {
+    pub fn from_privmsg(received: DateTime<Local>, msg: &PrivmsgMessage, text: &str) -> Self {
+        LogRecord {
+            timestamp: rfc3339(received),
+            channel: msg.channel_login.clone(),
+            sender_login: Some(msg.sender.login.clone()),
+            sender_name: Some(msg.sender.name.clone()),
+            user_id: Some(msg.sender.id.clone()),
+            message_id: Some(msg.message_id.clone()),
+            badges: msg.badges.iter().map(|b| format!("{}/{}", b.name, b.version)).collect(),
+            color: msg.name_color.as_ref().map(hex_color),
+            bits: msg.bits,
+            message_text: text.to_string(),
+            event_type: None,
+        }
+    }
+
+    pub fn from_user_notice(received: DateTime<Local>, msg: &UserNoticeMessage, event_type: &str) -> Self {
+        LogRecord {
+            timestamp: rfc3339(received),
+            channel: msg.channel_login.clone(),
+            sender_login: Some(msg.sender.login.clone()),
+            sender_name: Some(msg.sender.name.clone()),
+            user_id: Some(msg.sender.id.clone()),
+            message_id: Some(msg.message_id.clone()),
+            badges: msg.badges.iter().map(|b| format!("{}/{}", b.name, b.version)).collect(),
+            color: None,
+            bits: None,
+            message_text: msg.message_text.clone().unwrap_or_else(|| msg.system_message.trim().to_string()),
+            event_type: Some(event_type.to_string()),
+        }
+    }
+
+    /// Ban, timeout or deletion; `target_login` is the moderated user, if there is one.
+    pub fn moderation(received: DateTime<Local>, channel: &str, event_type: &str, target_login: Option<&str>, text: &str) -> Self {
+        LogRecord {
+            timestamp: rfc3339(received),
+            channel: channel.to_string(),
+            sender_login: target_login.map(str::to_string),
+            sender_name: None,
+            user_id: None,
+            message_id: None,
+            badges: Vec::new(),
+            color: None,
+            bits: None,
+            message_text: text.to_string(),
+            event_type: Some(event_type.to_string()),
+        }
+    }
+
+    /// A `[<kind>] <text>` line of the logger itself.
+    pub fn marker(received: DateTime<Local>, channel: &str, kind: &str, text: &str) -> Self {
+        LogRecord {
+            timestamp: rfc3339(received),
+            channel: channel.to_string(),
+            sender_login: None,
+            sender_name: None,
+            user_id: None,
+            message_id: None,
+            badges: Vec::new(),
+            color: None,
+            bits: None,
+            message_text: text.to_string(),
+            event_type: Some(kind.to_string()),
+        }
+    }
+
+    /// A log line known only as text: `time`, and the line without its leading time as
+    /// `message_text`.
+    pub fn from_text(time: DateTime<Local>, channel: &str, line: &str) -> Self {
+        let text = match line_time(line) {
+            Some(_) => line[8..].trim_start(),
+            None => line,
+        };
+        LogRecord {
+            timestamp: rfc3339(time),
+            channel: channel.to_string(),
+            sender_login: None,
+            sender_name: None,
+            user_id: None,
+            message_id: None,
+            badges: Vec::new(),
+            color: None,
+            bits: None,
+            message_text: text.to_string(),
+            event_type: None,
+        }
+    }
+}
+
+/// Content of the JSON Lines message log of `channel`. Lines known only as text carry the
+/// time of their line, their date starts at the session start and moves on when the time
+/// goes back past midnight.
+pub fn format_jsonl_log(channel: &str, lines: &[LogLine]) -> String {
+    let mut date = SESSION_START.date_naive();
+    let mut last: Option<NaiveTime> = None;
+    let mut jsonl = String::new();
+    for line in lines {
+        let time = line_time(line.text());
+        // More than 12h back is the next day, less is a late line (a held VIP PART)
+        if let (Some(last), Some(time)) = (last, time) {
+            if last - time > ChronoDuration::hours(12) {
+                date = date.succ_opt().unwrap_or(date);
+            }
+        }
+        last = time.or(last);
+        let text_record;
+        let record = match line.record() {
+            Some(record) => record,
+            None => {
+                let time = Local.from_local_datetime(&date.and_time(time.unwrap_or_default())).earliest().unwrap_or(*SESSION_START);
+                text_record = LogRecord::from_text(time, channel, line.text());
+                &text_record
+            }
+        };
+        // Serializing strings and numbers cannot fail
+        jsonl.push_str(&serde_json::to_string(record).unwrap_or_default());
+        jsonl.push('\n');
+    }
+    jsonl
+}
+
+/// Record of an entry that came without one from its handler.
+pub fn entry_record(entry: &LogEntry) -> LogRecord {
+    LogRecord::from_text(entry.received, &entry.channel, &entry.line)
+}
+
+pub struct JsonlSink {
+    dir: PathBuf,
+    /// Encrypted channels, never written in plaintext.
+    skip: Vec<String>,
+    files: HashMap<String, (NaiveDate, BufWriter<File>)>,
+    order: OrderBuffer,
+}
+
+impl JsonlSink {
+    /// Records are written once they were received `hold()` ago, see `OrderBuffer`.
+    pub fn new(dir: PathBuf, skip: Vec<String>, hold: impl Fn() -> Duration + Send + 'static) -> Self {
+        JsonlSink { dir, skip, files: HashMap::new(), order: OrderBuffer::new(hold) }
+    }
+
+    fn append(&mut self, entries: Vec<LogEntry>) -> io::Result<()> {
+        let today = Local::now().date_naive();
+        for entry in &entries {
+            if self.files.get(&entry.channel).is_some_and(|(date, _)| *date != today) {
+                if let Some((_, mut yesterday)) = self.files.remove(&entry.channel) {
+                    yesterday.flush()?;
+                }
+            }
+            let (_, file) = match self.files.entry(entry.channel.clone()) {
+                Entry::Occupied(open) => open.into_mut(),
+                Entry::Vacant(vacant) => {
+                    let path = jsonl_file(&self.dir, &entry.channel, today);
+                    let file = OpenOptions::new().create(true).append(true).open(path)?;
+                    vacant.insert((today, BufWriter::new(file)))
+                }
+            };
+            let record = entry.record.as_deref().cloned().unwrap_or_else(|| entry_record(entry));
+            serde_json::to_writer(&mut *file, &record)?;
+            file.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn flush_files(&mut self) -> io::Result<()> {
+        for (_, file) in self.files.values_mut() {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// File the records of `channel` go to on `date`.
+pub fn jsonl_file(dir: &Path, channel: &str, date: NaiveDate) -> PathBuf {
+    dir.join(format!("{}_msgs_{}.jsonl", channel, date.format("%Y-%m-%d")))
+}
+
+impl Sink for JsonlSink {
+    fn name(&self) -> &str {
+        JSONL_SINK
+    }
+
+    fn accepts(&self, entry: &LogEntry) -> bool {
+        entry.bucket == "msgs" && !self.skip.contains(&entry.channel)
+    }
+
+    fn write(&mut self, entry: &LogEntry) -> io::Result<()> {
+        self.order.push(entry);
+        let due = self.order.due(Local::now());
+        self.append(due)
+    }
+
+    /// Writes the held records too.
+    fn flush(&mut self) -> io::Result<()> {
+        let held = self.order.take(None);
+        self.append(held)?;
+        self.flush_files()
+    }
+
+    fn idle(&mut self) -> io::Result<()> {
+        let due = self.order.due(Local::now());
+        self.append(due)?;
+        self.flush_files()
+    }
+
+    fn idle_tick(&self) -> Option<Duration> {
+        Some(HOLD_TICK)
+    }
+
+    fn close_channel(&mut self, channel: &str) -> io::Result<()> {
+        let held = self.order.take(Some(channel));
+        self.append(held)?;
+        match self.files.remove(channel) {
+            Some((_, mut file)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Register the jsonl sink if `log_format` is jsonl. Left out with `--dry-run`.
+pub fn spawn_jsonl(state: &LoggerState) {
+    if state.settings.lock().unwrap().log_format.value != LogFormat::Jsonl {
+        return;
+    }
+    if is_dry_run() {
+        print_status("dry-run: no JSON Lines are written");
+        return;
+    }
+    let Some(dir) = log_dir(state) else {
+        return;
+    };
+    print_status(&format!("Writing JSON Lines to {}", dir.display()));
+    let policy = SinkPolicy { capacity: 4096, backpressure: Backpressure::Block, disable_on_error: false };
+    let settings = state.settings.clone();
+    let sink = JsonlSink::new(dir, encrypted_channels(state), move || late_part_window(&settings.lock().unwrap()));
+    state.sinks.register(Box::new(sink), policy);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buckets::{append_line_with, append_marker};
+    use crate::load::load_log;
+    use twitch_irc::message::{IRCMessage, ServerMessage};
+
+    #[test]
+    fn records_round_trip() {
+        let raw = "@badge-info=;badges=moderator/1,subscriber/12;bits=100;color=#FF4500;display-name=Alice;emotes=;id=abc-123;room-id=1;tmi-sent-ts=1594562632383;user-id=42 :alice!alice@alice.tmi.twitch.tv PRIVMSG #forsen :hi, \"chat\"";
+        let ServerMessage::Privmsg(msg) = ServerMessage::try_from(IRCMessage::parse(raw).unwrap()).unwrap() else {
+            panic!("not a PRIVMSG");
+        };
+        let record = LogRecord::from_privmsg(Local::now(), &msg, &msg.message_text);
+        assert_eq!(record.user_id.as_deref(), Some("42"));
+        assert_eq!(record.message_id.as_deref(), Some("abc-123"));
+        assert_eq!(record.badges, ["moderator/1", "subscriber/12"]);
+        assert_eq!(record.color.as_deref(), Some("#FF4500"));
+        assert_eq!(record.bits, Some(100));
+        let line = serde_json::to_string(&record).unwrap();
+        assert_eq!(serde_json::from_str::<LogRecord>(&line).unwrap(), record);
+
+        let time = Local.with_ymd_and_hms(2026, 10, 17, 20, 15, 5).unwrap();
+        let text = LogRecord::from_text(time, "forsen", "20:15:05 <Alice> [mod/1]\nhi\n");
+        assert_eq!((text.message_text.as_str(), text.event_type), ("<Alice> [mod/1]\nhi\n", None));
+        assert!(text.timestamp.starts_with("2026-10-17T20:15:05"), "{}", text.timestamp);
+
+        let loaded = [LogLine::Text("23:59:59 <a>\nx\n".to_string()), LogLine::Text("00:00:01 <b>\ny\n".to_string())];
+        let records: Vec<LogRecord> = format_jsonl_log("forsen", &loaded).lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert!(records[1].timestamp > records[0].timestamp);
+    }
+
+    #[test]
+    fn saved_records_keep_what_the_handler_knew() {
+        let raw = "@badge-info=;badges=;color=#1E90FF;display-name=Alice;emotes=;id=abc-123;room-id=1;tmi-sent-ts=1594562632383;user-id=42 :alice!alice@alice.tmi.twitch.tv PRIVMSG #forsen :hi";
+        let ServerMessage::Privmsg(msg) = ServerMessage::try_from(IRCMessage::parse(raw).unwrap()).unwrap() else {
+            panic!("not a PRIVMSG");
+        };
+        let state = LoggerState::default();
+        let record = LogRecord::from_privmsg(Local::now(), &msg, "hi");
+        append_line_with(&state, "msgs", "forsen", "12:00:01 <Alice>\nhi\n".to_string(), false, Some(record));
+        append_marker(&state, "forsen", "12:00:02", "GAP", "connection lost for 12s");
+
+        // A LOADed line goes in front, without a record
+        let saved = std::env::temp_dir().join(format!("twitch_logger_jsonl_load_{}.txt", std::process::id()));
+        std::fs::write(&saved, "1. 11:00:00 <Bob>\r\nearlier\r\n").unwrap();
+        load_log(&state, "forsen", &saved).unwrap();
+        let _ = std::fs::remove_file(&saved);
+
+        let lines = state.logs.lock().unwrap()["forsen"].clone();
+        assert_eq!(lines[2], "12:00:02 [GAP] connection lost for 12s");
+        let jsonl = format_jsonl_log("forsen", &lines);
+        let saved: Vec<LogRecord> = jsonl.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(saved.len(), 3);
+        assert_eq!((saved[0].message_text.as_str(), saved[0].user_id.as_deref()), ("<Bob>\nearlier\n", None));
+        assert_eq!((saved[1].user_id.as_deref(), saved[1].message_id.as_deref()), (Some("42"), Some("abc-123")));
+        assert_eq!((saved[1].sender_login.as_deref(), saved[1].color.as_deref()), (Some("alice"), Some("#1E90FF")));
+        assert_eq!((saved[2].event_type.as_deref(), saved[2].message_text.as_str()), (Some("GAP"), "connection lost for 12s"));
+    }
+}
@@ -0,0 +1,65 @@
+//! Core of the Twitch chat logger: message handlers, shared state and saving.
+//! Used by the interactive `twitch_chat_logger` and the `twitch_logger_headless` archiver.
+
+pub mod alert_mode;
+pub mod alltime;
+pub mod anomaly;
+pub mod autosave;
+pub mod banner;
+pub mod buckets;
+pub mod build_info;
+pub mod capacity;
+pub mod channel_config;
+pub mod channel_file;
+pub mod commands;
+pub mod console;
+pub mod csv_log;
+pub mod diag;
+pub mod encryption;
+pub mod handlers;
+pub mod health;
+pub mod heatmap;
+pub mod helix;
+pub mod hotkey;
+pub mod incident;
+pub mod irc_relay;
+pub mod journal;
+pub mod json_log;
+pub mod jsonl_log;
+pub mod lists;
+pub mod load;
+pub mod membership;
+pub mod normalize;
+pub mod notification;
+pub mod output;
+pub mod pauses;
+pub mod prune;
+pub mod query;
+pub mod raids;
+pub mod rate_limiter;
+pub mod records;
+pub mod redaction;
+pub mod report;
+pub mod retention;
+pub mod save;
+pub mod sequence;
+pub mod say_queue;
+pub mod session_report;
+pub mod settings;
+pub mod sink;
+pub mod sound;
+pub mod spam;
+pub mod sqlite_log;
+pub mod startup;
+pub mod state;
+pub mod status_bar;
+pub mod stats;
+pub mod stray;
+pub mod subs;
+pub mod stream;
+pub mod tags;
+pub mod timestamps;
+pub mod vip_import;
+pub mod vip_incidents;
+pub mod vip_parts;
+pub mod vip_visits;
@@ -0,0 +1,199 @@
+//! `LISTS EXPORT` / `LISTS IMPORT`: the lists managed with commands at runtime (SOUND and
+//! NOTIFY channels, MEMBERS modes) as one JSON document, to carry them to another machine.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use twitch_irc::validate::validate_login;
+
+use crate::membership::{set_membership_mode, MembershipMode};
+use crate::output::write_file;
+use crate::state::LoggerState;
+
+/// Increased when the document layout changes in an incompatible way.
+pub const LISTS_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ListsDocument {
+    pub version: u32,
+    /// Channels with SOUND on.
+    #[serde(default)]
+    pub sound: BTreeSet<String>,
+    /// Channels with NOTIFY on.
+    #[serde(default)]
+    pub notify: BTreeSet<String>,
+    /// MEMBERS mode per channel.
+    #[serde(default)]
+    pub members: BTreeMap<String, String>,
+}
+
+/// A document that passed `validate`, with the modes parsed.
+#[derive(Debug)]
+pub struct ValidatedLists {
+    sound: BTreeSet<String>,
+    notify: BTreeSet<String>,
+    members: BTreeMap<String, MembershipMode>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Entries that changed something.
+    pub added: usize,
+    /// Entries that were already in effect.
+    pub skipped: usize,
+    /// Entries dropped by `--replace`.
+    pub removed: usize,
+}
+
+pub fn export_lists(state: &LoggerState) -> ListsDocument {
+    ListsDocument {
+        version: LISTS_VERSION,
+        sound: state.sound_channels.lock().unwrap().iter().cloned().collect(),
+        notify: state.notification_channels.lock().unwrap().iter().cloned().collect(),
+        members: state.membership.lock().unwrap()
+        .iter()
+        .map(|(channel, tracker)| (channel.clone(), tracker.mode.to_string()))
+        .collect(),
+    }
+}
+
+/// Write the lists to `path`. Returns the number of entries, `None` with `--dry-run`.
+pub fn save_lists(state: &LoggerState, path: &Path) -> Result<Option<usize>, String> {
+    let document = export_lists(state);
+    let json = serde_json::to_string_pretty(&document).map_err(|e| e.to_string())?;
+    let written = write_file(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(written.then_some(document.sound.len() + document.notify.len() + document.members.len()))
+}
+
+/// Check the whole document before anything is applied, so a bad file changes nothing.
+pub fn validate(document: ListsDocument) -> Result<ValidatedLists, String> {
+    if document.version != LISTS_VERSION {
+        return Err(format!("unsupported lists version {} (expected {})", document.version, LISTS_VERSION));
+    }
+    let channels = document.sound.iter().chain(&document.notify).chain(document.members.keys());
+    for channel in channels {
+        validate_login(channel).map_err(|_| format!("invalid channel name '{}'", channel))?;
+    }
+    if let Some(channel) = document.sound.intersection(&document.notify).next() {
+        return Err(format!("{} is in both sound and notify, a channel can only have one", channel));
+    }
+    let mut members = BTreeMap::new();
+    for (channel, mode) in document.members {
+        members.insert(channel.clone(), mode.parse().map_err(|e| format!("{}: {}", channel, e))?);
+    }
+    Ok(ValidatedLists { sound: document.sound, notify: document.notify, members })
+}
+
+pub fn load_lists(path: &Path) -> Result<ValidatedLists, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let document: ListsDocument = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    validate(document)
+}
+
+/// Merge the lists into the running state, or with `replace` make them the only entries.
+pub fn apply_lists(state: &LoggerState, lists: ValidatedLists, replace: bool) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+
+    {
+        let mut sound = state.sound_channels.lock().unwrap();
+        let mut notify = state.notification_channels.lock().unwrap();
+        if replace {
+            summary.removed += sound.iter().filter(|c| !lists.sound.contains(*c)).count();
+            summary.removed += notify.iter().filter(|c| !lists.notify.contains(*c)).count();
+            sound.retain(|c| lists.sound.contains(c));
+            notify.retain(|c| lists.notify.contains(c));
+        }
+        // Like the SOUND and NOTIFY commands, turning one on turns the other off
+        for channel in lists.sound {
+            notify.remove(&channel);
+            if sound.insert(channel) {
+                summary.added += 1;
+            } else {
+                summary.skipped += 1;
+            }
+        }
+        for channel in lists.notify {
+            sound.remove(&channel);
+            if notify.insert(channel) {
+                summary.added += 1;
+            } else {
+                summary.skipped += 1;
+            }
+        }
+    }
+
+    if replace {
+        // Dropped channels fall back to their configured mode on the next event
+        let dropped: Vec<String> = state.membership.lock().unwrap()
+        .keys()
+        .filter(|c| !lists.members.contains_key(*c))
+        .cloned()
+        .collect();
+        for channel in dropped {
+            let tracker = state.membership.lock().unwrap().remove(&channel);
+            if let Some(line) = tracker.and_then(|mut t| t.flush()) {
+                state.join_queue.push(&channel, line);
+            }
+            summary.removed += 1;
+        }
+    }
+    for (channel, mode) in lists.members {
+        let current = state.membership.lock().unwrap().get(&channel).map(|t| t.mode);
+        if current == Some(mode) {
+            summary.skipped += 1;
+        } else {
+            set_membership_mode(state, &channel, mode);
+            summary.added += 1;
+        }
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(json: &str) -> ListsDocument {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let source = LoggerState::default();
+        source.sound_channels.lock().unwrap().insert("forsen".to_string());
+        source.notification_channels.lock().unwrap().insert("pajlada".to_string());
+        set_membership_mode(&source, "xqcow", MembershipMode::VipsOnly);
+
+        let target = LoggerState::default();
+        target.sound_channels.lock().unwrap().insert("forsen".to_string());
+        let summary = apply_lists(&target, validate(export_lists(&source)).unwrap(), false);
+
+        assert_eq!(summary, ImportSummary { added: 2, skipped: 1, removed: 0 });
+        assert_eq!(export_lists(&target), export_lists(&source));
+    }
+
+    #[test]
+    fn replace_drops_entries_missing_from_the_file() {
+        let state = LoggerState::default();
+        state.sound_channels.lock().unwrap().insert("old".to_string());
+        let lists = validate(document(r#"{"version": 1, "sound": ["forsen"]}"#)).unwrap();
+        let summary = apply_lists(&state, lists, true);
+        assert_eq!(summary, ImportSummary { added: 1, skipped: 0, removed: 1 });
+        assert_eq!(export_lists(&state).sound, BTreeSet::from(["forsen".to_string()]));
+    }
+
+    #[test]
+    fn invalid_documents_are_rejected_as_a_whole() {
+        for json in [
+            r#"{"version": 2}"#,
+            r#"{"version": 1, "sound": ["forsen", "not a channel"]}"#,
+            r#"{"version": 1, "sound": ["forsen"], "notify": ["forsen"]}"#,
+            r#"{"version": 1, "members": {"forsen": "sometimes"}}"#,
+        ] {
+            assert!(validate(document(json)).is_err(), "{}", json);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! `LOAD <channel> <file>`: read a saved message log back into the channel's buffer,
+//! e.g. after a restart, so the next SAVE contains the whole day again.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::buckets::LogLine;
+use crate::journal::parse_journal;
+use crate::state::LoggerState;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LoadSummary {
+    pub loaded: usize,
+    /// Entries already in the buffer, e.g. when the file was saved by this session.
+    pub duplicates: usize,
+}
+
+/// "N. HH:MM:SS ..." of a numbered log line, without the number.
+fn strip_number(line: &str) -> Option<&str> {
+    let (number, rest) = line.split_once(". ")?;
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let time = rest.get(0..8)?.as_bytes();
+    let is_time = time.iter().enumerate().all(|(i, b)| if i == 2 || i == 5 { *b == b':' } else { b.is_ascii_digit() });
+    is_time.then_some(rest)
+}
+
+/// Entries of a saved message log, oldest first. Takes the text format written by SAVE
+/// (any header, numbered entries spanning several lines, with or without BOM) and the
+/// JSON lines of a journal (`msgs` entries of `channel`).
+pub fn parse_saved_log(content: &str, channel: &str) -> Vec<String> {
+    let content = content.trim_start_matches('\u{feff}');
+
+    if content.trim_start().starts_with('{') {
+        let (entries, _) = parse_journal(content);
+        return entries
+        .into_iter()
+        .filter(|e| e.bucket == "msgs" && e.channel == channel)
+        .map(|e| e.line)
+        .collect();
+    }
+
+    // Entries are joined with "\n" and chat messages end with "\n" themselves, so the
+    // lines up to the next numbered line belong to the entry. Header lines come before
+    // the first numbered line and are skipped.
+    let mut entries: Vec<Vec<&str>> = Vec::new();
+    for line in content.split('\n').map(|l| l.trim_end_matches('\r')) {
+        match strip_number(line) {
+            Some(first) => entries.push(vec![first]),
+            None => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.push(line);
+                }
+            }
+        }
+    }
+    entries.into_iter().map(|lines| lines.join("\n")).collect()
+}
+
+/// Put the entries of `path` in front of the channel's buffer. Loaded entries are counted
+/// in `state.loaded_lines`, so live rates can leave them out.
+pub fn load_log(state: &LoggerState, channel: &str, path: &Path) -> Result<LoadSummary, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let entries = parse_saved_log(&content, channel);
+    if entries.is_empty() {
+        return Err(format!("no log entries in {}", path.display()));
+    }
+
+    let total = entries.len();
+    let mut logs = state.logs.lock().unwrap();
+    let buffer = logs.entry(channel.to_string()).or_default();
+    // Loaded lines have only their text
+    let mut loaded: Vec<LogLine> = {
+        let existing: HashSet<&str> = buffer.iter().map(LogLine::text).collect();
+        entries.into_iter().filter(|entry| !existing.contains(entry.as_str())).map(LogLine::Text).collect()
+    };
+    let summary = LoadSummary { loaded: loaded.len(), duplicates: total - loaded.len() };
+    loaded.append(buffer);
+    *buffer = loaded;
+
+    *state.loaded_lines.lock().unwrap().entry(channel.to_string()).or_default() += summary.loaded;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_log_parses_back() {
+        let saved = "\u{feff}--- Message/Event Log --- (v1)\r\n# forsen\r\n(2 messages from 2 chatters)\r\n1. 12:00:00 <a> [sub/3]\r\nhi\r\n\r\n2. 12:00:05 USER_BANNED: [#forsen] b\r\n3. 12:00:09 <c>\r\n10. not a number\r\n";
+        assert_eq!(
+            parse_saved_log(saved, "forsen"),
+            vec!["12:00:00 <a> [sub/3]\nhi\n", "12:00:05 USER_BANNED: [#forsen] b", "12:00:09 <c>\n10. not a number\n"]
+        );
+    }
+
+    #[test]
+    fn journal_lines_parse_back() {
+        let journal = "{\"bucket\":\"msgs\",\"channel\":\"forsen\",\"line\":\"one\"}\n{\"bucket\":\"joins\",\"channel\":\"forsen\",\"line\":\"join\"}\n{\"bucket\":\"msgs\",\"channel\":\"xqcow\",\"line\":\"other\"}\n";
+        assert_eq!(parse_saved_log(journal, "forsen"), vec!["one"]);
+    }
+
+    #[test]
+    fn loaded_entries_go_first_without_duplicates() {
+        let path = std::env::temp_dir().join(format!("load_test_{}.txt", std::process::id()));
+        fs::write(&path, "1. 12:00:00 <a>\nearly\n\n2. 12:30:00 <b>\nsaved before\n").unwrap();
+        let state = LoggerState::default();
+        state.logs.lock().unwrap().insert(
+            "forsen".to_string(),
+            vec![LogLine::Text("12:30:00 <b>\nsaved before\n".to_string()), LogLine::Text("13:00:00 <c>\nlive\n".to_string())],
+        );
+
+        let summary = load_log(&state, "forsen", &path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(summary, LoadSummary { loaded: 1, duplicates: 1 });
+        assert_eq!(state.logs.lock().unwrap()["forsen"][0], "12:00:00 <a>\nearly\n");
+        assert_eq!(state.logs.lock().unwrap()["forsen"].len(), 3);
+        assert_eq!(state.loaded_lines.lock().unwrap()["forsen"], 1);
+    }
+}
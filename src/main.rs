@@ -1,231 +1,290 @@
-use rustyline::Editor;
+use rustyline::{Editor, EventHandler};
 use rustyline::history::DefaultHistory;
 
 mod completer;
-use completer::CommandCompleter;
+use completer::{load_history, save_history, CommandCompleter};
 
 use anyhow::Result;
-use chrono::Local;
-use clap::Parser;
-use once_cell::sync::Lazy;
+use clap::{Parser, Subcommand};
 use owo_colors::OwoColorize;
 use rustyline::error::ReadlineError;
 
-use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
-    io::{self,Write},
-    sync::{Arc, Mutex},
-    process,
-    process::Command,
-};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
 use twitch_irc::login::StaticLoginCredentials;
-use twitch_irc::message::{PrivmsgMessage, ServerMessage};
-use twitch_irc::message::ClearChatAction;
 use twitch_irc::{ClientConfig, SecureTCPTransport, TwitchIRCClient};
-use chrono::prelude::*;
-use chrono_tz::Europe::Berlin;
-mod channel_config; // declares the module
-use channel_config::{ChannelConfig, load_channel_config, apply_named_color};
 
-mod sound;
-use sound::play_sound;
-
-
-static CONFIG: Lazy<ChannelConfig> = Lazy::new(|| {
-    match load_channel_config("/home/steve/.rustTwitchLogger/channels.txt") {
-        Ok(cfg) => cfg,
-    Err(e) => {
-        eprintln!("⚠️ Warning: Failed to load channels.txt: {e}");
-        process::exit(1);
-    }
-    }
-});
-
-static STARTUP_DATE: Lazy<String> = Lazy::new(|| {
-    let now = Utc::now().with_timezone(&Berlin);
-    // Get the abbreviated weekday (e.g., "Sa")
-    let day_abbr = &now.format("%a").to_string()[0..2];
-    format!("{}_{}", day_abbr, now.format("%d_%m_%Y"))
-});
+use twitch_logger_core::banner::print_banner;
+use twitch_logger_core::build_info;
+use twitch_logger_core::channel_file::{read_channel_file, watch_channel_file};
+use twitch_logger_core::commands::{finish_session, CommandSession, Flow, COMMANDS};
+use twitch_logger_core::console::{self, ColorChoice};
+use twitch_logger_core::console_println;
+use twitch_logger_core::diag;
+use twitch_logger_core::encryption::{decrypt_file, plaintext_path, read_passphrase, setup_encryption};
+use twitch_logger_core::handlers::{handle_connection_event, handle_received};
+use twitch_logger_core::hotkey::{spawn_quick_save, QuickSaveHandler};
+use twitch_logger_core::journal::{offer_recovery, spawn_journal};
+use twitch_logger_core::alltime::{save_alltime, spawn_alltime_merger};
+use twitch_logger_core::autosave::{spawn_autosave, spawn_snapshots};
+use twitch_logger_core::irc_relay::start_irc_relay;
+use twitch_logger_core::membership::spawn_join_log_writer;
+use twitch_logger_core::save::{AutosaveFiles, HeaderFormat, LogFormat};
+use twitch_logger_core::say_queue::{credentials_from_env, spawn_say_queue};
+use twitch_logger_core::settings::{Settings, Source};
+use twitch_logger_core::output;
+use twitch_logger_core::retention;
+use twitch_logger_core::session_report::save_session_report;
+use twitch_logger_core::startup::{initial_channels, join_initial_channels, startup_delay};
+use twitch_logger_core::jsonl_log::spawn_jsonl;
+use twitch_logger_core::sqlite_log::spawn_sqlite;
+use twitch_logger_core::stream::spawn_stream;
+use twitch_logger_core::state::{self, LoggerState};
+use twitch_logger_core::status_bar::{self, spawn_status_bar};
+use twitch_logger_core::vip_incidents::save_vip_incidents;
+use twitch_logger_core::vip_parts::{release_parts, spawn_part_release};
+use twitch_logger_core::vip_visits::save_vip_join_counts;
 
 
 // --- Command-Line Argument Parser ---
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version = build_info::VERSION, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    tool: Option<Tool>,
+
     /// List of Twitch channels to join
     #[arg(name = "CHANNELS")]
     channels: Vec<String>,
-}
 
+    /// channels.txt to use instead of $XDG_CONFIG_HOME/rustTwitchLogger/ or ~/.rustTwitchLogger/
+    #[arg(long = "config", value_name = "FILE")]
+    config: Option<PathBuf>,
 
-fn get_local_timestamp() -> String {
-    let now = Utc::now().with_timezone(&Berlin);
-    // Get the abbreviated weekday (e.g., "Sat") and take the first two letters.
-    let day_abbr = &now.format("%a").to_string()[0..2];
+    /// Directory the logs are saved to (overrides log_dir in channels.txt)
+    #[arg(long = "log-dir", visible_alias = "output-dir", value_name = "DIR")]
+    log_dir: Option<PathBuf>,
 
-    format!(
-        "{}_{}", // Prepend the two-letter day to the rest of the timestamp
-        day_abbr,
-        now.format("%d_%m_%Y_%H-%M-%S")
-    )
-}
+    /// Header of saved message logs: full, minimal or none (overrides log_header in channels.txt)
+    #[arg(long = "log-header", value_name = "FORMAT")]
+    log_header: Option<HeaderFormat>,
 
-use notify_rust::Notification;
+    /// Format of SAVE: text, json, csv or jsonl; jsonl also writes JSON Lines live (overrides log_format)
+    #[arg(long = "format", value_name = "FORMAT")]
+    log_format: Option<LogFormat>,
 
-// This can be your new, efficient notification function!
-fn send_desktop_notification(summary: &str, body: &str) {
-    if let Err(e) = Notification::new()
-        .summary(summary) // Set the title
-        .body(body)       // Set the message content
-        .show()           // Display the notification
-        {
-            eprintln!("⚠️ Failed to send notification: {}", e);
-        }
+    /// Also write every message and event to this SQLite database (overrides sqlite_db)
+    #[arg(long = "sqlite", value_name = "PATH")]
+    sqlite: Option<PathBuf>,
+
+    /// Autosave every N minutes into one file per channel and day (overrides autosave_minutes and autosave_files)
+    #[arg(long = "autosave", value_name = "MINUTES")]
+    autosave: Option<u64>,
+
+    /// Draw a gold frame around messages of first-time chatters (also highlight_first_msg = true in channels.txt)
+    #[arg(long = "highlight-first-msg")]
+    highlight_first_msg: bool,
+
+    /// Open every saved message log with the default text editor or viewer
+    #[arg(long = "open-after-save")]
+    open_after_save: bool,
+
+    /// Load the journal of a crashed session without asking
+    #[arg(long = "resume")]
+    resume: bool,
+
+    /// Run the commands in this file (one per line, # starts a comment) before the prompt
+    #[arg(long = "script", value_name = "FILE")]
+    script: Option<std::path::PathBuf>,
+
+    /// Exit after the --script instead of showing the prompt
+    #[arg(long = "no-interactive", requires = "script")]
+    no_interactive: bool,
+
+    /// Don't print the configuration banner at startup (see CONFIG SHOW) and start without the status bar
+    #[arg(long = "quiet", short = 'q')]
+    quiet: bool,
+
+    /// No prompt: write the messages without colors to stdout (for grep, tee, awk, ...) until SIGTERM
+    #[arg(long = "pipe", conflicts_with_all = ["script", "open_after_save", "resume"])]
+    pipe: bool,
+
+    /// Wait this many seconds before joining the channels (spreads out several loggers started together)
+    #[arg(long = "startup-delay", value_name = "SECONDS")]
+    startup_delay: Option<u64>,
+
+    /// Name saved files after the channel's display name (e.g. JanisTanTV) once the broadcaster has chatted
+    #[arg(long = "use-display-names")]
+    use_display_names: bool,
+
+    /// Also join the channels in this file (one per line) and follow changes to it
+    #[arg(long = "channel-file", value_name = "FILE")]
+    channel_file: Option<std::path::PathBuf>,
+
+    /// Connect, log and alert as usual, but don't write, move or delete any file
+    #[arg(long = "dry-run")]
+    dry_run: bool,
+
+    /// Only report the saved files max_files_per_channel / max_total_log_bytes would delete
+    #[arg(long = "retention-dry-run")]
+    retention_dry_run: bool,
+
+    /// Colored console output: auto (on a terminal, unless NO_COLOR is set), always or never
+    #[arg(long = "color", value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Serve the chat of the joined channels to IRC clients (irssi, weechat) on this address, e.g. 127.0.0.1:6667
+    #[arg(long = "irc-listen", value_name = "ADDR:PORT")]
+    irc_listen: Option<String>,
+
+    /// Allow --irc-listen on a non-loopback address; the relay has no authentication
+    #[arg(long = "irc-listen-insecure", requires = "irc_listen")]
+    irc_listen_insecure: bool,
+
+    /// Passphrase for the channels with `encrypt=true` (first line of the file) instead of asking at startup
+    #[arg(long = "key-file", value_name = "FILE")]
+    key_file: Option<std::path::PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Tool {
+    /// Write the plaintext of a file saved for a channel with `encrypt=true`
+    Decrypt {
+        /// The saved .enc file
+        file: std::path::PathBuf,
+
+        /// Where to write the plaintext, "-" for stdout (default: the file name without .enc)
+        #[arg(long = "output", short = 'o', value_name = "FILE")]
+        output: Option<std::path::PathBuf>,
+
+        /// Read the passphrase from the first line of this file instead of asking
+        #[arg(long = "key-file", value_name = "FILE")]
+        key_file: Option<std::path::PathBuf>,
+    },
 }
 
+
 // --- Main Application Logic ---
 #[tokio::main]
 async fn main() -> Result<()> {
 
-    println!("last update: 14.08.25");
-
     use tokio::sync::oneshot;
     let cli = Cli::parse();
-    //let (exit_tx, exit_rx) = oneshot::channel();
-    let (exit_tx, exit_rx) = oneshot::channel::<()>();
+    if let Some(path) = &cli.config {
+        state::set_config_file(path.clone());
+    }
+    output::set_dry_run(cli.dry_run);
+    diag::capture_panics();
+    retention::set_retention_dry_run(cli.retention_dry_run);
+    console::set_color_choice(cli.color);
 
+    if let Some(Tool::Decrypt { file, output, key_file }) = &cli.tool {
+        return decrypt(file, output.as_deref(), key_file.as_deref());
+    }
+    if cli.pipe {
+        return run_pipe(cli).await;
+    }
 
-    let initial_channels: Vec<String> = if cli.channels.is_empty() {
-        CONFIG.default_channels.iter().cloned().collect()
-    } else {
-        cli.channels
+    console_println!("{}", build_info::build_info().dimmed());
+    //let (exit_tx, exit_rx) = oneshot::channel();
+    let (exit_tx, exit_rx) = oneshot::channel::<()>();
+    let (snapshot_exit_tx, snapshot_exit_rx) = oneshot::channel::<()>();
+
+
+    let script_commands: Vec<String> = match &cli.script {
+        Some(path) => std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read script {}: {}", path.display(), e))?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect(),
+        None => Vec::new(),
     };
+    let no_interactive = cli.no_interactive;
 
-    let client_config = ClientConfig::default();
+    let file_channels = match &cli.channel_file {
+        Some(path) => read_channel_file(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read channel file {}: {}", path.display(), e))?,
+        None => Vec::new(),
+    };
+    let initial_channels = initial_channels(&cli.channels, &file_channels);
+
+    let (events_tx, mut connection_events) = mpsc::unbounded_channel();
+    // Anonymous unless a login is set for SAY
+    let credentials = credentials_from_env();
+    let login = credentials.credentials.token.is_some().then(|| credentials.credentials.login.clone());
+    let client_config = ClientConfig {
+        login_credentials: credentials,
+        emit_malformed_messages: true,
+        connection_events: Some(events_tx),
+        ..ClientConfig::default()
+    };
     let (mut incoming_messages, client) =
-    TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new(client_config);
+    TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new_timestamped(client_config);
 
     // --- Shared State ---
-    let channels        = Arc::new(Mutex::new(initial_channels.clone()));
-    let logs            = Arc::new(Mutex::new(HashMap::<String, Vec<String>>::new()));
-    let join_logs       = Arc::new(Mutex::new(HashMap::<String, Vec<String>>::new()));
-    let sound_channels  = Arc::new(Mutex::new(
-        initial_channels.iter().cloned().collect::<HashSet<String>>(),
-    ));
-
-    let notification_channels = Arc::new(Mutex::new(HashSet::<String>::new()));
-
-
+    let state = LoggerState::new(&initial_channels, true);
+    apply_flags(&cli, &mut state.settings.lock().unwrap());
+    state.say_queue.set_login(login);
 
 
+    if !cli.quiet {
+        print_banner(&state);
+    }
 
-    // --- Join Initial Channels ---
-    for channel in &initial_channels {
-        client.join(channel.clone())?;
-        println!("Joined initial channel: {}", channel.green());
+    // Before the journal, which seals the encrypted channels too
+    setup_encryption(&state, cli.key_file.as_deref()).map_err(anyhow::Error::msg)?;
+
+    // --- Crash Recovery Journal ---
+    // Before the recovery, so lines loaded back are streamed too
+    spawn_stream(&state);
+    spawn_jsonl(&state);
+    spawn_sqlite(&state);
+    spawn_journal(&state);
+    offer_recovery(&state, cli.resume, true);
+    spawn_join_log_writer(state.clone());
+    spawn_part_release(state.clone());
+    spawn_alltime_merger(state.clone());
+    spawn_autosave(state.clone());
+    spawn_snapshots(state.clone(), snapshot_exit_rx);
+    spawn_status_bar(state.clone(), client.clone());
+    spawn_say_queue(state.clone(), client.clone());
+    if let Some(addr) = &cli.irc_listen {
+        start_irc_relay(addr, cli.irc_listen_insecure, &state).await?;
     }
 
-    // --- Message Handling Task ---
-    let logs_for_tokio          = Arc::clone(&logs);
-    let join_logs_for_tokio = Arc::clone(&join_logs);
+    // --- Join Initial Channels ---
+    startup_delay(&state).await;
+    join_initial_channels(&client, &state).await;
+    let _channel_watcher = match cli.channel_file.clone() {
+        Some(path) => Some(watch_channel_file(path, file_channels, client.clone(), state.clone())?),
+        None => None,
+    };
 
+    // --- Connection Events Task ---
+    let state_for_events = state.clone();
+    tokio::spawn(async move {
+        while let Some(event) = connection_events.recv().await {
+            handle_connection_event(event, &state_for_events);
+        }
+    });
 
-    let sound_channels_for_tokio = Arc::clone(&sound_channels);
-    let notification_channels_for_tokio = Arc::clone(&notification_channels);
+    // --- Message Handling Task ---
+    let state_for_tokio = state.clone();
 
     let join_handle = tokio::spawn(async move {
         tokio::select! {
             _ = async {
-                while let Some(message) = incoming_messages.recv().await {
-                    let time_str = Local::now().format("%H:%M:%S").to_string();
-                    match message {
-                        ServerMessage::Privmsg(msg) => {
-                            handle_privmsg(&time_str, msg, &logs_for_tokio, &sound_channels_for_tokio,&notification_channels_for_tokio);
-                        }
-
-                        ServerMessage::Join(msg) =>{
-                           handle_join_or_part("JOIN", &time_str, &msg.channel_login, &msg.user_login, &logs_for_tokio, &join_logs_for_tokio);
-                        }
-
-                        ServerMessage::Part(msg) => {
-                            handle_join_or_part("PART", &time_str, &msg.channel_login, &msg.user_login, &logs_for_tokio, &join_logs_for_tokio);
-                        }
-
-                        ServerMessage::Ping(_msg) => {
-                            print!("{} PING      \r", time_str); // Padding to overwrite leftover text
-                            io::stdout().flush().unwrap();
-                        }
-                        ServerMessage::Pong(_msg) => {
-                            print!("{} PONG      \r", time_str); // Same here
-                            io::stdout().flush().unwrap();
-                        }
-                        ServerMessage::RoomState(_msg) =>{}
-
-                        ServerMessage::Notice(msg) => {
-                            println!("{}[{}][NOTICE] {}", time_str.dimmed(), msg.channel_login.unwrap_or("unknown".to_string()),msg.message_text);
-                        }
-
-                        ServerMessage::ClearChat(msg) => {
-                            match &msg.action {
-                                ClearChatAction::UserBanned { user_login, .. } => {
-                                    handle_moderation_event(
-                                        &time_str,
-                                        "USER_BANNED",
-                                        &msg.channel_login,
-                                        user_login,
-                                        owo_colors::Style::new().red().blink(),
-                                                            &logs_for_tokio, // Or your new moderation_logs store
-                                    );
-                                }
-                                ClearChatAction::UserTimedOut { user_login, timeout_length, .. } => {
-                                    let content = format!(
-                                        "{} ({}s timeout)",
-                                                          user_login,
-                                                          timeout_length.as_secs()
-                                    );
-                                    handle_moderation_event(
-                                        &time_str,
-                                        "TIMEOUT",
-                                        &msg.channel_login,
-                                        &content,
-                                        owo_colors::Style::new().red().blink(),
-                                                            &logs_for_tokio, // Or your new moderation_logs store
-                                    );
-                                }
-                                ClearChatAction::ChatCleared => {
-                                    handle_moderation_event(
-                                        &time_str,
-                                        "CHAT_CLEARED",
-                                        &msg.channel_login,
-                                        "The chat was cleared by a moderator.",
-                                        owo_colors::Style::new().dimmed(),
-                                                            &logs_for_tokio, // Or your new moderation_logs store
-                                    );
-                                }
-                            }
-                        }
-                        ServerMessage::ClearMsg(msg) => {
-                            handle_moderation_event(
-                                &time_str,
-                                "CLEARMSG",
-                                &msg.channel_login,
-                                &msg.message_text,
-                                owo_colors::Style::new().bright_black().blink(),
-                                                    &logs_for_tokio,
-                            );
-                        }
-                        ServerMessage::UserNotice(msg) => {
-                            handle_user_notice(&time_str, &msg, &logs_for_tokio);
-                        }
-
-                        _ => handle_default(&time_str, &message, &logs_for_tokio),
-                    }
+                while let Some(received) = incoming_messages.recv().await {
+                    handle_received(received, &state_for_tokio);
                 }
             } => {},
             _ = exit_rx => {
-                println!("Message loop received exit signal.");
+                console_println!("Message loop received exit signal.");
             }
         }
     });
@@ -234,138 +293,74 @@ async fn main() -> Result<()> {
     // --- User Input Handling Thread ---
 
 
-    let client_for_thread = client.clone();
-    let logs_for_thread = Arc::clone(&logs);
-     let join_logs_for_thread = Arc::clone(&join_logs);
-
-    let vips: Vec<String> = CONFIG.vips.keys().cloned().collect();
-
-    let msg_logs: Vec<String> = {
-        let logs_guard = logs.lock().unwrap();
-        logs_guard.keys().cloned().collect()
-    };
-
-    let channels_for_thread = Arc::clone(&channels);
-    let sound_channels_for_thread = Arc::clone(&sound_channels);
-    let notification_channels_for_thread = Arc::clone(&notification_channels);
+    let mut session = CommandSession::new(client.clone(), state.clone());
+    let logs_for_thread = Arc::clone(&state.logs);
+    let channels_for_thread = Arc::clone(&state.channels);
+    let vips: Vec<String> = state.config.vips.keys().cloned().collect();
+    let quick_save_key = state.settings.lock().unwrap().quick_save_key.value;
+    let quick_save = quick_save_key.map(|key| (key, spawn_quick_save(state.clone())));
 
     let handle = std::thread::spawn(move || -> Result<()> {
-        let commands = vec![
-            "JOIN".into(),
-                                    "PART".into(),
-                                    "SOUND".into(),
-                                    "SAVE".into(),
-                                    "NOTIFY".into(),
-                                    "EXIT".into(),
-                                    "RECONNECT".into(),
-                                    "PAUSES".into(),
-                                    "STATS".into(),
-        ];
-
         let completer = CommandCompleter {
-            commands: commands.clone(),
-                                    joined_channels: Arc::clone(&channels_for_thread),
-                                    vips: vips.clone(),
-                                    log_channels: Arc::clone(&logs_for_thread),
+            commands: COMMANDS.iter().map(|c| c.to_string()).collect(),
+                                    joined_channels: channels_for_thread,
+                                    vips,
+                                    imported_vips: Arc::clone(&state.imported_vips),
+                                    log_channels: logs_for_thread,
         };
 
         let mut rl = Editor::<CommandCompleter, DefaultHistory>::new()?;
         rl.set_helper(Some(completer));
+        if let Some((key, tx)) = quick_save {
+            rl.bind_sequence(key.key_event(), EventHandler::Conditional(Box::new(QuickSaveHandler(tx))));
+        }
+        let history = state.settings.lock().unwrap().history_file.value.clone().map(PathBuf::from);
+        if let Some(path) = &history {
+            load_history(&mut rl, path);
+        }
+
+        console_println!("Commands: JOIN <channel>, PART <channel...|ALL>, SOUND <channel>, SAVE <channel|ALL>, EXIT");
+        if let Some(key) = quick_save_key {
+            console_println!("{}", format!("{} saves all channels", key).dimmed());
+        }
 
-        println!("Commands: JOIN/PART <channel>, SOUND <channel>, SAVE <channel|ALL>, EXIT");
+        let mut script = script_commands.into_iter();
 
         loop {
-            match rl.readline(">> ") {
-                Ok(input) => {
+            // The --script runs first, echoed after the prompt; --no-interactive ends with EXIT
+            let line = match script.next() {
+                Some(command) => {
+                    console_println!("{}{}", session.prompt(), command);
+                    Ok(command)
+                }
+                None if no_interactive => Ok("EXIT".to_string()),
+                None => rl.readline(session.prompt()).inspect(|input| {
                     let _ = rl.add_history_entry(input.as_str());
-                    let parts: Vec<&str> = input.trim().split_whitespace().collect();
-                    if parts.is_empty() {
-                        continue;
-                    }
-
-                    let cmd = parts[0].to_uppercase();
-                    let arg = parts.get(1).map(|s| s.to_string());
-
-                    match cmd.as_str() {
-                        "JOIN" => {
-                            if let Some(channel) = arg {
-                                let _ = client_for_thread.join(channel.clone());
-                                channels_for_thread.lock().unwrap().push(channel.clone());
-                                println!("Joined {}", channel.green());
-                            }
-                        },
-                        "PART" => {
-                            if let Some(channel) = arg {
-                                let _ = client_for_thread.part(channel.clone());
-                                channels_for_thread.lock().unwrap().retain(|c| c != &channel);
-                                println!("Parted from {}", channel.red());
-                            }
-                        },
-                        "SOUND" => {
-                            if let Some(channel) = arg {
-                                let mut sound_chans = sound_channels_for_thread.lock().unwrap();
-                                if sound_chans.contains(&channel) {
-                                    sound_chans.remove(&channel);
-                                    println!("Sound OFF for {}", channel.yellow());
-                                } else {
-                                    sound_chans.insert(channel.clone());
-                                    notification_channels_for_thread.lock().unwrap().remove(&channel);
-                                    println!("Sound ON for {}", channel.green());
-                                }
-                            }
-                        },
-                        "NOTIFY" => {
-                            if let Some(channel) = arg {
-                                let mut notify_chans = notification_channels_for_thread.lock().unwrap();
-                                if notify_chans.contains(&channel) {
-                                    // It was on, so turn it off
-                                    notify_chans.remove(&channel);
-                                    println!("Notifications OFF for {}", channel.yellow());
-                                } else {
-                                    // It was off, so turn it on and ensure sound is off
-                                    notify_chans.insert(channel.clone());
-                                    sound_channels_for_thread.lock().unwrap().remove(&channel);
-                                    println!("Notifications ON for {} (Sound is now OFF)", channel.cyan());
-                                }
-                            }
-                        },
-                        "SAVE" => {
-                            if parts.len() >= 2 {
-                                let target = parts[1];
-                                let custom_name = if parts.len() > 2 {
-                                    Some(parts[2..].join("_"))
-                                } else {
-                                    None
-                                };
-                                save_logs(
-                                    target,
-                                    &logs_for_thread,
-                                    &join_logs_for_thread,
-                                    custom_name.as_deref()
-                                );
-                            } else {
-                                println!("Usage: SAVE <channel|ALL> [optional_custom_name]");
-                            }
-                        },
-                        "EXIT" => {
-                            println!("Shutting down...");
-                            let joined_channels = channels_for_thread.lock().unwrap().clone();
-                            for channel in joined_channels {
-                                let _ = client_for_thread.part(channel.clone());
-                                println!("Left channel: {}", channel);
-                            }
-                            let _ = exit_tx.send(()); // notify the async task
-                            break;
+                }),
+            };
+            match line {
+                Ok(input) => {
+                    if session.execute(&input) == Flow::Exit {
+                        if let Some(path) = &history {
+                            save_history(&mut rl, path);
                         }
-                        _ => println!("{}: '{}'", "Unknown command".red(), input.trim()),
+                        let _ = exit_tx.send(()); // notify the async task
+                        let _ = snapshot_exit_tx.send(());
+                        break;
                     }
                 }
                 Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
-                    println!("Exiting...");
+                    console_println!("Exiting...");
+                    if let Some(path) = &history {
+                        save_history(&mut rl, path);
+                    }
+                    finish_session(&state);
+                    let _ = exit_tx.send(());
+                    let _ = snapshot_exit_tx.send(());
                     break;
                 }
                 Err(err) => {
-                    println!("Input Error: {:?}", err);
+                    console_println!("Input Error: {:?}", err);
                     break;
                 }
             }
@@ -379,351 +374,131 @@ async fn main() -> Result<()> {
     }
 
     join_handle.await?;
+    status_bar::clear();
 
     Ok(())
 }
-// --- Message Handlers ---
-
-fn handle_default(
-    time: &str,
-    message: &ServerMessage,
-    _logs: &Arc<Mutex<HashMap<String, Vec<String>>>>,
-) {
-    use twitch_irc::message::ServerMessage;
-
-    let kind = match message {
-        ServerMessage::Ping(_) => "PING",
-        ServerMessage::Pong(_) => "PONG",
-        ServerMessage::Reconnect(_) => "RECONNECT",
-        ServerMessage::GlobalUserState(_) => "GLOBAL_USER_STATE",
-        ServerMessage::UserState(_) => "USER_STATE",
-        ServerMessage::RoomState(_) => "ROOM_STATE",
-        ServerMessage::Whisper(_) => "WHISPER",
-        ServerMessage::Generic(_)=> "HIDDEN",
-        _ => "OTHER",
-    };
 
-    if kind == "OTHER" {
-        println!("{} [SYSTEM: OTHER] {:?}", time.dimmed(), message
-        .source()
-        .tags
-        .0
-        .get("msg-id")
-        .and_then(|v| v.as_deref())
-        .unwrap_or("unknown"));
-    } else {
-        println!("{} ...", time.dimmed())
+/// Command line flags override channels.txt.
+fn apply_flags(cli: &Cli, settings: &mut Settings) {
+    if let Some(dir) = &cli.log_dir {
+        settings.log_dir.set(dir.display().to_string(), Source::Flag);
     }
-}
-
-fn handle_privmsg(
-    time_str: &str,
-    msg: PrivmsgMessage,
-    logs: &Arc<Mutex<HashMap<String, Vec<String>>>>,
-    sound_channels: &Arc<Mutex<HashSet<String>>>,
-    notification_channels: &Arc<Mutex<HashSet<String>>>
-) {
-
-    // Use vips for colorized printing
-    let info = CONFIG.vips.get(&msg.channel_login);
-    let channel_display = apply_named_color(&msg.channel_login, info.and_then(|c| c.color.as_deref()));
-
-    let mut custom_badges = msg.badges.iter()
-    .map(|b| format!("{}/{}", b.name, b.version))
-    .collect::<Vec<_>>();
-
-    let tags = &msg.source.tags;
-
-    // Add virtual badges based on tag fields
-    if let Some(first_msg) = tags.0.get("first-msg").and_then(|v| v.as_deref()) {
-        if first_msg == "1" {
-            custom_badges.push("(FIRSTMSG)".to_string());
-        }
+    if let Some(header) = cli.log_header {
+        settings.log_header.set(header, Source::Flag);
     }
-
-    if let Some(returning) = tags.0.get("returning-chatter").and_then(|v| v.as_deref()) {
-        if returning == "1" {
-            custom_badges.push("(RETURNING)".to_string());
-        }
+    if let Some(format) = cli.log_format {
+        settings.log_format.set(format, Source::Flag);
     }
-
-    let badges_for_log = custom_badges.join(",");
-    let badge_info_for_console = if !custom_badges.is_empty() {
-        format!("[{}]", custom_badges.join(", ").yellow())
-    } else {
-        String::new()
-    };
-
-    let log_line = format!(
-        "{} <{}>{}\n{}\n",
-        time_str,
-        msg.sender.name,
-        if badges_for_log.is_empty() {
-            "".to_string()
-        } else {
-            format!(" [{}]", badges_for_log.replace("moderator/","mod/").replace("subscriber/","sub/").replace("premium/","prime/"))
-        },//badges at the end in the logfile
-        msg.message_text
-    );
-
-    logs.lock().unwrap().entry(msg.channel_login.clone()).or_default().push(log_line);
-
-    // --- END OF BADGE LOGIC ---
-
-    let user_styled = if let Some(color) = msg.name_color {
-        msg.sender.name.truecolor(color.r, color.g, color.b).to_string()
-    } else {
-        msg.sender.name.clone()
-    };
-
-    println!(
-        "{} [{}] {}{}: {}",
-        time_str.dimmed(),
-             channel_display,
-             user_styled.bold(),
-             badge_info_for_console.replace("moderator/","mod/").replace("subscriber/","sub/").replace("premium/","prime/"),
-             msg.message_text
-    );
-
-    let summary = format!("#{}", msg.channel_login);
-    let body = format!("{}: {}", msg.sender.name, msg.message_text);
-
-
-    if sound_channels.lock().unwrap().contains(&msg.channel_login) {
-
-        send_desktop_notification(&summary, &body);
-        play_sound();
-    }else if notification_channels.lock().unwrap().contains(&msg.channel_login) {
-        // Notify mode: only sends a notification
-        send_desktop_notification(&summary, &body);
+    if let Some(path) = &cli.sqlite {
+        settings.sqlite_db.set(Some(path.display().to_string()), Source::Flag);
+    }
+    if let Some(minutes) = cli.autosave {
+        settings.autosave_minutes.set(minutes, Source::Flag);
+        settings.autosave_files.set(AutosaveFiles::Daily, Source::Flag);
+    }
+    if cli.open_after_save {
+        settings.open_after_save.set(true, Source::Flag);
+    }
+    if cli.highlight_first_msg {
+        settings.highlight_first_msg.set(true, Source::Flag);
+    }
+    if let Some(delay) = cli.startup_delay {
+        settings.startup_delay.set(delay, Source::Flag);
+    }
+    if cli.use_display_names {
+        settings.use_display_names.set(true, Source::Flag);
+    }
+    if cli.quiet {
+        settings.status_bar.set(false, Source::Flag);
     }
 }
 
-/*https://docs.rs/twitch-irc/latest/twitch_irc/message/enum.UserNoticeEvent.html*/
-
-fn handle_user_notice(
-    time: &str,
-    msg: &twitch_irc::message::UserNoticeMessage,
-    logs: &Arc<Mutex<HashMap<String, Vec<String>>>>,
-) {
-    use owo_colors::OwoColorize;
-    use twitch_irc::message::UserNoticeEvent;
-
-    // Fallback to raw msg-id tag if the event is unknown
-    let raw_msg_id = msg
-    .source
-    .tags
-    .0
-    .get("msg-id")
-    .and_then(|v| v.as_deref())
-    .unwrap_or("unknown");
-
-    let event_type = match &msg.event {
-        UserNoticeEvent::Unknown => raw_msg_id.to_uppercase(),
-        other => format!("{:?}", other).to_uppercase(),
+/// `decrypt <file>`: the plaintext goes to a new file, an existing one is not overwritten.
+fn decrypt(file: &Path, output: Option<&Path>, key_file: Option<&Path>) -> Result<()> {
+    let target = match output {
+        Some(output) => output.to_path_buf(),
+        None => plaintext_path(file).ok_or_else(|| anyhow::anyhow!("{} doesn't end in .enc, use --output", file.display()))?,
     };
+    let passphrase = read_passphrase(key_file, false).map_err(anyhow::Error::msg)?;
+    let plaintext = decrypt_file(file, &passphrase).map_err(anyhow::Error::msg)?;
 
-    let channel = &msg.channel_login;
-    let user = &msg.sender.name;
-    let user_msg = msg.message_text.as_deref().unwrap_or("");
-    let sys_msg = msg.system_message.trim();
-
-    // Compose log line
-    let line = format!(
-        "{} [{}][{}] <{}> {} → {}",
-        time,
-        channel,
-        user,
-        event_type,
-        user_msg,
-        sys_msg
-    );
-
-    println!(
-        "{} [{}][{}] {}: {}\n→ {}",
-        time.dimmed(),
-             channel,
-             user,
-             event_type.blue(),
-             user_msg,
-             sys_msg.yellow()
-    );
-
-    if let Ok(mut logs) = logs.lock() {
-        logs.entry(channel.clone())
-        .or_default()
-        .push(line);
+    if target == Path::new("-") {
+        std::io::stdout().write_all(&plaintext)?;
+        return Ok(());
     }
+    let mut out = std::fs::OpenOptions::new()
+    .write(true)
+    .create_new(true)
+    .open(&target)
+    .map_err(|e| anyhow::anyhow!("{}: {}", target.display(), e))?;
+    out.write_all(&plaintext)?;
+    eprintln!("Decrypted {} to {}", file.display(), target.display());
+    Ok(())
 }
 
+/// `--pipe`: headless logging with stdout as the output. Messages are printed without
+/// colors, status output goes to stderr, and nothing is read from the terminal.
+async fn run_pipe(cli: Cli) -> Result<()> {
+    console::set_plain(true);
+    eprintln!("{}", build_info::build_info());
 
-fn handle_moderation_event(
-    time_str: &str,
-    event_type: &str,
-    channel: &str,
-    content: &str,
-    style: owo_colors::Style,
-    log_store: &Arc<Mutex<HashMap<String, Vec<String>>>>,
-) {
-    let log_line = format!("{time_str} {event_type}: [#{channel}] {content}");
-    println!("{}", log_line.style(style));
-
-    let summary = format!("Moderation in #{}", channel);
-    let body = format!("[{}] {}", event_type, content);
-    send_desktop_notification(&summary, &body);
-    play_sound();
-
-
-    let mut logs = log_store.lock().unwrap();
-    logs.entry(channel.to_string()).or_default().push(log_line);
-}
-
-
-
-fn handle_join_or_part(
-     event_type: &str,
-     time_str: &str,
-     channel: &str,
-     username: &str,
-     log_store: &Arc<Mutex<HashMap<String, Vec<String>>>>,
-     join_log_store: &Arc<Mutex<HashMap<String, Vec<String>>>>,
-  ){
-
-     let msg = format!("{time_str} [{event_type}] {username}");
-     join_log_store.lock().unwrap()
-     .entry(channel.to_string())
-     .or_default()
-     .push(msg.clone().replace("[JOIN] ","[J] ").replace("[PART] ","[P] "));
-
-     if CONFIG.vips.contains_key(username) {
-         println!("{}", format!("*** VIP {username} has {event_type}ed {channel} ***").yellow());
-
-
-         // Save in general log when it's a VIP, but on same channel
-        if username != channel {
-         log_store.lock().unwrap()
-         .entry(channel.to_string())
-         .or_default()
-         .push(msg.clone());
-        }
-
-         if event_type == "JOIN" && username != channel {
-             play_sound();
-             send_desktop_notification(channel, &format!("{} joined",username));
-         }
-     }
-}
-
-// --- Utility Functions ---
-fn save_logs(
-    target: &str,
-    logs: &Arc<Mutex<HashMap<String, Vec<String>>>>,
-    join_logs: &Arc<Mutex<HashMap<String, Vec<String>>>>,
-    // The `first_message_times` parameter is now gone
-    custom_name: Option<&str>,
-) {
-    let logs_locked = logs.lock().unwrap();
-    let join_logs_locked = join_logs.lock().unwrap();
-
-    let targets: Vec<String> = if target.eq_ignore_ascii_case("ALL") {
-        logs_locked.keys().cloned().collect()
-    } else {
-        vec![target.to_string()]
+    let file_channels = match &cli.channel_file {
+        Some(path) => read_channel_file(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read channel file {}: {}", path.display(), e))?,
+        None => Vec::new(),
     };
+    let initial_channels = initial_channels(&cli.channels, &file_channels);
 
-    for chan in targets {
-        // --- NEW LOGIC: Get time from the first log entry ---
-        let time_part = logs_locked
-        .get(&chan)
-        // Find the first message in the log vector for this channel
-        .and_then(|messages| messages.iter().find(|line| line.contains("<") && line.contains(">")))
-        // Parse the timestamp (HH:MM:SS) from the beginning of the line
-        .map(|first_line| first_line[0..8].replace(':', "-")) // "HH:MM:SS" -> "HH-MM-SS"
-        // If no messages exist for the channel, use the current time as a fallback
-        .unwrap_or_else(|| Local::now().format("%H-%M-%S").to_string());
-
-        // Combine the static date part with the parsed time part.
-        let timestamp = format!("{}_{}", *STARTUP_DATE, time_part);
-
-        // --- Save the main message log ---
-        if let Some(messages) = logs_locked.get(&chan) {
-            let file = if let Some(name) = custom_name {
-                format!("/tmp/{}_{}_{}.txt", chan, name, timestamp)
-            } else {
-                format!("/tmp/{}_msgs_{}.txt", chan, timestamp)
-            };
-
-            let mut msg_count = 0;
-            let mut unique_chatters = HashSet::new();
-            let mut mod_events = 0;
-            let mut sub_events = 0;
-            let mut raid_events = 0;
-
-            for line in messages {
-                if line.contains("<SUBORRESUB") || line.contains("<SUBGIFT") || line.contains("<SUBMYSTERYGIFT")
-                    || line.contains("<ANONSUBMYSTERYGIFT") || line.contains("<GIFTPAIDUPGRADE") || line.contains("ANONPAIDGIFTUPGRADE") {
-                        sub_events += 1;
-                    } else if line.contains("USER_BANNED") || line.contains("CLEARMSG") || line.contains("TIMEOUT") {
-                        mod_events += 1;
-                    } else if line.contains("<RAID") {
-                        raid_events += 1;
-                    } else if line.matches("<").count() == 1 && line.contains(">") {
-                        msg_count += 1;
-                        if let Some(start) = line.find('<') {
-                            if let Some(end) = line.find('>') {
-                                let username = &line[start + 1..end];
-                                if let Some(uname) = username.split(']').last() {
-                                    unique_chatters.insert(uname.trim().to_string());
-                                }
-                            }
-                        }
-                    }
-            }
-
-            let header = format!(
-                "--- Message/Event Log ---\n# {}\n({} messages from {} chatters)\n({} Banns, Deletions, and Timeouts)\n({} Subs/Giftsubs)\n({} Raids)\n",
-                                 chan,
-                                 msg_count,
-                                 unique_chatters.len(),
-                                 mod_events,
-                                 sub_events,
-                                 raid_events
-            );
-
-            let numbered_messages = messages
-            .iter()
-            .enumerate()
-            .map(|(i, line)| format!("{}. {}", i + 1, line))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-            let final_content = format!("{}{}", header, numbered_messages);
-
-            let mut content_with_bom = vec![0xEF, 0xBB, 0xBF];
-            content_with_bom.extend_from_slice(final_content.as_bytes());
-
-            if let Ok(mut f) = File::create(&file) {
-                if f.write_all(&content_with_bom).is_ok() {
-                    println!("Saved {} messages to {}", messages.len(), file);
-                }
-            }
-        }
-
-
-        // --- Save the join/part log to a separate file ---
-        if let Some(join_msgs) = join_logs_locked.get(&chan) {
-            if !join_msgs.is_empty() {
-                let file = if let Some(name) = custom_name {
-                    format!("/tmp/{}_{}_joins_{}.txt", chan, name, timestamp)
-                } else {
-                    format!("/tmp/{}_joins_{}.txt", chan, timestamp)
-                };
+    let (events_tx, mut connection_events) = mpsc::unbounded_channel();
+    let client_config = ClientConfig {
+        emit_malformed_messages: true,
+        connection_events: Some(events_tx),
+        ..ClientConfig::default()
+    };
+    let (mut incoming_messages, client) =
+    TwitchIRCClient::<SecureTCPTransport, StaticLoginCredentials>::new_timestamped(client_config);
+
+    // No sounds or desktop notifications, like the headless archiver
+    let state = LoggerState::new(&initial_channels, false);
+    apply_flags(&cli, &mut state.settings.lock().unwrap());
+    setup_encryption(&state, cli.key_file.as_deref()).map_err(anyhow::Error::msg)?;
+
+    spawn_stream(&state);
+    spawn_jsonl(&state);
+    spawn_sqlite(&state);
+    spawn_join_log_writer(state.clone());
+    spawn_part_release(state.clone());
+    spawn_alltime_merger(state.clone());
+    spawn_autosave(state.clone());
+    let (snapshot_exit_tx, snapshot_exit_rx) = tokio::sync::oneshot::channel::<()>();
+    spawn_snapshots(state.clone(), snapshot_exit_rx);
+    if let Some(addr) = &cli.irc_listen {
+        start_irc_relay(addr, cli.irc_listen_insecure, &state).await?;
+    }
+    startup_delay(&state).await;
+    join_initial_channels(&client, &state).await;
+    let _channel_watcher = match cli.channel_file.clone() {
+        Some(path) => Some(watch_channel_file(path, file_channels, client.clone(), state.clone())?),
+        None => None,
+    };
 
-                if std::fs::write(&file, join_msgs.join("\n")).is_ok() {
-                    println!("Saved {} JOIN/PART events to {}", join_msgs.len(), file);
-                }
-            }
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    loop {
+        tokio::select! {
+            Some(received) = incoming_messages.recv() => handle_received(received, &state),
+            Some(event) = connection_events.recv() => handle_connection_event(event, &state),
+            _ = sigint.recv() => break,
+            _ = sigterm.recv() => break,
         }
     }
-}
 
+    let _ = snapshot_exit_tx.send(());
+    release_parts(&state, None);
+    save_session_report(&state);
+    save_vip_incidents(&state);
+    save_alltime(&state);
+    save_vip_join_counts(&state.vip_join_counts.lock().unwrap());
+    Ok(())
+}
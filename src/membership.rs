@@ -0,0 +1,248 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::buckets::append_line;
+use crate::sequence::{current_stamp, with_stamp, EventStamp};
+use crate::state::LoggerState;
+
+/// How often queued join log lines are moved into the join logs.
+const JOIN_DRAIN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How JOIN/PART events of a channel end up in its join log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MembershipMode {
+    /// Every single JOIN/PART (the old behavior).
+    #[default]
+    All,
+    /// Only JOIN/PARTs of configured VIPs.
+    VipsOnly,
+    /// One aggregate line per minute instead of individual events.
+    CountsOnly,
+    /// Nothing is recorded and VIP alerts are off too.
+    Off,
+}
+
+impl FromStr for MembershipMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "all" => Ok(MembershipMode::All),
+            "vips" | "vips-only" => Ok(MembershipMode::VipsOnly),
+            "counts" | "counts-only" => Ok(MembershipMode::CountsOnly),
+            "off" => Ok(MembershipMode::Off),
+            other => Err(format!("unknown membership mode '{}' (all, vips-only, counts-only, off)", other)),
+        }
+    }
+}
+
+impl fmt::Display for MembershipMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MembershipMode::All => "all",
+            MembershipMode::VipsOnly => "vips-only",
+            MembershipMode::CountsOnly => "counts-only",
+            MembershipMode::Off => "off",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Runtime membership state of one channel: its mode and the counts-only aggregate.
+#[derive(Debug, Default)]
+pub struct ChannelMembership {
+    pub mode: MembershipMode,
+    minute: Option<String>,
+    joins: u32,
+    parts: u32,
+    present: i64,
+}
+
+impl ChannelMembership {
+    pub fn new(mode: MembershipMode) -> Self {
+        Self { mode, ..Default::default() }
+    }
+
+    /// Count one event in the minute of `time_str` ("HH:MM:SS").
+    /// Returns the finished aggregate line when a new minute starts.
+    pub fn record(&mut self, time_str: &str, join: bool) -> Option<String> {
+        let minute = time_str.get(0..5).unwrap_or(time_str);
+        let finished = match &self.minute {
+            Some(current) if current != minute => self.flush(),
+            _ => None,
+        };
+        self.minute = Some(minute.to_string());
+
+        if join {
+            self.joins += 1;
+            self.present += 1;
+        } else {
+            self.parts += 1;
+            self.present = (self.present - 1).max(0);
+        }
+        finished
+    }
+
+    /// Close the pending minute, e.g. before saving.
+    pub fn flush(&mut self) -> Option<String> {
+        let minute = self.minute.take()?;
+        let line = format_aggregate(&minute, self.joins, self.parts, self.present);
+        self.joins = 0;
+        self.parts = 0;
+        Some(line)
+    }
+}
+
+/// "HH:MM [COUNTS] +214 / -189, ~12.3k present"
+pub fn format_aggregate(minute: &str, joins: u32, parts: u32, present: i64) -> String {
+    let present = if present >= 1000 {
+        format!("{:.1}k", present as f64 / 1000.0)
+    } else {
+        present.to_string()
+    };
+    format!("{} [COUNTS] +{} / -{}, ~{} present", minute, joins, parts, present)
+}
+
+/// Number of JOIN/PART events a join log represents, aggregate lines included.
+pub fn event_count(lines: &[String]) -> u64 {
+    lines
+    .iter()
+    .map(|line| match line.split_once(" [COUNTS] ") {
+        Some((_, rest)) => {
+            let mut total = 0;
+            for part in rest.split(',').next().unwrap_or("").split('/') {
+                total += part.trim().trim_start_matches(['+', '-']).parse::<u64>().unwrap_or(0);
+            }
+            total
+        }
+        None => 1,
+    })
+    .sum()
+}
+
+/// The channel's `members=` option from channels.txt, else the global `members` setting.
+pub fn configured_mode(channel: &str, state: &LoggerState) -> MembershipMode {
+    state.vip_info(channel)
+    .and_then(|info| info.members)
+    .unwrap_or_else(|| state.settings.lock().unwrap().members.value)
+}
+
+/// Switch the JOIN/PART logging of `channel`, closing its pending counts-only minute first.
+pub fn set_membership_mode(state: &LoggerState, channel: &str, mode: MembershipMode) {
+    let pending = {
+        let mut membership = state.membership.lock().unwrap();
+        let tracker = membership.entry(channel.to_string()).or_default();
+        let pending = tracker.flush();
+        tracker.mode = mode;
+        pending
+    };
+    if let Some(line) = pending {
+        state.join_queue.push(channel, line);
+    }
+}
+
+/// Write any pending counts-only aggregates into the join logs.
+pub fn flush_counts(state: &LoggerState) {
+    for (channel, tracker) in state.membership.lock().unwrap().iter_mut() {
+        if let Some(line) = tracker.flush() {
+            state.join_queue.push(channel, line);
+        }
+    }
+    drain_join_queue(state);
+}
+
+/// Join log lines on their way into `join_logs`. The handlers only send into the queue,
+/// so busy channels don't fight over the `join_logs` lock for every single event;
+/// `drain_join_queue` moves the lines over in batches.
+#[derive(Clone)]
+pub struct JoinQueue {
+    tx: Sender<(String, String, EventStamp)>,
+    rx: Arc<Mutex<Receiver<(String, String, EventStamp)>>>,
+}
+
+impl Default for JoinQueue {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self { tx, rx: Arc::new(Mutex::new(rx)) }
+    }
+}
+
+impl JoinQueue {
+    pub fn push(&self, channel: &str, line: String) {
+        // The receiver lives as long as the sender, both are in the same struct
+        let _ = self.tx.send((channel.to_string(), line, current_stamp()));
+    }
+}
+
+/// Append everything queued so far to the join logs, under a single lock.
+pub fn drain_join_queue(state: &LoggerState) {
+    let rx = state.join_queue.rx.lock().unwrap();
+    let batch: Vec<(String, String, EventStamp)> = rx.try_iter().collect();
+    if batch.is_empty() {
+        return;
+    }
+    for (channel, line, stamp) in batch {
+        with_stamp(stamp, || append_line(state, "joins", &channel, line));
+    }
+}
+
+/// Drain the join queue in the background until the process exits.
+pub fn spawn_join_log_writer(state: LoggerState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(JOIN_DRAIN_INTERVAL);
+        loop {
+            interval.tick().await;
+            drain_join_queue(&state);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::{save_logs, LogFormat};
+
+    #[test]
+    fn no_join_events_lost_during_concurrent_saves() {
+        const EVENTS: usize = 2_000;
+        let channel = "forsen";
+        let dir = std::env::temp_dir().join(format!("join_saves_{}", std::process::id()));
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().log_dir.value = dir.join("during").display().to_string();
+
+        let pump = {
+            let state = state.clone();
+            std::thread::spawn(move || {
+                // What `handle_join_or_part` does, without needing channels.txt
+                for i in 0..EVENTS {
+                    let event = if i % 2 == 0 { "J" } else { "P" };
+                    state.join_queue.push(channel, format!("12:00:00 [{}] user{}", event, i));
+                    if i % 50 == 0 {
+                        drain_join_queue(&state);
+                    }
+                }
+            })
+        };
+        while !pump.is_finished() {
+            save_logs(channel, &state, None, false, LogFormat::Text);
+        }
+        pump.join().unwrap();
+        drain_join_queue(&state);
+        state.settings.lock().unwrap().log_dir.value = dir.join("after").display().to_string();
+        save_logs(channel, &state, None, false, LogFormat::Text);
+
+        let saved: Vec<_> = std::fs::read_dir(dir.join("after")).unwrap().flatten().map(|e| e.path()).collect();
+        let joins = saved.iter().find(|path| path.file_name().unwrap().to_string_lossy().contains("_joins_")).unwrap();
+        let content = std::fs::read_to_string(joins).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let events: Vec<&str> = content.lines().filter(|line| line.starts_with("12:00:00 [")).collect();
+        assert_eq!(events.len(), EVENTS);
+        for (i, line) in events.iter().enumerate() {
+            let event = if i % 2 == 0 { "J" } else { "P" };
+            assert_eq!(*line, format!("12:00:00 [{}] user{}", event, i));
+        }
+    }
+}
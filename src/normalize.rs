@@ -0,0 +1,69 @@
+//! `normalize_messages`: chat text is stored trimmed, with long runs of spaces collapsed
+//! and without the invisible characters used to get around Twitch's duplicate-message
+//! check. The console still shows the text as sent. Copypasta and cross-channel spam
+//! detection see the stored text, so such variants count as the same message.
+
+/// Invisible in chat and only there to make a message look different: tag characters
+/// (U+E0000 to U+E007F, Chatterino and 7TV append U+E0000) and the combining grapheme
+/// joiner (U+034F) of older clients.
+fn is_bypass_char(c: char) -> bool {
+    matches!(c, '\u{e0000}'..='\u{e007f}' | '\u{034f}')
+}
+
+/// A zero width joiner belongs between two emoji (👨‍👩‍👧); anywhere else it only hides
+/// a repeat.
+fn is_stray_joiner(prev: Option<char>, c: char, next: Option<char>) -> bool {
+    let joins = |c: Option<char>| c.is_some_and(|c| !c.is_ascii() && !c.is_whitespace() && !is_bypass_char(c));
+    c == '\u{200d}' && !(joins(prev) && joins(next))
+}
+
+/// The normalized text, or `None` if `text` is already normal. Runs of more than
+/// `max_space_run` spaces are cut to that many; 0 leaves them alone.
+pub fn normalize_message(text: &str, max_space_run: usize) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut kept = String::with_capacity(text.len());
+    let mut run = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        if is_bypass_char(c) || is_stray_joiner(prev, c, chars.get(i + 1).copied()) {
+            continue;
+        }
+        run = if c == ' ' { run + 1 } else { 0 };
+        if max_space_run > 0 && run > max_space_run {
+            continue;
+        }
+        kept.push(c);
+    }
+    let normalized = kept.trim();
+    (normalized != text).then(|| normalized.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_text_is_left_alone() {
+        assert_eq!(normalize_message("hello chat", 1), None);
+        assert_eq!(normalize_message("family 👨\u{200d}👩\u{200d}👧", 1), None);
+    }
+
+    #[test]
+    fn whitespace_is_trimmed_and_collapsed() {
+        assert_eq!(normalize_message("  hello chat \t", 1).as_deref(), Some("hello chat"));
+        assert_eq!(normalize_message("a    b  c", 1).as_deref(), Some("a b c"));
+        assert_eq!(normalize_message("a    b  c", 2).as_deref(), Some("a  b  c"));
+        assert_eq!(normalize_message("a    b", 0), None);
+    }
+
+    #[test]
+    fn bypass_characters_are_removed() {
+        assert_eq!(normalize_message("gg \u{e0000}", 1).as_deref(), Some("gg"));
+        assert_eq!(normalize_message("\u{e0000}gg", 1).as_deref(), Some("gg"));
+        assert_eq!(normalize_message("g\u{034f}g", 1).as_deref(), Some("gg"));
+        assert_eq!(normalize_message("gg\u{200d}", 1).as_deref(), Some("gg"));
+        assert_eq!(normalize_message("g\u{200d}g", 1).as_deref(), Some("gg"));
+        // The run of spaces is counted after removing what was between them
+        assert_eq!(normalize_message("a \u{e0000} b", 1).as_deref(), Some("a b"));
+    }
+}
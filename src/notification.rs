@@ -0,0 +1,90 @@
+use notify_rust::Notification;
+use owo_colors::OwoColorize;
+
+use crate::console_eprintln;
+use crate::state::LoggerState;
+
+// This can be your new, efficient notification function!
+pub fn send_desktop_notification(state: &LoggerState, summary: &str, body: &str) {
+    let result = Notification::new()
+        .summary(summary) // Set the title
+        .body(body)       // Set the message content
+        .show();          // Display the notification
+    record_delivery(state, result.map(|_| ()));
+}
+
+/// Count the outcome in `state.notifications`. After `notification_failure_warning`
+/// failures in a row there is one prominent warning, since alerts silently not showing
+/// up is easy to miss.
+fn record_delivery(state: &LoggerState, result: Result<(), notify_rust::error::Error>) {
+    let warn_after = state.settings.lock().unwrap().notification_failure_warning.value;
+    let warn = state.notifications.lock().unwrap().record(result.is_ok(), warn_after);
+    if let Err(e) = result {
+        eprintln!("⚠️ Failed to send notification: {}", e);
+        if warn {
+            console_eprintln!(
+                "{}",
+                format!(
+                    "*** {} desktop notifications in a row failed, alerts are NOT shown. Check the notification daemon, or use SOUND instead of NOTIFY ***",
+                    warn_after
+                ).red().bold()
+            );
+        }
+    }
+}
+
+/// Notification about a channel. With `notification_action = <command>` set
+/// (e.g. `xdg-open https://twitch.tv/{channel}`) it gets an "Open chat" button running that command.
+/// Without the setting, or on platforms without actions, this is `send_desktop_notification`.
+pub fn send_channel_notification(state: &LoggerState, channel: &str, summary: &str, body: &str) {
+    let (action, timeout_secs) = {
+        let settings = state.settings.lock().unwrap();
+        (settings.notification_action.value.clone(), settings.notification_action_timeout.value)
+    };
+    match action {
+        Some(template) if cfg!(all(unix, not(target_os = "macos"))) => {
+            let command = template.replace("{channel}", channel);
+            notify_with_action(state.clone(), summary.to_string(), body.to_string(), command, timeout_secs);
+        }
+        _ => send_desktop_notification(state, summary, body),
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn notify_with_action(state: LoggerState, summary: String, body: String, command: String, timeout_secs: u64) {
+    use notify_rust::Timeout;
+    use std::time::{Duration, Instant};
+
+    // Waiting for the click blocks, so the listener gets its own thread
+    std::thread::spawn(move || {
+        let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+        let handle = match Notification::new()
+            .summary(&summary)
+            .body(&body)
+            .action("open", "Open chat")
+            .timeout(Timeout::Milliseconds(timeout_secs as u32 * 1000))
+            .show()
+            {
+                Ok(handle) => handle,
+                Err(e) => {
+                    record_delivery(&state, Err(e));
+                    return;
+                }
+            };
+        record_delivery(&state, Ok(()));
+
+        // Daemons without action support just close the notification
+        handle.wait_for_action(|action| {
+            if action == "open" && Instant::now() <= deadline {
+                if let Err(e) = std::process::Command::new("sh").arg("-c").arg(&command).spawn() {
+                    eprintln!("⚠️ Failed to run notification action '{}': {}", command, e);
+                }
+            }
+        });
+    });
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn notify_with_action(state: LoggerState, summary: String, body: String, _command: String, _timeout_secs: u64) {
+    send_desktop_notification(&state, &summary, &body);
+}
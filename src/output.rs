@@ -0,0 +1,104 @@
+//! Every file the logger writes, renames or deletes goes through here, so `--dry-run`
+//! only has to be enforced in one place.
+
+use std::fs;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::console::print_status;
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// `--dry-run`: connect, log and alert as usual, but leave the disk alone.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Write `content` to `path`. Returns `false` if it was skipped because of `--dry-run`,
+/// after reporting what would have been written.
+pub fn write_file(path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> io::Result<bool> {
+    let (path, content) = (path.as_ref(), content.as_ref());
+    if is_dry_run() {
+        print_status(&format!("dry-run: skipped writing {} bytes to {}", content.len(), path.display()));
+        return Ok(false);
+    }
+    fs::write(path, content)?;
+    Ok(true)
+}
+
+/// Append `content` to `path`, creating it. Skipped with `--dry-run`, like `write_file`.
+pub fn append_file(path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> io::Result<bool> {
+    let (path, content) = (path.as_ref(), content.as_ref());
+    if is_dry_run() {
+        print_status(&format!("dry-run: skipped appending {} bytes to {}", content.len(), path.display()));
+        return Ok(false);
+    }
+    fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(content)?;
+    Ok(true)
+}
+
+/// `write_file` through `<path>.tmp` and a rename, so the file is never seen half written.
+pub fn replace_file(path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> io::Result<bool> {
+    let path = path.as_ref();
+    let tmp = PathBuf::from(format!("{}.tmp", path.display()));
+    if !write_file(&tmp, content)? {
+        return Ok(false);
+    }
+    fs::rename(&tmp, path)?;
+    Ok(true)
+}
+
+/// Replace the content of an open (e.g. locked) file. Skipped with `--dry-run`.
+pub fn rewrite_file(file: &mut fs::File, path: &Path, content: impl AsRef<[u8]>) -> io::Result<bool> {
+    let content = content.as_ref();
+    if is_dry_run() {
+        print_status(&format!("dry-run: skipped writing {} bytes to {}", content.len(), path.display()));
+        return Ok(false);
+    }
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(content)?;
+    Ok(true)
+}
+
+/// Create `dir` and its parents if missing. Skipped with `--dry-run`.
+pub fn create_dir(dir: &Path) -> io::Result<bool> {
+    if dir.is_dir() {
+        return Ok(true);
+    }
+    if is_dry_run() {
+        print_status(&format!("dry-run: skipped creating {}", dir.display()));
+        return Ok(false);
+    }
+    fs::create_dir_all(dir)?;
+    Ok(true)
+}
+
+/// Move `from` to `to`, creating the target directory. Skipped with `--dry-run`.
+pub fn move_file(from: &Path, to: &Path) -> io::Result<bool> {
+    if is_dry_run() {
+        print_status(&format!("dry-run: skipped moving {} to {}", from.display(), to.display()));
+        return Ok(false);
+    }
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::rename(from, to)?;
+    Ok(true)
+}
+
+/// Delete `path`. Like `fs::remove_file` a missing file is an error, also with `--dry-run`.
+pub fn remove_file(path: &Path) -> io::Result<bool> {
+    if is_dry_run() {
+        fs::metadata(path)?;
+        print_status(&format!("dry-run: skipped deleting {}", path.display()));
+        return Ok(false);
+    }
+    fs::remove_file(path)?;
+    Ok(true)
+}
@@ -0,0 +1,92 @@
+//! `PAUSES <channel> [minutes]`: stretches without chat in a channel's log, e.g. to find
+//! breaks in a stream. Only chat messages count, markers like `[GAP]` or JOIN/PART lines
+//! are skipped. `SAVE ... --pauses` appends the same list to the saved message log.
+
+use std::time::Duration;
+
+use chrono::NaiveTime;
+
+use crate::incident::format_duration;
+use crate::query::line_time;
+
+/// Gaps shorter than this are not listed unless a threshold is given.
+pub const DEFAULT_PAUSE_MINUTES: u64 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pause {
+    /// Last message before the pause.
+    pub start: NaiveTime,
+    /// First message after it.
+    pub end: NaiveTime,
+    pub length: Duration,
+}
+
+/// Time of a chat message line ("HH:MM:SS <name> ..."), `None` for other lines.
+fn message_time(line: &str) -> Option<NaiveTime> {
+    line.get(8..10).filter(|rest| *rest == " <").and(line_time(line))
+}
+
+/// Gaps between consecutive chat messages of at least `min`. The lines only have times,
+/// so an earlier time than the previous one is taken as the next day.
+pub fn find_pauses(lines: &[String], min: Duration) -> Vec<Pause> {
+    let mut pauses = Vec::new();
+    let mut previous: Option<NaiveTime> = None;
+    for time in lines.iter().filter_map(|line| message_time(line)) {
+        if let Some(start) = previous {
+            let mut secs = (time - start).num_seconds();
+            if secs < 0 {
+                secs += 24 * 60 * 60;
+            }
+            let length = Duration::from_secs(secs as u64);
+            if length >= min && !length.is_zero() {
+                pauses.push(Pause { start, end: time, length });
+            }
+        }
+        previous = Some(time);
+    }
+    pauses
+}
+
+/// "--- #channel pauses of 5 minutes or more (2) ---" and a line per pause.
+pub fn format_pauses(channel: &str, pauses: &[Pause], min_minutes: u64) -> String {
+    let mut lines = vec![format!("--- #{} pauses of {} minutes or more ({}) ---", channel, min_minutes, pauses.len())];
+    if pauses.is_empty() {
+        lines.push("No pauses".to_string());
+    }
+    lines.extend(pauses.iter().map(|p| format!("{} - {}  {}", p.start, p.end, format_duration(p.length))));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn only_chat_messages_count() {
+        let log = lines(&[
+            "12:00:00 <alice> []\nhi\n",
+            "12:03:00 [J] bob",
+            "12:04:00 [GAP] connection lost for 30s, messages in between are missing",
+            "12:10:30 <bob>\nback\n",
+            "12:11:00 <alice>\nwb\n",
+        ]);
+        let pauses = find_pauses(&log, Duration::from_secs(5 * 60));
+        assert_eq!(pauses.len(), 1);
+        assert_eq!(pauses[0].start.to_string(), "12:00:00");
+        assert_eq!(pauses[0].end.to_string(), "12:10:30");
+        assert_eq!(pauses[0].length, Duration::from_secs(630));
+        assert!(format_pauses("forsen", &pauses, 5).ends_with("12:00:00 - 12:10:30  10m 30s"));
+    }
+
+    #[test]
+    fn midnight_is_not_a_negative_gap() {
+        let log = lines(&["23:59:00 <alice>\nlate\n", "00:01:00 <bob>\nearly\n"]);
+        let pauses = find_pauses(&log, Duration::from_secs(60));
+        assert_eq!(pauses[0].length, Duration::from_secs(120));
+        assert!(find_pauses(&log, Duration::from_secs(5 * 60)).is_empty());
+    }
+}
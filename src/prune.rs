@@ -0,0 +1,147 @@
+//! Channels that could not be joined: renamed, deleted or suspended ones. They are
+//! collected from `msg_channel_suspended` NOTICEs and from the initial JOINs that were
+//! never confirmed, summed up after startup and listed in the session report.
+//! `PRUNE` parts them, `PRUNE CONFIG CONFIRM` also comments them out in channels.txt.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use chrono::Local;
+
+use crate::output::write_file;
+use crate::state::LoggerState;
+
+/// Reason of initial channels without a JOIN confirmation; PRUNE checks them again.
+pub const NOT_CONFIRMED: &str = "JOIN not confirmed";
+
+/// NOTICE ids that mean the channel can't be joined.
+const JOIN_FAILURE_NOTICES: &[&str] = &["msg_channel_suspended", "tos_ban"];
+
+/// Remember why `channel` could not be joined; the first reason wins.
+pub fn record_join_failure(state: &LoggerState, channel: &str, reason: &str) {
+    state.join_failures.lock().unwrap().entry(channel.to_string()).or_insert_with(|| reason.to_string());
+}
+
+/// A NOTICE about a channel that can't be joined. Returns whether it was one.
+pub fn check_join_notice(state: &LoggerState, channel: Option<&str>, message_id: Option<&str>, text: &str) -> bool {
+    match (channel, message_id) {
+        (Some(channel), Some(id)) if JOIN_FAILURE_NOTICES.contains(&id) => {
+            record_join_failure(state, channel, &format!("{}: {}", id, text));
+            true
+        }
+        _ => false,
+    }
+}
+
+/// "a (reason), b (reason)" for the startup summary and PRUNE.
+pub fn format_join_failures(failures: &BTreeMap<String, String>) -> String {
+    failures.iter().map(|(channel, reason)| format!("{} ({})", channel, reason)).collect::<Vec<_>>().join(", ")
+}
+
+/// channels.txt with the lines of `channels` commented out as `# pruned <date> (<note>): <line>`.
+/// Everything else stays as it was, byte for byte, except that the number of default
+/// channels on line 1 goes down for each pruned default, so the same channels stay
+/// defaults. Returns the new content and the channels that were found.
+pub fn prune_config(content: &str, channels: &[String], note: &str) -> (String, Vec<String>) {
+    let prune: HashSet<String> = channels.iter().map(|c| c.to_lowercase()).collect();
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+    let Some(first) = lines.first() else {
+        return (content.to_string(), Vec::new());
+    };
+    let bom = if first.starts_with('\u{feff}') { "\u{feff}" } else { "" };
+    let Ok(default_count) = first.trim_start_matches('\u{feff}').trim().parse::<usize>() else {
+        return (content.to_string(), Vec::new());
+    };
+
+    let mut seen = HashSet::new();
+    let mut defaults_seen = 0;
+    let mut pruned_defaults = 0;
+    let mut pruned = Vec::new();
+    for line in lines.iter_mut().skip(1) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        // Same tests as `parse_channel_config`: settings are not channels, duplicates don't count
+        if let Some((key, _)) = trimmed.split_once('=') {
+            let key = key.trim();
+            if !key.contains(char::is_whitespace) && !key.contains(':') {
+                continue;
+            }
+        }
+        let name = trimmed.split_whitespace().next().unwrap_or_default().split(':').next().unwrap_or_default().trim().to_lowercase();
+        if name.is_empty() {
+            continue;
+        }
+        let first = seen.insert(name.clone());
+        let is_default = first && defaults_seen < default_count;
+        defaults_seen += usize::from(is_default);
+        // A duplicate would become the channel's line once the first one is gone
+        if prune.contains(&name) {
+            let ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+            *line = format!("# pruned {} ({}): {}{}", Local::now().format("%Y-%m-%d"), note, line.trim_end_matches(['\r', '\n']), ending);
+            pruned_defaults += usize::from(is_default);
+            if first {
+                pruned.push(name);
+            }
+        }
+    }
+
+    if pruned_defaults > 0 {
+        let ending = &lines[0][lines[0].trim_end_matches(['\r', '\n']).len()..].to_string();
+        lines[0] = format!("{}{}{}", bom, default_count - pruned_defaults, ending);
+    }
+    (lines.concat(), pruned)
+}
+
+/// Comment out `channels` in the channels.txt at `path`, after copying it to `<path>.bak`.
+pub fn prune_config_file(path: &Path, channels: &[String]) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let (new_content, pruned) = prune_config(&content, channels, "join failed");
+    if pruned.is_empty() {
+        return Ok(pruned);
+    }
+    let backup = format!("{}.bak", path.display());
+    write_file(&backup, &content).map_err(|e| format!("{}: {}", backup, e))?;
+    write_file(path, new_content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pruned_lines_are_commented_out_in_place() {
+        let content = "\u{feff}2\r\n# my defaults\r\ngone:red members=off\r\nforsen\r\nlog_header = minimal\r\ngone\r\nxqcow\r\n";
+        let (new, pruned) = prune_config(content, &["GONE".to_string(), "nothere".to_string()], "join failed");
+        assert_eq!(pruned, vec!["gone"]);
+
+        let lines: Vec<&str> = new.split("\r\n").collect();
+        assert_eq!(lines[0], "\u{feff}1");
+        assert_eq!(lines[1], "# my defaults");
+        assert!(lines[2].starts_with("# pruned ") && lines[2].ends_with("(join failed): gone:red members=off"), "{}", lines[2]);
+        assert_eq!(&lines[3..5], ["forsen", "log_header = minimal"]);
+        // Otherwise the duplicate would take over
+        assert!(lines[5].ends_with("(join failed): gone"));
+        assert_eq!(&lines[6..], ["xqcow", ""]);
+    }
+
+    #[test]
+    fn vips_keep_the_default_count() {
+        let (new, pruned) = prune_config("1\nforsen\ngone\n", &["gone".to_string()], "x");
+        assert_eq!(pruned, vec!["gone"]);
+        assert!(new.starts_with("1\nforsen\n# pruned "));
+        let (same, pruned) = prune_config("1\nforsen\n", &["gone".to_string()], "x");
+        assert!(pruned.is_empty());
+        assert_eq!(same, "1\nforsen\n");
+    }
+
+    #[test]
+    fn suspension_notices_are_recorded() {
+        let state = LoggerState::default();
+        assert!(!check_join_notice(&state, Some("forsen"), Some("msg_banned"), "banned"));
+        assert!(check_join_notice(&state, Some("gone"), Some("msg_channel_suspended"), "This channel has been suspended."));
+        assert_eq!(format_join_failures(&state.join_failures.lock().unwrap()), "gone (msg_channel_suspended: This channel has been suspended.)");
+    }
+}
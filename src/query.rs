@@ -0,0 +1,155 @@
+use chrono::NaiveTime;
+use regex::{Regex, RegexBuilder};
+
+use crate::buckets::line_texts;
+use crate::state::LogStore;
+
+/// What SEARCH looks for.
+#[derive(Debug)]
+pub enum SearchPattern {
+    /// Case-insensitive, stored lowercased.
+    Substring(String),
+    /// Case-insensitive like the substrings, `(?-i)` turns that off.
+    Regex(Regex),
+}
+
+impl SearchPattern {
+    /// `/pattern/` or `regex` is a regular expression, anything else a substring.
+    pub fn parse(pattern: &str, regex: bool) -> Result<Self, String> {
+        let slashed = pattern.len() > 2 && pattern.starts_with('/') && pattern.ends_with('/');
+        if regex || slashed {
+            let source = if slashed { &pattern[1..pattern.len() - 1] } else { pattern };
+            return RegexBuilder::new(source)
+            .case_insensitive(true)
+            .build()
+            .map(SearchPattern::Regex)
+            .map_err(|e| format!("invalid regex: {}", e));
+        }
+        Ok(SearchPattern::Substring(pattern.to_lowercase()))
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        match self {
+            SearchPattern::Substring(needle) => line.to_lowercase().contains(needle.as_str()),
+            SearchPattern::Regex(regex) => regex.is_match(line),
+        }
+    }
+}
+
+/// Matching log lines of `channel` with their numbers as in saved files (from 1).
+/// `None` if nothing of the channel is logged.
+pub fn search(channel: &str, pattern: &SearchPattern, logs: &LogStore) -> Option<Vec<(usize, String)>> {
+    let logs = logs.lock().unwrap();
+    let lines = logs.get(channel)?;
+    Some(lines.iter().enumerate().filter(|(_, line)| pattern.matches(line.text())).map(|(i, line)| (i + 1, line.text().to_string())).collect())
+}
+
+/// Leading "HH:MM:SS" of a stored log line.
+pub fn line_time(line: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(line.get(0..8)?, "%H:%M:%S").ok()
+}
+
+/// Parse a user supplied "HH:MM:SS" (or "HH:MM").
+pub fn parse_time_arg(arg: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(arg, "%H:%M:%S")
+    .or_else(|_| NaiveTime::parse_from_str(arg, "%H:%M"))
+    .ok()
+}
+
+/// Log lines of `channel` stamped after `after`.
+/// Logs are in insertion order, so the scan runs backwards and stops at the first older line.
+pub fn since(channel: &str, after: NaiveTime, logs: &LogStore) -> Vec<String> {
+    let logs = logs.lock().unwrap();
+    let Some(lines) = logs.get(channel) else {
+        return Vec::new();
+    };
+
+    let mut found: Vec<String> = Vec::new();
+    for line in lines.iter().rev() {
+        match line_time(line.text()) {
+            Some(t) if t > after => found.push(line.text().to_string()),
+            Some(_) => break,
+            None => continue,
+        }
+    }
+    found.reverse();
+    found
+}
+
+/// The last `count` log lines of `channel`, oldest first.
+pub fn tail(channel: &str, count: usize, logs: &LogStore) -> Vec<String> {
+    let logs = logs.lock().unwrap();
+    let Some(lines) = logs.get(channel) else {
+        return Vec::new();
+    };
+    line_texts(&lines[lines.len().saturating_sub(count)..])
+}
+
+/// Log lines of `channel` stamped within `start..=end`.
+/// An `end` before `start` means the range crosses midnight.
+pub fn between(channel: &str, start: NaiveTime, end: NaiveTime, logs: &LogStore) -> Vec<String> {
+    let logs = logs.lock().unwrap();
+    let Some(lines) = logs.get(channel) else {
+        return Vec::new();
+    };
+
+    let in_range = |t: NaiveTime| {
+        if start <= end {
+            t >= start && t <= end
+        } else {
+            t >= start || t <= end
+        }
+    };
+
+    lines
+    .iter()
+    .filter(|line| line_time(line.text()).is_some_and(in_range))
+    .map(|line| line.text().to_string())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use crate::buckets::LogLine;
+
+    fn logs() -> LogStore {
+        let lines = ["12:00:00 <Alice>\nHello chat\n", "12:00:05 <Bob>\nforsenE\n", "12:00:09 <Carol>\nhello again\n"]
+        .map(|line| LogLine::Text(line.to_string()))
+        .to_vec();
+        Arc::new(Mutex::new(HashMap::from([("forsen".to_string(), lines)])))
+    }
+
+    #[test]
+    fn substring_search_ignores_case_and_keeps_numbers() {
+        let pattern = SearchPattern::parse("HELLO", false).unwrap();
+        let found: Vec<usize> = search("forsen", &pattern, &logs()).unwrap().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(found, [1, 3]);
+        assert!(search("xqcow", &pattern, &logs()).is_none());
+    }
+
+    #[test]
+    fn regex_with_flag_or_slashes() {
+        let slashed = SearchPattern::parse("/<(Bob|Carol)>/", false).unwrap();
+        assert_eq!(search("forsen", &slashed, &logs()).unwrap().len(), 2);
+        let flagged = SearchPattern::parse("^12:00:0[0-5]", true).unwrap();
+        assert_eq!(search("forsen", &flagged, &logs()).unwrap().len(), 2);
+        assert!(SearchPattern::parse("/(/", false).is_err());
+        let any_case = SearchPattern::parse("hello (chat|again)", true).unwrap();
+        assert_eq!(search("forsen", &any_case, &logs()).unwrap().len(), 2);
+        let any_case = SearchPattern::parse("/<BOB>/", false).unwrap();
+        assert_eq!(search("forsen", &any_case, &logs()).unwrap().len(), 1);
+        let exact = SearchPattern::parse("/(?-i)hello/", false).unwrap();
+        assert_eq!(search("forsen", &exact, &logs()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn tail_takes_the_newest_lines() {
+        assert_eq!(tail("forsen", 2, &logs()), ["12:00:05 <Bob>\nforsenE\n", "12:00:09 <Carol>\nhello again\n"]);
+        assert_eq!(tail("forsen", 500, &logs()).len(), 3);
+        assert!(tail("xqcow", 20, &logs()).is_empty());
+    }
+}
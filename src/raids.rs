@@ -0,0 +1,118 @@
+//! Raids seen during the session, so raids between logged channels can be followed.
+
+use std::fmt;
+
+use serde::Serialize;
+use twitch_irc::message::{UserNoticeEvent, UserNoticeMessage};
+
+use crate::buckets::append_marker;
+use crate::state::LoggerState;
+
+/// One raid, as announced by the USERNOTICE in the raided channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Raid {
+    /// HH:MM:SS receive time.
+    pub time: String,
+    /// Login of the raiding channel (the sender of the notice).
+    pub from: String,
+    /// Display name of the raiding channel. Can differ from the login in more than
+    /// case, e.g. for names in other scripts.
+    pub from_name: String,
+    pub to: String,
+    pub viewers: u64,
+}
+
+impl Raid {
+    /// `None` for every other USERNOTICE.
+    pub fn from_notice(time: &str, msg: &UserNoticeMessage) -> Option<Self> {
+        let UserNoticeEvent::Raid { viewer_count, .. } = &msg.event else {
+            return None;
+        };
+        Some(Raid {
+            time: time.to_string(),
+            from: msg.sender.login.clone(),
+            from_name: msg.sender.name.clone(),
+            to: msg.channel_login.clone(),
+            viewers: *viewer_count,
+        })
+    }
+
+    /// `#login`, followed by the display name if it is more than a different capitalization.
+    pub fn source(&self) -> String {
+        if self.from_name.to_lowercase() == self.from {
+            format!("#{}", self.from)
+        } else {
+            format!("#{} ({})", self.from, self.from_name)
+        }
+    }
+}
+
+impl fmt::Display for Raid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} → #{} ({} viewers)", self.time, self.source(), self.to, self.viewers)
+    }
+}
+
+/// Remember a raid notice. If the raiding channel is logged as well, the raided
+/// channel's log gets a line pointing to it.
+pub fn record_raid(time: &str, msg: &UserNoticeMessage, state: &LoggerState) {
+    let Some(raid) = Raid::from_notice(time, msg) else {
+        return;
+    };
+
+    if state.channels.lock().unwrap().contains(&raid.from) {
+        let line = format!("incoming raid from {} (logged channel), {} viewers", raid.source(), raid.viewers);
+        append_marker(state, &raid.to, time, "RAID", &line);
+    }
+    state.raids.lock().unwrap().push(raid);
+}
+
+/// Raids from or to `channel`.
+pub fn raids_of(raids: &[Raid], channel: &str) -> Vec<Raid> {
+    raids.iter().filter(|r| r.from == channel || r.to == channel).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::handle_message;
+    use twitch_irc::message::{IRCMessage, ServerMessage};
+
+    fn raid_notice(login: &str, display_name: &str, channel: &str) -> ServerMessage {
+        let raw = format!(
+            "@badge-info=;badges=;color=#FF69B4;display-name={name};emotes=;flags=;id=bb99dda7-3736-4583-9114-52aa11b23d17;login={login};mod=0;msg-id=raid;msg-param-displayName={name};msg-param-login={login};msg-param-profileImageURL=https://static-cdn.jtvnw.net/jtv_user_pictures/x-profile_image-70x70.png;msg-param-viewerCount=430;room-id=71092938;subscriber=0;system-msg=430\\sraiders\\sfrom\\s{name}\\shave\\sjoined!;tmi-sent-ts=1594517796120;user-id=155874595;user-type= :tmi.twitch.tv USERNOTICE #{channel}",
+            name = display_name,
+            login = login,
+            channel = channel
+        );
+        ServerMessage::try_from(IRCMessage::parse(&raw).unwrap()).unwrap()
+    }
+
+    fn state_with_channels(channels: &[&str]) -> LoggerState {
+        let state = LoggerState::default();
+        *state.channels.lock().unwrap() = channels.iter().map(|c| c.to_string()).collect();
+        state
+    }
+
+    #[test]
+    fn raid_is_recorded() {
+        let state = state_with_channels(&["xqcow"]);
+        handle_message("12:00:00", raid_notice("iamelisabete", "IAmElisabete", "xqcow"), &state);
+        let raids = state.raids.lock().unwrap();
+        assert_eq!(raids.len(), 1);
+        assert_eq!(raids[0].to_string(), "12:00:00 #iamelisabete → #xqcow (430 viewers)");
+        // Only the notice itself, the raider is not logged
+        assert_eq!(state.logs.lock().unwrap()["xqcow"].len(), 1);
+    }
+
+    #[test]
+    fn raid_between_logged_channels_is_annotated() {
+        let state = state_with_channels(&["xqcow", "iamelisabete"]);
+        handle_message("12:00:00", raid_notice("iamelisabete", "エリザベテ", "xqcow"), &state);
+        let logs = state.logs.lock().unwrap();
+        assert_eq!(
+            logs["xqcow"].last().unwrap().text(),
+            "12:00:00 [RAID] incoming raid from #iamelisabete (エリザベテ) (logged channel), 430 viewers"
+        );
+    }
+}
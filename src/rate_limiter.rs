@@ -0,0 +1,82 @@
+//! Token bucket for commands that send something to Twitch, so a burst of JOINs
+//! typed or pasted into the prompt doesn't run into Twitch's own limits.
+
+use std::time::{Duration, Instant};
+
+/// Twitch allows 20 JOINs per 10 seconds.
+pub const JOIN_CAPACITY: u32 = 20;
+pub const JOIN_RATE: f64 = 2.0;
+
+pub struct TokenBucket {
+    capacity: u32,
+    tokens: f64,
+    last_refill: Instant,
+    /// Tokens added per second.
+    rate: f64,
+}
+
+impl TokenBucket {
+    /// A full bucket of `capacity` tokens, refilled with `rate` tokens per second.
+    pub fn new(capacity: u32, rate: f64) -> Self {
+        Self {
+            capacity,
+            tokens: capacity as f64,
+            last_refill: Instant::now(),
+            rate,
+        }
+    }
+
+    pub fn try_consume(&mut self) -> bool {
+        self.try_consume_at(Instant::now())
+    }
+
+    pub fn try_consume_at(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Time until the next token is available.
+    pub fn retry_after(&self) -> Duration {
+        Duration::from_secs_f64(((1.0 - self.tokens) / self.rate).max(0.0))
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity as f64);
+        self.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_up_to_capacity_then_refill() {
+        let mut bucket = TokenBucket::new(3, 2.0);
+        let t0 = bucket.last_refill;
+        assert!(bucket.try_consume_at(t0));
+        assert!(bucket.try_consume_at(t0));
+        assert!(bucket.try_consume_at(t0));
+        assert!(!bucket.try_consume_at(t0));
+        assert_eq!(bucket.retry_after(), Duration::from_millis(500));
+
+        assert!(!bucket.try_consume_at(t0 + Duration::from_millis(400)));
+        assert!(bucket.try_consume_at(t0 + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn refill_stops_at_capacity() {
+        let mut bucket = TokenBucket::new(2, 2.0);
+        let t0 = bucket.last_refill;
+        let later = t0 + Duration::from_secs(60);
+        assert!(bucket.try_consume_at(later));
+        assert!(bucket.try_consume_at(later));
+        assert!(!bucket.try_consume_at(later));
+    }
+}
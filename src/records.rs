@@ -0,0 +1,162 @@
+//! Per-channel records for recaps: the longest message and the most repeated one
+//! (copypastas). Shown by REPORT and, with `header_records`, in saved message logs.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use serde::Serialize;
+
+/// Distinct messages the repeat counter keeps; the least recently seen one makes room.
+pub const REPEAT_CAPACITY: usize = 2000;
+/// Characters of a message shown in previews.
+const PREVIEW_CHARS: usize = 80;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LongestMessage {
+    pub chars: usize,
+    pub sender: String,
+    pub preview: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepeatedMessage {
+    pub count: u32,
+    pub text: String,
+}
+
+/// The records of a channel at one point, for REPORT and the log header.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ChannelRecords {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longest_message: Option<LongestMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub most_repeated: Option<RepeatedMessage>,
+}
+
+impl ChannelRecords {
+    /// "longest: ..." and "most repeated: ..." lines, as far as there is something to show.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(longest) = &self.longest_message {
+            lines.push(format!("longest: {} chars by {}: {}", longest.chars, longest.sender, longest.preview));
+        }
+        if let Some(repeated) = &self.most_repeated {
+            lines.push(format!("most repeated: {}x {}", repeated.count, preview(&repeated.text)));
+        }
+        lines
+    }
+}
+
+#[derive(Debug)]
+struct RepeatEntry {
+    count: u32,
+    /// `tick` of the last time the message was seen.
+    seen: u64,
+    text: String,
+}
+
+/// Counts identical messages, bounded to `REPEAT_CAPACITY` distinct ones so channels
+/// where every message is different don't grow without limit.
+#[derive(Debug, Default)]
+pub struct RepeatCounter {
+    entries: HashMap<u64, RepeatEntry>,
+    tick: u64,
+    /// Kept apart from `entries`, so the record survives its entry being evicted.
+    top: Option<RepeatedMessage>,
+}
+
+impl RepeatCounter {
+    pub fn record(&mut self, text: &str) {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let key = hasher.finish();
+        self.tick += 1;
+
+        if !self.entries.contains_key(&key) && self.entries.len() >= REPEAT_CAPACITY {
+            if let Some(oldest) = self.entries.iter().min_by_key(|(_, e)| e.seen).map(|(k, _)| *k) {
+                self.entries.remove(&oldest);
+            }
+        }
+        let entry = self.entries.entry(key).or_insert_with(|| RepeatEntry { count: 0, seen: 0, text: text.to_string() });
+        entry.count += 1;
+        entry.seen = self.tick;
+
+        // A message said once is not a repeat
+        if entry.count > 1 && self.top.as_ref().is_none_or(|top| entry.count > top.count) {
+            self.top = Some(RepeatedMessage { count: entry.count, text: entry.text.clone() });
+        }
+    }
+
+    pub fn top(&self) -> Option<&RepeatedMessage> {
+        self.top.as_ref()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MessageRecords {
+    pub longest: Option<LongestMessage>,
+    pub repeats: RepeatCounter,
+}
+
+impl MessageRecords {
+    /// Count one chat message. Messages shorter than `min_repeat_chars` are left out of
+    /// the repeat counter, otherwise "LUL" and "KEKW" would always win.
+    pub fn record(&mut self, sender: &str, text: &str, min_repeat_chars: usize) {
+        let text = text.trim();
+        let chars = text.chars().count();
+        if self.longest.as_ref().is_none_or(|l| chars > l.chars) {
+            self.longest = Some(LongestMessage { chars, sender: sender.to_string(), preview: preview(text) });
+        }
+        if chars >= min_repeat_chars {
+            self.repeats.record(text);
+        }
+    }
+
+    pub fn snapshot(&self) -> ChannelRecords {
+        ChannelRecords { longest_message: self.longest.clone(), most_repeated: self.repeats.top().cloned() }
+    }
+}
+
+/// The first `PREVIEW_CHARS` characters of `text`, with "…" if it was cut.
+fn preview(text: &str) -> String {
+    match text.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_and_most_repeated() {
+        let mut records = MessageRecords::default();
+        let pasta = "this is a copypasta that everyone sends";
+        records.record("a", pasta, 10);
+        records.record("b", "LUL", 10);
+        records.record("c", "LUL", 10);
+        records.record("c", "LUL", 10);
+        records.record("d", &format!("  {}  ", pasta), 10);
+        records.record("e", &"x".repeat(100), 10);
+
+        let longest = records.longest.as_ref().unwrap();
+        assert_eq!((longest.chars, longest.sender.as_str()), (100, "e"));
+        assert_eq!(longest.preview, format!("{}…", "x".repeat(PREVIEW_CHARS)));
+        assert_eq!(records.repeats.top(), Some(&RepeatedMessage { count: 2, text: pasta.to_string() }));
+        assert_eq!(records.snapshot().lines()[1], format!("most repeated: 2x {}", pasta));
+    }
+
+    #[test]
+    fn repeat_counter_is_bounded_but_keeps_the_record() {
+        let mut counter = RepeatCounter::default();
+        counter.record("the old copypasta");
+        counter.record("the old copypasta");
+        for i in 0..REPEAT_CAPACITY * 2 {
+            counter.record(&format!("unique message {}", i));
+        }
+        assert_eq!(counter.entries.len(), REPEAT_CAPACITY);
+        assert_eq!(counter.top().map(|t| t.count), Some(2));
+    }
+}
@@ -0,0 +1,130 @@
+//! Redaction: spans of chat text matching the `[redaction]` patterns of channels.txt are
+//! stored as `[REDACTED:<name>]`, before the log, the sinks, the journal and every export
+//! see them. With `redact_defaults = true` the built-in `email` and `token` (long hex or
+//! base64 strings) patterns apply too. The console shows the redacted text unless
+//! `redact_console` is off. Hits are counted per channel and pattern, shown by STATS.
+//!
+//! ```text
+//! [redaction]
+//! phone = \+?\d[\d -]{8,}\d
+//! ```
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use regex::Regex;
+
+use crate::state::LoggerState;
+
+/// Opt-in patterns of `redact_defaults`.
+pub const DEFAULT_PATTERNS: &[(&str, &str)] = &[
+    ("email", r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b"),
+    ("token", r"\b[0-9a-fA-F]{32,}\b|[A-Za-z0-9+/_-]{40,}={0,2}"),
+];
+
+/// Check a `[redaction]` line: the name is a word, the pattern a valid regex.
+pub fn parse_pattern(name: &str, pattern: &str) -> Result<Regex, String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(format!("'{}' is not a pattern name, use letters, digits, - and _", name));
+    }
+    Regex::new(pattern).map_err(|e| format!("{}: invalid pattern: {}", name, e))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    patterns: Vec<(String, Regex)>,
+}
+
+impl Redactor {
+    /// The patterns of channels.txt, after the defaults if `defaults`.
+    pub fn new(configured: &[(String, Regex)], defaults: bool) -> Self {
+        let mut patterns = Vec::new();
+        if defaults {
+            for (name, pattern) in DEFAULT_PATTERNS {
+                patterns.push((name.to_string(), Regex::new(pattern).expect("valid default pattern")));
+            }
+        }
+        patterns.extend(configured.iter().cloned());
+        Redactor { patterns }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// `text` with every match replaced, and the hits per pattern name.
+    pub fn redact<'a>(&self, text: &'a str) -> (Cow<'a, str>, Vec<(&str, u64)>) {
+        let mut text = Cow::Borrowed(text);
+        let mut hits = Vec::new();
+        for (name, regex) in &self.patterns {
+            let mut count = 0;
+            let replaced = regex.replace_all(&text, |_: &regex::Captures| {
+                count += 1;
+                format!("[REDACTED:{}]", name)
+            });
+            if count > 0 {
+                let replaced = replaced.into_owned();
+                text = Cow::Owned(replaced);
+                hits.push((name.as_str(), count));
+            }
+        }
+        (text, hits)
+    }
+}
+
+/// Redacted `text` of a message in `channel`, counting the hits; `None` if nothing matched.
+pub fn redact_message(state: &LoggerState, channel: &str, text: &str) -> Option<String> {
+    if state.redactor.is_empty() {
+        return None;
+    }
+    let (redacted, hits) = state.redactor.redact(text);
+    if hits.is_empty() {
+        return None;
+    }
+    let mut counts = state.redaction_hits.lock().unwrap();
+    let counts = counts.entry(channel.to_string()).or_default();
+    for (name, n) in hits {
+        *counts.entry(name.to_string()).or_default() += n;
+    }
+    Some(redacted.into_owned())
+}
+
+/// "Redacted: email 3, token 1" for STATS, `None` without hits.
+pub fn format_redaction_hits(hits: &BTreeMap<String, u64>) -> Option<String> {
+    if hits.is_empty() {
+        return None;
+    }
+    let counts: Vec<String> = hits.iter().map(|(name, n)| format!("{} {}", name, n)).collect();
+    Some(format!("Redacted: {}", counts.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn matches_are_replaced_and_counted() {
+        let phone = parse_pattern("phone", r"\+\d{6,}").unwrap();
+        assert!(parse_pattern("bad name", "x").is_err());
+        assert!(parse_pattern("broken", "(").is_err());
+
+        let state = LoggerState { redactor: Arc::new(Redactor::new(&[("phone".to_string(), phone)], true)), ..Default::default() };
+        let text = "mail me at Jane.Doe@example.co.uk or +4915112345678, key 0123456789abcdef0123456789abcdef";
+        assert_eq!(
+            redact_message(&state, "forsen", text).as_deref(),
+            Some("mail me at [REDACTED:email] or [REDACTED:phone], key [REDACTED:token]")
+        );
+        assert_eq!(redact_message(&state, "forsen", "a@b.cd and c@d.ef").as_deref(), Some("[REDACTED:email] and [REDACTED:email]"));
+        assert_eq!(redact_message(&state, "forsen", "nothing here"), None);
+        assert_eq!(
+            format_redaction_hits(&state.redaction_hits.lock().unwrap()["forsen"]).as_deref(),
+            Some("Redacted: email 3, phone 1, token 1")
+        );
+
+        // Without redact_defaults only the configured patterns apply
+        let configured = Redactor::new(&[], false);
+        assert!(configured.is_empty());
+        assert_eq!(configured.redact("a@b.cd").0, "a@b.cd");
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::raids::Raid;
+use crate::records::ChannelRecords;
+use crate::output::write_file;
+use crate::save::{file_timestamp, render_file_name};
+use crate::stats::{compute_channel_stats, format_channel_stats, format_latency, is_chat_line, ChannelLatency, ChannelStats};
+
+/// Rows shown in the "top" sections.
+const TOP_ROWS: usize = 10;
+/// Width of the activity histogram bars.
+const HISTOGRAM_WIDTH: usize = 30;
+/// Shorter words are mostly filler and would crowd the word list.
+const MIN_WORD_LEN: usize = 4;
+
+/// Everything the REPORT command shows for one channel.
+/// Empty sections are left out of both the text and the JSON version.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelReport {
+    pub stats: ChannelStats,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub top_chatters: Vec<(String, u32)>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub top_words: Vec<(String, usize)>,
+    /// Sub, gift sub and upgrade USERNOTICEs by event type.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subs: Vec<(String, usize)>,
+    /// Different users that got a gift sub this session.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub gift_recipients: usize,
+    /// Bans, timeouts and deletions by event type.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub moderation: Vec<(String, usize)>,
+    /// Chat messages per hour ("HH").
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub activity: Vec<(String, usize)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<String>,
+    /// Raids from or to the channel.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub raids: Vec<Raid>,
+    /// Longest and most repeated message.
+    #[serde(flatten)]
+    pub records: ChannelRecords,
+    /// `TAG` of the channel, also part of the file name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+}
+
+/// Build the report from snapshots, so the logs are not locked while counting.
+pub fn build_report(
+    channel: &str,
+    messages: &[String],
+    user_counts: Option<&HashMap<String, u32>>,
+    latency: Option<&ChannelLatency>,
+    raids: Vec<Raid>,
+    records: ChannelRecords,
+    gift_recipients: usize,
+) -> ChannelReport {
+    let mut words: HashMap<String, usize> = HashMap::new();
+    let mut subs: HashMap<String, usize> = HashMap::new();
+    let mut moderation: HashMap<String, usize> = HashMap::new();
+    let mut activity: HashMap<String, usize> = HashMap::new();
+
+    for line in messages {
+        // Event lines first, a USERNOTICE line looks like chat to `is_chat_line`
+        if let Some(event) = usernotice_event(line) {
+            if event.contains("SUB") || event.contains("GIFT") {
+                *subs.entry(event.to_string()).or_default() += 1;
+            }
+        } else if let Some(event) = moderation_event(line) {
+            *moderation.entry(event.to_string()).or_default() += 1;
+        } else if is_chat_line(line) {
+            if let Some(hour) = line.get(0..2) {
+                *activity.entry(hour.to_string()).or_default() += 1;
+            }
+            // "HH:MM:SS <user> [badges]\ntext\n"
+            if let Some((_, text)) = line.split_once('\n') {
+                for word in text.split_whitespace() {
+                    let word = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+                    if word.chars().count() >= MIN_WORD_LEN {
+                        *words.entry(word).or_default() += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut top_chatters: Vec<(String, u32)> = user_counts
+    .map(|counts| counts.iter().map(|(u, n)| (u.clone(), *n)).collect())
+    .unwrap_or_default();
+    top_chatters.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_chatters.truncate(TOP_ROWS);
+
+    let mut activity: Vec<(String, usize)> = activity.into_iter().collect();
+    activity.sort();
+
+    ChannelReport {
+        stats: compute_channel_stats(channel, messages),
+        top_chatters,
+        top_words: top(words, TOP_ROWS),
+        subs: top(subs, usize::MAX),
+        gift_recipients,
+        moderation: top(moderation, usize::MAX),
+        activity,
+        latency: latency.and_then(format_latency),
+        raids,
+        records,
+        event: None,
+    }
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// Most frequent entries first, ties alphabetically.
+fn top(counts: HashMap<String, usize>, limit: usize) -> Vec<(String, usize)> {
+    let mut entries: Vec<(String, usize)> = counts.into_iter().collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    entries.truncate(limit);
+    entries
+}
+
+/// "<EVENT>" of a "{time} [{channel}][{user}] <{EVENT}> ..." USERNOTICE line.
+pub(crate) fn usernotice_event(line: &str) -> Option<&str> {
+    let notice = line.get(9..)?.strip_prefix('[')?;
+    let rest = &notice[notice.find("] <")? + 3..];
+    Some(&rest[..rest.find('>')?])
+}
+
+/// "EVENT" of a "{time} EVENT: [#channel] ..." moderation line.
+pub(crate) fn moderation_event(line: &str) -> Option<&str> {
+    let event = line.get(9..)?.split(": [#").next()?;
+    matches!(event, "USER_BANNED" | "TIMEOUT" | "CLEARMSG" | "CHAT_CLEARED").then_some(event)
+}
+
+/// Plain text version printed by REPORT and written to the report file.
+pub fn format_report(report: &ChannelReport) -> String {
+    let event = report.event.as_ref().map(|e| format!(" ({})", e)).unwrap_or_default();
+    let mut out = format!("=== REPORT #{}{} ===\n{}", report.stats.channel, event, format_channel_stats(&report.stats));
+    if let Some(latency) = &report.latency {
+        out.push('\n');
+        out.push_str(latency);
+    }
+
+    let mut section = |title: &str, rows: Vec<String>| {
+        if !rows.is_empty() {
+            out.push_str(&format!("\n\n--- {} ---\n{}", title, rows.join("\n")));
+        }
+    };
+
+    section("Top chatters", report.top_chatters.iter().map(|(u, n)| format!("{:>5}  {}", n, u)).collect());
+    section("Top words", report.top_words.iter().map(|(w, n)| format!("{:>5}  {}", n, w)).collect());
+    let mut subs: Vec<String> = report.subs.iter().map(|(e, n)| format!("{:>5}  {}", n, e)).collect();
+    if report.gift_recipients > 0 {
+        subs.push(format!("{:>5}  gift recipients", report.gift_recipients));
+    }
+    section("Subs", subs);
+    section("Moderation", report.moderation.iter().map(|(e, n)| format!("{:>5}  {}", n, e)).collect());
+    section("Raids", report.raids.iter().map(|r| r.to_string()).collect());
+    section("Records", report.records.lines());
+
+    let max = report.activity.iter().map(|(_, n)| *n).max().unwrap_or(0);
+    section(
+        "Activity (messages per hour)",
+        report.activity.iter()
+        .map(|(hour, n)| format!("{}h {:>5} {}", hour, n, "#".repeat((n * HISTOGRAM_WIDTH).div_ceil(max))))
+        .collect(),
+    );
+
+    out
+}
+
+/// Write the report as `<channel>_[<tag>_]report_<timestamp>.txt` (or `.json`), or as the template says.
+pub fn save_report(dir: &Path, template: &str, report: &ChannelReport, messages: &[String], json: bool) {
+    let timestamp = file_timestamp(Some(messages));
+    let suffix = match &report.event {
+        Some(tag) => format!("{}_report", tag),
+        None => "report".to_string(),
+    };
+    let file_name = |extension| render_file_name(template, &report.stats.channel, &suffix, &timestamp, extension);
+    let (file, content) = if json {
+        match serde_json::to_string_pretty(report) {
+            Ok(json) => (dir.join(file_name("json")), json),
+            Err(e) => {
+                eprintln!("⚠️ Failed to serialize report: {}", e);
+                return;
+            }
+        }
+    } else {
+        (dir.join(file_name("txt")), format_report(report))
+    };
+    let file = file.display().to_string();
+
+    match write_file(&file, content) {
+        Ok(true) => println!("Saved report to {}", file),
+        Ok(false) => {}
+        Err(e) => eprintln!("⚠️ Failed to write {}: {}", file, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn notices_and_moderation_are_not_chat() {
+        let messages = lines(&[
+            "12:00:01 <alice> [sub/12]\nhello chat\n",
+            "12:00:05 [forsen][bob] <SUBORRESUB> hello again → tier 1 resub (12 months)",
+            "12:00:09 [forsen][carol] <SUBGIFT> carol → dave (tier 1)",
+            "12:00:10 [forsen][carol] <SUBGIFT> carol → erin (tier 1)",
+            "12:00:12 [forsen][frank] <RAID> 120 raiders from frank",
+            "13:00:00 TIMEOUT: [#forsen] troll (600s timeout)",
+            "13:00:01 USER_BANNED: [#forsen] troll",
+            "13:00:02 <alice>\nhello\n",
+        ]);
+        let report = build_report("forsen", &messages, None, None, Vec::new(), ChannelRecords::default(), 2);
+
+        assert_eq!(report.subs, vec![("SUBGIFT".to_string(), 2), ("SUBORRESUB".to_string(), 1)]);
+        assert_eq!(report.moderation, vec![("TIMEOUT".to_string(), 1), ("USER_BANNED".to_string(), 1)]);
+        assert_eq!(report.activity, vec![("12".to_string(), 1), ("13".to_string(), 1)]);
+        // "again" is in the resub text only
+        assert_eq!(report.top_words, vec![("hello".to_string(), 2), ("chat".to_string(), 1)]);
+
+        let text = format_report(&report);
+        assert!(text.contains("--- Subs ---\n    2  SUBGIFT\n    1  SUBORRESUB\n    2  gift recipients"), "{}", text);
+        assert!(text.contains("12h     1 ####"), "{}", text);
+    }
+
+    #[test]
+    fn event_names_come_from_their_own_line_format() {
+        assert_eq!(usernotice_event("12:00:05 [forsen][bob] <RAID> 12 raiders"), Some("RAID"));
+        assert_eq!(usernotice_event("12:00:05 <bob> [sub/1]\n[x] <y> z\n"), None);
+        assert_eq!(moderation_event("12:00:05 CLEARMSG: [#forsen] bob: hi"), Some("CLEARMSG"));
+        assert_eq!(moderation_event("12:00:05 <bob>\nTIMEOUT: [#forsen]\n"), None);
+    }
+}
@@ -0,0 +1,296 @@
+//! Retention of saved files: with `max_files_per_channel` or `max_total_log_bytes` set, the
+//! oldest saved files of a channel are deleted after each save (SAVE and autosave) until it
+//! is within both limits. Only files directly in `log_dir` whose names follow the
+//! `file_name_template` (by default `<channel>[_<label>]_<kind>_<date>_<HH-MM-SS>.<ext>`)
+//! or are daily files of `stream_logs` and `jsonl_log` (`<channel>_<bucket>_<YYYY-MM-DD>.log`,
+//! `.jsonl` and `.summary.txt`) are considered, and never the ones just written or still
+//! streamed to today. `--retention-dry-run` only reports what would go.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
+use chrono::{Local, NaiveDate};
+use regex::Regex;
+
+use crate::console::print_status;
+use crate::output::remove_file;
+use crate::state::LoggerState;
+use crate::vip_import::all_vips;
+
+static RETENTION_DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// `--retention-dry-run`: report the files retention would delete, delete none.
+pub fn set_retention_dry_run(dry_run: bool) {
+    RETENTION_DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+/// Limits per channel, 0 turns one off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_files: usize,
+    pub max_bytes: u64,
+}
+
+impl Limits {
+    pub fn from_state(state: &LoggerState) -> Self {
+        let settings = state.settings.lock().unwrap();
+        Limits { max_files: settings.max_files_per_channel.value, max_bytes: settings.max_total_log_bytes.value }
+    }
+
+    pub fn is_off(&self) -> bool {
+        self.max_files == 0 && self.max_bytes == 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedFile {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub modified: SystemTime,
+}
+
+/// File names `save` writes for `channel` with `file_template`, compared without case
+/// like the display names.
+fn template(channel: &str, file_template: &str) -> Regex {
+    let mut pattern = regex::escape(file_template);
+    for (field, part) in [
+        ("channel", regex::escape(channel)),
+        ("suffix", r"(?:.+_)?(?:msgs|joins|moderation|stray|report|stats)".to_string()),
+        ("weekday", r"[a-z]{2}".to_string()),
+        ("date", r"\d{2}_\d{2}_\d{4}".to_string()),
+        ("time", r"(?:\d{2}-\d{2}-\d{2}|autosave)".to_string()),
+    ] {
+        pattern = pattern.replace(&regex::escape(&format!("{{{}}}", field)), &part);
+    }
+    Regex::new(&format!(r"(?i)^{}\.(?:txt|json|jsonl|csv)(?:\.enc)?$", pattern)).expect("valid file name template")
+}
+
+/// Daily files the stream and JSON Lines sinks write for `channel`, whatever the template.
+fn streamed(channel: &str) -> Regex {
+    let pattern = format!(r"(?i)^{}_(?:msgs|joins|moderation|stray)_\d{{4}}-\d{{2}}-\d{{2}}\.(?:log|jsonl|summary\.txt)$", regex::escape(channel));
+    Regex::new(&pattern).expect("valid stream file pattern")
+}
+
+/// Whether `path` is a daily stream or JSON Lines file of `today`, possibly still open.
+fn streamed_on(path: &Path, today: NaiveDate) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let date = today.format("%Y-%m-%d");
+    name.ends_with(&format!("_{}.log", date)) || name.ends_with(&format!("_{}.jsonl", date))
+}
+
+/// Saved files of `channel` in `dir`, oldest first. A name that also starts with one of the
+/// longer `other_channels` (`forsen_fan_msgs_...` for `forsen`) belongs to that channel.
+pub fn channel_files(dir: &Path, file_template: &str, channel: &str, other_channels: &[String]) -> io::Result<Vec<SavedFile>> {
+    let template = template(channel, file_template);
+    let streamed = streamed(channel);
+    let others: Vec<String> = other_channels
+    .iter()
+    .filter(|other| other.len() > channel.len() && !other.eq_ignore_ascii_case(channel))
+    .map(|other| format!("{}_", other.to_lowercase()))
+    .collect();
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !(template.is_match(&name) || streamed.is_match(&name)) || others.iter().any(|prefix| name.to_lowercase().starts_with(prefix.as_str())) {
+            continue;
+        }
+        // Not followed, a symlink could point anywhere
+        let Ok(meta) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        files.push(SavedFile { path: entry.path(), bytes: meta.len(), modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH) });
+    }
+    files.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+    Ok(files)
+}
+
+/// The oldest of `files` (sorted oldest first) to delete for the rest to be within
+/// `limits`, skipping the ones in `keep`.
+pub fn expired<'a>(files: &'a [SavedFile], limits: Limits, keep: &[PathBuf]) -> Vec<&'a SavedFile> {
+    let mut count = files.len();
+    let mut bytes: u64 = files.iter().map(|f| f.bytes).sum();
+    let mut expired = Vec::new();
+    for file in files {
+        let too_many = limits.max_files > 0 && count > limits.max_files;
+        let too_big = limits.max_bytes > 0 && bytes > limits.max_bytes;
+        if !too_many && !too_big {
+            break;
+        }
+        if keep.contains(&file.path) {
+            continue;
+        }
+        count -= 1;
+        bytes -= file.bytes;
+        expired.push(file);
+    }
+    expired
+}
+
+/// Apply the limits to the saved files of `channel` (as in the file names) after a save
+/// that wrote `written`. Returns the number of files deleted (or reported).
+pub fn enforce_retention(state: &LoggerState, dir: &Path, channel: &str, written: &[PathBuf]) -> usize {
+    let limits = Limits::from_state(state);
+    if limits.is_off() {
+        return 0;
+    }
+    let mut others: Vec<String> = state.channels.lock().unwrap().clone();
+    others.extend(state.logs.lock().unwrap().keys().cloned());
+    others.extend(all_vips(state));
+    let file_template = state.settings.lock().unwrap().file_name_template.value.clone();
+    let files = match channel_files(dir, &file_template, channel, &others) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("⚠️ Retention: failed to list {}: {}", dir.display(), e);
+            return 0;
+        }
+    };
+
+    let today = Local::now().date_naive();
+    let mut keep = written.to_vec();
+    keep.extend(files.iter().filter(|file| streamed_on(&file.path, today)).map(|file| file.path.clone()));
+
+    let dry_run = RETENTION_DRY_RUN.load(Ordering::Relaxed);
+    let mut removed = 0;
+    for file in expired(&files, limits, &keep) {
+        if dry_run {
+            print_status(&format!("retention-dry-run: would delete {} ({} bytes)", file.path.display(), file.bytes));
+            removed += 1;
+            continue;
+        }
+        match remove_file(&file.path) {
+            Ok(true) => {
+                print_status(&format!("Retention: deleted {} ({} bytes)", file.path.display(), file.bytes));
+                removed += 1;
+            }
+            Ok(false) => {}
+            Err(e) => eprintln!("⚠️ Retention: failed to delete {}: {}", file.path.display(), e),
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::save::DEFAULT_FILE_TEMPLATE;
+
+    /// A directory of saved files, the first one the oldest.
+    fn saved_files(test: &str, names: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("retention_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let start = SystemTime::now() - Duration::from_secs(3600);
+        for (i, name) in names.iter().enumerate() {
+            let file = fs::File::create(dir.join(name)).unwrap();
+            file.set_len(100).unwrap();
+            file.set_modified(start + Duration::from_secs(60 * i as u64)).unwrap();
+        }
+        dir
+    }
+
+    fn names(files: &[&SavedFile]) -> Vec<String> {
+        files.iter().map(|f| f.path.file_name().unwrap().to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn only_files_of_the_channel_count() {
+        let dir = saved_files(
+            "template",
+            &[
+                "forsen_msgs_Sa_17_10_2026_20-00-00.txt",
+                "Forsen_joins_Sa_17_10_2026_20-00-00.txt.enc",
+                "forsen_highlights_msgs_Sa_17_10_2026_21-00-00.txt",
+                "forsen_report_Sa_17_10_2026_20-00-00.json",
+                "forsen_fan_msgs_Sa_17_10_2026_20-00-00.txt",
+                "forsen_notes.txt",
+                "xqcow_msgs_Sa_17_10_2026_20-00-00.txt",
+            ],
+        );
+        fs::create_dir(dir.join("forsen_msgs_Sa_17_10_2026_22-00-00.txt")).unwrap();
+        let files = channel_files(&dir, DEFAULT_FILE_TEMPLATE, "forsen", &["forsen_fan".to_string()]).unwrap();
+        let found: Vec<&SavedFile> = files.iter().collect();
+        assert_eq!(
+            names(&found),
+            [
+                "forsen_msgs_Sa_17_10_2026_20-00-00.txt",
+                "Forsen_joins_Sa_17_10_2026_20-00-00.txt.enc",
+                "forsen_highlights_msgs_Sa_17_10_2026_21-00-00.txt",
+                "forsen_report_Sa_17_10_2026_20-00-00.json",
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dated = template("forsen", "{channel}_{date}_{time}_{suffix}");
+        assert!(streamed("forsen").is_match("Forsen_joins_2026-10-17.log"));
+        assert!(!streamed("forsen").is_match("forsen_fan_msgs_2026-10-17.jsonl"));
+        assert!(dated.is_match("forsen_17_10_2026_20-00-00_msgs.json"));
+        assert!(!dated.is_match("forsen_msgs_Sa_17_10_2026_20-00-00.txt"));
+    }
+
+    #[test]
+    fn oldest_go_first_and_new_files_stay() {
+        let dir = saved_files(
+            "limits",
+            &[
+                "forsen_msgs_Fr_16_10_2026_20-00-00.txt",
+                "forsen_msgs_Sa_17_10_2026_20-00-00.txt",
+                "forsen_msgs_So_18_10_2026_20-00-00.txt",
+                "forsen_msgs_Mo_19_10_2026_20-00-00.txt",
+            ],
+        );
+        let files = channel_files(&dir, DEFAULT_FILE_TEMPLATE, "forsen", &[]).unwrap();
+        let by_count = expired(&files, Limits { max_files: 2, max_bytes: 0 }, &[]);
+        assert_eq!(names(&by_count), ["forsen_msgs_Fr_16_10_2026_20-00-00.txt", "forsen_msgs_Sa_17_10_2026_20-00-00.txt"]);
+        let by_size = expired(&files, Limits { max_files: 0, max_bytes: 350 }, &[]);
+        assert_eq!(names(&by_size), ["forsen_msgs_Fr_16_10_2026_20-00-00.txt"]);
+        // The file just written is never deleted, even if it is the oldest
+        let keep = [files[0].path.clone()];
+        let kept = expired(&files, Limits { max_files: 3, max_bytes: 0 }, &keep);
+        assert_eq!(names(&kept), ["forsen_msgs_Sa_17_10_2026_20-00-00.txt"]);
+        assert!(expired(&files, Limits::default(), &[]).is_empty());
+
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().max_files_per_channel.value = 1;
+        assert_eq!(enforce_retention(&state, &dir, "forsen", &[]), 3);
+        assert_eq!(channel_files(&dir, DEFAULT_FILE_TEMPLATE, "forsen", &[]).unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn streamed_daily_files_expire_but_today_stays() {
+        let today = Local::now().date_naive();
+        let today_log = format!("forsen_msgs_{}.log", today.format("%Y-%m-%d"));
+        let today_jsonl = format!("forsen_msgs_{}.jsonl", today.format("%Y-%m-%d"));
+        let dir = saved_files(
+            "streamed",
+            &[
+                &today_log,
+                &today_jsonl,
+                "forsen_msgs_2020-01-01.log",
+                "forsen_msgs_2020-01-01.summary.txt",
+                "forsen_msgs_2020-01-01.jsonl",
+                "forsen_joins_2020-01-02.log",
+                "forsen_msgs_Sa_17_10_2026_20-00-00.txt",
+            ],
+        );
+        let files = channel_files(&dir, DEFAULT_FILE_TEMPLATE, "forsen", &[]).unwrap();
+        assert_eq!(files.len(), 7);
+
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().max_files_per_channel.value = 1;
+        assert_eq!(enforce_retention(&state, &dir, "forsen", &[]), 5);
+        let left = channel_files(&dir, DEFAULT_FILE_TEMPLATE, "forsen", &[]).unwrap();
+        let left: Vec<&SavedFile> = left.iter().collect();
+        assert_eq!(names(&left), [today_log, today_jsonl]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
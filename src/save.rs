@@ -0,0 +1,699 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Local;
+use serde::Serialize;
+
+use crate::buckets::{line_texts, log_texts, LogLine, LOG_BUCKETS};
+use crate::encryption::{encrypts, LogKey, SEALED_EXTENSION};
+use crate::membership::flush_counts;
+use crate::csv_log::{format_csv_join_log, format_csv_log};
+use crate::json_log::format_json_log;
+use crate::jsonl_log::format_jsonl_log;
+use crate::output::{create_dir, replace_file, write_file};
+use crate::pauses::{find_pauses, format_pauses, DEFAULT_PAUSE_MINUTES};
+use crate::retention::enforce_retention;
+use crate::sqlite_log::session_lines;
+use crate::state::{data_file, date_stamp, LoggerState, STARTUP_DATE};
+use crate::stream::{finalize_stream, is_streamed};
+use crate::stats::ChannelStats;
+use crate::tags::tagged;
+
+/// Default of `log_dir`, where saved logs, stats and reports are written: `logs` in the
+/// directory of channels.txt.
+pub fn default_log_dir() -> String {
+    data_file("logs").display().to_string()
+}
+
+/// Appended to the snapshots of `auto_save_interval`.
+pub const AUTOSAVE_EXTENSION: &str = ".autosave";
+
+/// `{time}` of the files of `daily_autosave`.
+pub const DAILY_AUTOSAVE_TIME: &str = "autosave";
+
+/// Default of `file_name_template`, `forsen_msgs_Sa_17_10_2026_20-15-03.txt`.
+pub const DEFAULT_FILE_TEMPLATE: &str = "{channel}_{suffix}_{weekday}_{date}_{time}";
+
+/// Placeholders of `file_name_template`. `{suffix}` is the kind of file ("msgs", "joins",
+/// "stats", ...) and the custom name of `SAVE <channel> <name>`.
+pub const TEMPLATE_FIELDS: &[&str] = &["channel", "suffix", "weekday", "date", "time"];
+
+/// Check a `file_name_template`: only known placeholders, `{channel}` and `{suffix}` so
+/// the files of different channels and kinds can't collide, and no directories.
+/// A trailing `.txt` is dropped, the extension follows the format.
+pub fn parse_file_template(template: &str) -> Result<String, String> {
+    let template = template.strip_suffix(".txt").unwrap_or(template);
+    if template.contains(['/', '\\']) || template.contains("..") {
+        return Err(format!("file_name_template '{}' must be a file name, not a path", template));
+    }
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!("file_name_template '{}': unclosed {{", template));
+        };
+        let field = &rest[start + 1..start + end];
+        if !TEMPLATE_FIELDS.contains(&field) {
+            return Err(format!("file_name_template: unknown placeholder {{{}}} ({})", field, TEMPLATE_FIELDS.join(", ")));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    for required in ["{channel}", "{suffix}"] {
+        if !template.contains(required) {
+            return Err(format!("file_name_template '{}' needs {}", template, required));
+        }
+    }
+    Ok(template.to_string())
+}
+
+/// File name from the template and a `file_timestamp` ("Sa_17_10_2026_20-15-03").
+pub fn render_file_name(template: &str, channel: &str, suffix: &str, timestamp: &str, extension: &str) -> String {
+    let (weekday, rest) = timestamp.split_once('_').unwrap_or(("", timestamp));
+    let (date, time) = rest.rsplit_once('_').unwrap_or((rest, ""));
+    let name = template
+    .replace("{channel}", channel)
+    .replace("{suffix}", suffix)
+    .replace("{weekday}", weekday)
+    .replace("{date}", date)
+    .replace("{time}", time);
+    format!("{}.{}", name, extension)
+}
+
+/// `SAVE <channel> <name>`: the name without path separators and dots, so it can't
+/// point outside `log_dir`. `None` if nothing is left.
+pub fn sanitize_custom_name(name: &str) -> Option<String> {
+    let name: String = name.chars().filter(|c| !matches!(c, '/' | '\\' | '.') && !c.is_control()).collect();
+    let name = name.trim_matches('_');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// The `log_dir` setting, created if missing. `None` once the reason is printed.
+pub fn log_dir(state: &LoggerState) -> Option<PathBuf> {
+    let dir = PathBuf::from(&state.settings.lock().unwrap().log_dir.value);
+    match create_dir(&dir) {
+        Ok(_) => Some(dir),
+        Err(e) => {
+            eprintln!("⚠️ Failed to create the log directory {}: {}", dir.display(), e);
+            None
+        }
+    }
+}
+
+/// What goes above the numbered lines of a saved message log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeaderFormat {
+    /// Build info, channel and all event counts.
+    #[default]
+    Full,
+    /// `# <channel>: N messages (M chatters)`
+    Minimal,
+    /// Just the numbered lines.
+    None,
+}
+
+impl FromStr for HeaderFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(HeaderFormat::Full),
+            "minimal" => Ok(HeaderFormat::Minimal),
+            "none" => Ok(HeaderFormat::None),
+            other => Err(format!("unknown log header format '{}' (full, minimal, none)", other)),
+        }
+    }
+}
+
+impl fmt::Display for HeaderFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HeaderFormat::Full => "full",
+            HeaderFormat::Minimal => "minimal",
+            HeaderFormat::None => "none",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// File format of the saved message log, `SAVE ... --format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Header and numbered lines.
+    #[default]
+    Text,
+    /// One document with a summary and the entries split into fields, see `json_log`.
+    Json,
+    /// RFC 4180 CSV, one row per entry, see `csv_log`. The join log gets one too.
+    Csv,
+    /// One JSON object per line, see `jsonl_log`.
+    Jsonl,
+}
+
+impl LogFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LogFormat::Text => "txt",
+            LogFormat::Json => "json",
+            LogFormat::Csv => "csv",
+            LogFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogFormat::Text => "text",
+            format => format.extension(),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" | "txt" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            "csv" => Ok(LogFormat::Csv),
+            "jsonl" => Ok(LogFormat::Jsonl),
+            other => Err(format!("unknown log format '{}' (text, json, csv, jsonl)", other)),
+        }
+    }
+}
+
+/// How the autosave names its files, `autosave_files`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutosaveFiles {
+    /// A new file each time, like SAVE.
+    #[default]
+    Timestamped,
+    /// One file per channel, log and day, replaced each time, see `daily_autosave`.
+    Daily,
+}
+
+impl fmt::Display for AutosaveFiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutosaveFiles::Timestamped => write!(f, "timestamped"),
+            AutosaveFiles::Daily => write!(f, "daily"),
+        }
+    }
+}
+
+impl FromStr for AutosaveFiles {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "timestamped" => Ok(AutosaveFiles::Timestamped),
+            "daily" => Ok(AutosaveFiles::Daily),
+            other => Err(format!("unknown autosave files '{}' (timestamped, daily)", other)),
+        }
+    }
+}
+
+/// Remember the display name of `channel` from a message its broadcaster sent.
+pub fn record_channel_display_name(state: &LoggerState, channel: &str, sender_login: &str, sender_name: &str) {
+    // Localized names (other scripts) would make the files hard to find, only capitalization is taken
+    if sender_login == channel && sender_name.to_lowercase() == channel {
+        state.channel_display_names.lock().unwrap().insert(channel.to_string(), sender_name.to_string());
+    }
+}
+
+/// Channel part of saved file names: the display name with `use_display_names`, if the
+/// broadcaster has written something this session, otherwise the login.
+pub fn file_channel_name(state: &LoggerState, channel: &str) -> String {
+    if !state.settings.lock().unwrap().use_display_names.value {
+        return channel.to_string();
+    }
+    state.channel_display_names.lock().unwrap().get(channel).cloned().unwrap_or_else(|| channel.to_string())
+}
+
+/// "<date>_<HH-MM-SS>" part of the file names, taken from the first message of the channel.
+pub fn file_timestamp<L: AsRef<str>>(messages: Option<&[L]>) -> String {
+    // --- NEW LOGIC: Get time from the first log entry ---
+    let time_part = messages
+    // Find the first message in the log vector for this channel
+    .and_then(|messages| messages.iter().map(AsRef::as_ref).find(|line| line.contains("<") && line.contains(">")))
+    // Parse the timestamp (HH:MM:SS) from the beginning of the line
+    .map(|first_line| first_line[0..8].replace(':', "-")) // "HH:MM:SS" -> "HH-MM-SS"
+    // If no messages exist for the channel, use the current time as a fallback
+    .unwrap_or_else(|| Local::now().format("%H-%M-%S").to_string());
+
+    // Combine the static date part with the parsed time part.
+    format!("{}_{}", *STARTUP_DATE, time_part)
+}
+
+/// Open `file` with the default application of the desktop, without waiting for it.
+pub fn open_file(file: &str) {
+    // Linux and the BSDs
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = Command::new("xdg-open");
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        // `start` is built into cmd; the empty argument is the window title
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+
+    match command.arg(file).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+        // Reap the opener in the background so it doesn't linger as a zombie
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => eprintln!("⚠️ Failed to open {}: {}", file, e),
+    }
+}
+
+/// STATS --save: the stats plus the `TAG` of the channel.
+#[derive(Serialize)]
+struct StatsExport<'a> {
+    #[serde(flatten)]
+    stats: &'a ChannelStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'a str>,
+}
+
+/// Write the STATS of a channel as `<channel>_[<tag>_]stats_<timestamp>.json`, or as the template says.
+pub fn save_stats_json(dir: &Path, template: &str, stats: &ChannelStats, messages: &[String], tag: Option<&str>) {
+    let suffix = match tag {
+        Some(tag) => format!("{}_stats", tag),
+        None => "stats".to_string(),
+    };
+    let file = dir.join(render_file_name(template, &stats.channel, &suffix, &file_timestamp(Some(messages)), "json")).display().to_string();
+    match serde_json::to_string_pretty(&StatsExport { stats, event: tag }) {
+        Ok(json) => match write_file(&file, json) {
+            Ok(true) => println!("Saved stats to {}", file),
+            Ok(false) => {}
+            Err(e) => eprintln!("⚠️ Failed to write {}: {}", file, e),
+        },
+        Err(e) => eprintln!("⚠️ Failed to serialize stats: {}", e),
+    }
+}
+
+pub fn save_logs(
+    target: &str,
+    state: &LoggerState,
+    // The `first_message_times` parameter is now gone
+    custom_name: Option<&str>,
+    // Append the PAUSES list to the message logs
+    pauses: bool,
+    format: LogFormat,
+) {
+    // Pending counts-only minutes belong into this save
+    flush_counts(state);
+
+    let save_all = target.eq_ignore_ascii_case("ALL");
+    let targets: Vec<String> = if save_all {
+        logged_channels(state)
+    } else {
+        vec![target.to_string()]
+    };
+
+    let open = state.settings.lock().unwrap().open_after_save.value;
+    let (written, skipped) = save_channels(&targets, state, FileNaming::Timestamped(custom_name), !save_all, open, pauses, format);
+
+    if save_all && skipped > 0 {
+        println!("dry-run: skipped {} files", skipped);
+    } else if save_all {
+        print_save_summary(&written);
+    }
+}
+
+/// Channels with lines in any of the logs, what `SAVE ALL` saves.
+pub fn logged_channels(state: &LoggerState) -> Vec<String> {
+    let mut channels = BTreeSet::new();
+    for bucket in LOG_BUCKETS {
+        channels.extend((bucket.store)(state).lock().unwrap().keys().cloned());
+    }
+    channels.into_iter().collect()
+}
+
+/// The quick-save hotkey: `SAVE ALL <label>` without the per-file output. Returns the
+/// number of files and of channels written.
+pub fn quick_save(state: &LoggerState, label: &str) -> (usize, usize) {
+    flush_counts(state);
+    let (written, _) = save_channels(&logged_channels(state), state, FileNaming::Timestamped(Some(label)), false, false, false, LogFormat::Text);
+    let channels: BTreeSet<&String> = written.iter().map(|(channel, ..)| channel).collect();
+    (written.len(), channels.len())
+}
+
+/// Autosave of one channel: like `SAVE <channel>`, without output, into files named as
+/// `files` says. Returns the entries written.
+pub fn autosave_channel(channel: &str, state: &LoggerState, files: AutosaveFiles) -> u64 {
+    flush_counts(state);
+    let naming = match files {
+        AutosaveFiles::Timestamped => FileNaming::Timestamped(None),
+        AutosaveFiles::Daily => FileNaming::Daily,
+    };
+    let (written, _) = save_channels(&[channel.to_string()], state, naming, false, false, false, LogFormat::Text);
+    written.iter().map(|(_, _, count, _)| count).sum()
+}
+
+/// `AUTOSAVE NOW`: every channel like `SAVE ALL`, but into one file per channel, log and day
+/// (`{time}` is "autosave"), replaced each time. Channels a SAVE is writing just now are
+/// left for the next round. Returns the channels and the entries written.
+pub fn daily_autosave(state: &LoggerState) -> (usize, u64) {
+    flush_counts(state);
+    let (written, _) = save_channels(&logged_channels(state), state, FileNaming::Daily, false, false, false, LogFormat::Text);
+    let channels: BTreeSet<&String> = written.iter().map(|(channel, ..)| channel).collect();
+    let entries = written.iter().map(|(_, _, count, _)| count).sum();
+    (channels.len(), entries)
+}
+
+/// How the files of a save are named.
+#[derive(Debug, Clone, Copy)]
+enum FileNaming<'a> {
+    /// Session start date and first message time, with the custom name of `SAVE <channel> <name>`.
+    Timestamped(Option<&'a str>),
+    /// Today's date and "autosave" for the time, see `daily_autosave`. Skips channels a
+    /// save is writing instead of waiting for them.
+    Daily,
+}
+
+/// `channel` marked as being saved until dropped, so the timed autosave keeps off it.
+pub struct SaveGuard<'a> {
+    state: &'a LoggerState,
+    channel: String,
+}
+
+impl<'a> SaveGuard<'a> {
+    /// `None` if a save of `channel` is running.
+    pub fn try_begin(state: &'a LoggerState, channel: &str) -> Option<Self> {
+        state.saving.lock().unwrap().insert(channel.to_string()).then(|| SaveGuard { state, channel: channel.to_string() })
+    }
+
+    /// Waits for a running save of `channel` to finish.
+    pub fn begin(state: &'a LoggerState, channel: &str) -> Self {
+        let mut saving = state.saving.lock().unwrap();
+        while saving.contains(channel) {
+            saving = state.save_finished.wait(saving).unwrap();
+        }
+        saving.insert(channel.to_string());
+        SaveGuard { state, channel: channel.to_string() }
+    }
+}
+
+impl Drop for SaveGuard<'_> {
+    fn drop(&mut self) {
+        self.state.saving.lock().unwrap().remove(&self.channel);
+        self.state.save_finished.notify_all();
+    }
+}
+
+/// Encrypted channels are only written sealed, never in plaintext: their key, `Err` once
+/// the reason is printed if there is none.
+fn sealing_key(state: &LoggerState, channel: &str) -> Result<Option<Arc<LogKey>>, ()> {
+    if !encrypts(state, channel) {
+        return Ok(None);
+    }
+    let key = state.log_key.lock().unwrap().clone();
+    if key.is_none() {
+        eprintln!("⚠️ Not saving {}: it is encrypted, but no passphrase was given", channel);
+        return Err(());
+    }
+    Ok(key)
+}
+
+/// `auto_save_interval`: every bucket of `channel` as `<channel>_<bucket>.txt.autosave` in
+/// `log_dir`, overwriting the previous snapshot. Unlike a save, nothing counts as saved
+/// afterwards and retention leaves the files alone. Returns the files written.
+pub fn snapshot_channel(channel: &str, state: &LoggerState) -> usize {
+    let Ok(key) = sealing_key(state, channel) else {
+        return 0;
+    };
+    let mut dir: Option<PathBuf> = None;
+    let mut written = 0;
+    for bucket in LOG_BUCKETS {
+        let lines = log_texts((bucket.store)(state), channel);
+        let Some(lines) = lines.filter(|lines| !lines.is_empty()) else {
+            continue;
+        };
+        if dir.is_none() {
+            dir = log_dir(state);
+        }
+        let Some(dir) = &dir else {
+            return written;
+        };
+        let mut content = if bucket.bom { vec![0xEF, 0xBB, 0xBF] } else { Vec::new() };
+        content.extend_from_slice((bucket.format)(channel, &lines, state).as_bytes());
+        let mut name = format!("{}_{}.txt", file_channel_name(state, channel), bucket.name);
+        if let Some(key) = &key {
+            content = key.seal(&content);
+            name.push_str(SEALED_EXTENSION);
+        }
+        let file = dir.join(format!("{}{}", name, AUTOSAVE_EXTENSION));
+        match write_file(&file, &content) {
+            Ok(true) => written += 1,
+            Ok(false) => {}
+            Err(e) => eprintln!("⚠️ Failed to write {}: {}", file.display(), e),
+        }
+    }
+    written
+}
+
+/// Write the log buckets of `targets`; returns (channel, bucket, entries, file) of the
+/// written files and the number skipped by dry-run.
+fn save_channels(
+    targets: &[String],
+    state: &LoggerState,
+    naming: FileNaming,
+    print_each: bool,
+    open: bool,
+    pauses: bool,
+    format: LogFormat,
+) -> (Vec<(String, &'static str, u64, String)>, usize) {
+    let mut written = Vec::new();
+    let mut skipped = 0;
+    // Created with the first file, a save without anything to write leaves no directory
+    let mut dir: Option<PathBuf> = None;
+    let mut stream_flushed = false;
+
+    for chan in targets {
+        let chan = chan.clone();
+        // Other saves wait for a running save of the channel, the daily autosave skips it
+        let _guard = match naming {
+            FileNaming::Daily => match SaveGuard::try_begin(state, &chan) {
+                Some(guard) => guard,
+                None => continue,
+            },
+            FileNaming::Timestamped(_) => SaveGuard::begin(state, &chan),
+        };
+        let Ok(key) = sealing_key(state, &chan) else {
+            continue;
+        };
+        // Before the snapshots, lines logged while writing stay unsaved
+        state.unsaved.lock().unwrap().saved(&chan);
+        let timestamp = match naming {
+            FileNaming::Daily => format!("{}_{}", date_stamp(), DAILY_AUTOSAVE_TIME),
+            FileNaming::Timestamped(_) => file_timestamp(state.logs.lock().unwrap().get(&chan).map(Vec::as_slice)),
+        };
+        let custom_name = match naming {
+            FileNaming::Timestamped(name) => name,
+            FileNaming::Daily => None,
+        };
+        // The TAG goes after the custom name
+        let tag = state.session_tags.lock().unwrap().for_channel(&chan).map(str::to_string);
+        let label = match custom_name.and_then(sanitize_custom_name) {
+            Some(name) => Some(tagged(&name, tag.as_deref())),
+            None => tag,
+        };
+        let template = state.settings.lock().unwrap().file_name_template.value.clone();
+
+        for bucket in LOG_BUCKETS {
+            // Snapshot, the handlers keep logging while the file is formatted and written
+            let log = (bucket.store)(state).lock().unwrap().get(&chan).cloned();
+            let log = match log {
+                // Rows from the database have only their text
+                Some(in_memory) if bucket.name == "msgs" => Some(match session_lines(state, &chan, &in_memory) {
+                    Some(all) => all.into_iter().map(LogLine::Text).collect(),
+                    None => in_memory,
+                }),
+                log => log,
+            };
+            let Some(log) = log.filter(|log| !log.is_empty()) else {
+                continue;
+            };
+            let lines = line_texts(&log);
+
+            if dir.is_none() {
+                dir = log_dir(state);
+            }
+            let Some(dir) = &dir else {
+                return (written, skipped);
+            };
+            let count = (bucket.count)(&lines);
+            if is_streamed(state, &chan) {
+                if !stream_flushed {
+                    state.sinks.flush();
+                    stream_flushed = true;
+                }
+                let file = finalize_stream(state, dir, &chan, bucket.name, &lines);
+                if print_each {
+                    println!("{} are streamed to {}, flushed", bucket.label, file);
+                }
+                if bucket.name == "msgs" {
+                    state.last_saved.lock().unwrap().insert(chan.clone(), file.clone());
+                }
+                written.push((chan.clone(), bucket.name, count, file));
+                continue;
+            }
+            // The other logs have no JSON format yet, and only the join log a CSV one
+            let format = match (bucket.name, format) {
+                ("msgs", _) | ("joins", LogFormat::Csv) => format,
+                _ => LogFormat::Text,
+            };
+            let mut file = bucket.file_name(dir, &template, &file_channel_name(state, &chan), label.as_deref(), &timestamp, format.extension());
+            let mut content = if bucket.bom && format == LogFormat::Text { vec![0xEF, 0xBB, 0xBF] } else { Vec::new() };
+            match format {
+                LogFormat::Text => content.extend_from_slice((bucket.format)(&chan, &lines, state).as_bytes()),
+                LogFormat::Json => content.extend_from_slice(format_json_log(&chan, &lines, state).as_bytes()),
+                LogFormat::Csv if bucket.name == "joins" => content.extend_from_slice(format_csv_join_log(&chan, &lines).as_bytes()),
+                LogFormat::Csv => content.extend_from_slice(format_csv_log(&chan, &lines).as_bytes()),
+                LogFormat::Jsonl => content.extend_from_slice(format_jsonl_log(&chan, &log).as_bytes()),
+            }
+            if pauses && bucket.name == "msgs" && format == LogFormat::Text {
+                let found = find_pauses(&lines, Duration::from_secs(DEFAULT_PAUSE_MINUTES * 60));
+                content.extend_from_slice(format!("\n\n{}\n", format_pauses(&chan, &found, DEFAULT_PAUSE_MINUTES)).as_bytes());
+            }
+            if let Some(key) = &key {
+                content = key.seal(&content);
+                file.push_str(SEALED_EXTENSION);
+            }
+
+            let result = match naming {
+                FileNaming::Daily => replace_file(&file, &content),
+                FileNaming::Timestamped(_) => write_file(&file, &content),
+            };
+            match result {
+                Ok(true) => {}
+                Ok(false) => {
+                    skipped += 1;
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("⚠️ Failed to write {}: {}", file, e);
+                    continue;
+                }
+            }
+            if print_each {
+                println!("Saved {} {} to {}", count, bucket.label, file);
+            }
+
+            if bucket.name == "msgs" {
+                state.last_saved.lock().unwrap().insert(chan.clone(), file.clone());
+                if open && key.is_none() {
+                    open_file(&file);
+                }
+            }
+            written.push((chan.clone(), bucket.name, count, file));
+        }
+
+        let new_files: Vec<PathBuf> = written.iter().filter(|(c, ..)| *c == chan).map(|(.., file)| PathBuf::from(file)).collect();
+        if let (Some(dir), false) = (&dir, new_files.is_empty()) {
+            enforce_retention(state, dir, &file_channel_name(state, &chan), &new_files);
+        }
+    }
+    if !written.is_empty() {
+        *state.last_save.lock().unwrap() = Some(Local::now());
+    }
+    (written, skipped)
+}
+
+/// One row per written file, columns aligned.
+fn print_save_summary(written: &[(String, &str, u64, String)]) {
+    if written.is_empty() {
+        println!("Nothing to save");
+        return;
+    }
+    let channel_width = written.iter().map(|(c, ..)| c.chars().count()).max().unwrap_or(0);
+    let bucket_width = written.iter().map(|(_, b, ..)| b.len()).max().unwrap_or(0);
+    let count_width = written.iter().map(|(_, _, n, _)| n.to_string().len()).max().unwrap_or(0);
+    for (channel, bucket, count, file) in written {
+        println!("{:<cw$}  {:<bw$}  {:>nw$}  {}", channel, bucket, count, file, cw = channel_width, bw = bucket_width, nw = count_width);
+    }
+    println!("Saved {} files", written.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use crate::state::DATA_DIR;
+
+    #[test]
+    fn a_save_waits_for_the_running_one() {
+        let state = LoggerState::default();
+        let running = SaveGuard::begin(&state, "forsen");
+        assert!(SaveGuard::try_begin(&state, "forsen").is_none());
+        let waiting = {
+            let state = state.clone();
+            std::thread::spawn(move || {
+                let _guard = SaveGuard::begin(&state, "forsen");
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!waiting.is_finished());
+        drop(running);
+        waiting.join().unwrap();
+        assert!(state.saving.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn logs_default_to_the_data_dir() {
+        assert_eq!(Settings::default().log_dir.value, DATA_DIR.join("logs").display().to_string());
+        let state = LoggerState::default();
+        assert_eq!(state.settings.lock().unwrap().log_dir.value, default_log_dir());
+    }
+
+    #[test]
+    fn display_name_only_with_the_setting() {
+        let state = LoggerState::default();
+        record_channel_display_name(&state, "janistantv", "janistantv", "JanisTanTV");
+        assert_eq!(file_channel_name(&state, "janistantv"), "janistantv");
+        state.settings.lock().unwrap().use_display_names.value = true;
+        assert_eq!(file_channel_name(&state, "janistantv"), "JanisTanTV");
+        assert_eq!(file_channel_name(&state, "forsen"), "forsen");
+    }
+
+    #[test]
+    fn file_names_follow_the_template() {
+        let default = parse_file_template(DEFAULT_FILE_TEMPLATE).unwrap();
+        assert_eq!(render_file_name(&default, "forsen", "msgs", "Sa_17_10_2026_20-15-03", "txt"), "forsen_msgs_Sa_17_10_2026_20-15-03.txt");
+        let dated = parse_file_template("{channel}_{date}_{time}_{suffix}.txt").unwrap();
+        assert_eq!(render_file_name(&dated, "forsen", "joins", "Sa_17_10_2026_20-15-03", "json"), "forsen_17_10_2026_20-15-03_joins.json");
+
+        assert!(parse_file_template("{channel}_{date}").is_err());
+        assert!(parse_file_template("{channel}_{suffix}_{year}").is_err());
+        assert!(parse_file_template("../{channel}_{suffix}").is_err());
+        assert!(parse_file_template("logs/{channel}_{suffix}").is_err());
+    }
+
+    #[test]
+    fn custom_names_stay_in_the_log_dir() {
+        assert_eq!(sanitize_custom_name("highlights").as_deref(), Some("highlights"));
+        assert_eq!(sanitize_custom_name("../../etc/passwd").as_deref(), Some("etcpasswd"));
+        assert_eq!(sanitize_custom_name("day2.final").as_deref(), Some("day2final"));
+        assert_eq!(sanitize_custom_name("..\\.."), None);
+    }
+
+    #[test]
+    fn only_the_broadcaster_names_the_channel() {
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().use_display_names.value = true;
+        record_channel_display_name(&state, "janistantv", "someone", "Someone");
+        record_channel_display_name(&state, "xqcow", "xqcow", "エックス");
+        assert_eq!(file_channel_name(&state, "janistantv"), "janistantv");
+        assert_eq!(file_channel_name(&state, "xqcow"), "xqcow");
+    }
+}
@@ -0,0 +1,221 @@
+//! `SAY <channel> <message>`: outgoing chat messages go through a queue that spaces them
+//! to Twitch's limit for normal users (20 per 30 seconds), on top of the client's own
+//! connection handling. Each message gets a number; its confirmation, and a
+//! `msg_ratelimit`/`msg_duplicate` NOTICE rejecting it, are printed with that number.
+//! `SAYQUEUE` lists the pending messages, `SAYQUEUE CLEAR` drops them.
+//!
+//! Sending needs a login: `TWITCH_LOGIN` and `TWITCH_OAUTH_TOKEN` in the environment,
+//! otherwise the logger connects anonymously and SAY refuses.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use owo_colors::OwoColorize;
+use tokio::sync::Notify;
+use twitch_irc::login::{LoginCredentials, StaticLoginCredentials};
+use twitch_irc::transport::Transport;
+use twitch_irc::TwitchIRCClient;
+
+use crate::console::print_status;
+use crate::rate_limiter::TokenBucket;
+use crate::state::LoggerState;
+
+/// Twitch drops messages beyond 20 per 30 seconds of a normal user.
+pub const SAY_CAPACITY: u32 = 20;
+pub const SAY_RATE: f64 = 20.0 / 30.0;
+
+/// NOTICEs that reject the message just sent.
+const REJECTION_NOTICES: &[&str] = &["msg_ratelimit", "msg_duplicate", "msg_slowmode"];
+/// How long after sending a rejection is put down to the message.
+const REJECTION_WINDOW: Duration = Duration::from_secs(10);
+/// Twitch only compares with the previous message of the last 30 seconds.
+const DUPLICATE_WINDOW: Duration = Duration::from_secs(30);
+/// Appended to a repeated message with `say_duplicate_workaround`, invisible in chat.
+const DUPLICATE_SUFFIX: &str = " \u{e0000}";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outgoing {
+    pub id: u64,
+    pub channel: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+struct Sent {
+    message: Outgoing,
+    at: Instant,
+}
+
+/// Pending and recently sent messages, shared by SAY and the sending task.
+#[derive(Clone, Default)]
+pub struct SayQueue {
+    pending: Arc<Mutex<VecDeque<Outgoing>>>,
+    /// Last message sent per channel, for NOTICEs and duplicates.
+    sent: Arc<Mutex<HashMap<String, Sent>>>,
+    wake: Arc<Notify>,
+    next_id: Arc<AtomicU64>,
+    /// Login the messages are sent as, `None` when connected anonymously.
+    login: Arc<Mutex<Option<String>>>,
+}
+
+impl SayQueue {
+    pub fn set_login(&self, login: Option<String>) {
+        *self.login.lock().unwrap() = login;
+    }
+
+    pub fn login(&self) -> Option<String> {
+        self.login.lock().unwrap().clone()
+    }
+
+    /// Queue a message; returns its number.
+    pub fn push(&self, channel: &str, text: &str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.pending.lock().unwrap().push_back(Outgoing { id, channel: channel.to_string(), text: text.to_string() });
+        self.wake.notify_one();
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn pending(&self) -> Vec<Outgoing> {
+        self.pending.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Drop the pending messages; returns how many there were.
+    pub fn clear(&self) -> usize {
+        let mut pending = self.pending.lock().unwrap();
+        let dropped = pending.len();
+        pending.clear();
+        dropped
+    }
+
+    /// Take the next message and remember it as sent, with the text to send.
+    fn take_next(&self, workaround: bool, now: Instant) -> Option<Outgoing> {
+        let mut message = self.pending.lock().unwrap().pop_front()?;
+        let mut sent = self.sent.lock().unwrap();
+        if workaround {
+            let previous = sent
+            .get(&message.channel)
+            .filter(|s| now.saturating_duration_since(s.at) < DUPLICATE_WINDOW)
+            .map(|s| s.message.text.as_str());
+            message.text = avoid_duplicate(&message.text, previous);
+        }
+        sent.insert(message.channel.clone(), Sent { message: message.clone(), at: now });
+        Some(message)
+    }
+
+    /// The message a rejection NOTICE in `channel` is about: the last one sent there, if recent.
+    fn rejected(&self, channel: &str, now: Instant) -> Option<Outgoing> {
+        self.sent
+        .lock()
+        .unwrap()
+        .get(channel)
+        .filter(|s| now.saturating_duration_since(s.at) < REJECTION_WINDOW)
+        .map(|s| s.message.clone())
+    }
+}
+
+/// `text`, made different from the `previous` message so Twitch doesn't reject it as a
+/// duplicate. Alternates between with and without the suffix.
+pub fn avoid_duplicate(text: &str, previous: Option<&str>) -> String {
+    match previous {
+        Some(previous) if previous == text => format!("{}{}", text, DUPLICATE_SUFFIX),
+        _ => text.to_string(),
+    }
+}
+
+/// Credentials from `TWITCH_LOGIN` and `TWITCH_OAUTH_TOKEN`, anonymous without them.
+pub fn credentials_from_env() -> StaticLoginCredentials {
+    let login = std::env::var("TWITCH_LOGIN").ok().map(|l| l.trim().to_lowercase()).filter(|l| !l.is_empty());
+    let token = std::env::var("TWITCH_OAUTH_TOKEN").ok().map(|t| t.trim().trim_start_matches("oauth:").to_string()).filter(|t| !t.is_empty());
+    match (login, token) {
+        (Some(login), Some(token)) => StaticLoginCredentials::new(login, Some(token)),
+        _ => StaticLoginCredentials::anonymous(),
+    }
+}
+
+/// A NOTICE rejecting a SAY message: printed with the message it is about. Returns
+/// whether it was one.
+pub fn check_say_notice(state: &LoggerState, channel: Option<&str>, message_id: Option<&str>) -> bool {
+    let (Some(channel), Some(id)) = (channel, message_id) else {
+        return false;
+    };
+    if !REJECTION_NOTICES.contains(&id) {
+        return false;
+    }
+    let Some(message) = state.say_queue.rejected(channel, Instant::now()) else {
+        return false;
+    };
+    print_status(&format!("{} SAY #{} to #{} was rejected ({}): {}", "✗".red(), message.id, channel, id, message.text));
+    true
+}
+
+/// Send the queued messages as the rate allows.
+pub fn spawn_say_queue<T: Transport, L: LoginCredentials>(state: LoggerState, client: TwitchIRCClient<T, L>) {
+    tokio::spawn(async move {
+        let queue = state.say_queue.clone();
+        let mut bucket = TokenBucket::new(SAY_CAPACITY, SAY_RATE);
+        loop {
+            if queue.is_empty() {
+                queue.wake.notified().await;
+                continue;
+            }
+            // Wait for the token before taking the message, so SAYQUEUE CLEAR still gets it
+            if !bucket.try_consume() {
+                tokio::time::sleep(bucket.retry_after()).await;
+                continue;
+            }
+            let workaround = state.settings.lock().unwrap().say_duplicate_workaround.value;
+            let Some(message) = queue.take_next(workaround, Instant::now()) else {
+                continue;
+            };
+            match client.say(message.channel.clone(), message.text.clone()).await {
+                Ok(()) => print_status(&format!("{} SAY #{} sent to #{} ({} queued)", "✓".green(), message.id, message.channel, queue.len())),
+                Err(e) => print_status(&format!("{} SAY #{} to #{} failed: {}", "✗".red(), message.id, message.channel, e)),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_numbered_and_cleared() {
+        let queue = SayQueue::default();
+        assert_eq!(queue.push("forsen", "hi"), 1);
+        assert_eq!(queue.push("forsen", "hi again"), 2);
+        let now = Instant::now();
+        assert_eq!(queue.take_next(false, now).unwrap().id, 1);
+        assert_eq!(queue.clear(), 1);
+        assert!(queue.take_next(false, now).is_none());
+        // The sent one is what a NOTICE refers to, for a while
+        assert_eq!(queue.rejected("forsen", now).unwrap().text, "hi");
+        assert!(queue.rejected("forsen", now + REJECTION_WINDOW).is_none());
+        assert!(queue.rejected("xqcow", now).is_none());
+    }
+
+    #[test]
+    fn repeated_messages_alternate_with_the_workaround() {
+        let queue = SayQueue::default();
+        for _ in 0..3 {
+            queue.push("forsen", "gg");
+        }
+        let now = Instant::now();
+        let texts: Vec<String> = (0..3).map(|_| queue.take_next(true, now).unwrap().text).collect();
+        assert_eq!(texts, ["gg", "gg \u{e0000}", "gg"]);
+
+        queue.push("forsen", "gg");
+        assert_eq!(queue.take_next(false, now).unwrap().text, "gg");
+        assert_eq!(avoid_duplicate("gg", None), "gg");
+    }
+}
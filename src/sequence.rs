@@ -0,0 +1,90 @@
+//! Receive order of events. `handle_received` stamps every message with the next sequence
+//! number and the time it arrived, and the lines logged while handling it carry that
+//! stamp. Lines logged later on behalf of an event (a held VIP PART, the join queue) keep
+//! the stamp of the event, so `append_line` puts them back in place: each in-memory log is
+//! ordered by sequence number, and so is everything saved from it. Sinks get the stamp in
+//! `LogEntry`: the file sinks order by it (`sink::OrderBuffer`), the journal stores it and
+//! a recovery restores the lines in receive order.
+
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Local};
+
+/// Sequence numbers of the last lines per log; a late line is placed among these.
+const ORDER_WINDOW: usize = 1024;
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Stamp of the event being handled on this thread.
+    static CURRENT: Cell<Option<EventStamp>> = const { Cell::new(None) };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventStamp {
+    /// Increasing across the process, in the order events were received.
+    pub seq: u64,
+    pub received: DateTime<Local>,
+}
+
+/// Stamp for an event received at `received`.
+pub fn next_stamp(received: DateTime<Local>) -> EventStamp {
+    EventStamp { seq: NEXT_SEQ.fetch_add(1, Ordering::Relaxed), received }
+}
+
+/// Run `f` with the lines it logs stamped with `stamp`.
+pub fn with_stamp<R>(stamp: EventStamp, f: impl FnOnce() -> R) -> R {
+    let outer = CURRENT.with(|current| current.replace(Some(stamp)));
+    let result = f();
+    CURRENT.with(|current| current.set(outer));
+    result
+}
+
+/// Stamp of the event being handled, or a new one for lines of no event (commands, timers).
+pub fn current_stamp() -> EventStamp {
+    CURRENT.with(Cell::get).unwrap_or_else(|| next_stamp(Local::now()))
+}
+
+/// Sequence numbers at the end of each log by (bucket, channel), oldest first.
+#[derive(Debug, Default)]
+pub struct LogOrder {
+    tails: HashMap<(&'static str, String), VecDeque<u64>>,
+}
+
+impl LogOrder {
+    /// Where in a log of `len` lines the line stamped `seq` goes. The caller inserts it there.
+    pub fn insert(&mut self, bucket: &'static str, channel: &str, len: usize, seq: u64) -> usize {
+        let tail = self.tails.entry((bucket, channel.to_string())).or_default();
+        // Lines removed from the log (trimming, LOAD replacing it) shorten the window
+        while tail.len() > len {
+            tail.pop_front();
+        }
+        let later = tail.iter().rev().take_while(|&&s| s > seq).count();
+        tail.insert(tail.len() - later, seq);
+        if tail.len() > ORDER_WINDOW {
+            tail.pop_front();
+        }
+        len - later
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_lines_go_before_later_ones() {
+        let mut order = LogOrder::default();
+        assert_eq!(order.insert("msgs", "forsen", 0, 10), 0);
+        assert_eq!(order.insert("msgs", "forsen", 1, 12), 1);
+        assert_eq!(order.insert("msgs", "forsen", 2, 11), 1);
+        assert_eq!(order.insert("msgs", "forsen", 3, 13), 3);
+        assert_eq!(order.insert("joins", "forsen", 0, 9), 0);
+
+        let stamp = next_stamp(Local::now());
+        assert_eq!(with_stamp(stamp, current_stamp), stamp);
+        assert!(current_stamp().seq > stamp.seq);
+    }
+}
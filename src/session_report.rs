@@ -0,0 +1,208 @@
+//! Overview of a whole monitoring session across all channels, written as
+//! `session_report_<timestamp>.json` on a clean exit.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::buckets::line_texts;
+use crate::build_info::build_info;
+use crate::diag::UnknownMessages;
+use crate::output::write_file;
+use crate::report::{moderation_event, usernotice_event};
+use crate::state::{data_file, LoggerState, SESSION_START};
+use crate::tags::SessionTags;
+use crate::stats::{compute_channel_stats, is_chat_line, NotificationStats};
+
+/// Rows of the top chatter and top channel lists.
+const TOP_ROWS: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionReport {
+    /// RFC 3339.
+    pub session_start: String,
+    /// RFC 3339.
+    pub session_end: String,
+    pub duration_secs: i64,
+    pub messages: usize,
+    pub bans: usize,
+    pub timeouts: usize,
+    /// Sub, gift sub and upgrade notices.
+    pub subs: usize,
+    pub raids: usize,
+    /// Chatters by messages, summed over all channels.
+    pub top_chatters: Vec<(String, u32)>,
+    /// Channels by chat messages.
+    pub top_channels: Vec<(String, usize)>,
+    pub average_messages_per_minute: f64,
+    /// Highest number of chat messages within one clock minute, all channels together.
+    pub peak_messages_per_minute: usize,
+    /// The minute ("HH:MM") the peak happened in.
+    pub peak_minute: Option<String>,
+    /// Desktop notification delivery.
+    pub notifications: NotificationStats,
+    /// `TAG` labels at the end of the session.
+    #[serde(skip_serializing_if = "SessionTags::is_empty")]
+    pub tags: SessionTags,
+    /// Messages without a handler by type, as DIAG shows them.
+    #[serde(skip_serializing_if = "UnknownMessages::is_empty")]
+    pub unknown_messages: UnknownMessages,
+    /// Channels that could not be joined and why.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub join_failures: BTreeMap<String, String>,
+    pub build: String,
+}
+
+/// Build the report from snapshots of the message logs and USERS counts.
+pub fn build_session_report(
+    logs: &HashMap<String, Vec<String>>,
+    user_counts: &HashMap<String, HashMap<String, u32>>,
+    raids: usize,
+    notifications: NotificationStats,
+    unknown_messages: UnknownMessages,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> SessionReport {
+    let mut report = SessionReport {
+        session_start: start.to_rfc3339(),
+        session_end: end.to_rfc3339(),
+        duration_secs: (end - start).num_seconds().max(0),
+        messages: 0,
+        bans: 0,
+        timeouts: 0,
+        subs: 0,
+        raids,
+        top_chatters: Vec::new(),
+        top_channels: Vec::new(),
+        average_messages_per_minute: 0.0,
+        peak_messages_per_minute: 0,
+        peak_minute: None,
+        notifications,
+        tags: SessionTags::default(),
+        unknown_messages,
+        join_failures: BTreeMap::new(),
+        build: build_info(),
+    };
+
+    let mut per_minute: HashMap<&str, usize> = HashMap::new();
+    for (channel, lines) in logs {
+        let stats = compute_channel_stats(channel, lines);
+        report.messages += stats.message_count;
+        report.subs += stats.sub_events;
+        if stats.message_count > 0 {
+            report.top_channels.push((channel.clone(), stats.message_count));
+        }
+        for line in lines {
+            match moderation_event(line) {
+                Some("USER_BANNED") => report.bans += 1,
+                Some("TIMEOUT") => report.timeouts += 1,
+                _ if usernotice_event(line).is_none() && is_chat_line(line) => {
+                    if let Some(minute) = line.get(0..5) {
+                        *per_minute.entry(minute).or_default() += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    report.top_channels.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    report.top_channels.truncate(TOP_ROWS);
+
+    let mut chatters: HashMap<&str, u32> = HashMap::new();
+    for (user, count) in user_counts.values().flatten() {
+        *chatters.entry(user).or_default() += count;
+    }
+    let mut top_chatters: Vec<(String, u32)> = chatters.into_iter().map(|(u, n)| (u.to_string(), n)).collect();
+    top_chatters.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_chatters.truncate(TOP_ROWS);
+    report.top_chatters = top_chatters;
+
+    if report.duration_secs > 0 {
+        let rate = report.messages as f64 * 60.0 / report.duration_secs as f64;
+        report.average_messages_per_minute = (rate * 100.0).round() / 100.0;
+    }
+    if let Some((minute, count)) = per_minute.into_iter().max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0))) {
+        report.peak_messages_per_minute = count;
+        report.peak_minute = Some(minute.to_string());
+    }
+
+    report
+}
+
+/// The report of this session so far, or up to `session_end` once it ended.
+pub fn session_report(state: &LoggerState) -> SessionReport {
+    let end = state.session_end.lock().unwrap().unwrap_or_else(Local::now);
+    let mut logs: HashMap<String, Vec<String>> = state.logs.lock().unwrap()
+    .iter()
+    .map(|(channel, lines)| (channel.clone(), line_texts(lines)))
+    .collect();
+    // Lines read back with LOAD are from an earlier session
+    for (channel, loaded) in state.loaded_lines.lock().unwrap().iter() {
+        if let Some(lines) = logs.get_mut(channel) {
+            lines.drain(..(*loaded).min(lines.len()));
+        }
+    }
+    let user_counts = state.user_message_counts.lock().unwrap().clone();
+    let raids = state.raids.lock().unwrap().len();
+    let notifications = state.notifications.lock().unwrap().clone();
+    let unknown_messages = state.unknown_messages.lock().unwrap().clone();
+    let mut report = build_session_report(&logs, &user_counts, raids, notifications, unknown_messages, *SESSION_START, end);
+    report.tags = state.session_tags.lock().unwrap().clone();
+    report.join_failures = state.join_failures.lock().unwrap().clone();
+    report
+}
+
+/// Write the report of this session. Called on a clean exit, before the logs are saved.
+pub fn save_session_report(state: &LoggerState) {
+    let report = session_report(state);
+    let file = data_file(&format!("session_report_{}.json", SESSION_START.format("%Y-%m-%d_%H-%M-%S")));
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => match write_file(&file, json) {
+            Ok(true) => println!("Saved session report to {}", file.display()),
+            Ok(false) => {}
+            Err(e) => eprintln!("⚠️ Failed to write {}: {}", file.display(), e),
+        },
+        Err(e) => eprintln!("⚠️ Failed to serialize session report: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn totals_cover_all_channels() {
+        let logs = HashMap::from([
+            ("forsen".to_string(), lines(&[
+                "12:00:01 <a> []\nhi\n",
+                "12:00:30 <b> []\nhello\n",
+                "12:01:00 <a> []\nagain\n",
+                "12:01:05 USER_BANNED: [#forsen] b",
+                "12:00:40 [forsen][c] <SUBORRESUB> tier 1 resub (2 months)",
+            ])),
+            ("xqcow".to_string(), lines(&[
+                "12:00:10 <a> []\nyo\n",
+                "12:02:00 TIMEOUT: [#xqcow] c (600s)",
+            ])),
+        ]);
+        let user_counts = HashMap::from([
+            ("forsen".to_string(), HashMap::from([("a".to_string(), 2), ("b".to_string(), 1)])),
+            ("xqcow".to_string(), HashMap::from([("a".to_string(), 1)])),
+        ]);
+        let start = Local::now();
+        let report = build_session_report(&logs, &user_counts, 1, NotificationStats::default(), UnknownMessages::default(), start, start + Duration::minutes(2));
+
+        assert_eq!(report.duration_secs, 120);
+        assert_eq!((report.messages, report.bans, report.timeouts, report.raids), (4, 1, 1, 1));
+        assert_eq!(report.top_chatters, vec![("a".to_string(), 3), ("b".to_string(), 1)]);
+        assert_eq!(report.top_channels, vec![("forsen".to_string(), 3), ("xqcow".to_string(), 1)]);
+        assert_eq!(report.average_messages_per_minute, 2.0);
+        assert_eq!((report.peak_messages_per_minute, report.peak_minute.as_deref()), (3, Some("12:00")));
+    }
+}
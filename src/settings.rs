@@ -0,0 +1,591 @@
+//! Settings from channels.txt and the command line, with where each value came from.
+//! Shown by `CONFIG SHOW`, changed at runtime by `CONFIG SET`.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::channel_config::ChannelConfig;
+use crate::hotkey::HotKey;
+use crate::membership::MembershipMode;
+use crate::save::{default_log_dir, parse_file_template, AutosaveFiles, HeaderFormat, LogFormat, DEFAULT_FILE_TEMPLATE};
+use crate::stray::StrayMode;
+use crate::timestamps::TimeFormat;
+
+/// Where the value of a setting came from. Later sources override earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    /// `key = value` in channels.txt.
+    File,
+    /// Command line flag.
+    Flag,
+    /// `CONFIG SET`.
+    Runtime,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Source::Default => "default",
+            Source::File => "file",
+            Source::Flag => "flag",
+            Source::Runtime => "runtime",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    fn new(value: T) -> Self {
+        Self { value, source: Source::Default }
+    }
+
+    pub fn set(&mut self, value: T, source: Source) {
+        self.value = value;
+        self.source = source;
+    }
+}
+
+/// Keys in display order. Their names are the ones used in channels.txt.
+pub const SETTING_KEYS: &[&str] = &[
+    "log_dir",
+    "file_name_template",
+    "log_header",
+    "log_format",
+    "max_files_per_channel",
+    "max_total_log_bytes",
+    "open_after_save",
+    "highlight_first_msg",
+    "redact_defaults",
+    "redact_console",
+    "header_records",
+    "moderation_in_msgs",
+    "autosave_minutes",
+    "autosave_lines",
+    "autosave_files",
+    "auto_save_interval",
+    "stream_logs",
+    "stream_memory_lines",
+    "sqlite_db",
+    "copypasta_min_length",
+    "normalize_messages",
+    "max_space_run",
+    "members",
+    "stray_messages",
+    "console_channel_width",
+    "time_format",
+    "notification_action",
+    "notification_action_timeout",
+    "notification_failure_warning",
+    "chatter_spike_factor",
+    "chatter_spike_min",
+    "spam_auto_mute",
+    "channel_warnings",
+    "channel_cap",
+    "startup_delay",
+    "history_file",
+    "quick_save_key",
+    "own_login",
+    "helix_client_id",
+    "helix_token",
+    "use_display_names",
+    "vip_part_grace",
+    "status_bar",
+    "auto_colors",
+    "say_duplicate_workaround",
+];
+
+/// Only read once at startup, `CONFIG SET` refuses them.
+const RESTART_KEYS: &[&str] = &["startup_delay", "history_file", "quick_save_key", "stream_logs", "log_format", "sqlite_db", "redact_defaults"];
+
+/// Default of `history_file`: `~/.rustTwitchLogger/repl_history.txt`, off without `HOME`.
+pub fn default_history_file() -> Option<String> {
+    history_file_in(std::env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from))
+}
+
+fn history_file_in(home: Option<PathBuf>) -> Option<String> {
+    home.map(|home| home.join(".rustTwitchLogger").join("repl_history.txt").display().to_string())
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Directory the logs, reports and stats are saved to, created when missing.
+    pub log_dir: Setting<String>,
+    /// Names of saved files, see `save::TEMPLATE_FIELDS` for the placeholders.
+    pub file_name_template: Setting<String>,
+    /// Header of saved message logs.
+    pub log_header: Setting<HeaderFormat>,
+    /// Format SAVE writes without `--format`; `jsonl` also writes JSON Lines as messages arrive.
+    pub log_format: Setting<LogFormat>,
+    /// Keep at most this many saved files per channel, deleting the oldest, 0 keeps all.
+    pub max_files_per_channel: Setting<usize>,
+    /// Keep the saved files of a channel under this many bytes, deleting the oldest, 0 keeps all.
+    pub max_total_log_bytes: Setting<u64>,
+    /// Open every saved message log right away.
+    pub open_after_save: Setting<bool>,
+    /// Frame chat messages of first-time chatters.
+    pub highlight_first_msg: Setting<bool>,
+    /// Redact email addresses and tokens besides the `[redaction]` patterns, see `redaction`.
+    pub redact_defaults: Setting<bool>,
+    /// The console shows redacted text too; off shows messages as sent.
+    pub redact_console: Setting<bool>,
+    /// Longest and most repeated message in the full header of saved message logs.
+    pub header_records: Setting<bool>,
+    /// Moderation events also go into the message log, not only into the moderation log.
+    pub moderation_in_msgs: Setting<bool>,
+    /// Autosave a channel once its oldest unsaved line is this old, 0 turns the time budget off.
+    pub autosave_minutes: Setting<u64>,
+    /// Autosave a channel once it has this many unsaved lines, 0 turns the line budget off.
+    pub autosave_lines: Setting<u64>,
+    /// How the autosave names its files; `AUTOSAVE <minutes>` and `--autosave` pick `daily`.
+    pub autosave_files: Setting<AutosaveFiles>,
+    /// Snapshot all channels to `.autosave` files this often, for a crash; `off` by default.
+    pub auto_save_interval: Setting<Option<Duration>>,
+    /// Append every line to a file per channel and day as it comes in, see `stream`.
+    pub stream_logs: Setting<bool>,
+    /// With `stream_logs`, the lines of each log kept in memory for the queries.
+    pub stream_memory_lines: Setting<usize>,
+    /// Database every message and event also goes to, see `sqlite_log`; `off` by default.
+    pub sqlite_db: Setting<Option<String>>,
+    /// Shorter messages are not counted as repeats (copypastas).
+    pub copypasta_min_length: Setting<usize>,
+    /// Store chat text trimmed and without duplicate-bypass characters, see `normalize`.
+    pub normalize_messages: Setting<bool>,
+    /// Longer runs of spaces are cut to this many when normalizing, 0 keeps them.
+    pub max_space_run: Setting<usize>,
+    /// JOIN/PART logging of channels without their own `members=` option.
+    pub members: Setting<MembershipMode>,
+    /// What happens to messages of recently parted channels.
+    pub stray_messages: Setting<StrayMode>,
+    /// Fixed width of the `[channel]` console column, otherwise the longest joined channel name.
+    pub console_channel_width: Setting<Option<usize>>,
+    /// Console timestamps: absolute, relative or both. Saved files are always absolute.
+    pub time_format: Setting<TimeFormat>,
+    /// Command behind the "Open chat" button of notifications, `{channel}` is replaced.
+    pub notification_action: Setting<Option<String>>,
+    /// How long the "Open chat" button waits for a click, in seconds.
+    pub notification_action_timeout: Setting<u64>,
+    /// Warn after this many desktop notifications failed in a row, 0 never warns.
+    pub notification_failure_warning: Setting<u32>,
+    /// Alert when a minute has this many times the usual number of new chatters.
+    pub chatter_spike_factor: Setting<f64>,
+    /// Fewest new chatters in a minute that can alert, 0 turns the alert off.
+    pub chatter_spike_min: Setting<usize>,
+    /// MUTE the users of a cross-channel spam wave for the rest of the session.
+    pub spam_auto_mute: Setting<bool>,
+    /// Warn when the number of joined channels reaches one of these, ascending.
+    pub channel_warnings: Setting<Vec<usize>>,
+    /// JOINs that would go beyond this many channels need CONFIRM, 0 means no cap.
+    pub channel_cap: Setting<usize>,
+    /// Seconds to wait before joining the initial channels.
+    pub startup_delay: Setting<u64>,
+    /// Prompt history across sessions, `off` keeps none.
+    pub history_file: Setting<Option<String>>,
+    /// Key that saves all channels at once (`SAVE ALL hotkey_<time>`), `off` binds none.
+    pub quick_save_key: Setting<Option<HotKey>>,
+    /// Your own Twitch login; moderation of your messages is always alerted.
+    pub own_login: Setting<Option<String>>,
+    /// Client id of the application `helix_token` belongs to, for `VIP IMPORT HELIX`.
+    pub helix_client_id: Setting<Option<String>>,
+    /// User token with `user:read:follows`; `TWITCH_OAUTH_TOKEN` if not set. Never shown.
+    pub helix_token: Setting<Option<String>>,
+    /// Name saved files after the channel's display name (`JanisTanTV_msgs_...`) once it is known.
+    pub use_display_names: Setting<bool>,
+    /// Seconds a VIP PART is held for a rejoin (`[FLAP]`), 0 reports it right away.
+    pub vip_part_grace: Setting<u64>,
+    /// Counters at the bottom of the terminal, see `status_bar`.
+    pub status_bar: Setting<bool>,
+    /// Channels without a color in channels.txt get one from their name instead of cyan.
+    pub auto_colors: Setting<bool>,
+    /// Make a SAY message that repeats the previous one different with an invisible character.
+    pub say_duplicate_workaround: Setting<bool>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            log_dir: Setting::new(default_log_dir()),
+            file_name_template: Setting::new(DEFAULT_FILE_TEMPLATE.to_string()),
+            log_header: Setting::new(HeaderFormat::default()),
+            log_format: Setting::new(LogFormat::default()),
+            max_files_per_channel: Setting::new(0),
+            max_total_log_bytes: Setting::new(0),
+            open_after_save: Setting::new(false),
+            highlight_first_msg: Setting::new(false),
+            redact_defaults: Setting::new(false),
+            redact_console: Setting::new(true),
+            header_records: Setting::new(false),
+            moderation_in_msgs: Setting::new(true),
+            autosave_minutes: Setting::new(10),
+            autosave_lines: Setting::new(5000),
+            autosave_files: Setting::new(AutosaveFiles::default()),
+            auto_save_interval: Setting::new(None),
+            stream_logs: Setting::new(false),
+            stream_memory_lines: Setting::new(5000),
+            sqlite_db: Setting::new(None),
+            copypasta_min_length: Setting::new(20),
+            normalize_messages: Setting::new(false),
+            max_space_run: Setting::new(1),
+            members: Setting::new(MembershipMode::default()),
+            stray_messages: Setting::new(StrayMode::default()),
+            console_channel_width: Setting::new(None),
+            time_format: Setting::new(TimeFormat::default()),
+            notification_action: Setting::new(None),
+            notification_action_timeout: Setting::new(30),
+            notification_failure_warning: Setting::new(3),
+            chatter_spike_factor: Setting::new(5.0),
+            chatter_spike_min: Setting::new(10),
+            spam_auto_mute: Setting::new(false),
+            channel_warnings: Setting::new(vec![50, 100]),
+            channel_cap: Setting::new(150),
+            startup_delay: Setting::new(0),
+            history_file: Setting::new(default_history_file()),
+            quick_save_key: Setting::new(Some(HotKey::Function(5))),
+            own_login: Setting::new(None),
+            helix_client_id: Setting::new(None),
+            helix_token: Setting::new(None),
+            use_display_names: Setting::new(false),
+            vip_part_grace: Setting::new(60),
+            status_bar: Setting::new(true),
+            auto_colors: Setting::new(true),
+            say_duplicate_workaround: Setting::new(false),
+        }
+    }
+}
+
+impl Settings {
+    /// Defaults overridden by the `key = value` lines of channels.txt. Invalid or unknown
+    /// lines are reported and otherwise ignored.
+    pub fn from_config(config: &ChannelConfig) -> Self {
+        let mut settings = Settings::default();
+        let mut keys: Vec<&String> = config.settings.keys().collect();
+        keys.sort();
+        for key in keys {
+            if let Err(e) = settings.apply(key, &config.settings[key], Source::File) {
+                eprintln!("⚠️ channels.txt: {}", e);
+            }
+        }
+        settings
+    }
+
+    /// Parse `value` and set `key` to it.
+    pub fn apply(&mut self, key: &str, value: &str, source: Source) -> Result<(), String> {
+        match key {
+            "log_dir" => {
+                if value.is_empty() {
+                    return Err(format!("{}: expected a directory", key));
+                }
+                self.log_dir.set(value.to_string(), source);
+            }
+            "file_name_template" => self.file_name_template.set(parse_file_template(value)?, source),
+            "log_header" => self.log_header.set(value.parse()?, source),
+            "log_format" => self.log_format.set(value.parse()?, source),
+            "max_files_per_channel" => self.max_files_per_channel.set(parse_number(key, value)?, source),
+            "max_total_log_bytes" => self.max_total_log_bytes.set(parse_number(key, value)?, source),
+            "open_after_save" => self.open_after_save.set(parse_bool(key, value)?, source),
+            "highlight_first_msg" => self.highlight_first_msg.set(parse_bool(key, value)?, source),
+            "redact_defaults" => self.redact_defaults.set(parse_bool(key, value)?, source),
+            "redact_console" => self.redact_console.set(parse_bool(key, value)?, source),
+            "header_records" => self.header_records.set(parse_bool(key, value)?, source),
+            "moderation_in_msgs" => self.moderation_in_msgs.set(parse_bool(key, value)?, source),
+            "autosave_minutes" => self.autosave_minutes.set(parse_number(key, value)?, source),
+            "autosave_lines" => self.autosave_lines.set(parse_number(key, value)?, source),
+            "autosave_files" => self.autosave_files.set(value.parse()?, source),
+            "auto_save_interval" => self.auto_save_interval.set(parse_interval(key, value)?, source),
+            "stream_logs" => self.stream_logs.set(parse_bool(key, value)?, source),
+            "stream_memory_lines" => self.stream_memory_lines.set(parse_number(key, value)?, source),
+            "copypasta_min_length" => self.copypasta_min_length.set(parse_number(key, value)?, source),
+            "normalize_messages" => self.normalize_messages.set(parse_bool(key, value)?, source),
+            "max_space_run" => self.max_space_run.set(parse_number(key, value)?, source),
+            "members" => self.members.set(value.parse()?, source),
+            "stray_messages" => self.stray_messages.set(value.parse()?, source),
+            "console_channel_width" => {
+                let width = match value {
+                    "auto" => None,
+                    width => Some(parse_number(key, width)?),
+                };
+                self.console_channel_width.set(width, source);
+            }
+            "time_format" => self.time_format.set(value.parse()?, source),
+            "notification_action" => {
+                let action = Some(value.to_string()).filter(|a| !a.is_empty());
+                self.notification_action.set(action, source);
+            }
+            "notification_action_timeout" => self.notification_action_timeout.set(parse_number(key, value)?, source),
+            "notification_failure_warning" => self.notification_failure_warning.set(parse_number(key, value)?, source),
+            "chatter_spike_factor" => {
+                let factor: f64 = parse_number(key, value)?;
+                if factor.is_nan() || factor < 1.0 {
+                    return Err(format!("{}: expected a number of at least 1, got '{}'", key, value));
+                }
+                self.chatter_spike_factor.set(factor, source);
+            }
+            "chatter_spike_min" => self.chatter_spike_min.set(parse_number(key, value)?, source),
+            "spam_auto_mute" => self.spam_auto_mute.set(parse_bool(key, value)?, source),
+            "channel_warnings" => {
+                let mut warnings = match value {
+                    "off" => Vec::new(),
+                    list => list.split(',').map(|n| parse_number(key, n.trim())).collect::<Result<Vec<usize>, _>>()?,
+                };
+                warnings.sort_unstable();
+                warnings.dedup();
+                self.channel_warnings.set(warnings, source);
+            }
+            "channel_cap" => self.channel_cap.set(parse_number(key, value)?, source),
+            "startup_delay" => self.startup_delay.set(parse_number(key, value)?, source),
+            "history_file" => {
+                let path = Some(value.to_string()).filter(|p| !p.is_empty() && p != "off");
+                self.history_file.set(path, source);
+            }
+            "sqlite_db" => {
+                let path = Some(value.to_string()).filter(|p| !p.is_empty() && p != "off");
+                self.sqlite_db.set(path, source);
+            }
+            "quick_save_key" => {
+                let key = match value {
+                    "off" => None,
+                    key => Some(key.parse().map_err(|e| format!("{}: {}", key, e))?),
+                };
+                self.quick_save_key.set(key, source);
+            }
+            "own_login" => {
+                let login = Some(value.trim_start_matches('@').to_lowercase()).filter(|l| !l.is_empty());
+                self.own_login.set(login, source);
+            }
+            "helix_client_id" => self.helix_client_id.set(Some(value.to_string()).filter(|id| !id.is_empty()), source),
+            "helix_token" => {
+                let token = Some(value.trim_start_matches("oauth:").to_string()).filter(|t| !t.is_empty());
+                self.helix_token.set(token, source);
+            }
+            "use_display_names" => self.use_display_names.set(parse_bool(key, value)?, source),
+            "vip_part_grace" => self.vip_part_grace.set(parse_number(key, value)?, source),
+            "status_bar" => self.status_bar.set(parse_bool(key, value)?, source),
+            "auto_colors" => self.auto_colors.set(parse_bool(key, value)?, source),
+            "say_duplicate_workaround" => self.say_duplicate_workaround.set(parse_bool(key, value)?, source),
+            other => return Err(format!("unknown setting '{}'", other)),
+        }
+        Ok(())
+    }
+
+    /// `CONFIG SET`: like `apply`, except for settings that are only read at startup.
+    pub fn set_runtime(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if RESTART_KEYS.contains(&key) {
+            return Err(format!("{} is only read at startup, change it in channels.txt and restart", key));
+        }
+        self.apply(key, value, Source::Runtime)
+    }
+
+    /// Value (as shown) and source of a setting, `None` for unknown keys.
+    pub fn get(&self, key: &str) -> Option<(String, Source)> {
+        let entry = match key {
+            "log_dir" => (self.log_dir.value.clone(), self.log_dir.source),
+            "file_name_template" => (self.file_name_template.value.clone(), self.file_name_template.source),
+            "log_header" => (self.log_header.value.to_string(), self.log_header.source),
+            "log_format" => (self.log_format.value.to_string(), self.log_format.source),
+            "max_files_per_channel" => (self.max_files_per_channel.value.to_string(), self.max_files_per_channel.source),
+            "max_total_log_bytes" => (self.max_total_log_bytes.value.to_string(), self.max_total_log_bytes.source),
+            "open_after_save" => (self.open_after_save.value.to_string(), self.open_after_save.source),
+            "highlight_first_msg" => (self.highlight_first_msg.value.to_string(), self.highlight_first_msg.source),
+            "redact_defaults" => (self.redact_defaults.value.to_string(), self.redact_defaults.source),
+            "redact_console" => (self.redact_console.value.to_string(), self.redact_console.source),
+            "header_records" => (self.header_records.value.to_string(), self.header_records.source),
+            "moderation_in_msgs" => (self.moderation_in_msgs.value.to_string(), self.moderation_in_msgs.source),
+            "autosave_minutes" => (self.autosave_minutes.value.to_string(), self.autosave_minutes.source),
+            "autosave_lines" => (self.autosave_lines.value.to_string(), self.autosave_lines.source),
+            "autosave_files" => (self.autosave_files.value.to_string(), self.autosave_files.source),
+            "auto_save_interval" => (
+                self.auto_save_interval.value.map_or("off".to_string(), |d| format!("{}s", d.as_secs())),
+                self.auto_save_interval.source,
+            ),
+            "stream_logs" => (self.stream_logs.value.to_string(), self.stream_logs.source),
+            "stream_memory_lines" => (self.stream_memory_lines.value.to_string(), self.stream_memory_lines.source),
+            "copypasta_min_length" => (self.copypasta_min_length.value.to_string(), self.copypasta_min_length.source),
+            "normalize_messages" => (self.normalize_messages.value.to_string(), self.normalize_messages.source),
+            "max_space_run" => (self.max_space_run.value.to_string(), self.max_space_run.source),
+            "members" => (self.members.value.to_string(), self.members.source),
+            "stray_messages" => (self.stray_messages.value.to_string(), self.stray_messages.source),
+            "console_channel_width" => (
+                self.console_channel_width.value.map_or("auto".to_string(), |w| w.to_string()),
+                self.console_channel_width.source,
+            ),
+            "time_format" => (self.time_format.value.to_string(), self.time_format.source),
+            "notification_action" => (
+                self.notification_action.value.clone().unwrap_or_else(|| "-".to_string()),
+                self.notification_action.source,
+            ),
+            "notification_action_timeout" => (
+                self.notification_action_timeout.value.to_string(),
+                self.notification_action_timeout.source,
+            ),
+            "notification_failure_warning" => (
+                self.notification_failure_warning.value.to_string(),
+                self.notification_failure_warning.source,
+            ),
+            "chatter_spike_factor" => (self.chatter_spike_factor.value.to_string(), self.chatter_spike_factor.source),
+            "chatter_spike_min" => (self.chatter_spike_min.value.to_string(), self.chatter_spike_min.source),
+            "spam_auto_mute" => (self.spam_auto_mute.value.to_string(), self.spam_auto_mute.source),
+            "channel_warnings" => (format_list(&self.channel_warnings.value), self.channel_warnings.source),
+            "channel_cap" => (self.channel_cap.value.to_string(), self.channel_cap.source),
+            "startup_delay" => (self.startup_delay.value.to_string(), self.startup_delay.source),
+            "history_file" => (
+                self.history_file.value.clone().unwrap_or_else(|| "off".to_string()),
+                self.history_file.source,
+            ),
+            "sqlite_db" => (self.sqlite_db.value.clone().unwrap_or_else(|| "off".to_string()), self.sqlite_db.source),
+            "quick_save_key" => (
+                self.quick_save_key.value.map_or("off".to_string(), |k| k.to_string()),
+                self.quick_save_key.source,
+            ),
+            "own_login" => (
+                self.own_login.value.clone().unwrap_or_else(|| "-".to_string()),
+                self.own_login.source,
+            ),
+            "helix_client_id" => (
+                self.helix_client_id.value.clone().unwrap_or_else(|| "-".to_string()),
+                self.helix_client_id.source,
+            ),
+            "helix_token" => (
+                self.helix_token.value.as_ref().map_or("-", |_| "(set)").to_string(),
+                self.helix_token.source,
+            ),
+            "use_display_names" => (self.use_display_names.value.to_string(), self.use_display_names.source),
+            "vip_part_grace" => (self.vip_part_grace.value.to_string(), self.vip_part_grace.source),
+            "status_bar" => (self.status_bar.value.to_string(), self.status_bar.source),
+            "auto_colors" => (self.auto_colors.value.to_string(), self.auto_colors.source),
+            "say_duplicate_workaround" => (self.say_duplicate_workaround.value.to_string(), self.say_duplicate_workaround.source),
+            _ => return None,
+        };
+        Some(entry)
+    }
+}
+
+/// One `CONFIG SHOW` row: key, value and source, plus a note for startup-only settings.
+pub fn format_setting(key: &str, value: &str, source: Source) -> String {
+    let restart = if RESTART_KEYS.contains(&key) { ", restart to change" } else { "" };
+    format!("{:<28} {:<16} ({}{})", key, value, source, restart)
+}
+
+/// "50,100", or "off" for none.
+fn format_list(values: &[usize]) -> String {
+    if values.is_empty() {
+        return "off".to_string();
+    }
+    values.iter().map(usize::to_string).collect::<Vec<_>>().join(",")
+}
+
+pub(crate) fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "on" | "yes" => Ok(true),
+        "false" | "off" | "no" => Ok(false),
+        _ => Err(format!("{}: expected true or false, got '{}'", key, value)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{}: expected a number, got '{}'", key, value))
+}
+
+/// "90s", "5m", "1h" or plain seconds; "off" or 0 is `None`.
+fn parse_interval(key: &str, value: &str) -> Result<Option<Duration>, String> {
+    let value = value.trim().to_lowercase();
+    if value == "off" {
+        return Ok(None);
+    }
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 3600),
+        _ => (value.as_str(), 1),
+    };
+    let secs = number
+    .trim()
+    .parse::<u64>()
+    .ok()
+    .and_then(|number| number.checked_mul(unit))
+    .ok_or_else(|| format!("{}: expected an interval like 90s, 5m or 1h, or off, got '{}'", key, value))?;
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_defaults_to_the_home_dir() {
+        assert_eq!(
+            history_file_in(Some(PathBuf::from("/home/someone"))),
+            Some("/home/someone/.rustTwitchLogger/repl_history.txt".to_string())
+        );
+        assert_eq!(history_file_in(None), None);
+    }
+
+    #[test]
+    fn later_sources_override() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.get("log_header"), Some(("full".to_string(), Source::Default)));
+        settings.apply("log_header", "minimal", Source::File).unwrap();
+        settings.apply("log_header", "none", Source::Flag).unwrap();
+        assert_eq!(settings.get("log_header"), Some(("none".to_string(), Source::Flag)));
+        settings.set_runtime("log_header", "full").unwrap();
+        assert_eq!(settings.log_header.value, HeaderFormat::Full);
+        assert_eq!(settings.log_header.source, Source::Runtime);
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let mut settings = Settings::default();
+        assert!(settings.set_runtime("highlight_first_msg", "maybe").is_err());
+        assert!(settings.set_runtime("console_channel_width", "wide").is_err());
+        assert!(settings.set_runtime("chatter_spike_factor", "0.5").is_err());
+        assert!(settings.set_runtime("channel_warnings", "50,lots").is_err());
+        assert!(settings.set_runtime("no_such_setting", "1").is_err());
+        assert_eq!(settings.highlight_first_msg.source, Source::Default);
+    }
+
+    #[test]
+    fn channel_warnings_are_a_sorted_list() {
+        let mut settings = Settings::default();
+        settings.set_runtime("channel_warnings", "100, 50,50").unwrap();
+        assert_eq!(settings.channel_warnings.value, vec![50, 100]);
+        assert_eq!(settings.get("channel_warnings").unwrap().0, "50,100");
+        settings.set_runtime("channel_warnings", "off").unwrap();
+        assert!(settings.channel_warnings.value.is_empty());
+    }
+
+    #[test]
+    fn intervals_take_units() {
+        let mut settings = Settings::default();
+        settings.set_runtime("auto_save_interval", "5m").unwrap();
+        assert_eq!(settings.auto_save_interval.value, Some(Duration::from_secs(300)));
+        assert_eq!(settings.get("auto_save_interval").unwrap().0, "300s");
+        settings.set_runtime("auto_save_interval", "90").unwrap();
+        assert_eq!(settings.auto_save_interval.value, Some(Duration::from_secs(90)));
+        settings.set_runtime("auto_save_interval", "off").unwrap();
+        assert_eq!(settings.auto_save_interval.value, None);
+        assert!(settings.set_runtime("auto_save_interval", "soon").is_err());
+        assert!(settings.set_runtime("auto_save_interval", &format!("{}h", u64::MAX / 60)).is_err());
+        assert_eq!(settings.auto_save_interval.value, None);
+    }
+
+    #[test]
+    fn startup_settings_need_a_restart() {
+        let mut settings = Settings::default();
+        assert!(settings.set_runtime("startup_delay", "5").is_err());
+        assert_eq!(settings.startup_delay.value, 0);
+    }
+
+    #[test]
+    fn every_key_can_be_shown() {
+        let settings = Settings::default();
+        for key in SETTING_KEYS {
+            assert!(settings.get(key).is_some(), "{}", key);
+        }
+    }
+}
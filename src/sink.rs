@@ -0,0 +1,407 @@
+//! Streaming outputs of log lines. Every line added to a log bucket goes through
+//! `append_line`, which hands it to each enabled sink over a bounded channel. Each sink
+//! writes on its own thread, so a slow disk never holds up the message handlers.
+//!
+//! Adding a sink: implement `Sink` (`write` gets every accepted line, `flush` is called
+//! on exit, `idle` whenever the sink has caught up) and register it at startup with
+//! `state.sinks.register(Box::new(MySink { .. }), SinkPolicy { .. })`. `SINKS` lists the
+//! registered sinks with their drop and error counts, `SINKS ENABLE|DISABLE <name>`
+//! switches one on or off at runtime.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use crate::jsonl_log::LogRecord;
+
+/// One line added to a log bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// `LogBucket::name`.
+    pub bucket: &'static str,
+    pub channel: String,
+    pub line: String,
+    /// The chat text in `line` was changed by `normalize_messages`.
+    pub normalized: bool,
+    /// Receive order of the event the line is for, see `sequence`. A line logged late
+    /// (a held VIP PART) has a lower number than the ones before it.
+    pub seq: u64,
+    /// When that event arrived.
+    pub received: DateTime<Local>,
+    /// Everything the handler knew about a message log entry, for the `jsonl` sink.
+    pub record: Option<Arc<LogRecord>>,
+}
+
+pub trait Sink: Send + 'static {
+    /// Shown by `SINKS`, used by `SINKS ENABLE|DISABLE`.
+    fn name(&self) -> &str;
+
+    /// Whether the sink wants `entry` at all.
+    fn accepts(&self, _entry: &LogEntry) -> bool {
+        true
+    }
+
+    fn write(&mut self, entry: &LogEntry) -> io::Result<()>;
+
+    /// Called on exit and by `SinkRegistry::flush`.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called when no more entries are waiting, and every `idle_tick` while none come.
+    fn idle(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+
+    /// For sinks that hold back writes: how often `idle` is called while nothing arrives.
+    fn idle_tick(&self) -> Option<Duration> {
+        None
+    }
+
+    /// `channel` was parted, for sinks that keep something open per channel.
+    fn close_channel(&mut self, _channel: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Entries a file sink holds back for a while before writing them, so that a line logged
+/// late (a held VIP PART) still goes to the file in receive order. An entry is due once it
+/// was received `hold` ago, the hold is asked for each time.
+pub struct OrderBuffer {
+    held: Vec<LogEntry>,
+    hold: Box<dyn Fn() -> Duration + Send>,
+}
+
+impl OrderBuffer {
+    pub fn new(hold: impl Fn() -> Duration + Send + 'static) -> Self {
+        OrderBuffer { held: Vec::new(), hold: Box::new(hold) }
+    }
+
+    /// Hold `entry`, after the ones received before it.
+    pub fn push(&mut self, entry: &LogEntry) {
+        let at = self.held.partition_point(|held| held.seq <= entry.seq);
+        self.held.insert(at, entry.clone());
+    }
+
+    /// The entries that are due at `now`, in receive order.
+    pub fn due(&mut self, now: DateTime<Local>) -> Vec<LogEntry> {
+        let hold = chrono::Duration::from_std((self.hold)()).unwrap_or(chrono::Duration::MAX);
+        let due = self.held.iter().take_while(|entry| now - entry.received >= hold).count();
+        self.held.drain(..due).collect()
+    }
+
+    /// Everything held for `channel`, or all of it with `None`, in receive order.
+    pub fn take(&mut self, channel: Option<&str>) -> Vec<LogEntry> {
+        let (taken, kept) = std::mem::take(&mut self.held).into_iter().partition(|entry| channel.is_none_or(|channel| entry.channel == channel));
+        self.held = kept;
+        taken
+    }
+}
+
+/// What happens when a sink's channel is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Count the entry as dropped and go on.
+    Drop,
+    /// Wait for the sink, for sinks that must not lose anything.
+    Block,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SinkPolicy {
+    /// Entries that can wait for the sink.
+    pub capacity: usize,
+    pub backpressure: Backpressure,
+    /// Disable the sink after the first failed write instead of reporting every failure.
+    pub disable_on_error: bool,
+}
+
+enum SinkCommand {
+    Write(Arc<LogEntry>),
+    /// Flush and confirm.
+    Flush(mpsc::Sender<()>),
+    CloseChannel(String),
+}
+
+struct SinkHandle {
+    name: String,
+    tx: SyncSender<SinkCommand>,
+    backpressure: Backpressure,
+    enabled: Arc<AtomicBool>,
+    dropped: AtomicU64,
+    errors: Arc<AtomicU64>,
+    /// Entries passed over while the sink was disabled.
+    skipped: Arc<AtomicU64>,
+}
+
+/// A row of `SINKS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkStatus {
+    pub name: String,
+    pub enabled: bool,
+    pub dropped: u64,
+    pub errors: u64,
+}
+
+/// The registered sinks. Cloning is cheap and shares the registry.
+#[derive(Clone, Default)]
+pub struct SinkRegistry {
+    sinks: Arc<Mutex<Vec<Arc<SinkHandle>>>>,
+}
+
+impl SinkRegistry {
+    /// Start a writer thread for `sink`. It receives every line dispatched from now on.
+    pub fn register(&self, sink: Box<dyn Sink>, policy: SinkPolicy) {
+        let (tx, rx) = mpsc::sync_channel(policy.capacity);
+        let handle = Arc::new(SinkHandle {
+            name: sink.name().to_string(),
+            tx,
+            backpressure: policy.backpressure,
+            enabled: Arc::new(AtomicBool::new(true)),
+            dropped: AtomicU64::new(0),
+            errors: Arc::new(AtomicU64::new(0)),
+            skipped: Arc::new(AtomicU64::new(0)),
+        });
+
+        let enabled = Arc::clone(&handle.enabled);
+        let errors = Arc::clone(&handle.errors);
+        let skipped = Arc::clone(&handle.skipped);
+        std::thread::spawn(move || run_sink(sink, rx, enabled, errors, skipped, policy.disable_on_error));
+        self.sinks.lock().unwrap().push(handle);
+    }
+
+    /// Whether any sink is registered, so callers can skip building entries.
+    pub fn is_active(&self) -> bool {
+        !self.sinks.lock().unwrap().is_empty()
+    }
+
+    /// Hand `entry` to every enabled sink.
+    pub fn dispatch(&self, entry: LogEntry) {
+        let sinks = self.sinks.lock().unwrap().clone();
+        if sinks.is_empty() {
+            return;
+        }
+        let entry = Arc::new(entry);
+        for sink in &sinks {
+            if !sink.enabled.load(Ordering::Relaxed) {
+                sink.skipped.fetch_add(1, Ordering::Relaxed);
+            } else if !sink.send(SinkCommand::Write(Arc::clone(&entry))) {
+                sink.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Tell every sink that `channel` was parted.
+    pub fn close_channel(&self, channel: &str) {
+        let sinks = self.sinks.lock().unwrap().clone();
+        for sink in &sinks {
+            sink.send(SinkCommand::CloseChannel(channel.to_string()));
+        }
+    }
+
+    /// Whether the sink called `name` is registered and enabled.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.sinks.lock().unwrap().iter().any(|s| s.name == name && s.enabled.load(Ordering::Relaxed))
+    }
+
+    /// Whether the sink called `name` is enabled and got every entry since it was
+    /// registered: none dropped, none passed over while disabled, no failed write.
+    pub fn is_complete(&self, name: &str) -> bool {
+        self.sinks.lock().unwrap().iter().any(|s| {
+            s.name == name
+                && s.enabled.load(Ordering::Relaxed)
+                && s.dropped.load(Ordering::Relaxed) == 0
+                && s.errors.load(Ordering::Relaxed) == 0
+                && s.skipped.load(Ordering::Relaxed) == 0
+        })
+    }
+
+    /// Returns `false` if there is no sink called `name`.
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        let sinks = self.sinks.lock().unwrap();
+        match sinks.iter().find(|s| s.name.eq_ignore_ascii_case(name)) {
+            Some(sink) => {
+                sink.enabled.store(enabled, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wait until every sink has written and flushed what it got so far. Called on exit.
+    pub fn flush(&self) {
+        let sinks = self.sinks.lock().unwrap().clone();
+        let waiting: Vec<mpsc::Receiver<()>> = sinks
+        .iter()
+        .filter_map(|sink| {
+            let (done_tx, done_rx) = mpsc::channel();
+            sink.tx.send(SinkCommand::Flush(done_tx)).ok().map(|_| done_rx)
+        })
+        .collect();
+        for done in waiting {
+            // An error means the writer thread is gone, nothing left to wait for
+            let _ = done.recv();
+        }
+    }
+
+    pub fn status(&self) -> Vec<SinkStatus> {
+        self.sinks.lock().unwrap()
+        .iter()
+        .map(|sink| SinkStatus {
+            name: sink.name.clone(),
+            enabled: sink.enabled.load(Ordering::Relaxed),
+            dropped: sink.dropped.load(Ordering::Relaxed),
+            errors: sink.errors.load(Ordering::Relaxed),
+        })
+        .collect()
+    }
+}
+
+impl SinkHandle {
+    /// Send by the backpressure policy, false if the command was dropped.
+    fn send(&self, command: SinkCommand) -> bool {
+        match self.backpressure {
+            Backpressure::Block => self.tx.send(command).is_ok(),
+            Backpressure::Drop => match self.tx.try_send(command) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => false,
+            },
+        }
+    }
+}
+
+/// Writer thread of one sink: write what arrives, flush once caught up.
+fn run_sink(
+    mut sink: Box<dyn Sink>,
+    rx: Receiver<SinkCommand>,
+    enabled: Arc<AtomicBool>,
+    errors: Arc<AtomicU64>,
+    skipped: Arc<AtomicU64>,
+    disable_on_error: bool,
+) {
+    let report = |sink: &dyn Sink, e: io::Error| {
+        errors.fetch_add(1, Ordering::Relaxed);
+        eprintln!("⚠️ Sink {} failed: {}", sink.name(), e);
+        if disable_on_error {
+            enabled.store(false, Ordering::Relaxed);
+            eprintln!("⚠️ Sink {} disabled, SINKS ENABLE {} to retry", sink.name(), sink.name());
+        }
+    };
+
+    let mut next = rx.recv().ok();
+    while let Some(command) = next {
+        match command {
+            SinkCommand::Write(entry) => {
+                // Entries queued before a DISABLE are skipped too
+                if !enabled.load(Ordering::Relaxed) {
+                    skipped.fetch_add(1, Ordering::Relaxed);
+                } else if sink.accepts(&entry) {
+                    if let Err(e) = sink.write(&entry) {
+                        report(sink.as_ref(), e);
+                    }
+                }
+            }
+            SinkCommand::Flush(done) => {
+                if let Err(e) = sink.flush() {
+                    report(sink.as_ref(), e);
+                }
+                let _ = done.send(());
+            }
+            SinkCommand::CloseChannel(channel) => {
+                if let Err(e) = sink.close_channel(&channel) {
+                    report(sink.as_ref(), e);
+                }
+            }
+        }
+
+        next = match rx.try_recv() {
+            Ok(command) => Some(command),
+            Err(TryRecvError::Empty) => loop {
+                if let Err(e) = sink.idle() {
+                    report(sink.as_ref(), e);
+                }
+                let Some(tick) = sink.idle_tick() else {
+                    break rx.recv().ok();
+                };
+                match rx.recv_timeout(tick) {
+                    Ok(command) => break Some(command),
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break None,
+                }
+            },
+            Err(TryRecvError::Disconnected) => None,
+        };
+    }
+    let _ = sink.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collects the lines of one bucket.
+    struct Collect {
+        bucket: &'static str,
+        lines: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Sink for Collect {
+        fn name(&self) -> &str {
+            self.bucket
+        }
+
+        fn accepts(&self, entry: &LogEntry) -> bool {
+            entry.bucket == self.bucket
+        }
+
+        fn write(&mut self, entry: &LogEntry) -> io::Result<()> {
+            self.lines.lock().unwrap().push(entry.line.clone());
+            Ok(())
+        }
+    }
+
+    fn entry(bucket: &'static str, line: &str) -> LogEntry {
+        LogEntry { bucket, channel: "forsen".to_string(), line: line.to_string(), normalized: false, seq: 0, received: Local::now(), record: None }
+    }
+
+    fn collect(registry: &SinkRegistry, bucket: &'static str) -> Arc<Mutex<Vec<String>>> {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let policy = SinkPolicy { capacity: 16, backpressure: Backpressure::Block, disable_on_error: false };
+        registry.register(Box::new(Collect { bucket, lines: Arc::clone(&lines) }), policy);
+        lines
+    }
+
+    #[test]
+    fn sinks_get_the_entries_they_accept() {
+        let registry = SinkRegistry::default();
+        let msgs = collect(&registry, "msgs");
+        let joins = collect(&registry, "joins");
+
+        registry.dispatch(entry("msgs", "one"));
+        registry.dispatch(entry("joins", "join"));
+        registry.dispatch(entry("msgs", "two"));
+        registry.flush();
+
+        assert_eq!(*msgs.lock().unwrap(), vec!["one", "two"]);
+        assert_eq!(*joins.lock().unwrap(), vec!["join"]);
+    }
+
+    #[test]
+    fn disabled_sinks_get_nothing() {
+        let registry = SinkRegistry::default();
+        let msgs = collect(&registry, "msgs");
+
+        assert!(registry.set_enabled("MSGS", false));
+        registry.dispatch(entry("msgs", "missed"));
+        assert!(registry.set_enabled("msgs", true));
+        registry.dispatch(entry("msgs", "seen"));
+        registry.flush();
+
+        assert_eq!(*msgs.lock().unwrap(), vec!["seen"]);
+        assert!(!registry.set_enabled("nope", true));
+    }
+}
@@ -1,22 +1,98 @@
-use rodio::{OutputStream, Sink, Source};
+use rodio::{Decoder, OutputStream, Sink, Source};
+
+use std::fs::File;
+
+use std::io::BufReader;
+
+use std::path::Path;
 
 use std::sync::mpsc::{self, Sender};
 
 use std::thread;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use once_cell::sync::Lazy;
 
+use crate::alert_mode::sound_cooldown_passed;
+use crate::state::{LoggerState, CONFIG};
+
+
+pub static SOUND_TX: Lazy<Sender<SoundKind>> = Lazy::new(start_sound_thread);
+
+#[cfg(test)]
+thread_local! {
+    /// Sounds sent on this thread, for the tests.
+    pub static SENT: std::cell::RefCell<Vec<SoundKind>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+
+/// What to play: the usual short beep, or the alarm for things that must not be missed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoundKind {
+    /// The beep of a channel's profile, `None` for the `sound_path` file or the default beep.
+    Alert(Option<SoundProfile>),
+    /// Three rising beeps.
+    Alarm,
+}
+
+
+/// Beep of a channel, `janistantv:red:440:200` in channels.txt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoundProfile {
+    pub freq_hz: f32,
+    pub duration_ms: u64,
+}
+
+impl Default for SoundProfile {
+    fn default() -> Self {
+        SoundProfile { freq_hz: 69.0, duration_ms: 150 }
+    }
+}
 
-pub static SOUND_TX: Lazy<Sender<()>> = Lazy::new(start_sound_thread);
 
+/// Whether there is an audio output device to play alert sounds on.
+pub fn audio_available() -> bool {
+    use rodio::cpal::traits::HostTrait;
+    rodio::cpal::default_host().default_output_device().is_some()
+}
 
-/// Call this function to play the generated sound.
 
+/// Call this function to play the alert: the `sound_path` file of channels.txt, or the
+/// generated beep.
 pub fn play_sound() {
 
-    if let Err(e) = SOUND_TX.send(()) {
+    send_sound(SoundKind::Alert(None));
+
+}
+
+
+/// The alert of `channel`: its sound profile from channels.txt if it has one, otherwise
+/// `play_sound`. Nothing while its `sound_cooldown` since the last one has not passed.
+pub fn play_channel_sound(state: &LoggerState, channel: &str) {
+
+    if !sound_cooldown_passed(state, channel, Instant::now()) {
+        return;
+    }
+    send_sound(SoundKind::Alert(state.vip_info(channel).and_then(|info| info.sound)));
+
+}
+
+
+/// The alarm, for moderation aimed at a VIP.
+pub fn play_alarm() {
+
+    send_sound(SoundKind::Alarm);
+
+}
+
+
+fn send_sound(kind: SoundKind) {
+
+    #[cfg(test)]
+    SENT.with(|sent| sent.borrow_mut().push(kind));
+
+    if let Err(e) = SOUND_TX.send(kind) {
 
         eprintln!("Failed to send sound trigger: {}", e);
 
@@ -25,13 +101,38 @@ pub fn play_sound() {
 }
 
 
-fn start_sound_thread() -> Sender<()> {
+/// Decoder of the MP3/WAV file at `path`.
+fn open_sound_file(path: &Path) -> Result<Decoder<BufReader<File>>, String> {
+
+    let file = File::open(path).map_err(|e| e.to_string())?;
+
+    Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())
+
+}
+
+
+fn start_sound_thread() -> Sender<SoundKind> {
 
-    let (tx, rx) = mpsc::channel::<()>();
+    let (tx, rx) = mpsc::channel::<SoundKind>();
 
 
     thread::spawn(move || {
 
+        // Checked once, a file that does not play falls back to the beep for the session
+        let sound_path = CONFIG.sound_path.clone().filter(|path| match open_sound_file(path) {
+
+            Ok(_) => true,
+
+            Err(e) => {
+
+                eprintln!("⚠️ channels.txt: sound_path {}: {}, using the beep", path.display(), e);
+
+                false
+
+            }
+
+        });
+
         let (_stream, stream_handle) = match OutputStream::try_default() {
 
             Ok(tuple) => tuple,
@@ -47,13 +148,33 @@ fn start_sound_thread() -> Sender<()> {
         };
 
 
-        while let Ok(()) = rx.recv() {
+        while let Ok(kind) = rx.recv() {
 
             if let Ok(sink) = Sink::try_new(&stream_handle) {
 
-                let source = SquareWave::new(69.0, Duration::from_millis(150));
+                match kind {
 
-                sink.append(source);
+                    SoundKind::Alert(Some(profile)) => sink.append(SquareWave::from_profile(profile)),
+
+                    SoundKind::Alert(None) => match sound_path.as_deref().map(open_sound_file) {
+
+                        Some(Ok(decoder)) => sink.append(decoder),
+
+                        _ => sink.append(SquareWave::from_profile(SoundProfile::default())),
+
+                    },
+
+                    SoundKind::Alarm => {
+
+                        for freq in [440.0, 660.0, 880.0] {
+
+                            sink.append(SquareWave::new(freq, Duration::from_millis(200)));
+
+                        }
+
+                    }
+
+                }
 
                 sink.detach();
 
@@ -103,6 +224,13 @@ impl SquareWave {
 
     }
 
+
+    pub fn from_profile(profile: SoundProfile) -> Self {
+
+        Self::new(profile.freq_hz, Duration::from_millis(profile.duration_ms))
+
+    }
+
 }
 
 
@@ -0,0 +1,142 @@
+//! Cross-channel spam: the same message posted into several logged channels within a
+//! minute, the way scam bots work. Texts are compared without case and whitespace;
+//! anything older than the window is dropped, so the map only holds the last minute.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How close together the posts have to be.
+pub const SPAM_WINDOW: Duration = Duration::from_secs(60);
+/// Distinct channels with the same text that make it spam.
+pub const SPAM_MIN_CHANNELS: usize = 3;
+/// Posts kept per text, enough to name the channels of a big wave.
+const MAX_SIGHTINGS: usize = 64;
+/// How often texts seen only once are swept out, instead of on every message.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+struct Sighting {
+    channel: String,
+    user: String,
+    at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct SpamText {
+    sightings: VecDeque<Sighting>,
+    /// Already alerted; quiet until the text is gone for a whole window.
+    alerted: bool,
+}
+
+/// What an alert reports, both sorted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpamWave {
+    pub channels: Vec<String>,
+    pub users: Vec<String>,
+}
+
+/// Recent texts of all channels, fed by the message handler.
+#[derive(Debug, Default)]
+pub struct CrossChannelSpam {
+    texts: HashMap<String, SpamText>,
+    last_sweep: Option<Instant>,
+}
+
+/// "Free  V-Bucks at x.com" and "free v-bucks at X.com" are the same text.
+pub fn normalize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect()
+}
+
+impl CrossChannelSpam {
+    /// Count a message. Returns the wave the first time its text reaches
+    /// `SPAM_MIN_CHANNELS` channels within `SPAM_WINDOW`. Texts shorter than `min_length`
+    /// (after normalizing) are ignored, short ones like "lol" are everywhere.
+    pub fn record(&mut self, text: &str, channel: &str, user: &str, now: Instant, min_length: usize) -> Option<SpamWave> {
+        self.sweep(now);
+        let key = normalize(text);
+        if key.chars().count() < min_length.max(1) {
+            return None;
+        }
+        let entry = self.texts.entry(key).or_default();
+        expire(entry, now);
+        entry.sightings.push_back(Sighting { channel: channel.to_string(), user: user.to_string(), at: now });
+        if entry.sightings.len() > MAX_SIGHTINGS {
+            entry.sightings.pop_front();
+        }
+
+        let channels: BTreeSet<&str> = entry.sightings.iter().map(|s| s.channel.as_str()).collect();
+        if entry.alerted || channels.len() < SPAM_MIN_CHANNELS {
+            return None;
+        }
+        entry.alerted = true;
+        let users: BTreeSet<&str> = entry.sightings.iter().map(|s| s.user.as_str()).collect();
+        Some(SpamWave {
+            channels: channels.into_iter().map(str::to_string).collect(),
+            users: users.into_iter().map(str::to_string).collect(),
+        })
+    }
+
+    /// Texts currently remembered.
+    pub fn len(&self) -> usize {
+        self.texts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty()
+    }
+
+    fn sweep(&mut self, now: Instant) {
+        if self.last_sweep.is_some_and(|last| now.saturating_duration_since(last) < SWEEP_INTERVAL) {
+            return;
+        }
+        self.last_sweep = Some(now);
+        self.texts.retain(|_, text| {
+            expire(text, now);
+            !text.sightings.is_empty()
+        });
+    }
+}
+
+/// Drop the sightings older than the window; an emptied text may alert again.
+fn expire(text: &mut SpamText, now: Instant) {
+    while text.sightings.front().is_some_and(|s| now.saturating_duration_since(s.at) >= SPAM_WINDOW) {
+        text.sightings.pop_front();
+    }
+    if text.sightings.is_empty() {
+        text.alerted = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCAM: &str = "Get viewers at cheap-viewers dot com";
+
+    #[test]
+    fn same_text_in_three_channels_is_a_wave() {
+        let start = Instant::now();
+        let mut spam = CrossChannelSpam::default();
+        assert_eq!(spam.record(SCAM, "forsen", "bot1", start, 20), None);
+        assert_eq!(spam.record(SCAM, "forsen", "bot2", start, 20), None);
+        assert_eq!(spam.record("get VIEWERS at cheap-viewers  dot com", "xqcow", "bot1", start, 20), None);
+        let wave = spam.record(SCAM, "pajlada", "bot3", start + Duration::from_secs(30), 20).unwrap();
+        assert_eq!(wave.channels, ["forsen", "pajlada", "xqcow"]);
+        assert_eq!(wave.users, ["bot1", "bot2", "bot3"]);
+        // Once per wave
+        assert_eq!(spam.record(SCAM, "zneix", "bot4", start + Duration::from_secs(31), 20), None);
+    }
+
+    #[test]
+    fn old_and_short_texts_are_not_kept() {
+        let start = Instant::now();
+        let mut spam = CrossChannelSpam::default();
+        for (i, channel) in ["a", "b", "c"].iter().enumerate() {
+            assert_eq!(spam.record("lol", channel, "user", start, 20), None);
+            assert_eq!(spam.record(SCAM, channel, "bot", start + SPAM_WINDOW * i as u32, 20), None);
+        }
+        assert_eq!(spam.len(), 1);
+        spam.record("something else entirely here", "a", "user", start + SPAM_WINDOW * 4, 20);
+        assert_eq!(spam.len(), 1);
+    }
+}
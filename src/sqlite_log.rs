@@ -0,0 +1,377 @@
+//! `--sqlite <path>` (`sqlite_db`): every entry of the message logs also goes to a SQLite
+//! database, chat messages to `messages` and everything else (user notices, bans,
+//! timeouts, deletions, markers) to `events`, with `channels` and `users` for the names.
+//! Rows carry the message and user ids, so "everything user X wrote in any channel" is
+//! one query. The `sqlite` sink inserts on its own thread in batches of `BATCH_ROWS`,
+//! one transaction each; when its queue is full, entries are dropped rather than making
+//! the handlers wait. The in-memory log stays the source of SAVE and STATS; only when
+//! `stream_logs` has cut it down to the last lines do they read the session's lines back
+//! from the database, and only if it provably has all of them: nothing dropped, skipped or
+//! lost to a failed write, nothing LOADed into the channel, and as many rows as the
+//! channel logged. Otherwise they warn and use what is in memory. Encrypted channels are
+//! not written.
+
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use rusqlite::{params, Connection, OpenFlags};
+
+use crate::buckets::LogLine;
+use crate::console::print_status;
+use crate::encryption::{encrypted_channels, encrypts};
+use crate::jsonl_log::{entry_record, LogRecord};
+use crate::output::is_dry_run;
+use crate::sink::{Backpressure, LogEntry, Sink, SinkPolicy};
+use crate::state::{LoggerState, SESSION_START};
+
+/// Name of the sink in `SINKS`.
+pub const SQLITE_SINK: &str = "sqlite";
+/// Entries that can wait for the database; more are dropped.
+const SQLITE_CAPACITY: usize = 16384;
+/// Rows inserted in one transaction...
+const BATCH_ROWS: usize = 500;
+/// ...or whatever is there after this long.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS channels (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    login TEXT NOT NULL,
+    display_name TEXT,
+    last_seen TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY,
+    session TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    message_id TEXT,
+    user_id TEXT REFERENCES users(id),
+    sender_name TEXT,
+    channel_id INTEGER NOT NULL REFERENCES channels(id),
+    timestamp TEXT NOT NULL,
+    badges TEXT NOT NULL,
+    color TEXT,
+    bits INTEGER,
+    text TEXT NOT NULL,
+    line TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    session TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    event_type TEXT NOT NULL,
+    message_id TEXT,
+    user_id TEXT REFERENCES users(id),
+    login TEXT,
+    channel_id INTEGER NOT NULL REFERENCES channels(id),
+    timestamp TEXT NOT NULL,
+    text TEXT NOT NULL,
+    line TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS messages_user ON messages(user_id);
+CREATE INDEX IF NOT EXISTS messages_channel ON messages(channel_id, session, seq);
+CREATE INDEX IF NOT EXISTS events_channel ON events(channel_id, session, seq);
+";
+
+/// Lines of `channel` logged in `session`, in receive order.
+const SESSION_LINES: &str = "
+SELECT line FROM (
+    SELECT seq, id, 0 AS source, line, channel_id, session FROM messages
+    UNION ALL
+    SELECT seq, id, 1 AS source, line, channel_id, session FROM events
+)
+WHERE channel_id = (SELECT id FROM channels WHERE name = ?1) AND session = ?2
+ORDER BY seq, source, id
+";
+
+/// Open the database at `path`, creating the tables if needed.
+pub fn open_database(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    // Readers (SAVE, STATS) do not wait for the writer
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+fn session_id() -> String {
+    SESSION_START.to_rfc3339()
+}
+
+pub struct SqliteSink {
+    conn: Connection,
+    session: String,
+    /// Encrypted channels, never written in plaintext.
+    skip: Vec<String>,
+    pending: Vec<(u64, String, LogRecord)>,
+    last_flush: Instant,
+}
+
+impl SqliteSink {
+    pub fn new(conn: Connection, skip: Vec<String>) -> Self {
+        SqliteSink { conn, session: session_id(), skip, pending: Vec::new(), last_flush: Instant::now() }
+    }
+
+    fn insert_batch(&mut self) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut channel = tx.prepare_cached("INSERT OR IGNORE INTO channels (name) VALUES (?1)")?;
+            let mut user = tx.prepare_cached(
+                "INSERT INTO users (id, login, display_name, last_seen) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(id) DO UPDATE SET login = excluded.login, display_name = excluded.display_name, last_seen = excluded.last_seen",
+            )?;
+            let mut message = tx.prepare_cached(
+                "INSERT INTO messages (session, seq, message_id, user_id, sender_name, channel_id, timestamp, badges, color, bits, text, line)
+                 VALUES (?1, ?2, ?3, ?4, ?5, (SELECT id FROM channels WHERE name = ?6), ?7, ?8, ?9, ?10, ?11, ?12)",
+            )?;
+            let mut event = tx.prepare_cached(
+                "INSERT INTO events (session, seq, event_type, message_id, user_id, login, channel_id, timestamp, text, line)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, (SELECT id FROM channels WHERE name = ?7), ?8, ?9, ?10)",
+            )?;
+            for (seq, line, record) in &self.pending {
+                channel.execute(params![record.channel])?;
+                if let (Some(id), Some(login)) = (&record.user_id, &record.sender_login) {
+                    user.execute(params![id, login, record.sender_name, record.timestamp])?;
+                }
+                let seq = *seq as i64;
+                match &record.event_type {
+                    None => message.execute(params![
+                        self.session,
+                        seq,
+                        record.message_id,
+                        record.user_id,
+                        record.sender_name,
+                        record.channel,
+                        record.timestamp,
+                        record.badges.join(","),
+                        record.color,
+                        record.bits.map(|b| b as i64),
+                        record.message_text,
+                        line,
+                    ])?,
+                    Some(event_type) => event.execute(params![
+                        self.session,
+                        seq,
+                        event_type,
+                        record.message_id,
+                        record.user_id,
+                        record.sender_login,
+                        record.channel,
+                        record.timestamp,
+                        record.message_text,
+                        line,
+                    ])?,
+                };
+            }
+        }
+        tx.commit()
+    }
+}
+
+impl Sink for SqliteSink {
+    fn name(&self) -> &str {
+        SQLITE_SINK
+    }
+
+    fn accepts(&self, entry: &LogEntry) -> bool {
+        entry.bucket == "msgs" && !self.skip.contains(&entry.channel)
+    }
+
+    fn write(&mut self, entry: &LogEntry) -> io::Result<()> {
+        let record = entry.record.as_deref().cloned().unwrap_or_else(|| entry_record(entry));
+        self.pending.push((entry.seq, entry.line.clone(), record));
+        if self.pending.len() >= BATCH_ROWS {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        // A failed batch is dropped, retrying it would fail the same way
+        let result = self.insert_batch().map_err(io::Error::other);
+        self.pending.clear();
+        result
+    }
+
+    fn idle(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() && self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn idle_tick(&self) -> Option<Duration> {
+        Some(FLUSH_INTERVAL)
+    }
+}
+
+/// Register the sqlite sink if `sqlite_db` is set. Left out with `--dry-run`.
+pub fn spawn_sqlite(state: &LoggerState) {
+    let Some(path) = state.settings.lock().unwrap().sqlite_db.value.clone() else {
+        return;
+    };
+    if is_dry_run() {
+        print_status("dry-run: nothing is written to the database");
+        return;
+    }
+    let conn = match open_database(Path::new(&path)) {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("⚠️ Failed to open {}: {}", path, e);
+            return;
+        }
+    };
+    print_status(&format!("Writing messages and events to {}", path));
+    let policy = SinkPolicy { capacity: SQLITE_CAPACITY, backpressure: Backpressure::Drop, disable_on_error: false };
+    state.sinks.register(Box::new(SqliteSink::new(conn, encrypted_channels(state))), policy);
+}
+
+/// All lines of `channel` logged this session, from the database, when `in_memory` (the
+/// message log snapshot) is missing the older ones. `None` means `in_memory` is the log to
+/// use: nothing was trimmed, the sqlite sink is not running, or the database is not
+/// complete for the channel (warned about). Flushes the sinks first.
+pub fn session_lines(state: &LoggerState, channel: &str, in_memory: &[LogLine]) -> Option<Vec<String>> {
+    let trimmed = state.trimmed_lines.lock().unwrap().get(channel).copied().unwrap_or(0);
+    if trimmed == 0 || encrypts(state, channel) {
+        return None;
+    }
+    let path = state.settings.lock().unwrap().sqlite_db.value.clone()?;
+    state.sinks.flush();
+    let loaded = state.loaded_lines.lock().unwrap().contains_key(channel);
+    if !state.sinks.is_complete(SQLITE_SINK) || loaded {
+        warn_incomplete(&path, channel, in_memory.len());
+        return None;
+    }
+    match read_session_lines(Path::new(&path), channel, &session_id()) {
+        // More rows than expected are lines logged since the snapshot
+        Ok(lines) if lines.len() >= in_memory.len() + trimmed => Some(lines),
+        Ok(_) => {
+            warn_incomplete(&path, channel, in_memory.len());
+            None
+        }
+        Err(e) => {
+            eprintln!("⚠️ Failed to read {}: {}", path, e);
+            None
+        }
+    }
+}
+
+fn warn_incomplete(path: &str, channel: &str, kept: usize) {
+    eprintln!("⚠️ {} is missing lines of #{}, using the last {} lines in memory", path, channel, kept);
+}
+
+fn read_session_lines(path: &Path, channel: &str, session: &str) -> rusqlite::Result<Vec<String>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut query = conn.prepare(SESSION_LINES)?;
+    let lines = query.query_map(params![channel, session], |row| row.get(0))?;
+    lines.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+    use std::sync::Arc;
+    use twitch_irc::message::{IRCMessage, ServerMessage};
+
+    fn privmsg_entry(seq: u64, channel: &str, login: &str, user_id: &str, text: &str) -> LogEntry {
+        let raw = format!(
+            "@badge-info=;badges=subscriber/12;color=#FF4500;display-name={login};emotes=;id=m{seq};room-id=1;tmi-sent-ts=1594562632383;user-id={user_id} :{login}!{login}@{login}.tmi.twitch.tv PRIVMSG #{channel} :{text}"
+        );
+        let ServerMessage::Privmsg(msg) = ServerMessage::try_from(IRCMessage::parse(&raw).unwrap()).unwrap() else {
+            panic!("not a PRIVMSG");
+        };
+        LogEntry {
+            bucket: "msgs",
+            channel: channel.to_string(),
+            line: format!("12:00:{:02} <{}> [sub/12]\n{}\n", seq, login, text),
+            normalized: false,
+            seq,
+            received: Local::now(),
+            record: Some(Arc::new(LogRecord::from_privmsg(Local::now(), &msg, text))),
+        }
+    }
+
+    #[test]
+    fn messages_and_events_are_queryable() {
+        let path = std::env::temp_dir().join(format!("twitch_logger_sqlite_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut sink = SqliteSink::new(open_database(&path).unwrap(), vec!["secret".to_string()]);
+
+        let ban = LogEntry {
+            bucket: "msgs",
+            channel: "forsen".to_string(),
+            line: "12:00:03 USER_BANNED: [#forsen] troll".to_string(),
+            normalized: false,
+            seq: 3,
+            received: Local::now(),
+            record: Some(Arc::new(LogRecord::moderation(Local::now(), "forsen", "USER_BANNED", Some("troll"), "troll"))),
+        };
+        for entry in [privmsg_entry(1, "forsen", "alice", "42", "hi"), privmsg_entry(4, "xqcow", "alice", "42", "yo"), ban, privmsg_entry(2, "forsen", "bob", "7", "hey")] {
+            sink.write(&entry).unwrap();
+        }
+        assert!(!sink.accepts(&privmsg_entry(5, "secret", "alice", "42", "psst")));
+        sink.flush().unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let mut query = conn.prepare(
+            "SELECT c.name, m.text, m.message_id FROM messages m JOIN channels c ON c.id = m.channel_id WHERE m.user_id = '42' ORDER BY m.seq",
+        ).unwrap();
+        let alice: Vec<(String, String, String)> = query.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?))).unwrap().map(Result::unwrap).collect();
+        assert_eq!(alice, [("forsen".to_string(), "hi".to_string(), "m1".to_string()), ("xqcow".to_string(), "yo".to_string(), "m4".to_string())]);
+        let event: String = conn.query_row("SELECT event_type FROM events", [], |r| r.get(0)).unwrap();
+        assert_eq!(event, "USER_BANNED");
+        let login: String = conn.query_row("SELECT login FROM users WHERE id = '7'", [], |r| r.get(0)).unwrap();
+        assert_eq!(login, "bob");
+
+        let lines = read_session_lines(&path, "forsen", &session_id()).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].contains("<bob>") && lines[2].contains("USER_BANNED"), "{:?}", lines);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[test]
+    fn dropped_rows_leave_the_log_in_memory() {
+        let path = std::env::temp_dir().join(format!("twitch_logger_sqlite_dropped_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().sqlite_db.value = Some(path.display().to_string());
+        let policy = SinkPolicy { capacity: 16, backpressure: Backpressure::Drop, disable_on_error: false };
+        state.sinks.register(Box::new(SqliteSink::new(open_database(&path).unwrap(), Vec::new())), policy);
+
+        // Three lines logged, memory kept the last one
+        for seq in 1..=3 {
+            state.sinks.dispatch(privmsg_entry(seq, "forsen", "alice", "42", "hi"));
+        }
+        let memory = vec![LogLine::Text(privmsg_entry(3, "forsen", "alice", "42", "hi").line)];
+        assert_eq!(session_lines(&state, "forsen", &memory), None, "nothing trimmed, memory has it all");
+        state.trimmed_lines.lock().unwrap().insert("forsen".to_string(), 2);
+        assert_eq!(session_lines(&state, "forsen", &memory).map(|lines| lines.len()), Some(3));
+
+        // Fewer rows than the channel logged
+        state.trimmed_lines.lock().unwrap().insert("forsen".to_string(), 5);
+        assert_eq!(session_lines(&state, "forsen", &memory), None);
+        state.trimmed_lines.lock().unwrap().insert("forsen".to_string(), 2);
+
+        // A row that never reached the database
+        state.sinks.set_enabled(SQLITE_SINK, false);
+        state.sinks.dispatch(privmsg_entry(4, "forsen", "alice", "42", "lost"));
+        state.sinks.set_enabled(SQLITE_SINK, true);
+        state.sinks.dispatch(privmsg_entry(5, "forsen", "alice", "42", "hi"));
+        assert_eq!(session_lines(&state, "forsen", &memory), None);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+use twitch_irc::login::LoginCredentials;
+use twitch_irc::transport::Transport;
+use twitch_irc::TwitchIRCClient;
+
+use crate::capacity::{check_join, threshold_warning, JoinCheck};
+use crate::console::print_status;
+use crate::prune::{record_join_failure, NOT_CONFIRMED};
+use crate::state::{LoggerState, CONFIG};
+use crate::stray::{clear_parted, mark_parted};
+use crate::vip_parts::release_parts;
+
+/// Pause between the initial JOINs, so the connection pool is not hit with all of them at once.
+const JOIN_STAGGER: Duration = Duration::from_millis(100);
+/// After this long the initial channels that are not confirmed yet are reported as pending.
+const JOIN_CONFIRM_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Channels from the command line and the `--channel-file`, or the default channels
+/// from channels.txt if neither names any.
+pub fn initial_channels(cli_channels: &[String], file_channels: &[String]) -> Vec<String> {
+    let mut channels = cli_channels.to_vec();
+    for channel in file_channels {
+        if !channels.contains(channel) {
+            channels.push(channel.clone());
+        }
+    }
+    if channels.is_empty() {
+        channels = CONFIG.default_channels.to_vec();
+    }
+    channels
+}
+
+/// JOIN a channel at runtime and add it to the joined channels.
+pub fn join_channel<T: Transport, L: LoginCredentials>(client: &TwitchIRCClient<T, L>, state: &LoggerState, channel: &str) {
+    clear_parted(state, channel);
+    let _ = client.join(channel.to_string());
+    {
+        let mut channels = state.channels.lock().unwrap();
+        if !channels.iter().any(|c| c == channel) {
+            channels.push(channel.to_string());
+        }
+    }
+    state.channel_joined_at.lock().unwrap().insert(channel.to_string(), Instant::now());
+    state.refresh_channel_width();
+}
+
+/// PART a channel at runtime; messages still arriving for it are handled by `divert_stray`.
+pub fn part_channel<T: Transport, L: LoginCredentials>(client: &TwitchIRCClient<T, L>, state: &LoggerState, channel: &str) {
+    client.part(channel.to_string());
+    mark_parted(state, channel);
+    release_parts(state, Some(channel));
+    state.sinks.close_channel(channel);
+    state.channel_joined_at.lock().unwrap().remove(channel);
+    state.channels.lock().unwrap().retain(|c| c != channel);
+    state.refresh_channel_width();
+}
+
+/// `startup_delay` setting: wait before the first JOIN, so several loggers started together
+/// don't all connect and join at the same moment.
+pub async fn startup_delay(state: &LoggerState) {
+    let seconds = state.settings.lock().unwrap().startup_delay.value;
+    if seconds > 0 {
+        print_status(&format!("Waiting {}s before joining channels", seconds));
+        tokio::time::sleep(Duration::from_secs(seconds)).await;
+    }
+}
+
+/// Join the channels of `state` one after another with progress output.
+/// Invalid channel names are reported and dropped instead of aborting the startup.
+pub async fn join_initial_channels<T: Transport, L: LoginCredentials>(
+    client: &TwitchIRCClient<T, L>,
+    state: &LoggerState,
+) {
+    let channels = state.channels.lock().unwrap().clone();
+    let total = channels.len();
+    let mut joined = Vec::new();
+
+    for (i, channel) in channels.into_iter().enumerate() {
+        match client.join(channel.clone()) {
+            Ok(()) => {
+                print_status(&format!("joining {}/{}: {}", i + 1, total, channel));
+                state.channel_joined_at.lock().unwrap().insert(channel.clone(), Instant::now());
+                joined.push(channel);
+            }
+            Err(e) => eprintln!("⚠️ Skipping channel '{}': {}", channel, e),
+        }
+        tokio::time::sleep(JOIN_STAGGER).await;
+    }
+
+    if joined.len() != total {
+        state.channels.lock().unwrap().retain(|c| joined.contains(c));
+        state.sound_channels.lock().unwrap().retain(|c| joined.contains(c));
+        state.refresh_channel_width();
+    }
+    // The initial channels were asked for explicitly, so no cap, only the warnings
+    if let JoinCheck::Allowed { warnings } = check_join(0, joined.len(), &state.settings.lock().unwrap().channel_warnings.value, 0, true) {
+        if let Some(&highest) = warnings.last() {
+            eprintln!("{}", threshold_warning(highest));
+        }
+    }
+
+    // Report in the background, the logger is already running by then
+    let client = client.clone();
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(JOIN_CONFIRM_TIMEOUT).await;
+        let mut pending = Vec::new();
+        for channel in &joined {
+            let (_wanted, confirmed) = client.get_channel_status(channel.clone()).await;
+            if !confirmed {
+                record_join_failure(&state, channel, NOT_CONFIRMED);
+                pending.push(channel.as_str());
+            }
+        }
+        if pending.is_empty() {
+            print_status(&format!("All {} channels joined", joined.len()));
+        } else {
+            print_status(&format!(
+                "{}/{} channels joined, never confirmed: {} (renamed or deleted? PRUNE parts them)",
+                joined.len() - pending.len(),
+                joined.len(),
+                pending.join(", ")
+            ));
+        }
+    });
+}
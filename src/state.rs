@@ -0,0 +1,347 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+use chrono::prelude::*;
+use chrono_tz::Europe::Berlin;
+use once_cell::sync::{Lazy, OnceCell};
+
+use crate::alltime::SessionTotals;
+use crate::anomaly::ChatterSpikeDetector;
+use crate::autosave::UnsavedLines;
+use crate::buckets::LogLine;
+use crate::channel_config::{ChannelConfig, ChannelInfo, load_channel_config};
+use crate::diag::UnknownMessages;
+use crate::encryption::LogKey;
+use crate::incident::IncidentTracker;
+use crate::irc_relay::RelayHub;
+use crate::membership::{ChannelMembership, JoinQueue};
+use crate::raids::Raid;
+use crate::records::MessageRecords;
+use crate::redaction::Redactor;
+use crate::stats::{ChannelLatency, NotificationStats};
+use crate::say_queue::SayQueue;
+use crate::sequence::LogOrder;
+use crate::settings::Settings;
+use crate::sink::SinkRegistry;
+use crate::spam::CrossChannelSpam;
+use crate::status_bar::MessageRate;
+use crate::subs::SubCounts;
+use crate::tags::{load_session_tags, SessionTags};
+use crate::vip_incidents::VipIncident;
+use crate::vip_parts::PendingParts;
+use crate::vip_visits::{load_vip_join_counts, VipJoinCounts};
+
+/// Per-channel list of log lines.
+pub type LogStore = Arc<Mutex<HashMap<String, Vec<LogLine>>>>;
+
+/// `--config`, set by `set_config_file` before anything reads `CONFIG`.
+static CONFIG_OVERRIDE: OnceCell<PathBuf> = OnceCell::new();
+
+/// `--config <path>`: use this channels.txt instead of looking for one.
+pub fn set_config_file(path: PathBuf) {
+    let _ = CONFIG_OVERRIDE.set(path);
+}
+
+/// Where channels.txt is looked for without `--config`, in order:
+/// `$XDG_CONFIG_HOME/rustTwitchLogger/channels.txt`, then `~/.rustTwitchLogger/channels.txt`.
+pub fn config_candidates() -> Vec<PathBuf> {
+    let var = |name: &str| env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    candidates_in(var("XDG_CONFIG_HOME"), var("HOME"))
+}
+
+fn candidates_in(xdg_config_home: Option<PathBuf>, home: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(xdg) = xdg_config_home {
+        candidates.push(xdg.join("rustTwitchLogger").join("channels.txt"));
+    }
+    if let Some(home) = home {
+        candidates.push(home.join(".rustTwitchLogger").join("channels.txt"));
+    }
+    candidates
+}
+
+/// `override_path`, otherwise the first of `candidates` that exists, otherwise the first
+/// one (where one would go).
+fn pick_config_file(override_path: Option<&Path>, candidates: &[PathBuf]) -> Option<PathBuf> {
+    if let Some(path) = override_path {
+        return Some(path.to_path_buf());
+    }
+    candidates.iter().find(|path| path.is_file()).or(candidates.first()).cloned()
+}
+
+/// The channels.txt in use: `--config`, otherwise the first candidate that exists, otherwise
+/// the first candidate (where one would go). `None` without `HOME` and `XDG_CONFIG_HOME`.
+pub static CONFIG_FILE: Lazy<Option<PathBuf>> = Lazy::new(|| pick_config_file(CONFIG_OVERRIDE.get().map(PathBuf::as_path), &config_candidates()));
+
+/// Directory of the logger's own files (journal, tags, all-time stats, the default log
+/// directory): the one channels.txt is in, or `.rustTwitchLogger` in the working
+/// directory without `HOME` and `XDG_CONFIG_HOME`.
+pub static DATA_DIR: Lazy<PathBuf> = Lazy::new(|| data_dir_of(CONFIG_FILE.as_deref()));
+
+fn data_dir_of(config_file: Option<&Path>) -> PathBuf {
+    match config_file.map(|file| file.parent().unwrap_or(Path::new(""))) {
+        Some(dir) if dir.as_os_str().is_empty() => PathBuf::from("."),
+        Some(dir) => dir.to_path_buf(),
+        None => PathBuf::from(".rustTwitchLogger"),
+    }
+}
+
+/// `name` in `DATA_DIR`.
+pub fn data_file(name: &str) -> PathBuf {
+    DATA_DIR.join(name)
+}
+
+/// `CONFIG_FILE` for messages.
+pub fn config_file_name() -> String {
+    CONFIG_FILE.as_ref().map_or_else(|| "channels.txt".to_string(), |path| path.display().to_string())
+}
+
+pub static CONFIG: Lazy<Arc<ChannelConfig>> = Lazy::new(|| {
+    let Some(path) = CONFIG_FILE.as_ref().filter(|path| path.is_file()) else {
+        if let Some(path) = CONFIG_OVERRIDE.get() {
+            eprintln!("⚠️ --config {}: no such file", path.display());
+            process::exit(1);
+        }
+        let looked: Vec<String> = config_candidates().iter().map(|path| path.display().to_string()).collect();
+        eprintln!("No channels.txt found (looked for {}), starting without VIPs; --config <path> picks one", looked.join(", "));
+        return Arc::new(ChannelConfig::default());
+    };
+    match load_channel_config(path) {
+        Ok(cfg) => Arc::new(cfg),
+    Err(e) => {
+        eprintln!("⚠️ Warning: Failed to load {}: {e}", path.display());
+        process::exit(1);
+    }
+    }
+});
+
+pub static STARTUP_DATE: Lazy<String> = Lazy::new(date_stamp);
+
+/// "Sa_17_10_2026" for today, the date part of saved file names.
+pub fn date_stamp() -> String {
+    let now = Utc::now().with_timezone(&Berlin);
+    // Get the abbreviated weekday (e.g., "Sa")
+    let day_abbr = &now.format("%a").to_string()[0..2];
+    format!("{}_{}", day_abbr, now.format("%d_%m_%Y"))
+}
+
+/// When this logger process was started.
+pub static SESSION_START: Lazy<DateTime<Local>> = Lazy::new(Local::now);
+
+/// Everything the message handlers and the command loop share.
+/// Cloning is cheap, every field is an `Arc`.
+#[derive(Clone, Default)]
+pub struct LoggerState {
+    pub channels: Arc<Mutex<Vec<String>>>,
+    pub logs: LogStore,
+    pub join_logs: LogStore,
+    /// Bans, timeouts, deletions and clears, also in `logs` with `moderation_in_msgs`.
+    pub moderation_logs: LogStore,
+    /// New join log lines, see `JoinQueue`.
+    pub join_queue: JoinQueue,
+    /// Raw messages that arrived shortly after PART (`stray_messages = bucket`).
+    pub stray_messages: LogStore,
+    /// Display names of channels, seen on messages of the broadcaster (login -> name).
+    pub channel_display_names: Arc<Mutex<HashMap<String, String>>>,
+    /// When each joined channel was joined, shown by COUNTUP.
+    pub channel_joined_at: Arc<Mutex<HashMap<String, Instant>>>,
+    /// Time of the first message log line of each channel this session, see `timestamps`.
+    pub first_line_times: Arc<Mutex<HashMap<String, NaiveTime>>>,
+    /// Entries at the start of a channel's log that came from `LOAD`, not from this session.
+    pub loaded_lines: Arc<Mutex<HashMap<String, usize>>>,
+    /// Message log lines of each streamed channel no longer in memory, see `stream`.
+    pub trimmed_lines: Arc<Mutex<HashMap<String, usize>>>,
+    /// Channels PARTed recently and when, see `divert_stray`.
+    pub recently_parted: Arc<Mutex<HashMap<String, Instant>>>,
+    pub sound_channels: Arc<Mutex<HashSet<String>>>,
+    /// When each SOUND channel last beeped, for its `sound_cooldown`.
+    pub last_sounds: Arc<Mutex<HashMap<String, Instant>>>,
+    pub notification_channels: Arc<Mutex<HashSet<String>>>,
+    pub incidents: Arc<Mutex<HashMap<String, IncidentTracker>>>,
+    /// JOIN/PART logging mode and counts per channel.
+    pub membership: Arc<Mutex<HashMap<String, ChannelMembership>>>,
+    /// Chat messages per user (channel -> user -> count), shown by USERS.
+    pub user_message_counts: Arc<Mutex<HashMap<String, HashMap<String, u32>>>>,
+    /// Longest and most repeated message per channel, shown by REPORT.
+    pub message_records: Arc<Mutex<HashMap<String, MessageRecords>>>,
+    /// New chatters per minute, for the `[ANOMALY]` alert.
+    pub chatter_spikes: Arc<Mutex<HashMap<String, ChatterSpikeDetector>>>,
+    /// Chat messages per channel and `heatmap::slot`, merged into the all-time stats.
+    pub hourly_messages: Arc<Mutex<HashMap<String, BTreeMap<usize, u64>>>>,
+    /// Texts of the last minute across all channels, for the `[SPAM]` alert.
+    pub cross_channel_spam: Arc<Mutex<CrossChannelSpam>>,
+    /// Users whose messages are logged but not printed, set by MUTE and `spam_auto_mute`.
+    pub muted_users: Arc<Mutex<HashSet<String>>>,
+    /// VIP PARTs waiting out `vip_part_grace`, see `vip_parts`.
+    pub pending_parts: Arc<Mutex<PendingParts>>,
+    /// VIP join counts across sessions, saved on clean exit.
+    pub vip_join_counts: Arc<Mutex<VipJoinCounts>>,
+    /// This session's numbers as last merged into the all-time stats, see `alltime`.
+    pub alltime_merged: Arc<Mutex<HashMap<String, SessionTotals>>>,
+    /// Clients of `--irc-listen`, sent every chat message of the channels they joined.
+    pub irc_relay: RelayHub,
+    /// Key for the channels with `encrypt=true`, set at startup by `setup_encryption`.
+    pub log_key: Arc<Mutex<Option<Arc<LogKey>>>>,
+    /// `TAG` labels of saved files, kept across restarts.
+    pub session_tags: Arc<Mutex<SessionTags>>,
+    /// Receive delay of chat messages per channel, shown by STATS.
+    pub latency: Arc<Mutex<HashMap<String, ChannelLatency>>>,
+    /// Delivery of desktop notifications, shown by STATS.
+    pub notifications: Arc<Mutex<NotificationStats>>,
+    /// Logins that got a gift sub this session per channel, counted in the REPORT subs.
+    pub gift_recipients: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+    /// Subs by tier and the longest shared streak per channel, shown by SUBS.
+    pub sub_counts: Arc<Mutex<HashMap<String, SubCounts>>>,
+    /// Channels a save is writing right now, see `save::SaveGuard`.
+    pub saving: Arc<Mutex<HashSet<String>>>,
+    /// Notified whenever a channel leaves `saving`.
+    pub save_finished: Arc<Condvar>,
+    /// `[redaction]` patterns of channels.txt, plus the defaults with `redact_defaults`.
+    pub redactor: Arc<Redactor>,
+    /// Redacted spans per channel and pattern, shown by STATS.
+    pub redaction_hits: Arc<Mutex<HashMap<String, BTreeMap<String, u64>>>>,
+    /// Messages without a handler by type, shown by DIAG.
+    pub unknown_messages: Arc<Mutex<UnknownMessages>>,
+    /// Channels that could not be joined and why, see `prune`.
+    pub join_failures: Arc<Mutex<BTreeMap<String, String>>>,
+    /// Raids in the logged channels, oldest first, shown by RAIDS.
+    pub raids: Arc<Mutex<Vec<Raid>>>,
+    /// Bans and timeouts of VIPs, oldest first, shown by SUMMARY.
+    pub vip_incidents: Arc<Mutex<Vec<VipIncident>>>,
+    /// VIPs added with `VIP IMPORT` this session, see `vip_import`.
+    pub imported_vips: Arc<Mutex<BTreeSet<String>>>,
+    /// Width of the `[channel]` console column, see `refresh_channel_width`.
+    pub channel_width: Arc<AtomicUsize>,
+    /// The `stream` sink is running, see `stream`.
+    pub streaming: Arc<AtomicBool>,
+    /// Sequence numbers at the end of each log, see `sequence`.
+    pub log_order: Arc<Mutex<LogOrder>>,
+    /// Channel selected with `TAIL`; while set, other channels are logged but not printed.
+    pub tail: Arc<Mutex<Option<String>>>,
+    /// Most recently saved message log per channel, opened by `OPEN`.
+    pub last_saved: Arc<Mutex<HashMap<String, String>>>,
+    /// Outgoing SAY messages, see `say_queue`.
+    pub say_queue: SayQueue,
+    /// Chat messages this session and in the last minute, for the status bar.
+    pub message_rate: Arc<Mutex<MessageRate>>,
+    /// When anything, PONGs included, last arrived from the server, see `health`.
+    pub last_received: Arc<Mutex<Option<Instant>>>,
+    /// When a save last wrote a file.
+    pub last_save: Arc<Mutex<Option<DateTime<Local>>>>,
+    /// Lines logged since the last save per channel, for the autosave and LIST.
+    pub unsaved: Arc<Mutex<UnsavedLines>>,
+    /// Set on shutdown, so the final save records when the session ended.
+    pub session_end: Arc<Mutex<Option<DateTime<Local>>>>,
+    /// Streaming outputs of the log lines, see `sink`.
+    pub sinks: SinkRegistry,
+    /// Effective settings, changed by flags and `CONFIG SET`.
+    pub settings: Arc<Mutex<Settings>>,
+    /// Sounds and desktop notifications; the headless archiver runs without them.
+    pub alerts: bool,
+    /// channels.txt as loaded at startup (`CONFIG`); empty in tests.
+    pub config: Arc<ChannelConfig>,
+}
+
+impl LoggerState {
+    pub fn new(initial_channels: &[String], alerts: bool) -> Self {
+        Lazy::force(&SESSION_START);
+
+        let sound_channels = if alerts {
+            initial_channels.iter().cloned().collect()
+        } else {
+            HashSet::new()
+        };
+
+        let settings = Settings::from_config(&CONFIG);
+        let redactor = Redactor::new(&CONFIG.redaction, settings.redact_defaults.value);
+
+        let state = Self {
+            channels: Arc::new(Mutex::new(initial_channels.to_vec())),
+            sound_channels: Arc::new(Mutex::new(sound_channels)),
+            vip_join_counts: Arc::new(Mutex::new(load_vip_join_counts())),
+            session_tags: Arc::new(Mutex::new(load_session_tags())),
+            settings: Arc::new(Mutex::new(settings)),
+            redactor: Arc::new(redactor),
+            alerts,
+            config: Arc::clone(&CONFIG),
+            ..Default::default()
+        };
+        state.refresh_channel_width();
+        state
+    }
+
+    /// The `console_channel_width` setting, otherwise the longest joined channel name.
+    /// Call again after JOIN/PART.
+    pub fn refresh_channel_width(&self) {
+        let fixed = self.settings.lock().unwrap().console_channel_width.value;
+        let width = fixed.unwrap_or_else(|| {
+            self.channels.lock().unwrap().iter().map(|c| c.chars().count()).max().unwrap_or(0)
+        });
+        self.channel_width.store(width, Ordering::Relaxed);
+    }
+
+    pub fn channel_width(&self) -> usize {
+        self.channel_width.load(Ordering::Relaxed)
+    }
+
+    /// Whether console output for `channel` should be shown (always, unless another channel is tailed).
+    pub fn is_visible(&self, channel: &str) -> bool {
+        self.tail.lock().unwrap().as_deref().is_none_or(|tailed| tailed == channel)
+    }
+
+    /// Whether `name` is a VIP: in channels.txt or imported with `VIP IMPORT`.
+    pub fn is_vip(&self, name: &str) -> bool {
+        self.config.vips.contains_key(name) || self.imported_vips.lock().unwrap().contains(name)
+    }
+
+    /// The channels.txt options of VIP `name`, the defaults for an imported one. `None` if
+    /// `name` is no VIP.
+    pub fn vip_info(&self, name: &str) -> Option<ChannelInfo> {
+        match self.config.vips.get(name) {
+            Some(info) => Some(info.clone()),
+            None => self.imported_vips.lock().unwrap().contains(name).then(ChannelInfo::default),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_is_looked_up_in_xdg_then_home() {
+        let xdg = PathBuf::from("/x/config");
+        let home = PathBuf::from("/x/home");
+        assert_eq!(
+            candidates_in(Some(xdg.clone()), Some(home.clone())),
+            [xdg.join("rustTwitchLogger/channels.txt"), home.join(".rustTwitchLogger/channels.txt")]
+        );
+        assert_eq!(candidates_in(None, Some(home.clone())), [home.join(".rustTwitchLogger/channels.txt")]);
+        assert!(candidates_in(None, None).is_empty());
+
+        // The first existing candidate wins, otherwise the first one; --config beats both
+        let dir = std::env::temp_dir().join(format!("twitch_logger_config_{}", std::process::id()));
+        let existing = dir.join(".rustTwitchLogger/channels.txt");
+        std::fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        std::fs::write(&existing, "0\n").unwrap();
+        let missing = dir.join("nope/channels.txt");
+        assert_eq!(pick_config_file(None, &[missing.clone(), existing.clone()]), Some(existing.clone()));
+        assert_eq!(pick_config_file(None, std::slice::from_ref(&missing)), Some(missing.clone()));
+        assert_eq!(pick_config_file(Some(Path::new("my.txt")), std::slice::from_ref(&existing)), Some(PathBuf::from("my.txt")));
+        assert_eq!(pick_config_file(None, &[]), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn data_files_live_next_to_channels_txt() {
+        assert_eq!(data_dir_of(Some(Path::new("/x/home/.rustTwitchLogger/channels.txt"))), Path::new("/x/home/.rustTwitchLogger"));
+        assert_eq!(data_dir_of(Some(Path::new("channels.txt"))), Path::new("."));
+        assert_eq!(data_dir_of(None), Path::new(".rustTwitchLogger"));
+        assert_eq!(data_file("tags.json"), DATA_DIR.join("tags.json"));
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::build_info::build_info;
+use crate::state::SESSION_START;
+
+/// Numbers shown by STATS and in the header of saved log files.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChannelStats {
+    pub channel: String,
+    pub message_count: usize,
+    pub unique_chatters: usize,
+    pub moderation_events: usize,
+    pub sub_events: usize,
+    pub raid_events: usize,
+    /// Highest number of chat messages within one clock minute.
+    pub peak_messages_per_minute: usize,
+    /// The minute ("HH:MM") the peak happened in.
+    pub peak_minute: Option<String>,
+    /// RFC 3339 time the logger was started.
+    pub session_start: String,
+    /// Which binary produced these numbers.
+    pub build: String,
+}
+
+/// Whether a stored log line is a chat message ("HH:MM:SS <user> ...").
+pub fn is_chat_line(line: &str) -> bool {
+    line.matches("<").count() == 1 && line.contains(">")
+}
+
+/// Classify the stored log lines of one channel.
+pub fn compute_channel_stats(channel: &str, messages: &[String]) -> ChannelStats {
+    let mut msg_count = 0;
+    let mut unique_chatters = HashSet::new();
+    let mut mod_events = 0;
+    let mut sub_events = 0;
+    let mut raid_events = 0;
+    let mut per_minute: HashMap<&str, usize> = HashMap::new();
+
+    for line in messages {
+        if line.contains("<SUBORRESUB") || line.contains("<SUBGIFT") || line.contains("<SUBMYSTERYGIFT")
+            || line.contains("<ANONSUBMYSTERYGIFT") || line.contains("<GIFTPAIDUPGRADE") || line.contains("ANONPAIDGIFTUPGRADE") {
+                sub_events += 1;
+            } else if line.contains("USER_BANNED") || line.contains("CLEARMSG") || line.contains("TIMEOUT") {
+                mod_events += 1;
+            } else if line.contains("<RAID") {
+                raid_events += 1;
+            } else if is_chat_line(line) {
+                msg_count += 1;
+                if let Some(start) = line.find('<') {
+                    if let Some(end) = line.find('>') {
+                        let username = &line[start + 1..end];
+                        if let Some(uname) = username.split(']').next_back() {
+                            unique_chatters.insert(uname.trim().to_string());
+                        }
+                    }
+                }
+                // "HH:MM:SS ..." -> bucket by "HH:MM"
+                if let Some(minute) = line.get(0..5) {
+                    *per_minute.entry(minute).or_default() += 1;
+                }
+            }
+    }
+
+    let peak = per_minute
+    .into_iter()
+    .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)));
+
+    ChannelStats {
+        channel: channel.to_string(),
+        message_count: msg_count,
+        unique_chatters: unique_chatters.len(),
+        moderation_events: mod_events,
+        sub_events,
+        raid_events,
+        peak_messages_per_minute: peak.map(|(_, n)| n).unwrap_or(0),
+        peak_minute: peak.map(|(m, _)| m.to_string()),
+        session_start: SESSION_START.to_rfc3339(),
+        build: build_info(),
+    }
+}
+
+/// Delay between Twitch's `tmi-sent-ts` and the message arriving on our socket.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChannelLatency {
+    pub samples: u64,
+    pub total_ms: i64,
+    pub max_ms: i64,
+}
+
+impl ChannelLatency {
+    pub fn record(&mut self, ms: i64) {
+        self.max_ms = if self.samples == 0 { ms } else { self.max_ms.max(ms) };
+        self.samples += 1;
+        self.total_ms += ms;
+    }
+
+    pub fn average_ms(&self) -> Option<i64> {
+        (self.samples > 0).then(|| self.total_ms / self.samples as i64)
+    }
+}
+
+/// One line for STATS, `None` before the first chat message came in.
+pub fn format_latency(latency: &ChannelLatency) -> Option<String> {
+    latency.average_ms().map(|avg| {
+        format!("latency: avg {} ms, max {} ms ({} msgs)", avg, latency.max_ms, latency.samples)
+    })
+}
+
+/// Delivery of desktop notifications during the session. A failed notification is
+/// otherwise only an easily missed line on stderr.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NotificationStats {
+    pub attempted: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Failures since the last successful notification.
+    #[serde(skip)]
+    pub consecutive_failures: u32,
+    #[serde(skip)]
+    warned: bool,
+}
+
+impl NotificationStats {
+    /// Count one notification. True once per session, when `warn_after` failures in a row
+    /// are reached (0 never warns).
+    pub fn record(&mut self, delivered: bool, warn_after: u32) -> bool {
+        self.attempted += 1;
+        if delivered {
+            self.succeeded += 1;
+            self.consecutive_failures = 0;
+            return false;
+        }
+        self.failed += 1;
+        self.consecutive_failures += 1;
+        if warn_after == 0 || self.warned || self.consecutive_failures < warn_after {
+            return false;
+        }
+        self.warned = true;
+        true
+    }
+}
+
+/// One line for STATS, `None` before the first notification.
+pub fn format_notification_stats(stats: &NotificationStats) -> Option<String> {
+    (stats.attempted > 0).then(|| {
+        format!(
+            "notifications: {} sent, {} delivered, {} failed",
+            stats.attempted, stats.succeeded, stats.failed
+        )
+    })
+}
+
+/// Width of the bar column in the USERS table.
+const USERS_BAR_WIDTH: usize = 20;
+
+/// USERS table: chatters by message count with their share of the session and a bar
+/// scaled to the most active chatter. `limit` cuts the list after that many rows.
+pub fn format_user_counts(counts: &HashMap<String, u32>, limit: usize) -> String {
+    let total: u32 = counts.values().sum();
+    let mut users: Vec<(&String, &u32)> = counts.iter().collect();
+    users.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.to_lowercase().cmp(&b.0.to_lowercase())));
+
+    let max = users.first().map(|(_, n)| **n).unwrap_or(0);
+    let name_width = users.iter().take(limit).map(|(u, _)| u.chars().count()).max().unwrap_or(0);
+
+    users
+    .iter()
+    .take(limit)
+    .map(|(user, count)| {
+        let share = **count as f64 * 100.0 / total as f64;
+        let bar = (**count as usize * USERS_BAR_WIDTH).div_ceil(max as usize);
+        format!(
+            "{:<name_width$} {:>5} {:>5.1}% {}",
+            user,
+            count,
+            share,
+            "#".repeat(bar),
+        )
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Human readable version printed by the STATS command.
+pub fn format_channel_stats(stats: &ChannelStats) -> String {
+    format!(
+        "# {}\n{} messages from {} chatters\n{} Banns, Deletions, and Timeouts\n{} Subs/Giftsubs\n{} Raids\npeak: {} msgs/min{}\nsession started: {}",
+        stats.channel,
+        stats.message_count,
+        stats.unique_chatters,
+        stats.moderation_events,
+        stats.sub_events,
+        stats.raid_events,
+        stats.peak_messages_per_minute,
+        stats.peak_minute.as_deref().map(|m| format!(" at {}", m)).unwrap_or_default(),
+        stats.session_start,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notification_failures_warn_once() {
+        let mut stats = NotificationStats::default();
+        assert!(!stats.record(false, 2));
+        assert!(stats.record(false, 2));
+        assert!(!stats.record(false, 2));
+        assert!(!stats.record(true, 2));
+        assert!(!stats.record(false, 2));
+        assert!(!stats.record(false, 2));
+        assert_eq!((stats.attempted, stats.succeeded, stats.failed), (6, 1, 5));
+        assert_eq!(stats.consecutive_failures, 2);
+    }
+}
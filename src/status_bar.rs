@@ -0,0 +1,198 @@
+//! The status line at the bottom of the terminal: joined channels, messages this session,
+//! messages per minute, unsaved lines and the connections. The last terminal row is
+//! kept out of the scroll region, so chat scrolls above it and the prompt is untouched.
+//! Only on a terminal and without `--quiet`/`--pipe`; `STATUSBAR ON|OFF` at runtime.
+
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+use twitch_irc::login::LoginCredentials;
+use twitch_irc::transport::Transport;
+use twitch_irc::{PoolStatus, TwitchIRCClient};
+
+use crate::console::{is_plain, styled};
+use crate::state::LoggerState;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(3);
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Terminal rows when the bar was last drawn, 0 while it is not shown.
+static DRAWN_ROWS: AtomicU16 = AtomicU16::new(0);
+
+/// Chat messages this session and in the last minute, for the status line.
+#[derive(Debug, Clone, Default)]
+pub struct MessageRate {
+    pub total: u64,
+    recent: VecDeque<Instant>,
+    pub last: Option<Instant>,
+}
+
+impl MessageRate {
+    pub fn record(&mut self, now: Instant) {
+        self.total += 1;
+        self.last = Some(now);
+        self.recent.push_back(now);
+        self.prune(now);
+    }
+
+    /// Messages within the last minute.
+    pub fn per_minute(&mut self, now: Instant) -> usize {
+        self.prune(now);
+        self.recent.len()
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while self.recent.front().is_some_and(|&t| now.saturating_duration_since(t) >= RATE_WINDOW) {
+            self.recent.pop_front();
+        }
+    }
+}
+
+/// What the status line shows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusCounters {
+    pub channels: usize,
+    pub messages: u64,
+    pub per_minute: usize,
+    pub unsaved: u64,
+    pub pool: PoolStatus,
+    /// Seconds since the last chat message.
+    pub quiet_for: Option<u64>,
+    /// SAY messages waiting to be sent.
+    pub say_queued: usize,
+}
+
+/// "12 channels │ 34512 msgs │ 820/min │ 1204 unsaved │ 2 conn, 12/12 joined"
+pub fn format_status(counters: &StatusCounters) -> String {
+    let pool = &counters.pool;
+    let mut connection = format!("{} conn, {}/{} joined", pool.connections, pool.server_channels, pool.wanted_channels);
+    if pool.pending_joins > 0 {
+        connection.push_str(&format!(", {} pending", pool.pending_joins));
+    }
+    if pool.connections == 0 && pool.wanted_channels > 0 {
+        connection.push_str(", disconnected");
+    }
+    let mut parts = vec![
+        format!("{} channels", counters.channels),
+        format!("{} msgs", counters.messages),
+        format!("{}/min", counters.per_minute),
+        format!("{} unsaved", counters.unsaved),
+        connection,
+    ];
+    // Long silence across all channels is worth noticing, a few seconds is not
+    if let Some(secs) = counters.quiet_for.filter(|&s| s >= 60) {
+        parts.push(format!("last msg {}s ago", secs));
+    }
+    if counters.say_queued > 0 {
+        parts.push(format!("{} SAY queued", counters.say_queued));
+    }
+    parts.join(" │ ")
+}
+
+/// Whether a bar can be shown at all: stdout is a terminal and not in pipe mode.
+pub fn is_supported() -> bool {
+    !is_plain() && io::stdout().is_terminal()
+}
+
+fn counters(state: &LoggerState, pool: PoolStatus) -> StatusCounters {
+    let now = Instant::now();
+    let mut rate = state.message_rate.lock().unwrap();
+    StatusCounters {
+        channels: state.channels.lock().unwrap().len(),
+        messages: rate.total,
+        per_minute: rate.per_minute(now),
+        unsaved: state.unsaved.lock().unwrap().total(),
+        pool,
+        quiet_for: rate.last.map(|t| now.saturating_duration_since(t).as_secs()),
+        say_queued: state.say_queue.len(),
+    }
+}
+
+/// Truncated to the terminal width, counting characters and not bytes.
+fn fit(text: &str, cols: u16) -> String {
+    text.chars().take(cols as usize).collect()
+}
+
+fn draw(text: &str) -> io::Result<()> {
+    let (cols, rows) = crossterm::terminal::size()?;
+    if rows < 2 {
+        return Ok(());
+    }
+    let mut out = io::stdout().lock();
+    if DRAWN_ROWS.swap(rows, Ordering::Relaxed) == 0 {
+        // Push the screen up a row, so the prompt isn't on the row taken by the bar
+        write!(out, "\n\x1b[1A")?;
+    }
+    // Save cursor, scroll region without the last row (this homes the cursor), bar on the
+    // last row, restore cursor
+    write!(out, "\x1b7\x1b[1;{}r\x1b[{};1H\x1b[2K{}\x1b8", rows - 1, rows, styled(&fit(text, cols)))?;
+    out.flush()
+}
+
+/// Remove the bar and give the last row back to the scroll region.
+pub fn clear() {
+    let rows = DRAWN_ROWS.swap(0, Ordering::Relaxed);
+    if rows == 0 {
+        return;
+    }
+    let mut out = io::stdout().lock();
+    let _ = write!(out, "\x1b7\x1b[r\x1b[{};1H\x1b[2K\x1b8", rows);
+    let _ = out.flush();
+}
+
+/// Refresh the bar every few seconds while the `status_bar` setting is on.
+pub fn spawn_status_bar<T: Transport, L: LoginCredentials>(state: LoggerState, client: TwitchIRCClient<T, L>) {
+    if !is_supported() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if !state.settings.lock().unwrap().status_bar.value {
+                clear();
+                continue;
+            }
+            let pool = client.pool_status().await;
+            if draw(&format_status(&counters(&state, pool))).is_err() {
+                clear();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_counts_the_last_minute() {
+        let start = Instant::now();
+        let mut rate = MessageRate::default();
+        for i in 0..3 {
+            rate.record(start + Duration::from_secs(i * 30));
+        }
+        assert_eq!(rate.total, 3);
+        assert_eq!(rate.per_minute(start + Duration::from_secs(60)), 2);
+        assert_eq!(rate.per_minute(start + Duration::from_secs(200)), 0);
+        assert_eq!(rate.total, 3);
+    }
+
+    #[test]
+    fn status_line_shows_the_counters() {
+        let mut counters = StatusCounters { channels: 2, messages: 120, per_minute: 40, unsaved: 7, quiet_for: Some(1), ..Default::default() };
+        counters.pool.connections = 1;
+        counters.pool.wanted_channels = 2;
+        counters.pool.server_channels = 2;
+        assert_eq!(format_status(&counters), "2 channels │ 120 msgs │ 40/min │ 7 unsaved │ 1 conn, 2/2 joined");
+
+        counters.pool.connections = 0;
+        counters.quiet_for = Some(90);
+        assert!(format_status(&counters).ends_with("disconnected │ last msg 90s ago"));
+        counters.say_queued = 3;
+        assert!(format_status(&counters).ends_with("last msg 90s ago │ 3 SAY queued"));
+        assert_eq!(fit("abcdef", 3), "abc");
+    }
+}
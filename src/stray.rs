@@ -0,0 +1,159 @@
+//! Messages Twitch still delivers for a channel shortly after we PARTed it.
+//! Without special handling they would re-create the channel's log.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use twitch_irc::message::{AsRawIRC, ServerMessage};
+
+use crate::buckets::append_line;
+use crate::state::LoggerState;
+
+/// How long after a PART messages of the channel count as stray.
+pub const PART_GRACE: Duration = Duration::from_secs(60);
+
+/// What happens to stray messages (`stray_messages = ...` in channels.txt).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StrayMode {
+    /// Ignore them.
+    #[default]
+    Drop,
+    /// Handle them normally if the channel still has a log, otherwise ignore them.
+    Append,
+    /// Keep the raw lines in the `stray` log bucket.
+    Bucket,
+}
+
+impl FromStr for StrayMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "drop" => Ok(StrayMode::Drop),
+            "append" => Ok(StrayMode::Append),
+            "bucket" => Ok(StrayMode::Bucket),
+            other => Err(format!("unknown stray message mode '{}' (drop, append, bucket)", other)),
+        }
+    }
+}
+
+impl fmt::Display for StrayMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StrayMode::Drop => "drop",
+            StrayMode::Append => "append",
+            StrayMode::Bucket => "bucket",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Call on PART: messages of the channel are stray for the next `PART_GRACE`.
+pub fn mark_parted(state: &LoggerState, channel: &str) {
+    state.recently_parted.lock().unwrap().insert(channel.to_string(), Instant::now());
+}
+
+/// Call on JOIN, so the channel is logged normally again.
+pub fn clear_parted(state: &LoggerState, channel: &str) {
+    state.recently_parted.lock().unwrap().remove(channel);
+}
+
+/// Channel of a message, from its `#channel` parameter.
+fn message_channel(message: &ServerMessage) -> Option<&str> {
+    message.source().params.first().and_then(|p| p.strip_prefix('#'))
+}
+
+/// Returns `true` if `message` is a stray message and was handled here, in which case
+/// it must not be dispatched as usual.
+pub fn divert_stray(time_str: &str, message: &ServerMessage, state: &LoggerState) -> bool {
+    let Some(channel) = message_channel(message) else {
+        return false;
+    };
+
+    {
+        let mut parted = state.recently_parted.lock().unwrap();
+        match parted.get(channel) {
+            None => return false,
+            Some(at) if at.elapsed() > PART_GRACE => {
+                parted.remove(channel);
+                return false;
+            }
+            Some(_) => {}
+        }
+    }
+
+    let mode = state.settings.lock().unwrap().stray_messages.value;
+    match mode {
+        StrayMode::Drop => true,
+        StrayMode::Append => !state.logs.lock().unwrap().contains_key(channel),
+        StrayMode::Bucket => {
+            append_line(state, "stray", channel, format!("{} {}", time_str, message.source().as_raw_irc()));
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buckets::LogLine;
+    use crate::handlers::handle_message;
+    use twitch_irc::message::IRCMessage;
+
+    fn clearmsg(channel: &str) -> ServerMessage {
+        let raw = format!(
+            "@login=someone;room-id=;target-msg-id=15e5164d-f8e6-4aec-baf4-2d6a330760c4;tmi-sent-ts=1594562632383 :tmi.twitch.tv CLEARMSG #{} :hello",
+            channel
+        );
+        ServerMessage::try_from(IRCMessage::parse(&raw).unwrap()).unwrap()
+    }
+
+    fn state_with(mode: StrayMode) -> LoggerState {
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().stray_messages.value = mode;
+        state
+    }
+
+    #[test]
+    fn message_after_part_is_dropped() {
+        let state = state_with(StrayMode::Drop);
+        mark_parted(&state, "pajlada");
+        handle_message("12:00:00", clearmsg("pajlada"), &state);
+        assert!(!state.logs.lock().unwrap().contains_key("pajlada"));
+    }
+
+    #[test]
+    fn message_after_part_goes_to_stray_bucket() {
+        let state = state_with(StrayMode::Bucket);
+        mark_parted(&state, "pajlada");
+        handle_message("12:00:00", clearmsg("pajlada"), &state);
+        assert!(!state.logs.lock().unwrap().contains_key("pajlada"));
+        let stray = state.stray_messages.lock().unwrap();
+        assert_eq!(stray["pajlada"].len(), 1);
+        assert!(stray["pajlada"][0].text().starts_with("12:00:00 @"));
+        assert!(stray["pajlada"][0].text().ends_with(" CLEARMSG #pajlada hello"));
+    }
+
+    #[test]
+    fn message_after_part_appends_only_to_existing_log() {
+        let state = state_with(StrayMode::Append);
+        state.logs.lock().unwrap().insert("pajlada".to_string(), vec![LogLine::Text("old".to_string())]);
+        mark_parted(&state, "pajlada");
+        mark_parted(&state, "forsen");
+        handle_message("12:00:00", clearmsg("pajlada"), &state);
+        handle_message("12:00:00", clearmsg("forsen"), &state);
+        let logs = state.logs.lock().unwrap();
+        assert_eq!(logs["pajlada"].len(), 2);
+        assert!(!logs.contains_key("forsen"));
+    }
+
+    #[test]
+    fn rejoin_logs_normally() {
+        let state = state_with(StrayMode::Drop);
+        mark_parted(&state, "pajlada");
+        clear_parted(&state, "pajlada");
+        handle_message("12:00:00", clearmsg("pajlada"), &state);
+        assert_eq!(state.logs.lock().unwrap()["pajlada"].len(), 1);
+    }
+}
@@ -0,0 +1,278 @@
+//! `stream_logs`: every log line is appended to `<channel>_<bucket>_<YYYY-MM-DD>.log` in
+//! `log_dir` as it comes in, and only the last `stream_memory_lines` lines of each log
+//! stay in memory for STATS, SEARCH and the other queries. Lines are held back for
+//! `late_part_window`, so a late VIP PART still lands in receive order, then written
+//! buffered and flushed every `FLUSH_LINES` lines or `FLUSH_INTERVAL`. A crash loses at
+//! most that, not the session. A PART closes the channel's files; a JOIN later the same
+//! day appends to them again. SAVE only flushes and writes the header of the message log as
+//! `<...>.summary.txt`. Encrypted channels are not streamed, they stay in memory and are
+//! saved sealed as before.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDate};
+
+use crate::buckets::format_message_header;
+use crate::console::print_status;
+use crate::encryption::{encrypted_channels, encrypts};
+use crate::output::{is_dry_run, write_file};
+use crate::save::log_dir;
+use crate::sink::{Backpressure, LogEntry, OrderBuffer, Sink, SinkPolicy};
+use crate::state::LoggerState;
+use crate::vip_parts::late_part_window;
+
+/// Name of the sink in `SINKS`.
+pub const STREAM_SINK: &str = "stream";
+/// Lines that can wait for the disk before the handlers wait for it.
+const STREAM_CAPACITY: usize = 4096;
+/// Buffered lines are written out after this many lines...
+const FLUSH_LINES: usize = 200;
+/// ...or after this long, whichever comes first.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// File `channel`'s `bucket` is streamed to on `date`.
+pub fn stream_file(dir: &Path, channel: &str, bucket: &str, date: NaiveDate) -> PathBuf {
+    dir.join(format!("{}_{}_{}.log", channel, bucket, date.format("%Y-%m-%d")))
+}
+
+struct OpenFile {
+    date: NaiveDate,
+    writer: BufWriter<File>,
+}
+
+pub struct StreamSink {
+    dir: PathBuf,
+    /// Encrypted channels, never written in plaintext.
+    skip: Vec<String>,
+    files: HashMap<(String, &'static str), OpenFile>,
+    order: OrderBuffer,
+    pending: usize,
+    last_flush: Instant,
+}
+
+impl StreamSink {
+    /// Lines are written once they were received `hold()` ago, see `OrderBuffer`.
+    pub fn new(dir: PathBuf, skip: Vec<String>, hold: impl Fn() -> Duration + Send + 'static) -> Self {
+        StreamSink { dir, skip, files: HashMap::new(), order: OrderBuffer::new(hold), pending: 0, last_flush: Instant::now() }
+    }
+
+    /// Open (append) the file of `entry` for today, closing the one of an earlier day.
+    fn file(&mut self, entry: &LogEntry, today: NaiveDate) -> io::Result<&mut BufWriter<File>> {
+        let key = (entry.channel.clone(), entry.bucket);
+        if self.files.get(&key).is_some_and(|open| open.date != today) {
+            if let Some(mut yesterday) = self.files.remove(&key) {
+                yesterday.writer.flush()?;
+            }
+        }
+        let open = match self.files.entry(key) {
+            Entry::Occupied(open) => open.into_mut(),
+            Entry::Vacant(vacant) => {
+                let path = stream_file(&self.dir, &entry.channel, entry.bucket, today);
+                let file = OpenOptions::new().create(true).append(true).open(&path)?;
+                let mut writer = BufWriter::new(file);
+                // Like the saved message log, for editors that need it to see UTF-8
+                if entry.bucket == "msgs" && writer.get_ref().metadata()?.len() == 0 {
+                    writer.write_all(&[0xEF, 0xBB, 0xBF])?;
+                }
+                vacant.insert(OpenFile { date: today, writer })
+            }
+        };
+        Ok(&mut open.writer)
+    }
+
+    fn flush_files(&mut self) -> io::Result<()> {
+        for open in self.files.values_mut() {
+            open.writer.flush()?;
+        }
+        self.pending = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    fn append(&mut self, entries: Vec<LogEntry>) -> io::Result<()> {
+        let today = Local::now().date_naive();
+        for entry in &entries {
+            let writer = self.file(entry, today)?;
+            writeln!(writer, "{}", entry.line)?;
+            self.pending += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for StreamSink {
+    fn name(&self) -> &str {
+        STREAM_SINK
+    }
+
+    fn accepts(&self, entry: &LogEntry) -> bool {
+        !self.skip.contains(&entry.channel)
+    }
+
+    fn write(&mut self, entry: &LogEntry) -> io::Result<()> {
+        self.order.push(entry);
+        let due = self.order.due(Local::now());
+        self.append(due)?;
+        if self.pending >= FLUSH_LINES {
+            self.flush_files()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the held lines too.
+    fn flush(&mut self) -> io::Result<()> {
+        let held = self.order.take(None);
+        self.append(held)?;
+        self.flush_files()
+    }
+
+    fn idle(&mut self) -> io::Result<()> {
+        let due = self.order.due(Local::now());
+        self.append(due)?;
+        if self.pending > 0 && self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush_files()?;
+        }
+        Ok(())
+    }
+
+    fn idle_tick(&self) -> Option<Duration> {
+        Some(FLUSH_INTERVAL)
+    }
+
+    fn close_channel(&mut self, channel: &str) -> io::Result<()> {
+        let held = self.order.take(Some(channel));
+        self.append(held)?;
+        let keys: Vec<(String, &'static str)> = self.files.keys().filter(|(c, _)| c == channel).cloned().collect();
+        for key in keys {
+            if let Some(mut open) = self.files.remove(&key) {
+                open.writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Register the stream sink if `stream_logs` is on. Like the journal, it is left out
+/// with `--dry-run`, and the logs stay in memory then.
+pub fn spawn_stream(state: &LoggerState) {
+    if !state.settings.lock().unwrap().stream_logs.value {
+        return;
+    }
+    if is_dry_run() {
+        print_status("dry-run: logs are not streamed to disk");
+        return;
+    }
+    let Some(dir) = log_dir(state) else {
+        return;
+    };
+    print_status(&format!("Streaming logs to {}", dir.display()));
+    let settings = state.settings.clone();
+    let sink = StreamSink::new(dir, encrypted_channels(state), move || late_part_window(&settings.lock().unwrap()));
+    // Lines streamed are dropped from memory, so none may be lost on the way
+    let policy = SinkPolicy { capacity: STREAM_CAPACITY, backpressure: Backpressure::Block, disable_on_error: false };
+    state.sinks.register(Box::new(sink), policy);
+    state.streaming.store(true, Ordering::Relaxed);
+}
+
+/// Whether the lines of `channel` go to disk as they come, and memory only keeps the last ones.
+pub fn is_streamed(state: &LoggerState, channel: &str) -> bool {
+    state.streaming.load(Ordering::Relaxed) && state.sinks.is_enabled(STREAM_SINK) && !encrypts(state, channel)
+}
+
+/// Lines of a streamed log kept in memory, `None` if `channel` is not streamed.
+pub fn memory_limit(state: &LoggerState, channel: &str) -> Option<usize> {
+    is_streamed(state, channel).then(|| state.settings.lock().unwrap().stream_memory_lines.value.max(1))
+}
+
+/// Cut a streamed log down to `keep` lines. Returns the number of lines dropped.
+pub fn trim_in_memory<T>(lines: &mut Vec<T>, keep: usize) -> usize {
+    let excess = lines.len().saturating_sub(keep);
+    lines.drain(..excess);
+    excess
+}
+
+/// SAVE of a streamed log: everything is on disk already, the message log gets its header
+/// as `<file>.summary.txt`. Returns the streamed file. Call after `state.sinks.flush()`.
+pub fn finalize_stream(state: &LoggerState, dir: &Path, channel: &str, bucket: &str, lines: &[String]) -> String {
+    let file = stream_file(dir, channel, bucket, Local::now().date_naive());
+    if bucket == "msgs" {
+        let summary = file.with_extension("summary.txt");
+        let content = format!(
+            "{}(counts cover the last {} lines kept in memory, the full log is {})\n",
+            format_message_header(channel, lines, state),
+            lines.len(),
+            file.display()
+        );
+        if let Err(e) = write_file(&summary, content) {
+            eprintln!("⚠️ Failed to write {}: {}", summary.display(), e);
+        }
+    }
+    file.display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn entry(channel: &str, line: &str) -> LogEntry {
+        LogEntry { bucket: "joins", channel: channel.to_string(), line: line.to_string(), normalized: false, seq: 0, received: Local::now(), record: None }
+    }
+
+    #[test]
+    fn parted_channels_are_appended_to_later_the_same_day() {
+        let dir = std::env::temp_dir().join(format!("stream_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut sink = StreamSink::new(dir.clone(), vec!["secret".to_string()], || Duration::ZERO);
+        sink.write(&entry("forsen", "12:00:00 [JOIN] alice")).unwrap();
+        sink.close_channel("forsen").unwrap();
+        assert!(sink.files.is_empty());
+        sink.write(&entry("forsen", "13:00:00 [JOIN] bob")).unwrap();
+        sink.flush().unwrap();
+        assert!(!sink.accepts(&entry("secret", "12:00:00 [JOIN] alice")));
+
+        let file = stream_file(&dir, "forsen", "joins", Local::now().date_naive());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "12:00:00 [JOIN] alice\n13:00:00 [JOIN] bob\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn late_lines_are_written_in_receive_order() {
+        let dir = std::env::temp_dir().join(format!("stream_order_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut sink = StreamSink::new(dir.clone(), Vec::new(), || Duration::from_secs(65));
+        let now = Local::now();
+        let stamped = |line: &str, seq: u64, secs_ago: i64| LogEntry { seq, received: now - chrono::Duration::seconds(secs_ago), ..entry("forsen", line) };
+        sink.write(&stamped("12:00:01 [JOIN] bob", 2, 30)).unwrap();
+        sink.write(&stamped("12:00:02 [JOIN] carol", 3, 20)).unwrap();
+        // The VIP PART, held for its grace period
+        sink.write(&stamped("12:00:00 [PART] alice", 1, 61)).unwrap();
+        sink.idle().unwrap();
+        let file = stream_file(&dir, "forsen", "joins", Local::now().date_naive());
+        assert!(!file.exists());
+        sink.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "12:00:00 [PART] alice\n12:00:01 [JOIN] bob\n12:00:02 [JOIN] carol\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn memory_keeps_the_last_lines() {
+        let state = LoggerState::default();
+        assert_eq!(memory_limit(&state, "forsen"), None);
+        state.streaming.store(true, Ordering::Relaxed);
+        // Without the sink running nothing is dropped
+        assert_eq!(memory_limit(&state, "forsen"), None);
+
+        let mut lines: Vec<String> = ["a", "b", "c"].iter().map(|l| l.to_string()).collect();
+        assert_eq!(trim_in_memory(&mut lines, 2), 1);
+        assert_eq!(lines, ["b", "c"]);
+        assert_eq!(trim_in_memory(&mut lines, 2), 0);
+    }
+}
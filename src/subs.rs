@@ -0,0 +1,118 @@
+//! Subs of the session per channel, shown by SUBS: subs and gift subs by tier, with
+//! Prime apart from the paid tiers, resubs, and the longest streak someone shared.
+//! Twitch sends the streak only when the user chose to share it; the cumulative months
+//! are always there.
+
+use std::collections::BTreeMap;
+
+use twitch_irc::message::{UserNoticeEvent, UserNoticeMessage};
+
+use crate::state::LoggerState;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubCounts {
+    /// Subs and gift subs by `sub_tier`: "prime", "tier 1", ...
+    pub tiers: BTreeMap<String, usize>,
+    pub resubs: usize,
+    /// Resubs that shared their streak.
+    pub shared_streaks: usize,
+    /// Display name and months of the longest shared streak, the first one on ties.
+    pub longest_streak: Option<(String, u64)>,
+}
+
+/// "tier 1" for sub plan "1000", "prime" for "Prime".
+pub fn sub_tier(sub_plan: &str) -> String {
+    match sub_plan {
+        "1000" => "tier 1".to_string(),
+        "2000" => "tier 2".to_string(),
+        "3000" => "tier 3".to_string(),
+        other => other.to_lowercase(),
+    }
+}
+
+/// "tier 1 resub (24 months, 6-month streak)" of a sub or resub, without the streak if
+/// it was not shared. `None` for every other USERNOTICE.
+pub fn sub_description(event: &UserNoticeEvent) -> Option<String> {
+    let UserNoticeEvent::SubOrResub { is_resub, cumulative_months, streak_months, sub_plan, .. } = event else {
+        return None;
+    };
+    if !is_resub {
+        return Some(format!("{} sub", sub_tier(sub_plan)));
+    }
+    let streak = streak_months.map(|s| format!(", {}-month streak", s)).unwrap_or_default();
+    Some(format!("{} resub ({} months{})", sub_tier(sub_plan), cumulative_months, streak))
+}
+
+/// Count a sub, resub or gift sub notice of its channel.
+pub fn record_sub(state: &LoggerState, msg: &UserNoticeMessage) {
+    let (sub_plan, streak) = match &msg.event {
+        UserNoticeEvent::SubOrResub { sub_plan, streak_months, .. } => (sub_plan, *streak_months),
+        UserNoticeEvent::SubGift { sub_plan, .. } => (sub_plan, None),
+        _ => return,
+    };
+    let mut counts = state.sub_counts.lock().unwrap();
+    let counts = counts.entry(msg.channel_login.clone()).or_default();
+    *counts.tiers.entry(sub_tier(sub_plan)).or_default() += 1;
+    if let UserNoticeEvent::SubOrResub { is_resub: true, .. } = msg.event {
+        counts.resubs += 1;
+    }
+    if let Some(months) = streak {
+        counts.shared_streaks += 1;
+        if counts.longest_streak.as_ref().is_none_or(|(_, longest)| months > *longest) {
+            counts.longest_streak = Some((msg.sender.name.clone(), months));
+        }
+    }
+}
+
+/// Text printed by SUBS.
+pub fn format_subs(channel: &str, counts: &SubCounts) -> String {
+    let mut out = format!("=== SUBS #{} ===", channel);
+    for (tier, n) in &counts.tiers {
+        out.push_str(&format!("\n{:>5}  {}", n, tier));
+    }
+    out.push_str(&format!("\n{:>5}  resubs, {} with a shared streak", counts.resubs, counts.shared_streaks));
+    if let Some((name, months)) = &counts.longest_streak {
+        out.push_str(&format!("\nLongest streak: {}, {} months", name, months));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twitch_irc::message::IRCMessage;
+
+    fn resub(name: &str, plan: &str, months: u64, streak: Option<u64>) -> UserNoticeMessage {
+        let streak = match streak {
+            Some(s) => format!("msg-param-should-share-streak=1;msg-param-streak-months={}", s),
+            None => "msg-param-should-share-streak=0;msg-param-streak-months=0".to_string(),
+        };
+        let raw = format!(
+            "@badge-info=;badges=;color=;display-name={name};emotes=;flags=;id=1;login={login};mod=0;msg-id=resub;msg-param-cumulative-months={months};msg-param-months=0;{streak};msg-param-sub-plan-name=Channel\\sSubscription\\s(xqcow);msg-param-sub-plan={plan};room-id=71092938;subscriber=1;system-msg={name}\\ssubscribed.;tmi-sent-ts=1581713640019;user-id=21156217;user-type= :tmi.twitch.tv USERNOTICE #xqcow",
+            login = name.to_lowercase(),
+        );
+        UserNoticeMessage::try_from(IRCMessage::parse(&raw).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn shared_streaks_are_told_apart() {
+        let shared = resub("Gutrin", "1000", 24, Some(6));
+        let hidden = resub("Alice", "Prime", 30, None);
+        assert_eq!(sub_description(&shared.event).as_deref(), Some("tier 1 resub (24 months, 6-month streak)"));
+        assert_eq!(sub_description(&hidden.event).as_deref(), Some("prime resub (30 months)"));
+
+        let state = LoggerState::default();
+        for msg in [&shared, &hidden, &resub("Bob", "1000", 12, Some(12)), &resub("Carol", "1000", 40, Some(3))] {
+            record_sub(&state, msg);
+        }
+        let counts = state.sub_counts.lock().unwrap()["xqcow"].clone();
+        assert_eq!(counts.tiers, BTreeMap::from([("prime".to_string(), 1), ("tier 1".to_string(), 3)]));
+        assert_eq!((counts.resubs, counts.shared_streaks), (4, 3));
+        // Alice's 30 months are cumulative, not a streak
+        assert_eq!(counts.longest_streak, Some(("Bob".to_string(), 12)));
+        assert_eq!(
+            format_subs("xqcow", &counts),
+            "=== SUBS #xqcow ===\n    1  prime\n    3  tier 1\n    4  resubs, 3 with a shared streak\nLongest streak: Bob, 12 months"
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Session tags (`TAG`): a label like "dreamhack_day2" added to the names of saved files
+//! and to their header, for the whole session or per channel. Kept in a file next to
+//! channels.txt until `TAG OFF`, so a restart in the middle of an event keeps it.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::write_file;
+use crate::state::data_file;
+
+pub fn session_tags_file() -> PathBuf {
+    data_file("session_tags.json")
+}
+
+/// Characters a label may have besides letters and digits; spaces become '_'.
+const LABEL_PUNCTUATION: &[char] = &['_', '-', '.'];
+const MAX_LABEL_CHARS: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTags {
+    /// Label of every channel without its own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// Channel -> label, set with `TAG <channel> <label>`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub channels: BTreeMap<String, String>,
+}
+
+impl SessionTags {
+    pub fn for_channel(&self, channel: &str) -> Option<&str> {
+        self.channels.get(channel).or(self.session.as_ref()).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.session.is_none() && self.channels.is_empty()
+    }
+}
+
+/// "dreamhack_day2, xqcow: finals" for TAG and the banner.
+pub fn format_tags(tags: &SessionTags) -> String {
+    let mut parts: Vec<String> = tags.session.iter().cloned().collect();
+    parts.extend(tags.channels.iter().map(|(channel, tag)| format!("{}: {}", channel, tag)));
+    if parts.is_empty() {
+        "none".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// `<name>_<tag>` in file names, `name` without a tag.
+pub fn tagged(name: &str, tag: Option<&str>) -> String {
+    match tag {
+        Some(tag) => format!("{}_{}", name, tag),
+        None => name.to_string(),
+    }
+}
+
+/// A label usable in file names: letters, digits and `_-.`, spaces turned into '_'.
+pub fn sanitize_label(label: &str) -> Result<String, String> {
+    let label = label.split_whitespace().collect::<Vec<_>>().join("_");
+    if label.is_empty() || label.chars().all(|c| c == '.') {
+        return Err("empty tag".to_string());
+    }
+    if label.chars().count() > MAX_LABEL_CHARS {
+        return Err(format!("tag longer than {} characters", MAX_LABEL_CHARS));
+    }
+    match label.chars().find(|c| !c.is_alphanumeric() && !LABEL_PUNCTUATION.contains(c)) {
+        Some(c) => Err(format!("'{}' is not allowed in a tag (letters, digits, _ - .)", c)),
+        None => Ok(label),
+    }
+}
+
+/// Tags of the previous run, empty if it ended with none.
+pub fn load_session_tags() -> SessionTags {
+    let path = session_tags_file();
+    match std::fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            eprintln!("⚠️ Ignoring broken {}: {}", path.display(), e);
+            SessionTags::default()
+        }),
+        Err(_) => SessionTags::default(),
+    }
+}
+
+/// Called on every change, the tags outlive a crash too.
+pub fn save_session_tags(tags: &SessionTags) {
+    match serde_json::to_string_pretty(tags) {
+        Ok(json) => {
+            let path = session_tags_file();
+            if let Err(e) = write_file(&path, json) {
+                eprintln!("⚠️ Failed to write {}: {}", path.display(), e);
+            }
+        }
+        Err(e) => eprintln!("⚠️ Failed to serialize session tags: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_must_be_file_name_safe() {
+        assert_eq!(sanitize_label("dreamhack day2"), Ok("dreamhack_day2".to_string()));
+        assert_eq!(sanitize_label("finals-v1.2"), Ok("finals-v1.2".to_string()));
+        assert!(sanitize_label("../etc").is_err());
+        assert!(sanitize_label("a/b").is_err());
+        assert!(sanitize_label("..").is_err());
+        assert!(sanitize_label("  ").is_err());
+    }
+
+    #[test]
+    fn channel_tags_override_the_session_tag() {
+        let mut tags = SessionTags::default();
+        assert_eq!(tags.for_channel("forsen"), None);
+        tags.session = Some("dreamhack_day2".to_string());
+        tags.channels.insert("xqcow".to_string(), "finals".to_string());
+        assert_eq!(tags.for_channel("forsen"), Some("dreamhack_day2"));
+        assert_eq!(tags.for_channel("xqcow"), Some("finals"));
+
+        let json = serde_json::to_string(&tags).unwrap();
+        assert_eq!(serde_json::from_str::<SessionTags>(&json).unwrap(), tags);
+    }
+}
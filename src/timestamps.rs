@@ -0,0 +1,174 @@
+//! How times are shown on the console (`time_format`, changed with `TIMEFMT`). Relative
+//! times count from the first logged message of the channel this session, which lines
+//! up with VOD timestamps when the logger was started with the stream. Saved files always
+//! keep the absolute HH:MM:SS.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Local, NaiveTime};
+use owo_colors::OwoColorize;
+
+use crate::channel_config::{apply_named_color, channel_color};
+use crate::json_log::parse_entry;
+use crate::state::LoggerState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeFormat {
+    /// HH:MM:SS
+    #[default]
+    Absolute,
+    /// `+H:MM:SS` since the first message of the channel; ages like "5m ago" in listings.
+    Relative,
+    /// Both next to each other.
+    Both,
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "absolute" => Ok(TimeFormat::Absolute),
+            "relative" => Ok(TimeFormat::Relative),
+            "both" => Ok(TimeFormat::Both),
+            other => Err(format!("unknown time format '{}' (absolute, relative, both)", other)),
+        }
+    }
+}
+
+impl fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TimeFormat::Absolute => "absolute",
+            TimeFormat::Relative => "relative",
+            TimeFormat::Both => "both",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Remember the time of the first message log line of `channel`, the zero point of its
+/// relative times.
+pub fn record_first_line(state: &LoggerState, channel: &str, line: &str) {
+    let mut first = state.first_line_times.lock().unwrap();
+    if !first.contains_key(channel) {
+        if let Some(time) = parse_time(line) {
+            first.insert(channel.to_string(), time);
+        }
+    }
+}
+
+/// `time_str` ("HH:MM:SS") of a live console line of `channel`, as `time_format` wants it.
+pub fn display_time(state: &LoggerState, channel: &str, time_str: &str) -> String {
+    let format = state.settings.lock().unwrap().time_format.value;
+    if format == TimeFormat::Absolute {
+        return time_str.to_string();
+    }
+    let first = state.first_line_times.lock().unwrap().get(channel).copied();
+    let offset = first.zip(parse_time(time_str)).map(|(first, time)| format_offset(seconds_between(first, time)));
+    match (format, offset) {
+        (TimeFormat::Relative, Some(offset)) => offset,
+        (TimeFormat::Both, Some(offset)) => format!("{} {}", time_str, offset),
+        _ => time_str.to_string(),
+    }
+}
+
+/// A stored log line for listings (SINCE, BETWEEN), its leading time replaced by or
+/// followed by its age.
+pub fn display_log_line(state: &LoggerState, line: &str) -> String {
+    let format = state.settings.lock().unwrap().time_format.value;
+    let Some(time) = parse_time(line).filter(|_| format != TimeFormat::Absolute) else {
+        return line.to_string();
+    };
+    let age = format_age(seconds_between(time, Local::now().time()));
+    match format {
+        TimeFormat::Both => format!("{} {}{}", &line[..8], age, &line[8..]),
+        _ => format!("{}{}", age, &line[8..]),
+    }
+}
+
+/// A stored log line of `channel` styled like the live console line: chat as
+/// `time [channel] Name[badges]: text`, other entries with the time dimmed.
+pub fn styled_log_line(state: &LoggerState, channel: &str, line: &str) -> String {
+    let entry = parse_entry(0, line);
+    let time = entry.timestamp.as_deref().map(|time| display_time(state, channel, time));
+    let time = time.unwrap_or_default().dimmed().to_string();
+    let Some(sender) = entry.sender else {
+        return format!("{} {}", time, entry.message_text);
+    };
+    let color = channel_color(state, channel);
+    let badges = if entry.badges.is_empty() { String::new() } else { format!("[{}]", entry.badges.join(", ").yellow()) };
+    format!(
+        "{} [{}] {}{}: {}",
+        time,
+        apply_named_color(channel, color.as_deref()),
+        sender.bold(),
+        badges,
+        entry.message_text
+    )
+}
+
+/// The leading "HH:MM:SS" of a log line.
+fn parse_time(line: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(line.get(0..8)?, "%H:%M:%S").ok()
+}
+
+/// Seconds from `from` to `to`, across midnight if `to` is earlier.
+fn seconds_between(from: NaiveTime, to: NaiveTime) -> i64 {
+    (to - from).num_seconds().rem_euclid(24 * 60 * 60)
+}
+
+/// "+1:02:03"
+pub fn format_offset(secs: i64) -> String {
+    format!("+{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+/// "45s ago", "12m ago", "2h05m ago"
+pub fn format_age(secs: i64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s ago", s),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s => format!("{}h{:02}m ago", s / 3600, s % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Source;
+
+    fn state_with(format: TimeFormat) -> LoggerState {
+        let state = LoggerState::default();
+        state.settings.lock().unwrap().time_format.set(format, Source::Runtime);
+        record_first_line(&state, "forsen", "23:59:00 <a> []\nfirst\n");
+        record_first_line(&state, "forsen", "23:59:30 <b> []\nsecond\n");
+        state
+    }
+
+    #[test]
+    fn relative_times_count_from_the_first_line() {
+        let state = state_with(TimeFormat::Relative);
+        assert_eq!(display_time(&state, "forsen", "23:59:45"), "+0:00:45");
+        assert_eq!(display_time(&state, "forsen", "01:01:05"), "+1:02:05");
+        // Nothing logged yet, nothing to count from
+        assert_eq!(display_time(&state, "xqcow", "12:00:00"), "12:00:00");
+
+        state.settings.lock().unwrap().time_format.set(TimeFormat::Both, Source::Runtime);
+        assert_eq!(display_time(&state, "forsen", "23:59:45"), "23:59:45 +0:00:45");
+    }
+
+    #[test]
+    fn absolute_is_unchanged() {
+        let state = state_with(TimeFormat::Absolute);
+        assert_eq!(display_time(&state, "forsen", "23:59:45"), "23:59:45");
+        assert_eq!(display_log_line(&state, "23:59:45 <a> []\nhi\n"), "23:59:45 <a> []\nhi\n");
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(format_age(45), "45s ago");
+        assert_eq!(format_age(12 * 60 + 5), "12m ago");
+        assert_eq!(format_age(2 * 3600 + 5 * 60), "2h05m ago");
+    }
+}
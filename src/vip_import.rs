@@ -0,0 +1,163 @@
+//! `VIP IMPORT <file> [--save]`: add VIPs from a list of followed channels, either plain
+//! (one name per line) or the JSON of Helix `Get Followed Channels` / the Twitch data
+//! export. `VIP IMPORT HELIX [--save]` fetches that list from the API, see `helix`.
+//! Names are lowercased and validated; they count as VIPs for the rest of the
+//! session, without a color, and `--save` also appends them to channels.txt.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use chrono::Local;
+use serde_json::Value;
+use twitch_irc::validate::validate_login;
+
+use crate::output::write_file;
+use crate::state::LoggerState;
+
+/// Fields holding the channel name in the known JSON layouts, most specific first.
+const NAME_FIELDS: &[&str] = &["broadcaster_login", "to_login", "channel_login", "login", "channel", "name"];
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VipImport {
+    /// Valid names, lowercased, in file order without repeats.
+    pub names: Vec<String>,
+    /// Entries that are not channel names.
+    pub invalid: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VipImportSummary {
+    pub added: Vec<String>,
+    /// Already VIPs, from channels.txt or an earlier import.
+    pub duplicates: usize,
+}
+
+/// Names in a JSON value: strings, objects with one of `NAME_FIELDS`, and arrays or a
+/// Helix `{"data": [...]}` of those.
+fn json_names(value: &Value, names: &mut Vec<String>) {
+    match value {
+        Value::String(name) => names.push(name.clone()),
+        Value::Array(items) => items.iter().for_each(|item| json_names(item, names)),
+        Value::Object(object) => {
+            if let Some(name) = NAME_FIELDS.iter().find_map(|field| object.get(*field).and_then(Value::as_str)) {
+                names.push(name.to_string());
+            } else if let Some(data) = object.get("data").or_else(|| object.get("follows")) {
+                json_names(data, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn parse_vip_import(content: &str) -> Result<VipImport, String> {
+    let content = content.trim_start_matches('\u{feff}').trim();
+    let raw: Vec<String> = if content.starts_with('{') || content.starts_with('[') {
+        let value: Value = serde_json::from_str(content).map_err(|e| format!("invalid JSON: {}", e))?;
+        let mut names = Vec::new();
+        json_names(&value, &mut names);
+        names
+    } else {
+        content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.split_whitespace().next().unwrap_or_default().to_string())
+        .collect()
+    };
+    Ok(validate_names(raw))
+}
+
+/// Lowercased valid names without repeats, and the invalid entries.
+pub fn validate_names(raw: Vec<String>) -> VipImport {
+    let mut import = VipImport::default();
+    for name in raw {
+        let login = name.trim().trim_start_matches('@').to_lowercase();
+        if validate_login(&login).is_err() {
+            import.invalid.push(name);
+        } else if !import.names.contains(&login) {
+            import.names.push(login);
+        }
+    }
+    import
+}
+
+pub fn load_vip_import(path: &Path) -> Result<VipImport, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_vip_import(&content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Add the names that are not VIPs yet to `imported_vips`.
+pub fn import_vips(state: &LoggerState, names: Vec<String>) -> VipImportSummary {
+    let mut summary = VipImportSummary::default();
+    for name in names {
+        if state.is_vip(&name) || !state.imported_vips.lock().unwrap().insert(name.clone()) {
+            summary.duplicates += 1;
+        } else {
+            summary.added.push(name);
+        }
+    }
+    summary
+}
+
+/// `content` of channels.txt with `names` appended as VIPs, under a comment.
+fn append_vips(content: &str, names: &[String]) -> String {
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut new = content.to_string();
+    if !new.is_empty() && !new.ends_with('\n') {
+        new.push_str(newline);
+    }
+    new.push_str(&format!("# imported {}{}", Local::now().format("%Y-%m-%d"), newline));
+    for name in names {
+        new.push_str(name);
+        new.push_str(newline);
+    }
+    new
+}
+
+/// Append `names` to the channels.txt at `path`, after copying it to `<path>.bak`.
+pub fn save_imported_vips(path: &Path, names: &[String]) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let backup = format!("{}.bak", path.display());
+    write_file(&backup, &content).map_err(|e| format!("{}: {}", backup, e))?;
+    write_file(path, append_vips(&content, names)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(())
+}
+
+/// VIPs of channels.txt and of `VIP IMPORT`, sorted.
+pub fn all_vips(state: &LoggerState) -> BTreeSet<String> {
+    let mut vips: BTreeSet<String> = state.config.vips.keys().cloned().collect();
+    vips.extend(state.imported_vips.lock().unwrap().iter().cloned());
+    vips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_lists_and_helix_json_are_read() {
+        let plain = parse_vip_import("\u{feff}# follows\nForsen\n@xQcOW extra words\n\nnot-a-name!\nforsen\n").unwrap();
+        assert_eq!(plain.names, ["forsen", "xqcow"]);
+        assert_eq!(plain.invalid, ["not-a-name!"]);
+
+        let helix = r#"{"total": 2, "data": [
+            {"broadcaster_id": "1", "broadcaster_login": "Pajlada", "broadcaster_name": "pajlada", "followed_at": "2022-05-24T22:22:08Z"},
+            {"broadcaster_id": "2", "broadcaster_login": "forsen", "broadcaster_name": "forsen", "followed_at": "2021-01-01T00:00:00Z"}
+        ], "pagination": {}}"#;
+        assert_eq!(parse_vip_import(helix).unwrap().names, ["pajlada", "forsen"]);
+        assert_eq!(parse_vip_import(r#"["a_b", {"login": "c"}]"#).unwrap().names, ["a_b", "c"]);
+        assert!(parse_vip_import("{broken").is_err());
+
+        let (config, _) = crate::channel_config::parse_channel_config("0\nforsen\n").unwrap();
+        let state = LoggerState { config: std::sync::Arc::new(config), ..Default::default() };
+        let summary = import_vips(&state, vec!["forsen".to_string(), "pajlada".to_string()]);
+        assert_eq!((summary.added, summary.duplicates), (vec!["pajlada".to_string()], 1));
+        assert_eq!(import_vips(&state, vec!["pajlada".to_string()]).duplicates, 1);
+        assert!(state.is_vip("pajlada") && !state.is_vip("xqcow"));
+        assert!(state.vip_info("pajlada").is_some_and(|info| info.color.is_none() && !info.encrypt));
+
+        let appended = append_vips("1\r\nforsen", &["pajlada".to_string()]);
+        assert!(appended.starts_with("1\r\nforsen\r\n# imported ") && appended.ends_with("\r\npajlada\r\n"), "{:?}", appended);
+    }
+}
@@ -0,0 +1,49 @@
+//! Bans and timeouts of configured VIPs in any logged channel. Each one raises the alarm
+//! (see `handlers::alert_vip_moderation`) and is kept for the session: SUMMARY lists them
+//! and on exit they are written to `vip_incidents_<timestamp>.txt`.
+
+use std::fmt;
+
+use crate::output::write_file;
+use crate::state::{data_file, LoggerState, SESSION_START};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VipIncident {
+    /// HH:MM:SS receive time.
+    pub time: String,
+    pub channel: String,
+    pub login: String,
+    /// "banned", "timed out for 600s"
+    pub action: String,
+}
+
+impl fmt::Display for VipIncident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} VIP {} {} in #{}", self.time, self.login, self.action, self.channel)
+    }
+}
+
+/// "VIP incidents (2):" and a line each, or "No VIP incidents".
+pub fn format_vip_incidents(incidents: &[VipIncident]) -> String {
+    if incidents.is_empty() {
+        return "No VIP incidents".to_string();
+    }
+    let mut lines = vec![format!("VIP incidents ({}):", incidents.len())];
+    lines.extend(incidents.iter().map(|incident| format!("  {}", incident)));
+    lines.join("\n")
+}
+
+/// Write the incidents of this session, if there were any. Called on exit.
+pub fn save_vip_incidents(state: &LoggerState) {
+    let incidents = state.vip_incidents.lock().unwrap().clone();
+    if incidents.is_empty() {
+        return;
+    }
+    let file = data_file(&format!("vip_incidents_{}.txt", SESSION_START.format("%Y-%m-%d_%H-%M-%S")));
+    let content: String = incidents.iter().map(|incident| format!("{}\n", incident)).collect();
+    match write_file(&file, content) {
+        Ok(true) => println!("Saved {} VIP incidents to {}", incidents.len(), file.display()),
+        Ok(false) => {}
+        Err(e) => eprintln!("⚠️ Failed to write {}: {}", file.display(), e),
+    }
+}
@@ -0,0 +1,111 @@
+//! Grace period for VIP PARTs (`vip_part_grace`). Chat connections blip: a VIP parts and
+//! is back a few seconds later. Their PART is held; a JOIN within the grace period turns
+//! both into one `[FLAP]` line without alerts, otherwise the PART is reported late with
+//! its own timestamp. Held parts of a channel are reported when it is parted, all of
+//! them on exit.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use owo_colors::OwoColorize;
+
+use crate::buckets::append_marker;
+use crate::console_println;
+use crate::handlers::report_vip_event;
+use crate::sequence::{current_stamp, with_stamp, EventStamp};
+use crate::settings::Settings;
+use crate::state::LoggerState;
+
+/// How often expired parts are looked for.
+const RELEASE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct PendingPart {
+    /// Timestamp of the PART, used when it is reported.
+    time_str: String,
+    /// The reported PART goes where the PART was received in the log.
+    stamp: EventStamp,
+    at: Instant,
+}
+
+/// Held VIP parts by (channel, login).
+pub type PendingParts = HashMap<(String, String), PendingPart>;
+
+fn grace(state: &LoggerState) -> Duration {
+    Duration::from_secs(state.settings.lock().unwrap().vip_part_grace.value)
+}
+
+/// How long after it was received a held PART can still be logged: the grace period and
+/// one round of `spawn_part_release`. The file sinks hold their lines this long.
+pub fn late_part_window(settings: &Settings) -> Duration {
+    match settings.vip_part_grace.value {
+        0 => Duration::ZERO,
+        secs => Duration::from_secs(secs) + RELEASE_INTERVAL,
+    }
+}
+
+/// Hold a VIP PART. False with the grace period off, then it is reported right away.
+pub fn hold_part(state: &LoggerState, channel: &str, login: &str, time_str: &str, now: Instant) -> bool {
+    if grace(state).is_zero() {
+        return false;
+    }
+    let part = PendingPart { time_str: time_str.to_string(), stamp: current_stamp(), at: now };
+    state.pending_parts.lock().unwrap().insert((channel.to_string(), login.to_string()), part);
+    true
+}
+
+/// A VIP JOIN: if their PART in `channel` is held, drop it and log a `[FLAP]` line.
+/// Returns whether it was a flap; a held PART past the grace period is reported first.
+pub fn check_flap(state: &LoggerState, channel: &str, login: &str, time_str: &str, now: Instant) -> bool {
+    let grace = grace(state);
+    let Some(part) = state.pending_parts.lock().unwrap().remove(&(channel.to_string(), login.to_string())) else {
+        return false;
+    };
+    let away = now.saturating_duration_since(part.at);
+    if away >= grace {
+        with_stamp(part.stamp, || report_vip_event(state, "PART", &part.time_str, channel, login, ""));
+        return false;
+    }
+
+    let line = format!("{} (parted at {}, back after {}s)", login, part.time_str, away.as_secs());
+    if state.is_visible(channel) {
+        console_println!("{}", format!("*** VIP {} flapped in {} ({}s) ***", login, channel, away.as_secs()).dimmed());
+    }
+    if login != channel {
+        append_marker(state, channel, time_str, "FLAP", &line);
+    }
+    true
+}
+
+/// Report the held parts that are past the grace period.
+pub fn release_expired_parts(state: &LoggerState, now: Instant) {
+    let grace = grace(state);
+    release(state, |_, part| now.saturating_duration_since(part.at) >= grace);
+}
+
+/// Report the held parts of `channel` right away, or all of them with `None`.
+pub fn release_parts(state: &LoggerState, channel: Option<&str>) {
+    release(state, |c, _| channel.is_none_or(|channel| channel == c));
+}
+
+fn release(state: &LoggerState, due: impl Fn(&str, &PendingPart) -> bool) {
+    let mut released: Vec<((String, String), PendingPart)> = {
+        let mut pending = state.pending_parts.lock().unwrap();
+        let keys: Vec<(String, String)> = pending.iter().filter(|((channel, _), part)| due(channel, part)).map(|(key, _)| key.clone()).collect();
+        keys.into_iter().filter_map(|key| pending.remove(&key).map(|part| (key, part))).collect()
+    };
+    released.sort_by_key(|(_, part)| part.at);
+    for ((channel, login), part) in released {
+        with_stamp(part.stamp, || report_vip_event(state, "PART", &part.time_str, &channel, &login, ""));
+    }
+}
+
+pub fn spawn_part_release(state: LoggerState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RELEASE_INTERVAL);
+        loop {
+            interval.tick().await;
+            release_expired_parts(&state, Instant::now());
+        }
+    });
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::write_file;
+use crate::state::data_file;
+
+/// Lives next to channels.txt and survives restarts.
+pub fn vip_join_counts_file() -> PathBuf {
+    data_file("vip_join_counts.json")
+}
+
+/// How often a VIP joined, in total and per channel.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VipJoinCount {
+    pub join_count: u32,
+    pub channels: HashMap<String, u32>,
+}
+
+/// VIP login -> join counts.
+pub type VipJoinCounts = HashMap<String, VipJoinCount>;
+
+/// Counts from earlier sessions; empty if the file does not exist yet.
+pub fn load_vip_join_counts() -> VipJoinCounts {
+    let file = vip_join_counts_file();
+    match std::fs::read_to_string(&file) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            eprintln!("⚠️ Ignoring broken {}: {}", file.display(), e);
+            VipJoinCounts::new()
+        }),
+        Err(_) => VipJoinCounts::new(),
+    }
+}
+
+pub fn save_vip_join_counts(counts: &VipJoinCounts) {
+    match serde_json::to_string_pretty(counts) {
+        Ok(json) => {
+            let file = vip_join_counts_file();
+            if let Err(e) = write_file(&file, json) {
+                eprintln!("⚠️ Failed to write {}: {}", file.display(), e);
+            }
+        }
+        Err(e) => eprintln!("⚠️ Failed to serialize VIP join counts: {}", e),
+    }
+}
+
+/// Count one JOIN of `vip` in `channel`, returns the new total (the "visit #").
+pub fn record_vip_join(counts: &mut VipJoinCounts, vip: &str, channel: &str) -> u32 {
+    let entry = counts.entry(vip.to_string()).or_default();
+    entry.join_count += 1;
+    *entry.channels.entry(channel.to_string()).or_default() += 1;
+    entry.join_count
+}
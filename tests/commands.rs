@@ -0,0 +1,113 @@
+//! Command lines through `CommandSession`, checked against the state and saved files.
+//! None of them joins a channel, so the client never connects.
+
+use twitch_irc::login::StaticLoginCredentials;
+use twitch_irc::{ClientConfig, SecureTCPTransport, TwitchIRCClient};
+use twitch_logger_core::buckets::LogLine;
+use twitch_logger_core::commands::{CommandSession, Flow};
+use twitch_logger_core::state::LoggerState;
+use twitch_logger_core::timestamps::TimeFormat;
+
+type Session = CommandSession<SecureTCPTransport, StaticLoginCredentials>;
+
+fn session(state: &LoggerState) -> Session {
+    let (_, client) = TwitchIRCClient::new(ClientConfig::default());
+    CommandSession::new(client, state.clone())
+}
+
+fn state_with_channels(channels: &[&str]) -> LoggerState {
+    let state = LoggerState::default();
+    *state.channels.lock().unwrap() = channels.iter().map(|c| c.to_string()).collect();
+    state
+}
+
+#[tokio::test]
+async fn sound_and_notify_switch_the_alert_sets() {
+    let state = state_with_channels(&["forsen", "xqcow"]);
+    let mut session = session(&state);
+
+    assert_eq!(session.execute("sound forsen"), Flow::Continue);
+    assert!(state.sound_channels.lock().unwrap().contains("forsen"));
+    session.execute("NOTIFY ALL ON");
+    assert!(state.sound_channels.lock().unwrap().is_empty());
+    assert_eq!(state.notification_channels.lock().unwrap().len(), 2);
+    session.execute("NOTIFY xqcow OFF");
+    assert!(!state.notification_channels.lock().unwrap().contains("xqcow"));
+}
+
+#[tokio::test]
+async fn settings_and_tail_prompt() {
+    let state = LoggerState::default();
+    let mut session = session(&state);
+
+    session.execute("TIMEFMT relative");
+    assert_eq!(state.settings.lock().unwrap().time_format.value, TimeFormat::Relative);
+    session.execute("CONFIG SET copypasta_min_length 5");
+    assert_eq!(state.settings.lock().unwrap().copypasta_min_length.value, 5);
+
+    session.execute("TAIL forsen");
+    assert_eq!(state.tail.lock().unwrap().as_deref(), Some("forsen"));
+    assert!(session.prompt().contains("TAIL"));
+    session.execute("TAIL OFF");
+    assert_eq!(session.prompt(), ">> ");
+
+    // Unknown commands and empty lines don't end the session
+    assert_eq!(session.execute("FROBNICATE"), Flow::Continue);
+    assert_eq!(session.execute("   "), Flow::Continue);
+}
+
+#[tokio::test]
+async fn join_over_the_cap_needs_confirm() {
+    let state = state_with_channels(&["forsen", "xqcow"]);
+    state.settings.lock().unwrap().channel_cap.value = 3;
+    let mut session = session(&state);
+
+    session.execute("JOIN a b");
+    assert_eq!(state.channels.lock().unwrap().len(), 2);
+    // Already joined channels don't count
+    session.execute("JOIN forsen xqcow CONFIRM");
+    assert_eq!(state.channels.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn save_writes_the_log() {
+    let channel = format!("cmd_save_{}", std::process::id());
+    let state = state_with_channels(&[&channel]);
+    state.logs.lock().unwrap().insert(
+        channel.clone(),
+        vec![LogLine::Text("12:00:00 <alice> []\nhello chat\n".to_string()), LogLine::Text("12:00:05 <bob> []\nhi alice\n".to_string())],
+    );
+    let mut session = session(&state);
+    let dir = std::env::temp_dir().join(&channel);
+    session.execute(&format!("CONFIG SET log_dir {}", dir.display()));
+
+    session.execute(&format!("SAVE {}", channel));
+    let file = state.last_saved.lock().unwrap().get(&channel).cloned().expect("saved");
+    assert!(file.starts_with(&dir.display().to_string()), "{}", file);
+    let content = std::fs::read_to_string(&file).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(content.contains("hello chat") && content.contains("hi alice"), "{}", content);
+}
+
+#[tokio::test]
+async fn search_results_are_paged() {
+    let state = LoggerState::default();
+    let lines: Vec<LogLine> = (0..25).map(|i| LogLine::Text(format!("12:00:{:02} <user{}>\nhello {}\n", i, i, i))).collect();
+    state.logs.lock().unwrap().insert("forsen".to_string(), lines);
+    let mut session = session(&state);
+
+    session.execute("SEARCH forsen hello");
+    assert_eq!(session.prompt(), "--more-- ");
+    session.execute("");
+    assert_eq!(session.prompt(), ">> ");
+
+    // Anything else at the --more-- prompt ends the paging and runs as a command
+    session.execute("SEARCH forsen /^12:00:[01]/");
+    assert_eq!(session.prompt(), ">> ");
+    session.execute("SEARCH ALL --regex user\\d");
+    assert_eq!(session.prompt(), "--more-- ");
+    session.execute("TAIL forsen");
+    assert!(session.prompt().contains("TAIL"));
+    session.execute("SEARCH xqcow hello");
+    assert!(session.prompt().contains("TAIL"));
+}
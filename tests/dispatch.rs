@@ -0,0 +1,121 @@
+//! Raw IRC lines through the message handlers, checked against the resulting logs,
+//! counters and alerts.
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use chrono::{Local, TimeZone};
+use twitch_irc::message::{IRCMessage, ReceivedMessage, ServerMessage};
+use twitch_logger_core::alert_mode::{alert_for, set_alert_mode, AlertMode};
+use twitch_logger_core::handlers::handle_received;
+use twitch_logger_core::heatmap::slot;
+use twitch_logger_core::redaction::{parse_pattern, Redactor};
+use twitch_logger_core::state::LoggerState;
+use twitch_logger_core::vip_import::import_vips;
+
+const PRIVMSG: &str = "@badge-info=;badges=;color=#FF0000;display-name=Alice;emotes=;first-msg=0;flags=;id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;mod=0;room-id=22484632;subscriber=0;tmi-sent-ts=1700000000000;turbo=0;user-id=11148817;user-type= :alice!alice@alice.tmi.twitch.tv PRIVMSG #forsen :hello chat";
+const BAN: &str = "@room-id=22484632;target-user-id=11148817;tmi-sent-ts=1700000000000 :tmi.twitch.tv CLEARCHAT #forsen :alice";
+const CLEARMSG: &str = "@login=alice;room-id=;target-msg-id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;tmi-sent-ts=1700000000000 :tmi.twitch.tv CLEARMSG #forsen :call me at +4915112345678";
+const UNKNOWN_NOTICE: &str = "@badge-info=;badges=;color=;display-name=Bob;emotes=;flags=;id=0f3c5b3e-1d1a-4b3e-9f3e-1d1a4b3e9f3e;login=bob;mod=0;msg-id=brandnewthing;room-id=22484632;subscriber=0;system-msg=Something\\snew;tmi-sent-ts=1700000000000;user-id=12345;user-type= :tmi.twitch.tv USERNOTICE #forsen";
+
+const MOD_PRIVMSG: &str = "@badge-info=subscriber/22;badges=moderator/1,subscriber/12,premium/1;color=;display-name=Bob;emotes=;first-msg=0;flags=;id=0b7a3c1e-2f4d-4c55-9a61-3e0d2b9c7f10;mod=1;room-id=22484632;subscriber=1;tmi-sent-ts=1700000000000;turbo=0;user-id=12345;user-type=mod :bob!bob@bob.tmi.twitch.tv PRIVMSG #forsen :hi";
+
+fn feed(state: &LoggerState, raw: &str) {
+    let message = ServerMessage::try_from(IRCMessage::parse(raw).unwrap()).unwrap();
+    handle_received(ReceivedMessage { message, received_at: SystemTime::now() }, state);
+}
+
+fn state_for(channel: &str) -> LoggerState {
+    let state = LoggerState::default();
+    state.channels.lock().unwrap().push(channel.to_string());
+    state
+}
+
+#[test]
+fn chat_and_moderation_are_logged_and_counted() {
+    let state = state_for("forsen");
+    feed(&state, PRIVMSG);
+    feed(&state, PRIVMSG);
+    feed(&state, BAN);
+
+    let logs = state.logs.lock().unwrap();
+    let lines = &logs["forsen"];
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].text().contains("hello chat"), "{:?}", lines[0]);
+    assert!(lines[2].text().contains("USER_BANNED"), "{:?}", lines[2]);
+    assert_eq!(state.user_message_counts.lock().unwrap()["forsen"]["Alice"], 2);
+    assert!(state.latency.lock().unwrap().contains_key("forsen"));
+}
+
+#[test]
+fn deleted_messages_are_stored_redacted() {
+    let phone = parse_pattern("phone", r"\+\d{6,}").unwrap();
+    let state = LoggerState { redactor: Arc::new(Redactor::new(&[("phone".to_string(), phone)], false)), ..state_for("forsen") };
+    feed(&state, CLEARMSG);
+
+    let logs = state.logs.lock().unwrap();
+    let line = logs["forsen"][0].text();
+    assert!(line.contains("CLEARMSG") && line.contains("call me at [REDACTED:phone]"), "{:?}", line);
+    assert!(!line.contains("4915112345678"), "{:?}", line);
+    assert_eq!(state.redaction_hits.lock().unwrap()["forsen"]["phone"], 1);
+}
+
+#[test]
+fn unknown_user_notices_are_counted_for_diag() {
+    let state = state_for("forsen");
+    for _ in 0..5 {
+        feed(&state, UNKNOWN_NOTICE);
+    }
+    assert_eq!(state.unknown_messages.lock().unwrap().counts["USERNOTICE/brandnewthing"], 5);
+    // Only printing is sampled, every notice is logged
+    assert_eq!(state.logs.lock().unwrap()["forsen"].len(), 5);
+}
+
+#[test]
+fn badges_are_logged_in_short_form() {
+    let state = state_for("forsen");
+    feed(&state, MOD_PRIVMSG);
+    let line = state.logs.lock().unwrap()["forsen"][0].text().to_string();
+    assert!(line.contains("<Bob> [mod/1,sub/12,prime/1]\nhi"), "{:?}", line);
+}
+
+#[test]
+fn heatmap_hours_follow_the_receive_time() {
+    let state = state_for("forsen");
+    // Received in the last second of Saturday 20:00, handled whenever
+    let received = Local.with_ymd_and_hms(2026, 10, 17, 20, 59, 59).unwrap();
+    let message = ServerMessage::try_from(IRCMessage::parse(PRIVMSG).unwrap()).unwrap();
+    handle_received(ReceivedMessage { message, received_at: received.into() }, &state);
+
+    let hourly = state.hourly_messages.lock().unwrap();
+    assert_eq!(hourly["forsen"].iter().collect::<Vec<_>>(), [(&slot(received), &1)]);
+    assert_eq!(slot(received), 5 * 24 + 20);
+}
+
+#[test]
+fn bans_of_vips_imported_at_runtime_are_incidents() {
+    let state = state_for("forsen");
+    feed(&state, BAN);
+    assert!(state.vip_incidents.lock().unwrap().is_empty());
+
+    import_vips(&state, vec!["alice".to_string()]);
+    feed(&state, BAN);
+    let incidents = state.vip_incidents.lock().unwrap();
+    assert_eq!(incidents.len(), 1);
+    assert_eq!((incidents[0].channel.as_str(), incidents[0].login.as_str()), ("forsen", "alice"));
+}
+
+#[test]
+fn alerts_follow_the_sound_and_notify_switches() {
+    let mut state = state_for("forsen");
+    state.alerts = true;
+    assert_eq!(alert_for(&state, "forsen"), None);
+    set_alert_mode(&state, "forsen", AlertMode::Notify, true);
+    assert_eq!(alert_for(&state, "forsen"), Some(AlertMode::Notify));
+    set_alert_mode(&state, "forsen", AlertMode::Sound, true);
+    assert_eq!(alert_for(&state, "forsen"), Some(AlertMode::Sound));
+
+    // The headless archiver never alerts
+    state.alerts = false;
+    assert_eq!(alert_for(&state, "forsen"), None);
+}
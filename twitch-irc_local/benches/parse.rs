@@ -0,0 +1,40 @@
+//! Parsing throughput of the raw lines a busy channel produces, from the test corpus.
+//!
+//! Run with `cargo bench --bench parse`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use twitch_irc::message::{IRCMessage, ServerMessage};
+
+const PRIVMSG: &str = "@badge-info=subscriber/16;badges=moderator/1,subscriber/12;color=#19E6E6;display-name=randers;emotes=;flags=;id=6e2ccb1f-01ed-44d0-85b6-edf762524475;mod=1;room-id=11148817;subscriber=1;tmi-sent-ts=1577040814959;turbo=0;user-id=40286300;user-type=mod :randers!randers@randers.tmi.twitch.tv PRIVMSG #pajlada :Pajapains";
+const USERNOTICE: &str = "@badge-info=subscriber/2;badges=subscriber/0,battlerite_1/1;color=#0000FF;display-name=Gutrin;emotes=1035663:0-3;flags=;id=e0975c76-054c-4954-8cb0-91b8867ec1ca;login=gutrin;mod=0;msg-id=resub;msg-param-cumulative-months=2;msg-param-months=0;msg-param-should-share-streak=1;msg-param-streak-months=2;msg-param-sub-plan-name=Channel\\sSubscription\\s(xqcow);msg-param-sub-plan=1000;room-id=71092938;subscriber=1;system-msg=Gutrin\\ssubscribed\\sat\\sTier\\s1.\\sThey've\\ssubscribed\\sfor\\s2\\smonths,\\scurrently\\son\\sa\\s2\\smonth\\sstreak!;tmi-sent-ts=1581713640019;user-id=21156217;user-type= :tmi.twitch.tv USERNOTICE #xqcow :xqcL";
+const CLEARCHAT: &str = "@ban-duration=1;room-id=11148817;target-user-id=148973258;tmi-sent-ts=1594553828245 :tmi.twitch.tv CLEARCHAT #pajlada :fabzeef";
+const JOIN: &str = ":randers!randers@randers.tmi.twitch.tv JOIN #pajlada";
+const PING: &str = "PING :tmi.twitch.tv";
+
+fn parse(c: &mut Criterion) {
+    let corpus = [
+        ("privmsg", PRIVMSG),
+        ("usernotice", USERNOTICE),
+        ("clearchat", CLEARCHAT),
+        ("join", JOIN),
+        ("ping", PING),
+    ];
+
+    let mut group = c.benchmark_group("IRCMessage::parse");
+    for (name, line) in corpus {
+        group.bench_function(name, |b| b.iter(|| IRCMessage::parse(black_box(line)).unwrap()));
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("ServerMessage::try_from");
+    for (name, line) in corpus {
+        let message = IRCMessage::parse(line).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| ServerMessage::try_from(black_box(message.clone())).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -1,6 +1,7 @@
 use crate::client::pool_connection::PoolConnection;
 #[cfg(feature = "metrics-collection")]
 use crate::client::pool_connection::ReportedConnectionState;
+use crate::client::{ConnectionEvent, PoolStatus};
 use crate::config::ClientConfig;
 use crate::connection::event_loop::ConnectionLoopCommand;
 use crate::connection::{Connection, ConnectionIncomingMessage};
@@ -8,13 +9,14 @@ use crate::error::Error;
 use crate::irc;
 use crate::login::LoginCredentials;
 use crate::message::commands::ServerMessage;
-use crate::message::{IRCMessage, JoinMessage, PartMessage};
+use crate::message::{IRCMessage, JoinMessage, PartMessage, ReceivedMessage};
 #[cfg(feature = "metrics-collection")]
 use crate::metrics::MetricsBundle;
 use crate::transport::Transport;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Weak};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Instant;
 use tracing::{info_span, Instrument};
 
 #[derive(Debug)]
@@ -42,12 +44,36 @@ pub(crate) enum ClientLoopCommand<T: Transport, L: LoginCredentials> {
     Ping {
         return_sender: oneshot::Sender<Result<(), Error<T, L>>>,
     },
+    GetPoolStatus {
+        return_sender: oneshot::Sender<PoolStatus>,
+    },
+    FlushPendingJoins {
+        connection_id: usize,
+    },
     IncomingMessage {
         source_connection_id: usize,
         message: Box<ConnectionIncomingMessage<T, L>>,
     },
 }
 
+/// Where the client loop delivers incoming messages, depending on which
+/// `TwitchIRCClient` constructor was used.
+#[derive(Debug)]
+pub(crate) enum IncomingMessagesSender {
+    Plain(mpsc::UnboundedSender<ServerMessage>),
+    Timestamped(mpsc::UnboundedSender<ReceivedMessage>),
+}
+
+impl IncomingMessagesSender {
+    fn send(&self, received: ReceivedMessage) {
+        // ignore if the library user is not using the incoming messages
+        match self {
+            IncomingMessagesSender::Plain(tx) => tx.send(received.message).ok(),
+            IncomingMessagesSender::Timestamped(tx) => tx.send(received).ok(),
+        };
+    }
+}
+
 pub(crate) struct ClientLoopWorker<T: Transport, L: LoginCredentials> {
     config: Arc<ClientConfig<L>>,
     next_connection_id: usize,
@@ -57,8 +83,20 @@ pub(crate) struct ClientLoopWorker<T: Transport, L: LoginCredentials> {
     current_whisper_connection_id: Option<usize>,
     client_loop_rx: mpsc::UnboundedReceiver<ClientLoopCommand<T, L>>,
     connections: VecDeque<PoolConnection<T, L>>,
+    /// channels that were re-joined after their connection failed, and when it failed.
+    /// Only filled if `config.connection_events` is set.
+    rejoining_channels: HashMap<String, Instant>,
+    /// connections that failed in a row before the server welcomed them.
+    /// Compared against `config.max_consecutive_connection_failures`.
+    consecutive_connection_failures: usize,
+    /// set once the client gave up because of `max_consecutive_connection_failures`.
+    /// From then on, no new connections are made and all commands fail with this error.
+    close_cause: Option<Error<T, L>>,
+    /// see `TwitchIRCClient::wait_closed`. Dropped (signalling a clean shutdown) when the
+    /// event loop ends.
+    closed_tx: watch::Sender<Option<Error<T, L>>>,
     client_loop_tx: Weak<mpsc::UnboundedSender<ClientLoopCommand<T, L>>>,
-    client_incoming_messages_tx: mpsc::UnboundedSender<ServerMessage>,
+    client_incoming_messages_tx: IncomingMessagesSender,
     #[cfg(feature = "metrics-collection")]
     metrics: Option<MetricsBundle>,
 }
@@ -68,7 +106,8 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
         config: Arc<ClientConfig<L>>,
         client_loop_tx: Weak<mpsc::UnboundedSender<ClientLoopCommand<T, L>>>,
         client_loop_rx: mpsc::UnboundedReceiver<ClientLoopCommand<T, L>>,
-        client_incoming_messages_tx: mpsc::UnboundedSender<ServerMessage>,
+        client_incoming_messages_tx: IncomingMessagesSender,
+        closed_tx: watch::Sender<Option<Error<T, L>>>,
         #[cfg(feature = "metrics-collection")] metrics: Option<MetricsBundle>,
     ) {
         let span = match &config.tracing_identifier {
@@ -82,6 +121,10 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
             current_whisper_connection_id: None,
             client_loop_rx,
             connections: VecDeque::new(),
+            rejoining_channels: HashMap::new(),
+            consecutive_connection_failures: 0,
+            close_cause: None,
+            closed_tx,
             client_loop_tx,
             client_incoming_messages_tx,
             #[cfg(feature = "metrics-collection")]
@@ -100,6 +143,11 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
     }
 
     fn process_command(&mut self, command: ClientLoopCommand<T, L>) {
+        if let Some(cause) = &self.close_cause {
+            Self::process_command_closed(command, cause);
+            return;
+        }
+
         match command {
             ClientLoopCommand::Connect { return_sender } => {
                 if self.connections.is_empty() {
@@ -125,6 +173,12 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
             }
             ClientLoopCommand::Part { channel_login } => self.part(channel_login),
             ClientLoopCommand::Ping { return_sender } => self.ping(return_sender),
+            ClientLoopCommand::GetPoolStatus { return_sender } => {
+                return_sender.send(self.pool_status()).ok();
+            }
+            ClientLoopCommand::FlushPendingJoins { connection_id } => {
+                self.flush_pending_joins(connection_id)
+            }
             ClientLoopCommand::IncomingMessage {
                 source_connection_id,
                 message,
@@ -132,6 +186,31 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
         }
     }
 
+    /// Answers commands after the client has given up, without making new connections.
+    fn process_command_closed(command: ClientLoopCommand<T, L>, cause: &Error<T, L>) {
+        match command {
+            ClientLoopCommand::Connect { return_sender } => {
+                return_sender.send(()).ok();
+            }
+            ClientLoopCommand::SendMessage { return_sender, .. }
+            | ClientLoopCommand::Ping { return_sender } => {
+                return_sender.send(Err(cause.clone())).ok();
+            }
+            ClientLoopCommand::GetChannelStatus { return_sender, .. } => {
+                return_sender.send((false, false)).ok();
+            }
+            ClientLoopCommand::GetPoolStatus { return_sender } => {
+                return_sender.send(PoolStatus::default()).ok();
+            }
+            // messages still queued from the connections that were closed
+            ClientLoopCommand::IncomingMessage { .. }
+            | ClientLoopCommand::FlushPendingJoins { .. }
+            | ClientLoopCommand::Join { .. }
+            | ClientLoopCommand::SetWantedChannels { .. }
+            | ClientLoopCommand::Part { .. } => {}
+        }
+    }
+
     #[must_use]
     fn make_new_connection(&mut self) -> PoolConnection<T, L> {
         let connection_id = self.next_connection_id;
@@ -276,7 +355,30 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
             // or else make a new connection
             .unwrap_or_else(|| self.make_new_connection());
 
-        // delegate join command to connection
+        // delegate join command to connection, unless it is already waiting for the join rate limit
+        if !pool_connection.pending_joins.contains(&channel_login) {
+            match pool_connection.next_join_allowed_at() {
+                None => Self::send_join(&mut pool_connection, &channel_login),
+                Some(allowed_at) => {
+                    tracing::debug!(
+                        "Join rate limit reached on connection {}, queueing JOIN for {}",
+                        pool_connection.id,
+                        channel_login
+                    );
+                    pool_connection.pending_joins.push_back(channel_login.clone());
+                    self.schedule_join_flush(&mut pool_connection, allowed_at);
+                }
+            }
+        }
+        pool_connection.wanted_channels.insert(channel_login);
+
+        // put the connection back to the end of the queue
+        self.connections.push_back(pool_connection);
+        // update metrics about channel numbers
+        self.update_metrics();
+    }
+
+    fn send_join(pool_connection: &mut PoolConnection<T, L>, channel_login: &str) {
         pool_connection
             .connection
             .connection_loop_tx
@@ -287,14 +389,69 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
             .unwrap();
 
         pool_connection.register_sent_message();
-        pool_connection.wanted_channels.insert(channel_login);
+        pool_connection.register_sent_join();
+    }
+
+    /// Makes the client loop send the pending JOINs of that connection at `at`.
+    fn schedule_join_flush(&self, pool_connection: &mut PoolConnection<T, L>, at: Instant) {
+        if pool_connection.join_flush_scheduled {
+            return;
+        }
+        pool_connection.join_flush_scheduled = true;
+        Self::spawn_join_flush(self.client_loop_tx.clone(), pool_connection.id, at);
+    }
+
+    fn spawn_join_flush(
+        client_loop_tx: Weak<mpsc::UnboundedSender<ClientLoopCommand<T, L>>>,
+        connection_id: usize,
+        at: Instant,
+    ) {
+        tokio::spawn(async move {
+            tokio::time::sleep_until(at).await;
+            if let Some(client_loop_tx) = client_loop_tx.upgrade() {
+                client_loop_tx
+                    .send(ClientLoopCommand::FlushPendingJoins { connection_id })
+                    .ok();
+            }
+        });
+    }
+
+    fn flush_pending_joins(&mut self, connection_id: usize) {
+        // the connection might have failed in the meantime, its channels are then
+        // re-joined on other connections anyways
+        let Some(pool_connection) = self.connections.iter_mut().find(|c| c.id == connection_id)
+        else {
+            return;
+        };
+        pool_connection.join_flush_scheduled = false;
+
+        while !pool_connection.pending_joins.is_empty() {
+            match pool_connection.next_join_allowed_at() {
+                None => {
+                    // unwrap: checked by the loop condition
+                    let channel_login = pool_connection.pending_joins.pop_front().unwrap();
+                    Self::send_join(pool_connection, &channel_login);
+                }
+                Some(allowed_at) => {
+                    pool_connection.join_flush_scheduled = true;
+                    Self::spawn_join_flush(self.client_loop_tx.clone(), connection_id, allowed_at);
+                    break;
+                }
+            }
+        }
 
-        // put the connection back to the end of the queue
-        self.connections.push_back(pool_connection);
-        // update metrics about channel numbers
         self.update_metrics();
     }
 
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus {
+            connections: self.connections.len(),
+            wanted_channels: self.connections.iter().map(|c| c.wanted_channels.len()).sum(),
+            server_channels: self.connections.iter().map(|c| c.server_channels.len()).sum(),
+            pending_joins: self.connections.iter().map(|c| c.pending_joins.len()).sum(),
+        }
+    }
+
     fn set_wanted_channels(&mut self, channels: HashSet<String>) {
         // part channels as needed
         self.connections
@@ -343,18 +500,28 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
             .and_then(|pos| self.connections.remove(pos))
             .unwrap();
 
-        // delegate part command to connection
-        pool_connection
-            .connection
-            .connection_loop_tx
-            .send(ConnectionLoopCommand::SendMessage(
-                irc!["PART", format!("#{}", channel_login)],
-                None,
-            ))
-            .unwrap();
-
-        pool_connection.register_sent_message();
+        if let Some(pos) = pool_connection
+            .pending_joins
+            .iter()
+            .position(|c| *c == channel_login)
+        {
+            // the JOIN was never sent, so there is nothing to PART
+            pool_connection.pending_joins.remove(pos);
+        } else {
+            // delegate part command to connection
+            pool_connection
+                .connection
+                .connection_loop_tx
+                .send(ConnectionLoopCommand::SendMessage(
+                    irc!["PART", format!("#{}", channel_login)],
+                    None,
+                ))
+                .unwrap();
+
+            pool_connection.register_sent_message();
+        }
         pool_connection.wanted_channels.remove(&channel_login);
+        self.rejoining_channels.remove(&channel_login);
 
         // put the connection back to the end of the queue
         self.connections.push_back(pool_connection);
@@ -372,8 +539,13 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
         message: ConnectionIncomingMessage<T, L>,
     ) {
         match message {
-            ConnectionIncomingMessage::IncomingMessage(message) => {
-                let is_whisper = matches!(*message, ServerMessage::Whisper(_));
+            ConnectionIncomingMessage::IncomingMessage(received) => {
+                let message = &received.message;
+                // RPL_WELCOME: the server accepted the login on this connection
+                if message.source().command == "001" {
+                    self.consecutive_connection_failures = 0;
+                }
+                let is_whisper = matches!(message, ServerMessage::Whisper(_));
                 if is_whisper {
                     match self.current_whisper_connection_id {
                         Some(current_whisper_connection_id) => {
@@ -399,7 +571,7 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
                     }
                 }
 
-                match &*message {
+                match message {
                     ServerMessage::Join(JoinMessage { channel_login, .. }) => {
                         // we successfully joined a channel
                         let c = self
@@ -411,6 +583,13 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
 
                         // update metrics about channel numbers
                         self.update_metrics();
+
+                        if let Some(failed_at) = self.rejoining_channels.remove(channel_login) {
+                            self.send_connection_event(ConnectionEvent::ChannelRejoined {
+                                channel: channel_login.clone(),
+                                downtime: failed_at.elapsed(),
+                            });
+                        }
                     }
                     ServerMessage::Part(PartMessage { channel_login, .. }) => {
                         // we successfully parted a channel
@@ -427,7 +606,7 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
                     _ => {}
                 }
 
-                self.client_incoming_messages_tx.send(*message).ok(); // ignore if the library user is not using the incoming messages
+                self.client_incoming_messages_tx.send(*received);
             }
             #[cfg(feature = "metrics-collection")]
             ConnectionIncomingMessage::StateOpen => {
@@ -464,6 +643,14 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
                 // also update twitch_irc_channels and twitch_irc_connections gauges
                 self.update_metrics();
 
+                self.consecutive_connection_failures += 1;
+                if let Some(max_failures) = self.config.max_consecutive_connection_failures {
+                    if self.consecutive_connection_failures >= max_failures {
+                        self.close(cause);
+                        return;
+                    }
+                }
+
                 // rejoin channels
                 tracing::debug!(
                     "Pool connection {} previously was joined to {} channels ({:?}), rejoining them",
@@ -471,7 +658,11 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
                     pool_connection.wanted_channels.len(),
                     pool_connection.wanted_channels
                 );
+                let failed_at = Instant::now();
                 for channel in pool_connection.wanted_channels.drain() {
+                    if self.config.connection_events.is_some() {
+                        self.rejoining_channels.insert(channel.clone(), failed_at);
+                    }
                     self.join(channel);
                 }
 
@@ -494,6 +685,30 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
         }
     }
 
+    /// Give up after too many failed connections: close all remaining connections and
+    /// report `cause` to `TwitchIRCClient::wait_closed`.
+    fn close(&mut self, cause: Error<T, L>) {
+        tracing::error!(
+            "{} connections failed in a row, closing the client: {}",
+            self.consecutive_connection_failures,
+            cause
+        );
+        // dropping the pool connections ends their connections and forward tasks
+        self.connections.clear();
+        self.rejoining_channels.clear();
+        self.current_whisper_connection_id = None;
+        self.update_metrics();
+
+        self.closed_tx.send_replace(Some(cause.clone()));
+        self.close_cause = Some(cause);
+    }
+
+    fn send_connection_event(&self, event: ConnectionEvent) {
+        if let Some(tx) = &self.config.connection_events {
+            tx.send(event).ok(); // ignore if the library user dropped the receiver
+        }
+    }
+
     #[cfg(feature = "metrics-collection")]
     fn update_metrics(&mut self) {
         if let Some(ref metrics) = self.metrics {
@@ -516,17 +731,18 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
                 .with_label_values(&["open"])
                 .set(num_open);
 
-            let (num_wanted, num_server) = self
+            let (num_wanted, num_server, num_pending_join) = self
                 .connections
                 .iter()
                 .map(|c| {
                     (
                         c.wanted_channels.len() as i64,
                         c.server_channels.len() as i64,
+                        c.pending_joins.len() as i64,
                     )
                 })
                 // sum up all the tuples (like vectors)
-                .fold((0, 0), |(a, b), (c, d)| (a + c, b + d));
+                .fold((0, 0, 0), |(a, b, c), (d, e, f)| (a + d, b + e, c + f));
 
             metrics
                 .channels
@@ -536,6 +752,10 @@ impl<T: Transport, L: LoginCredentials> ClientLoopWorker<T, L> {
                 .channels
                 .with_label_values(&["server"])
                 .set(num_server);
+            metrics
+                .channels
+                .with_label_values(&["pending_join"])
+                .set(num_pending_join);
         }
     }
 
@@ -3,13 +3,13 @@
 pub(crate) mod event_loop;
 mod pool_connection;
 
-use crate::client::event_loop::{ClientLoopCommand, ClientLoopWorker};
+use crate::client::event_loop::{ClientLoopCommand, ClientLoopWorker, IncomingMessagesSender};
 use crate::config::ClientConfig;
 use crate::error::Error;
 use crate::login::LoginCredentials;
 use crate::message::commands::ServerMessage;
 use crate::message::IRCTags;
-use crate::message::{IRCMessage, ReplyToMessage};
+use crate::message::{IRCMessage, ReceivedMessage, ReplyToMessage};
 #[cfg(feature = "metrics-collection")]
 use crate::metrics::MetricsBundle;
 use crate::transport::Transport;
@@ -18,7 +18,39 @@ use crate::{irc, validate};
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, oneshot};
+use std::future::Future;
+use tokio::sync::{mpsc, oneshot, watch};
+
+/// Events about the client's connection pool, delivered on
+/// [`ClientConfig::connection_events`](crate::ClientConfig::connection_events) if configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionEvent {
+    /// A connection failed and `channel` was joined again on a replacement connection.
+    ChannelRejoined {
+        /// Login name of the channel
+        channel: String,
+        /// Time from the failure of the old connection until the replacement connection
+        /// confirmed the JOIN. Messages sent to the channel during this time were missed.
+        downtime: Duration,
+    },
+}
+
+/// Snapshot of the client's connection pool, see
+/// [`TwitchIRCClient::pool_status`](crate::TwitchIRCClient::pool_status).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolStatus {
+    /// Number of open or connecting connections
+    pub connections: usize,
+    /// Channels the client wants to be joined to
+    pub wanted_channels: usize,
+    /// Channels the server confirmed as joined
+    pub server_channels: usize,
+    /// Wanted channels whose JOIN is waiting for
+    /// [`ClientConfig::join_rate_limit`](crate::ClientConfig::join_rate_limit)
+    pub pending_joins: usize,
+}
 
 /// A send-only handle to control the Twitch IRC Client.
 #[derive(Debug)]
@@ -33,6 +65,9 @@ pub struct TwitchIRCClient<T: Transport, L: LoginCredentials> {
     // it always only holds a Weak<> and has to check whether the weak reference is still
     // valid before sending itself messages.
     client_loop_tx: Arc<mpsc::UnboundedSender<ClientLoopCommand<T, L>>>,
+    // the client loop holds the sender. It sends the error it gave up with, or
+    // is dropped when the client loop ends normally.
+    closed_rx: watch::Receiver<Option<Error<T, L>>>,
 }
 
 // we have to implement Debug and Clone manually, the derive macro places
@@ -41,6 +76,7 @@ impl<T: Transport, L: LoginCredentials> Clone for TwitchIRCClient<T, L> {
     fn clone(&self) -> Self {
         TwitchIRCClient {
             client_loop_tx: self.client_loop_tx.clone(),
+            closed_rx: self.closed_rx.clone(),
         }
     }
 }
@@ -56,10 +92,41 @@ impl<T: Transport, L: LoginCredentials> TwitchIRCClient<T, L> {
         mpsc::UnboundedReceiver<ServerMessage>,
         TwitchIRCClient<T, L>,
     ) {
+        let (client_incoming_messages_tx, client_incoming_messages_rx) = mpsc::unbounded_channel();
+        let client = TwitchIRCClient::spawn(
+            config,
+            IncomingMessagesSender::Plain(client_incoming_messages_tx),
+        );
+        (client_incoming_messages_rx, client)
+    }
+
+    /// Create a new client like [`new`](TwitchIRCClient::new), but receive every message
+    /// together with the time it was read from the connection.
+    ///
+    /// Note this method is not side-effect-free - a background task will be spawned
+    /// as a result of calling this function.
+    pub fn new_timestamped(
+        config: ClientConfig<L>,
+    ) -> (
+        mpsc::UnboundedReceiver<ReceivedMessage>,
+        TwitchIRCClient<T, L>,
+    ) {
+        let (client_incoming_messages_tx, client_incoming_messages_rx) = mpsc::unbounded_channel();
+        let client = TwitchIRCClient::spawn(
+            config,
+            IncomingMessagesSender::Timestamped(client_incoming_messages_tx),
+        );
+        (client_incoming_messages_rx, client)
+    }
+
+    fn spawn(
+        config: ClientConfig<L>,
+        client_incoming_messages_tx: IncomingMessagesSender,
+    ) -> TwitchIRCClient<T, L> {
         let config = Arc::new(config);
         let (client_loop_tx, client_loop_rx) = mpsc::unbounded_channel();
         let client_loop_tx = Arc::new(client_loop_tx);
-        let (client_incoming_messages_tx, client_incoming_messages_rx) = mpsc::unbounded_channel();
+        let (closed_tx, closed_rx) = watch::channel(None);
 
         #[cfg(feature = "metrics-collection")]
         let metrics = MetricsBundle::new(&config.metrics_config);
@@ -70,14 +137,15 @@ impl<T: Transport, L: LoginCredentials> TwitchIRCClient<T, L> {
             Arc::downgrade(&client_loop_tx),
             client_loop_rx,
             client_incoming_messages_tx,
+            closed_tx,
             #[cfg(feature = "metrics-collection")]
             metrics,
         );
 
-        (
-            client_incoming_messages_rx,
-            TwitchIRCClient { client_loop_tx },
-        )
+        TwitchIRCClient {
+            client_loop_tx,
+            closed_rx,
+        }
     }
 }
 
@@ -447,6 +515,19 @@ impl<T: Transport, L: LoginCredentials> TwitchIRCClient<T, L> {
         return_rx.await.unwrap()
     }
 
+    /// Get a snapshot of the connections and channels of this client, e.g. to see how many
+    /// JOINs are still waiting for the join rate limit.
+    pub async fn pool_status(&self) -> PoolStatus {
+        let (return_tx, return_rx) = oneshot::channel();
+        self.client_loop_tx
+            .send(ClientLoopCommand::GetPoolStatus {
+                return_sender: return_tx,
+            })
+            .unwrap();
+        // unwrap: ClientLoopWorker should not die before all sender handles have been dropped
+        return_rx.await.unwrap()
+    }
+
     /// Part (leave) a channel, to stop receiving messages sent to that channel.
     ///
     /// This has the same semantics as `join()`. Similarly, a `part()` call will have no effect
@@ -472,4 +553,264 @@ impl<T: Transport, L: LoginCredentials> TwitchIRCClient<T, L> {
         // unwrap: ClientLoopWorker should not die before all sender handles have been dropped
         return_rx.await.unwrap()
     }
+
+    /// Resolves once the client has shut down, e.g. to watch the client from a supervisor.
+    ///
+    /// Returns the error the client gave up with, if it closed because of
+    /// [`ClientConfig::max_consecutive_connection_failures`](crate::ClientConfig::max_consecutive_connection_failures),
+    /// or `None` if it shut down normally because all `TwitchIRCClient` handles were dropped.
+    ///
+    /// The returned future does not borrow the client, so it can be awaited after dropping
+    /// all handles.
+    pub fn wait_closed(&self) -> impl Future<Output = Option<Error<T, L>>> + Send + 'static {
+        let mut closed_rx = self.closed_rx.clone();
+        async move {
+            loop {
+                if let Some(cause) = closed_rx.borrow_and_update().as_ref() {
+                    return Some(cause.clone());
+                }
+                if closed_rx.changed().await.is_err() {
+                    // the client loop ended without giving up
+                    return closed_rx.borrow().clone();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message::{IRCMessage, IRCParseError};
+    use crate::transport::Transport;
+    use crate::{irc, ClientConfig, ConnectionEvent, Error, TwitchIRCClient};
+    use async_trait::async_trait;
+    use either::Either;
+    use futures_channel::mpsc as futures_mpsc;
+    use futures_util::StreamExt;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio::time::Instant;
+    use tokio::time::timeout;
+
+    type MockIncoming =
+        futures_mpsc::UnboundedReceiver<Result<IRCMessage, Either<std::io::Error, IRCParseError>>>;
+
+    /// Transports handed out by `MockTransport::new()`, in order.
+    static MOCK_TRANSPORTS: Mutex<VecDeque<MockTransport>> = Mutex::new(VecDeque::new());
+    /// Held by every test using `MOCK_TRANSPORTS`, so tests don't take each other's transports.
+    static MOCK_TRANSPORTS_IN_USE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    #[derive(Debug)]
+    struct MockTransport {
+        incoming: MockIncoming,
+        outgoing: futures_mpsc::UnboundedSender<IRCMessage>,
+    }
+
+    /// The test side of a `MockTransport`: feed incoming lines, read what the client sent.
+    struct MockServer {
+        incoming_tx: futures_mpsc::UnboundedSender<
+            Result<IRCMessage, Either<std::io::Error, IRCParseError>>,
+        >,
+        outgoing_rx: futures_mpsc::UnboundedReceiver<IRCMessage>,
+    }
+
+    impl MockServer {
+        fn prepare() -> MockServer {
+            let (incoming_tx, incoming) = futures_mpsc::unbounded();
+            let (outgoing, outgoing_rx) = futures_mpsc::unbounded();
+            MOCK_TRANSPORTS
+                .lock()
+                .unwrap()
+                .push_back(MockTransport { incoming, outgoing });
+            MockServer {
+                incoming_tx,
+                outgoing_rx,
+            }
+        }
+
+        fn send(&self, raw: &str) {
+            self.incoming_tx
+                .unbounded_send(Ok(IRCMessage::parse(raw).unwrap()))
+                .unwrap();
+        }
+
+        async fn expect_command(&mut self, command: &str, param: &str) {
+            loop {
+                let message = timeout(Duration::from_secs(5), self.outgoing_rx.next())
+                    .await
+                    .expect("timed out waiting for outgoing message")
+                    .expect("client closed the transport");
+                if message.command == command
+                    && message.params.first().map(String::as_str) == Some(param)
+                {
+                    return;
+                }
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Transport for MockTransport {
+        type ConnectError = std::io::Error;
+        type IncomingError = std::io::Error;
+        type OutgoingError = futures_mpsc::SendError;
+        type Incoming = MockIncoming;
+        type Outgoing = futures_mpsc::UnboundedSender<IRCMessage>;
+
+        async fn new() -> Result<MockTransport, std::io::Error> {
+            MOCK_TRANSPORTS.lock().unwrap().pop_front().ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::ConnectionRefused,
+                    "no mock transport left",
+                )
+            })
+        }
+
+        fn split(self) -> (Self::Incoming, Self::Outgoing) {
+            (self.incoming, self.outgoing)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_channel_rejoined_event_after_connection_failure() {
+        let _in_use = MOCK_TRANSPORTS_IN_USE.lock().await;
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let config = ClientConfig {
+            new_connection_every: Duration::from_millis(10),
+            connection_events: Some(events_tx),
+            ..ClientConfig::default()
+        };
+
+        let mut first = MockServer::prepare();
+        let mut second = MockServer::prepare();
+
+        let (_incoming_messages, client) = TwitchIRCClient::<MockTransport, _>::new(config);
+        client.join("pajlada".to_owned()).unwrap();
+
+        first.expect_command("JOIN", "#pajlada").await;
+        first.send(":justinfan12345!justinfan12345@justinfan12345.tmi.twitch.tv JOIN #pajlada");
+
+        // kill the first connection mid-session
+        drop(first);
+
+        second.expect_command("JOIN", "#pajlada").await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        second.send(":justinfan12345!justinfan12345@justinfan12345.tmi.twitch.tv JOIN #pajlada");
+
+        let event = timeout(Duration::from_secs(5), events_rx.recv())
+            .await
+            .expect("timed out waiting for connection event")
+            .unwrap();
+        match event {
+            ConnectionEvent::ChannelRejoined { channel, downtime } => {
+                assert_eq!(channel, "pajlada");
+                assert!(downtime >= Duration::from_millis(50));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_closed_after_consecutive_connection_failures() {
+        let _in_use = MOCK_TRANSPORTS_IN_USE.lock().await;
+        // no mock transports prepared, so every connection fails to connect
+        let config = ClientConfig {
+            new_connection_every: Duration::from_millis(10),
+            max_consecutive_connection_failures: Some(3),
+            ..ClientConfig::default()
+        };
+
+        let (_incoming_messages, client) = TwitchIRCClient::<MockTransport, _>::new(config);
+        client.join("pajlada".to_owned()).unwrap();
+
+        let cause = timeout(Duration::from_secs(5), client.wait_closed())
+            .await
+            .expect("timed out waiting for the client to close");
+        assert!(matches!(cause, Some(Error::ConnectError(_))));
+
+        // the closed client makes no new connections
+        let result = client.send_message(irc!["PING", "tmi.twitch.tv"]).await;
+        assert!(matches!(result, Err(Error::ConnectError(_))));
+        assert_eq!(
+            client.get_channel_status("pajlada".to_owned()).await,
+            (false, false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_closed_on_clean_shutdown() {
+        let (_incoming_messages, client) =
+            TwitchIRCClient::<MockTransport, _>::new(ClientConfig::default());
+        let closed = client.wait_closed();
+        drop(client);
+
+        let cause = timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("timed out waiting for the client to close");
+        assert!(cause.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_join_rate_limit_paces_joins() {
+        let _in_use = MOCK_TRANSPORTS_IN_USE.lock().await;
+        let config = ClientConfig {
+            join_rate_limit: Some((2, Duration::from_millis(300))),
+            ..ClientConfig::default()
+        };
+
+        let mut server = MockServer::prepare();
+        let (_incoming_messages, client) = TwitchIRCClient::<MockTransport, _>::new(config);
+
+        let start = Instant::now();
+        for channel in ["a", "b", "c", "d"] {
+            client.join(channel.to_owned()).unwrap();
+        }
+        assert_eq!(client.pool_status().await.pending_joins, 2);
+
+        server.expect_command("JOIN", "#a").await;
+        server.expect_command("JOIN", "#b").await;
+        server.expect_command("JOIN", "#c").await;
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        server.expect_command("JOIN", "#d").await;
+
+        let status = client.pool_status().await;
+        assert_eq!(status.pending_joins, 0);
+        assert_eq!(status.wanted_channels, 4);
+    }
+
+    #[tokio::test]
+    async fn test_part_of_pending_join_sends_nothing() {
+        let _in_use = MOCK_TRANSPORTS_IN_USE.lock().await;
+        let config = ClientConfig {
+            join_rate_limit: Some((1, Duration::from_millis(200))),
+            ..ClientConfig::default()
+        };
+
+        let mut server = MockServer::prepare();
+        let (_incoming_messages, client) = TwitchIRCClient::<MockTransport, _>::new(config);
+
+        client.join("a".to_owned()).unwrap();
+        client.join("b".to_owned()).unwrap();
+        client.part("b".to_owned());
+        client.join("c".to_owned()).unwrap();
+        assert_eq!(client.pool_status().await.pending_joins, 1);
+
+        server.expect_command("JOIN", "#a").await;
+        // the next line after #a must be the JOIN for #c, without a PART or JOIN for #b
+        loop {
+            let message = timeout(Duration::from_secs(5), server.outgoing_rx.next())
+                .await
+                .unwrap()
+                .unwrap();
+            if message.params.first().map(String::as_str) == Some("#b") {
+                panic!("unexpected {} for pending channel", message.command);
+            }
+            if message.command == "JOIN" {
+                assert_eq!(message.params[0], "#c");
+                break;
+            }
+        }
+        assert_eq!(client.get_channel_status("b".to_owned()).await, (false, false));
+    }
 }
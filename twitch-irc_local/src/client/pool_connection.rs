@@ -4,8 +4,8 @@ use crate::login::LoginCredentials;
 use crate::transport::Transport;
 use std::collections::{HashSet, VecDeque};
 use std::sync::Arc;
-use std::time::Instant;
 use tokio::sync::oneshot;
+use tokio::time::Instant;
 
 /// The actual state of the connection loop is held only by the connection loop.
 /// However the connection sends out messages indicating that it has changed its state.
@@ -36,6 +36,14 @@ pub(crate) struct PoolConnection<T: Transport, L: LoginCredentials> {
     /// this has a list of times when messages were sent out on this pool connection,
     /// at the front there will be the oldest, and at the back the newest entries
     pub message_send_times: VecDeque<Instant>,
+    /// times when the most recent JOINs were sent out on this connection, oldest first.
+    /// Only tracked if `config.join_rate_limit` is set.
+    pub join_send_times: VecDeque<Instant>,
+    /// channels in `wanted_channels` whose JOIN is held back by `config.join_rate_limit`,
+    /// in the order they were requested
+    pub pending_joins: VecDeque<String>,
+    /// whether the client loop is already scheduled to send the `pending_joins`
+    pub join_flush_scheduled: bool,
     /// The actual state of the connection loop is held only by the connection loop.
     /// However the connection sends out messages indicating that it has changed its state.
     /// This enum tracks that "reported state" as received via messages from the connection.
@@ -64,6 +72,9 @@ impl<T: Transport, L: LoginCredentials> PoolConnection<T, L> {
             wanted_channels: HashSet::new(),
             server_channels: HashSet::new(),
             message_send_times: VecDeque::with_capacity(message_send_times_max_entries),
+            join_send_times: VecDeque::new(),
+            pending_joins: VecDeque::new(),
+            join_flush_scheduled: false,
             #[cfg(feature = "metrics-collection")]
             reported_state: ReportedConnectionState::Initializing,
             tx_kill_incoming: Some(tx_kill_incoming),
@@ -80,6 +91,36 @@ impl<T: Transport, L: LoginCredentials> PoolConnection<T, L> {
         }
     }
 
+    pub fn register_sent_join(&mut self) {
+        if self.config.join_rate_limit.is_some() {
+            self.join_send_times.push_back(Instant::now());
+        }
+    }
+
+    /// `None` if a JOIN may be sent out on this connection right now, otherwise the time when
+    /// `config.join_rate_limit` allows the next one.
+    pub fn next_join_allowed_at(&mut self) -> Option<Instant> {
+        let (max_joins, window) = self.config.join_rate_limit?;
+        let current_time = Instant::now();
+
+        // forget JOINs that have left the window
+        while let Some(send_time) = self.join_send_times.front() {
+            if *send_time + window <= current_time {
+                self.join_send_times.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if self.join_send_times.len() < max_joins as usize {
+            None
+        } else {
+            self.join_send_times
+                .get(self.join_send_times.len() - max_joins as usize)
+                .map(|send_time| *send_time + window)
+        }
+    }
+
     pub fn channels_limit_not_reached(&self) -> bool {
         let configured_limit = self.config.max_channels_per_connection;
         self.wanted_channels.len() < configured_limit
@@ -1,10 +1,11 @@
+use crate::client::ConnectionEvent;
 use crate::login::{LoginCredentials, StaticLoginCredentials};
 use std::borrow::Cow;
 #[cfg(feature = "metrics-collection")]
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::Semaphore;
+use tokio::sync::{mpsc, Semaphore};
 
 /// Configures settings for a `TwitchIRCClient`.
 #[derive(Debug)]
@@ -62,7 +63,8 @@ pub struct ClientConfig<L: LoginCredentials> {
     ///   you are joined to (Gauge). Allocated channels are joins that passed through the `TwitchIRCClient`
     ///   but may be waiting e.g. for the connection to finish connecting. Once a
     ///   confirmation response is received by Twitch that the channel was joined successfully,
-    ///   that channel is additionally `confirmed`.
+    ///   that channel is additionally `confirmed`. Channels whose JOIN is still held back by
+    ///   [`join_rate_limit`](ClientConfig::join_rate_limit) are also counted as `pending_join`.
     ///
     /// * `twitchirc_connections` counts how many connections this client has in use (Gauge).
     ///    The label `state=initializing/open` identifies how many connections are
@@ -117,6 +119,30 @@ pub struct ClientConfig<L: LoginCredentials> {
     /// client. This means that all log output from a single client will all be under that span,
     /// with that name.
     pub tracing_identifier: Option<Cow<'static, str>>,
+
+    /// If enabled, messages that fail to parse as their command's `ServerMessage` type are
+    /// emitted as [`ServerMessage::Malformed`](crate::message::ServerMessage::Malformed),
+    /// carrying the parse error. If disabled (the default), they are emitted like messages
+    /// of unknown commands and the parse error is only logged.
+    pub emit_malformed_messages: bool,
+
+    /// If set, the client reports events about its connection pool on this channel, e.g.
+    /// which channels had to be re-joined after a connection failed. See [`ConnectionEvent`].
+    /// Default: `None`, no events are sent.
+    pub connection_events: Option<mpsc::UnboundedSender<ConnectionEvent>>,
+
+    /// If set, the client gives up after this many connections in a row failed before the
+    /// server welcomed their login, e.g. because the credentials are rejected or the network
+    /// is down. All connections are then closed and the last error is returned from
+    /// [`TwitchIRCClient::wait_closed`](crate::TwitchIRCClient::wait_closed).
+    /// Default: `None`, failed connections are retried forever.
+    pub max_consecutive_connection_failures: Option<usize>,
+
+    /// Send at most this many JOINs per time window on each connection. Further JOINs are
+    /// queued and sent once the window allows it; Twitch silently ignores JOINs above its
+    /// limit. Default: `Some((20, Duration::from_secs(10)))`, Twitch's limit for normal
+    /// accounts. `None` sends all JOINs right away.
+    pub join_rate_limit: Option<(u32, Duration)>,
 }
 
 /// Used to configure the options around metrics collection using the `prometheus` crate.
@@ -176,6 +202,10 @@ impl<L: LoginCredentials> ClientConfig<L> {
             #[cfg(feature = "metrics-collection")]
             metrics_config: MetricsConfig::default(),
             tracing_identifier: None,
+            emit_malformed_messages: false,
+            connection_events: None,
+            max_consecutive_connection_failures: None,
+            join_rate_limit: Some((20, Duration::from_secs(10))),
         }
     }
 }
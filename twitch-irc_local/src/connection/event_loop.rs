@@ -6,6 +6,7 @@ use crate::login::{CredentialsPair, LoginCredentials};
 use crate::message::commands::ServerMessage;
 use crate::message::AsRawIRC;
 use crate::message::IRCMessage;
+use crate::message::ReceivedMessage;
 #[cfg(feature = "metrics-collection")]
 use crate::metrics::MetricsBundle;
 use crate::transport::Transport;
@@ -15,6 +16,7 @@ use futures_util::{SinkExt, StreamExt};
 use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::sync::{Arc, Weak};
+use std::time::SystemTime;
 use tokio::sync::{mpsc, oneshot};
 use tokio::time::{interval_at, Duration, Instant};
 use tracing::{debug_span, info_span, Instrument};
@@ -32,7 +34,8 @@ pub(crate) enum ConnectionLoopCommand<T: Transport, L: LoginCredentials> {
 
     // commands that come from the incoming loop
     // Some(Ok(_)) is an ordinary message, Some(Err(_)) an error, and None an EOF (end of stream)
-    IncomingMessage(Option<Result<IRCMessage, Error<T, L>>>),
+    // the SystemTime is when the incoming loop got the message from the transport
+    IncomingMessage(Option<Result<IRCMessage, Error<T, L>>>, SystemTime),
 
     // commands that come from the ping loop
     SendPing(),
@@ -54,6 +57,7 @@ trait ConnectionLoopStateMethods<T: Transport, L: LoginCredentials> {
     fn on_incoming_message(
         self,
         maybe_message: Option<Result<IRCMessage, Error<T, L>>>,
+        received_at: SystemTime,
     ) -> ConnectionLoopState<T, L>;
     fn send_ping(&mut self);
     fn check_pong(self) -> ConnectionLoopState<T, L>;
@@ -88,6 +92,7 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopWorker<T, L> {
                 commands_queue: VecDeque::new(),
                 connection_loop_tx: Weak::clone(&connection_loop_tx),
                 connection_incoming_tx,
+                emit_malformed_messages: config.emit_malformed_messages,
                 #[cfg(feature = "metrics-collection")]
                 metrics: metrics.clone(),
             }),
@@ -184,7 +189,7 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopWorker<T, L> {
             ConnectionLoopCommand::SendError(error) => {
                 self.state = self.state.on_send_error(error);
             }
-            ConnectionLoopCommand::IncomingMessage(maybe_msg) => {
+            ConnectionLoopCommand::IncomingMessage(maybe_msg, received_at) => {
                 match &maybe_msg {
                     Some(Ok(msg)) => {
                         tracing::trace!("< {}", msg.as_raw_irc());
@@ -200,7 +205,7 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopWorker<T, L> {
                     None => tracing::trace!("EOF from transport"),
                 }
 
-                self.state = self.state.on_incoming_message(maybe_msg);
+                self.state = self.state.on_incoming_message(maybe_msg, received_at);
             }
             ConnectionLoopCommand::SendPing() => self.state.send_ping(),
             ConnectionLoopCommand::CheckPong() => {
@@ -225,6 +230,7 @@ struct ConnectionLoopInitializingState<T: Transport, L: LoginCredentials> {
     commands_queue: CommandQueue<T, L>,
     connection_loop_tx: Weak<mpsc::UnboundedSender<ConnectionLoopCommand<T, L>>>,
     connection_incoming_tx: mpsc::UnboundedSender<ConnectionIncomingMessage<T, L>>,
+    emit_malformed_messages: bool,
     #[cfg(feature = "metrics-collection")]
     metrics: Option<MetricsBundle>,
 }
@@ -262,6 +268,7 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopInitializingState<T, L> {
                     break;
                 }
                 incoming_message = transport_incoming.next() => {
+                    let received_at = SystemTime::now();
                     let do_exit = matches!(incoming_message, None | Some(Err(_)));
                     let incoming_message = incoming_message.map(|x| x.map_err(|e| match e {
                         Either::Left(e) => Error::IncomingError(Arc::new(e)),
@@ -269,7 +276,7 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopInitializingState<T, L> {
                     }));
 
                     if let Some(connection_loop_tx) = connection_loop_tx.upgrade() {
-                        connection_loop_tx.send(ConnectionLoopCommand::IncomingMessage(incoming_message)).ok();
+                        connection_loop_tx.send(ConnectionLoopCommand::IncomingMessage(incoming_message, received_at)).ok();
                     } else {
                         break;
                     }
@@ -412,6 +419,7 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopStateMethods<T, L>
                     pong_received: false,
                     kill_incoming_loop_tx: Some(kill_incoming_loop_tx),
                     kill_pinger_tx: Some(kill_pinger_tx),
+                    emit_malformed_messages: self.emit_malformed_messages,
                     #[cfg(feature = "metrics-collection")]
                     metrics: self.metrics,
                 });
@@ -448,6 +456,7 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopStateMethods<T, L>
     fn on_incoming_message(
         self,
         _maybe_message: Option<Result<IRCMessage, Error<T, L>>>,
+        _received_at: SystemTime,
     ) -> ConnectionLoopState<T, L> {
         unreachable!("messages cannot come in while initializing")
     }
@@ -472,6 +481,7 @@ struct ConnectionLoopOpenState<T: Transport, L: LoginCredentials> {
     /// These fields are wrapped in `Option` so we can use `take()` in the Drop implementation.
     kill_incoming_loop_tx: Option<oneshot::Sender<()>>,
     kill_pinger_tx: Option<oneshot::Sender<()>>,
+    emit_malformed_messages: bool,
     #[cfg(feature = "metrics-collection")]
     metrics: Option<MetricsBundle>,
 }
@@ -536,6 +546,7 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopStateMethods<T, L>
     fn on_incoming_message(
         mut self,
         maybe_message: Option<Result<IRCMessage, Error<T, L>>>,
+        received_at: SystemTime,
     ) -> ConnectionLoopState<T, L> {
         match maybe_message {
             None => {
@@ -557,7 +568,10 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopStateMethods<T, L>
                     Ok(server_message) => {
                         self.connection_incoming_tx
                             .send(ConnectionIncomingMessage::IncomingMessage(Box::new(
-                                server_message.clone(),
+                                ReceivedMessage {
+                                    message: server_message.clone(),
+                                    received_at,
+                                },
                             )))
                             .ok();
 
@@ -579,10 +593,19 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopStateMethods<T, L>
                         }
                     }
                     Err(parse_error) => {
-                        tracing::error!("Failed to parse incoming message as ServerMessage (emitting as generic instead): {}", parse_error);
+                        let server_message = if self.emit_malformed_messages {
+                            tracing::error!("Failed to parse incoming message as ServerMessage (emitting as malformed instead): {}", parse_error);
+                            ServerMessage::new_malformed(parse_error)
+                        } else {
+                            tracing::error!("Failed to parse incoming message as ServerMessage (emitting as generic instead): {}", parse_error);
+                            ServerMessage::new_generic(IRCMessage::from(parse_error))
+                        };
                         self.connection_incoming_tx
                             .send(ConnectionIncomingMessage::IncomingMessage(Box::new(
-                                ServerMessage::new_generic(IRCMessage::from(parse_error)),
+                                ReceivedMessage {
+                                    message: server_message,
+                                    received_at,
+                                },
                             )))
                             .ok();
                     }
@@ -646,6 +669,7 @@ impl<T: Transport, L: LoginCredentials> ConnectionLoopStateMethods<T, L>
     fn on_incoming_message(
         self,
         _maybe_message: Option<Result<IRCMessage, Error<T, L>>>,
+        _received_at: SystemTime,
     ) -> ConnectionLoopState<T, L> {
         // do nothing, stay closed
         ConnectionLoopState::Closed(self)
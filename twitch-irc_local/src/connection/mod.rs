@@ -4,7 +4,7 @@ use crate::config::ClientConfig;
 use crate::connection::event_loop::{ConnectionLoopCommand, ConnectionLoopWorker};
 use crate::error::Error;
 use crate::login::LoginCredentials;
-use crate::message::commands::ServerMessage;
+use crate::message::ReceivedMessage;
 #[cfg(feature = "metrics-collection")]
 use crate::metrics::MetricsBundle;
 use crate::transport::Transport;
@@ -13,7 +13,7 @@ use tokio::sync::mpsc;
 
 #[derive(Debug)]
 pub enum ConnectionIncomingMessage<T: Transport, L: LoginCredentials> {
-    IncomingMessage(Box<ServerMessage>),
+    IncomingMessage(Box<ReceivedMessage>),
     #[cfg(feature = "metrics-collection")]
     StateOpen,
     StateClosed {
@@ -256,7 +256,7 @@ mod metrics;
 pub mod transport;
 pub mod validate;
 
-pub use client::TwitchIRCClient;
+pub use client::{ConnectionEvent, PoolStatus, TwitchIRCClient};
 pub use config::ClientConfig;
 #[cfg(feature = "metrics-collection")]
 pub use config::MetricsConfig;
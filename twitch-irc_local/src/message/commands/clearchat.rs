@@ -10,7 +10,8 @@ use {serde::Deserialize, serde::Serialize};
 
 /// Timeout, Permaban or when a chat is entirely cleared.
 ///
-/// This represents the `CLEARCHAT` IRC command.
+/// This represents the `CLEARCHAT` IRC command. Anonymous (`justinfan`) connections get
+/// the same tags as authenticated ones, so all fields are always present.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub struct ClearChatMessage {
@@ -108,6 +109,26 @@ impl TryFrom<IRCMessage> for ClearChatMessage {
     }
 }
 
+impl ClearChatMessage {
+    /// ID of the banned or timed out user (`target-user-id`), `None` if the chat was cleared.
+    pub fn target_user_id(&self) -> Option<&str> {
+        match &self.action {
+            ClearChatAction::UserBanned { user_id, .. }
+            | ClearChatAction::UserTimedOut { user_id, .. } => Some(user_id),
+            ClearChatAction::ChatCleared => None,
+        }
+    }
+
+    /// Login name of the banned or timed out user, `None` if the chat was cleared.
+    pub fn target_user_login(&self) -> Option<&str> {
+        match &self.action {
+            ClearChatAction::UserBanned { user_login, .. }
+            | ClearChatAction::UserTimedOut { user_login, .. } => Some(user_login),
+            ClearChatAction::ChatCleared => None,
+        }
+    }
+}
+
 impl From<ClearChatMessage> for IRCMessage {
     fn from(msg: ClearChatMessage) -> IRCMessage {
         msg.source
@@ -182,4 +203,17 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    pub fn test_target_user() {
+        let src = "@room-id=11148817;target-user-id=70948394;tmi-sent-ts=1594561360331 :tmi.twitch.tv CLEARCHAT #pajlada :weeb123";
+        let msg = ClearChatMessage::try_from(IRCMessage::parse(src).unwrap()).unwrap();
+        assert_eq!(msg.target_user_id(), Some("70948394"));
+        assert_eq!(msg.target_user_login(), Some("weeb123"));
+
+        let src = "@room-id=40286300;tmi-sent-ts=1594561392337 :tmi.twitch.tv CLEARCHAT #randers";
+        let msg = ClearChatMessage::try_from(IRCMessage::parse(src).unwrap()).unwrap();
+        assert_eq!(msg.target_user_id(), None);
+        assert_eq!(msg.target_user_login(), None);
+    }
 }
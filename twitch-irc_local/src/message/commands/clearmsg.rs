@@ -8,17 +8,25 @@ use {serde::Deserialize, serde::Serialize};
 
 /// Message for when a single message is deleted from chat.
 ///
-/// The deleted message is identified by its `message_id`.
+/// The deleted message is identified by its `message_id` (the `target-msg-id` tag), which
+/// is the `message_id` of the original `PrivmsgMessage`.
+///
+/// Twitch sends the same tags to anonymous (`justinfan`) and authenticated connections.
+/// The only gap is `room-id`, which is sent empty on all `CLEARMSG`s, so `channel_id`
+/// is `None` until Twitch fixes that.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
 pub struct ClearMsgMessage {
     /// Login name of the channel that the deleted message was posted in.
     pub channel_login: String,
-    // pub channel_id: String,
+    /// ID of the channel, if Twitch sent one (see
+    /// <https://github.com/twitchdev/issues/issues/163>).
+    pub channel_id: Option<String>,
     /// login name of the user that sent the original message that was deleted by this
     /// `CLEARMSG`.
     pub sender_login: String,
-    /// ID of the message that was deleted.
+    /// ID of the message that was deleted (`target-msg-id`).
+    #[doc(alias = "target_msg_id")]
     pub message_id: String,
     /// Text of the message that was deleted
     pub message_text: String,
@@ -41,13 +49,20 @@ impl TryFrom<IRCMessage> for ClearMsgMessage {
 
         // example msg:
         // @login=alazymeme;room-id=;target-msg-id=3c92014f-340a-4dc3-a9c9-e5cf182f4a84;tmi-sent-ts=1594561955611 :tmi.twitch.tv CLEARMSG #pajlada :NIGHT CUNT
-        // room-id is currently empty on all incoming messages, so we don't parse it
-        // see https://github.com/twitchdev/issues/issues/163
+        // room-id is currently empty on all incoming messages, it is only filled in
+        // if Twitch starts sending it, see https://github.com/twitchdev/issues/issues/163
         let (message_text, is_action) = source.try_get_message_text()?;
+        let channel_id = source
+            .tags
+            .0
+            .get("room-id")
+            .and_then(|value| value.as_deref())
+            .filter(|value| !value.is_empty())
+            .map(str::to_owned);
 
         Ok(ClearMsgMessage {
             channel_login: source.try_get_channel_login()?.to_owned(),
-            // channel_id: source.try_get_nonempty_tag_value("room-id")?.to_owned(),
+            channel_id,
             sender_login: source.try_get_nonempty_tag_value("login")?.to_owned(),
             message_id: source
                 .try_get_nonempty_tag_value("target-msg-id")?
@@ -82,6 +97,7 @@ mod tests {
             msg,
             ClearMsgMessage {
                 channel_login: "pajlada".to_owned(),
+                channel_id: None,
                 sender_login: "alazymeme".to_owned(),
                 message_id: "3c92014f-340a-4dc3-a9c9-e5cf182f4a84".to_owned(),
                 message_text: "NIGHT CUNT".to_owned(),
@@ -102,6 +118,7 @@ mod tests {
             msg,
             ClearMsgMessage {
                 channel_login: "pajlada".to_owned(),
+                channel_id: None,
                 sender_login: "randers".to_owned(),
                 message_id: "15e5164d-f8e6-4aec-baf4-2d6a330760c4".to_owned(),
                 message_text: "test".to_owned(),
@@ -111,4 +128,13 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    pub fn test_room_id() {
+        let src = "@login=randers;room-id=11148817;target-msg-id=15e5164d-f8e6-4aec-baf4-2d6a330760c4;tmi-sent-ts=1594562632383 :tmi.twitch.tv CLEARMSG #pajlada :test";
+        let msg = ClearMsgMessage::try_from(IRCMessage::parse(src).unwrap()).unwrap();
+
+        assert_eq!(msg.channel_id.as_deref(), Some("11148817"));
+        assert_eq!(msg.message_id, "15e5164d-f8e6-4aec-baf4-2d6a330760c4");
+    }
 }
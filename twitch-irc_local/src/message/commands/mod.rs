@@ -39,7 +39,7 @@ use {serde::Deserialize, serde::Serialize};
 
 /// Errors encountered while trying to parse an IRC message as a more specialized "server message",
 /// based on its IRC command.
-#[derive(Error, Debug, PartialEq, Eq)]
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
 pub enum ServerMessageParseError {
     /// That command's data is not parsed by this implementation
     ///
@@ -472,6 +472,18 @@ pub enum ServerMessage {
     UserState(UserStateMessage),
     /// `WHISPER` message
     Whisper(WhisperMessage),
+    /// A message of a known command that could not be parsed. Only emitted if
+    /// [`ClientConfig::emit_malformed_messages`](crate::ClientConfig::emit_malformed_messages)
+    /// is enabled, otherwise such messages are emitted the same way as unknown commands.
+    ///
+    /// This variant is never serialized or deserialized.
+    #[cfg_attr(feature = "with-serde", serde(skip))]
+    Malformed {
+        /// The message as it was received
+        source: IRCMessage,
+        /// Why the message could not be parsed
+        error: ServerMessageParseError,
+    },
     #[doc(hidden)]
     Generic(HiddenIRCMessage),
 }
@@ -519,6 +531,7 @@ impl From<ServerMessage> for IRCMessage {
             ServerMessage::UserNotice(msg) => msg.source,
             ServerMessage::UserState(msg) => msg.source,
             ServerMessage::Whisper(msg) => msg.source,
+            ServerMessage::Malformed { source, .. } => source,
             ServerMessage::Generic(msg) => msg.0,
         }
     }
@@ -543,6 +556,7 @@ impl ServerMessage {
             ServerMessage::UserNotice(msg) => &msg.source,
             ServerMessage::UserState(msg) => &msg.source,
             ServerMessage::Whisper(msg) => &msg.source,
+            ServerMessage::Malformed { source, .. } => source,
             ServerMessage::Generic(msg) => &msg.0,
         }
     }
@@ -550,6 +564,13 @@ impl ServerMessage {
     pub(crate) fn new_generic(message: IRCMessage) -> ServerMessage {
         ServerMessage::Generic(HiddenIRCMessage(message))
     }
+
+    pub(crate) fn new_malformed(error: ServerMessageParseError) -> ServerMessage {
+        ServerMessage::Malformed {
+            source: IRCMessage::from(error.clone()),
+            error,
+        }
+    }
 }
 
 impl AsRawIRC for ServerMessage {
@@ -557,3 +578,32 @@ impl AsRawIRC for ServerMessage {
         self.source().format_as_raw_irc(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::message::{IRCMessage, ServerMessage, ServerMessageParseError};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_corrupted_badges_emitted_as_malformed() {
+        let src = "@badge-info=;badges=moderator;color=#0000FF;display-name=JuN1oRRRR;emotes=;flags=;id=e9d998c3-36f1-430f-89ec-6b887c28af36;mod=0;room-id=11148817;subscriber=0;tmi-sent-ts=1594545155039;turbo=0;user-id=29803735;user-type= :jun1orrrr!jun1orrrr@jun1orrrr.tmi.twitch.tv PRIVMSG #pajlada :dank cam";
+        let irc_message = IRCMessage::parse(src).unwrap();
+        let msg = ServerMessage::try_from(irc_message.clone())
+            .unwrap_or_else(ServerMessage::new_malformed);
+
+        match msg {
+            ServerMessage::Malformed { source, error } => {
+                assert_eq!(source, irc_message);
+                assert_eq!(
+                    error,
+                    ServerMessageParseError::MalformedTagValue(
+                        irc_message,
+                        "badges",
+                        "moderator".to_owned()
+                    )
+                );
+            }
+            other => panic!("expected Malformed, got {:?}", other),
+        }
+    }
+}
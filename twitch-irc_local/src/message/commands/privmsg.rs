@@ -1,5 +1,5 @@
 use crate::message::commands::IRCMessageParseExt;
-use crate::message::twitch::{Badge, Emote, RGBColor, TwitchUserBasics};
+use crate::message::twitch::{Badge, BadgeSet, Emote, RGBColor, TwitchUserBasics};
 use crate::message::{IRCMessage, ReplyToMessage, ServerMessageParseError};
 use chrono::{DateTime, Utc};
 use std::convert::TryFrom;
@@ -36,6 +36,8 @@ pub struct PrivmsgMessage {
     pub badge_info: Vec<Badge>,
     /// List of badges that should be displayed alongside the message.
     pub badges: Vec<Badge>,
+    /// `badges` and `badge_info` in typed form, e.g. to check whether the sender is a moderator.
+    pub badge_set: BadgeSet,
     /// If present, specifies how many bits were cheered with this message.
     pub bits: Option<u64>,
     /// If present, specifies the color that the user's name should be displayed in. A value
@@ -65,6 +67,8 @@ impl TryFrom<IRCMessage> for PrivmsgMessage {
         }
 
         let (message_text, is_action) = source.try_get_message_text()?;
+        let badge_info = source.try_get_badges("badge-info")?;
+        let badges = source.try_get_badges("badges")?;
 
         Ok(PrivmsgMessage {
             channel_login: source.try_get_channel_login()?.to_owned(),
@@ -76,8 +80,9 @@ impl TryFrom<IRCMessage> for PrivmsgMessage {
                     .try_get_nonempty_tag_value("display-name")?
                     .to_owned(),
             },
-            badge_info: source.try_get_badges("badge-info")?,
-            badges: source.try_get_badges("badges")?,
+            badge_set: BadgeSet::new(&badges, &badge_info),
+            badge_info,
+            badges,
             bits: source.try_get_optional_number("bits")?,
             name_color: source.try_get_color("color")?,
             emotes: source.try_get_emotes("emotes", message_text)?,
@@ -108,7 +113,7 @@ impl ReplyToMessage for PrivmsgMessage {
 
 #[cfg(test)]
 mod tests {
-    use crate::message::twitch::{Badge, Emote, RGBColor, TwitchUserBasics};
+    use crate::message::twitch::{Badge, BadgeSet, Emote, RGBColor, TwitchUserBasics};
     use crate::message::{IRCMessage, PrivmsgMessage};
     use chrono::offset::TimeZone;
     use chrono::Utc;
@@ -135,6 +140,7 @@ mod tests {
                 },
                 badge_info: vec![],
                 badges: vec![],
+                badge_set: BadgeSet::default(),
                 bits: None,
                 name_color: Some(RGBColor {
                     r: 0x00,
@@ -182,6 +188,19 @@ mod tests {
                         version: "12".to_owned()
                     }
                 ],
+                badge_set: BadgeSet {
+                    badges: vec![
+                        Badge {
+                            name: "moderator".to_owned(),
+                            version: "1".to_owned()
+                        },
+                        Badge {
+                            name: "subscriber".to_owned(),
+                            version: "12".to_owned()
+                        }
+                    ],
+                    subscriber_months: Some(22)
+                },
                 bits: None,
                 name_color: Some(RGBColor {
                     r: 0x19,
@@ -217,6 +236,7 @@ mod tests {
                 },
                 badge_info: vec![],
                 badges: vec![],
+                badge_set: BadgeSet::default(),
                 bits: None,
                 name_color: None,
                 emotes: vec![],
@@ -236,6 +256,56 @@ mod tests {
         assert_eq!(msg.sender.name, "CarvedTaleare ");
     }
 
+    #[test]
+    fn test_badge_set_founder() {
+        let src = "@badge-info=founder/14;badges=founder/0,bits/1000;color=#19E6E6;display-name=randers;emotes=;flags=;id=d831d848-b7c7-4559-ae3a-2cb88f4dbfed;mod=0;room-id=11148817;subscriber=1;tmi-sent-ts=1594555275886;turbo=0;user-id=40286300;user-type= :randers!randers@randers.tmi.twitch.tv PRIVMSG #pajlada :test";
+        let irc_message = IRCMessage::parse(src).unwrap();
+        let msg = PrivmsgMessage::try_from(irc_message).unwrap();
+
+        assert_eq!(msg.badge_set.is_subscriber(), Some(14));
+        assert_eq!(msg.badge_set.has("founder"), Some("0"));
+        assert_eq!(msg.badge_set.has("subscriber"), None);
+        assert_eq!(msg.badge_set.has("bits"), Some("1000"));
+        assert!(!msg.badge_set.is_moderator());
+    }
+
+    #[test]
+    fn test_badge_set_subscriber_and_roles() {
+        let src = "@badge-info=subscriber/22;badges=broadcaster/1,moderator/1,vip/1,subscriber/12;color=#19E6E6;display-name=randers;emotes=;flags=;id=d831d848-b7c7-4559-ae3a-2cb88f4dbfed;mod=1;room-id=11148817;subscriber=1;tmi-sent-ts=1594555275886;turbo=0;user-id=40286300;user-type=mod :randers!randers@randers.tmi.twitch.tv PRIVMSG #pajlada :test";
+        let irc_message = IRCMessage::parse(src).unwrap();
+        let msg = PrivmsgMessage::try_from(irc_message).unwrap();
+
+        assert_eq!(msg.badge_set.is_subscriber(), Some(22));
+        assert_eq!(msg.badge_set.has("subscriber"), Some("12"));
+        assert_eq!(msg.badge_set.has("founder"), None);
+        assert!(msg.badge_set.is_moderator());
+        assert!(msg.badge_set.is_vip());
+        assert!(msg.badge_set.is_broadcaster());
+    }
+
+    #[test]
+    fn test_badge_set_bits_only() {
+        let src = "@badge-info=;badges=bits/100;color=#19E6E6;display-name=randers;emotes=;flags=;id=d831d848-b7c7-4559-ae3a-2cb88f4dbfed;mod=0;room-id=11148817;subscriber=0;tmi-sent-ts=1594555275886;turbo=0;user-id=40286300;user-type= :randers!randers@randers.tmi.twitch.tv PRIVMSG #pajlada :test";
+        let irc_message = IRCMessage::parse(src).unwrap();
+        let msg = PrivmsgMessage::try_from(irc_message).unwrap();
+
+        assert_eq!(msg.badge_set.has("bits"), Some("100"));
+        assert_eq!(msg.badge_set.is_subscriber(), None);
+        assert!(!msg.badge_set.is_vip());
+    }
+
+    #[test]
+    fn test_badge_set_empty() {
+        let src = "@badge-info=;badges=;color=;display-name=randers;emotes=;flags=;id=d831d848-b7c7-4559-ae3a-2cb88f4dbfed;mod=0;room-id=11148817;subscriber=0;tmi-sent-ts=1594555275886;turbo=0;user-id=40286300;user-type= :randers!randers@randers.tmi.twitch.tv PRIVMSG #pajlada :test";
+        let irc_message = IRCMessage::parse(src).unwrap();
+        let msg = PrivmsgMessage::try_from(irc_message).unwrap();
+
+        assert_eq!(msg.badge_set, BadgeSet::default());
+        assert_eq!(msg.badge_set.is_subscriber(), None);
+        assert!(!msg.badge_set.is_moderator());
+        assert!(!msg.badge_set.is_broadcaster());
+    }
+
     #[test]
     fn test_korean_display_name() {
         let src = "@badge-info=subscriber/35;badges=moderator/1,subscriber/3024;color=#FF0000;display-name=테스트계정420;emotes=;flags=;id=bdfa278e-11c4-484f-9491-0a61b16fab60;mod=1;room-id=11148817;subscriber=1;tmi-sent-ts=1593953876927;turbo=0;user-id=117166826;user-type=mod :testaccount_420!testaccount_420@testaccount_420.tmi.twitch.tv PRIVMSG #pajlada :@asd";
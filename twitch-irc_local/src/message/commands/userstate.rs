@@ -1,5 +1,5 @@
 use crate::message::commands::IRCMessageParseExt;
-use crate::message::twitch::{Badge, RGBColor};
+use crate::message::twitch::{Badge, BadgeSet, RGBColor};
 use crate::message::{IRCMessage, ServerMessageParseError};
 use std::collections::HashSet;
 use std::convert::TryFrom;
@@ -29,6 +29,8 @@ pub struct UserStateMessage {
     pub badge_info: Vec<Badge>,
     /// List of badges the logged in user has in this channel.
     pub badges: Vec<Badge>,
+    /// `badges` and `badge_info` in typed form, e.g. to check whether the logged in user is a moderator.
+    pub badge_set: BadgeSet,
     /// List of emote set IDs the logged in user has available. This always contains at least 0.
     pub emote_sets: HashSet<String>,
     /// What name color the logged in user has chosen. The same color is used in all channels.
@@ -46,13 +48,17 @@ impl TryFrom<IRCMessage> for UserStateMessage {
             return Err(ServerMessageParseError::MismatchedCommand(source));
         }
 
+        let badge_info = source.try_get_badges("badge-info")?;
+        let badges = source.try_get_badges("badges")?;
+
         Ok(UserStateMessage {
             channel_login: source.try_get_channel_login()?.to_owned(),
             user_name: source
                 .try_get_nonempty_tag_value("display-name")?
                 .to_owned(),
-            badge_info: source.try_get_badges("badge-info")?,
-            badges: source.try_get_badges("badges")?,
+            badge_set: BadgeSet::new(&badges, &badge_info),
+            badge_info,
+            badges,
             emote_sets: source.try_get_emote_sets("emote-sets")?,
             name_color: source.try_get_color("color")?,
             source,
@@ -70,7 +76,7 @@ impl From<UserStateMessage> for IRCMessage {
 mod tests {
     use crate::message::commands::userstate::UserStateMessage;
     use crate::message::twitch::RGBColor;
-    use crate::message::{Badge, IRCMessage};
+    use crate::message::{Badge, BadgeSet, IRCMessage};
     use std::convert::TryFrom;
 
     #[test]
@@ -86,6 +92,7 @@ mod tests {
                 user_name: "TESTUSER".to_owned(),
                 badge_info: vec![],
                 badges: vec![],
+                badge_set: BadgeSet::default(),
                 emote_sets: vec!["0".to_owned()].into_iter().collect(),
                 name_color: Some(RGBColor {
                     r: 0xFF,
@@ -113,6 +120,13 @@ mod tests {
                     name: "moderator".to_owned(),
                     version: "1".to_owned()
                 }],
+                badge_set: BadgeSet {
+                    badges: vec![Badge {
+                        name: "moderator".to_owned(),
+                        version: "1".to_owned()
+                    }],
+                    subscriber_months: None
+                },
                 emote_sets: vec![
                     "0".to_owned(),
                     "75c09c7b-332a-43ec-8be8-1d4571706155".to_owned()
@@ -26,11 +26,15 @@ pub use twitch::*;
 
 use std::fmt;
 use std::fmt::Write;
+use std::time::SystemTime;
 use thiserror::Error;
 
 #[cfg(feature = "with-serde")]
 use {serde::Deserialize, serde::Serialize};
 
+/// Initial capacity of `IRCMessage::params` when parsing.
+const PARAMS_CAPACITY: usize = 3;
+
 /// Error while parsing a string into an `IRCMessage`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
 pub enum IRCParseError {
@@ -178,7 +182,9 @@ impl IRCMessage {
     /// Parse a raw IRC wire-format message into an `IRCMessage`. `source` should be specified
     /// without trailing newline character(s).
     pub fn parse(mut source: &str) -> Result<IRCMessage, IRCParseError> {
-        if source.chars().any(|c| c == '\r' || c == '\n') {
+        // CR and LF are ASCII, so they can't be part of a multi-byte character
+        // and a byte scan is enough
+        if source.bytes().any(|b| b == b'\r' || b == b'\n') {
             return Err(IRCParseError::NewlinesInMessage);
         }
 
@@ -214,20 +220,25 @@ impl IRCMessage {
             None
         };
 
-        let mut command_split = source.splitn(2, ' ');
-        let mut command = command_split.next().unwrap().to_owned();
-        command.make_ascii_uppercase();
+        let (command, params_part) = match source.split_once(' ') {
+            Some((command, params_part)) => (command, Some(params_part)),
+            None => (source, None),
+        };
 
+        // validated before it is copied, so malformed input doesn't allocate
         if command.is_empty()
-            || !command.chars().all(|c| c.is_ascii_alphabetic())
-                && !command.chars().all(|c| c.is_ascii() && c.is_numeric())
+            || !command.bytes().all(|b| b.is_ascii_alphabetic())
+                && !command.bytes().all(|b| b.is_ascii_digit())
         {
             return Err(IRCParseError::MalformedCommand);
         }
+        let command = command.to_ascii_uppercase();
 
         let mut params;
-        if let Some(params_part) = command_split.next() {
-            params = vec![];
+        if let Some(params_part) = params_part {
+            // Twitch messages have at most a few parameters, typically the channel
+            // and a trailing parameter
+            params = Vec::with_capacity(PARAMS_CAPACITY);
 
             let mut rest = Some(params_part);
             while let Some(rest_str) = rest {
@@ -291,6 +302,20 @@ impl AsRawIRC for IRCMessage {
     }
 }
 
+/// A `ServerMessage` together with the time it was read from the connection.
+///
+/// Received from a client created with
+/// [`TwitchIRCClient::new_timestamped`](crate::TwitchIRCClient::new_timestamped).
+#[derive(Debug, Clone)]
+pub struct ReceivedMessage {
+    /// The parsed message
+    pub message: ServerMessage,
+    /// When the message was received from the transport, before it was queued for parsing
+    /// and forwarding. Unlike the time your code handles the message, this is not delayed
+    /// by bursts of messages waiting in the channel.
+    pub received_at: SystemTime,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -8,6 +8,11 @@ use std::fmt::Write;
 use {serde::Deserialize, serde::Serialize};
 
 fn decode_tag_value(raw: &str) -> String {
+    // Most values (ids, numbers, badges) contain no escapes at all
+    if !raw.contains('\\') {
+        return raw.to_owned();
+    }
+
     let mut output = String::with_capacity(raw.len());
 
     let mut iter = raw.chars();
@@ -84,15 +89,16 @@ impl IRCTags {
             panic!("invalid input")
         }
 
-        let mut tags = IRCTags::new();
+        // one entry per separator, so the map is allocated once
+        let count = source.bytes().filter(|b| *b == b';').count() + 1;
+        let mut tags = IRCTags(HashMap::with_capacity(count));
 
         for raw_tag in source.split(';') {
-            let mut tag_split = raw_tag.splitn(2, '=');
-
-            // always expected to be present, even splitting an empty string yields [""]
-            let key = tag_split.next().unwrap();
-            // can be missing if no = is present
-            let value = tag_split.next().map(decode_tag_value);
+            // the value can be missing if no = is present
+            let (key, value) = match raw_tag.split_once('=') {
+                Some((key, value)) => (key, Some(decode_tag_value(value))),
+                None => (raw_tag, None),
+            };
 
             tags.0.insert(key.to_owned(), value);
         }
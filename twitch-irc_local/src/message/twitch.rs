@@ -101,6 +101,72 @@ pub struct Badge {
     pub version: String,
 }
 
+/// The badges of a user in typed form, so roles don't have to be derived by matching
+/// badge names. Built from the `badges` and `badge-info` tags during parsing.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+pub struct BadgeSet {
+    pub(crate) badges: Vec<Badge>,
+    pub(crate) subscriber_months: Option<u32>,
+}
+
+impl BadgeSet {
+    /// Build the set from the parsed `badges` and `badge-info` tags.
+    pub fn new(badges: &[Badge], badge_info: &[Badge]) -> BadgeSet {
+        // founders are subscribers too, they just show a different badge
+        let subscriber_months = badges
+            .iter()
+            .find(|b| b.name == "subscriber" || b.name == "founder")
+            .map(|sub_badge| {
+                badge_info
+                    .iter()
+                    .find(|info| info.name == sub_badge.name)
+                    .and_then(|info| info.version.parse().ok())
+                    .unwrap_or(0)
+            });
+
+        BadgeSet {
+            badges: badges.to_vec(),
+            subscriber_months,
+        }
+    }
+
+    /// Version of the badge called `name`, if the user has it, e.g. `has("bits")` gives
+    /// the cheered amount tier like `"1000"`.
+    pub fn has(&self, name: &str) -> Option<&str> {
+        self.badges
+            .iter()
+            .find(|b| b.name == name)
+            .map(|b| b.version.as_str())
+    }
+
+    /// The badges in the order Twitch sent them.
+    pub fn iter(&self) -> impl Iterator<Item = &Badge> {
+        self.badges.iter()
+    }
+
+    /// Whether the user is a moderator in this channel.
+    pub fn is_moderator(&self) -> bool {
+        self.has("moderator").is_some()
+    }
+
+    /// Number of months subscribed (from `badge-info`) if the user shows a `subscriber` or
+    /// `founder` badge. `Some(0)` if Twitch did not send the month count.
+    pub fn is_subscriber(&self) -> Option<u32> {
+        self.subscriber_months
+    }
+
+    /// Whether the user is a VIP in this channel.
+    pub fn is_vip(&self) -> bool {
+        self.has("vip").is_some()
+    }
+
+    /// Whether the user is the owner of this channel.
+    pub fn is_broadcaster(&self) -> bool {
+        self.has("broadcaster").is_some()
+    }
+}
+
 /// Extract the `message_id` from a [`PrivmsgMessage`](crate::message::PrivmsgMessage) or directly
 /// use an arbitrary [`String`] or [`&str`] as a message ID. This trait allows you to plug both
 /// of these types directly into [`say_in_reply_to()`](crate::TwitchIRCClient::say_in_reply_to)